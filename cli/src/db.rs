@@ -90,6 +90,15 @@ pub async fn add_location(
 		Some(m) => format!("machine:{}", slug(m)),
 		None => "machine:local".to_string(),
 	};
+	// Another machine's path can't be looked at from here; the app's drive
+	// poll keeps `available` current from then on
+	let (available, is_dir) = match machine {
+		Some(_) => (true, None),
+		None => match std::fs::metadata(path) {
+			Ok(meta) => (true, Some(meta.is_dir())),
+			Err(_) => (false, None),
+		},
+	};
 
 	db.db
 		.query(&format!(
@@ -98,12 +107,15 @@ pub async fn add_location(
 			path: $path,
 			label: $label,
 			created_at: time::now(),
-			available: true,
+			available: $available,
+			is_dir: $is_dir,
 		}}",
 			location_id, machine_ref
 		))
 		.bind(("path", path_str))
 		.bind(("label", label_str))
+		.bind(("available", available))
+		.bind(("is_dir", is_dir))
 		.await?
 		.check()?;
 
@@ -348,6 +360,7 @@ const SCHEMA: &str = "
 	DEFINE FIELD OVERWRITE name ON drive TYPE string;
 	DEFINE FIELD OVERWRITE uuid ON drive TYPE string;
 	DEFINE FIELD OVERWRITE filesystem ON drive TYPE option<string>;
+	DEFINE FIELD OVERWRITE kind ON drive TYPE string DEFAULT 'local';
 	DEFINE FIELD OVERWRITE capacity_bytes ON drive TYPE option<int>;
 	DEFINE FIELD OVERWRITE mount_point ON drive TYPE option<string>;
 	DEFINE FIELD OVERWRITE connected ON drive TYPE bool DEFAULT false;
@@ -363,6 +376,7 @@ const SCHEMA: &str = "
 	DEFINE FIELD OVERWRITE label ON location TYPE option<string>;
 	DEFINE FIELD OVERWRITE created_at ON location TYPE datetime;
 	DEFINE FIELD OVERWRITE available ON location TYPE bool DEFAULT false;
+	DEFINE FIELD OVERWRITE is_dir ON location TYPE option<bool>;
 	DEFINE FIELD OVERWRITE graph_x ON location TYPE option<float>;
 	DEFINE FIELD OVERWRITE graph_y ON location TYPE option<float>;
 
@@ -383,5 +397,11 @@ const SCHEMA: &str = "
 	DEFINE FIELD OVERWRITE include_patterns ON intent TYPE option<array<string>>;
 	DEFINE FIELD OVERWRITE exclude_patterns ON intent TYPE option<array<string>>;
 	DEFINE FIELD OVERWRITE bidirectional ON intent TYPE bool DEFAULT false;
+	DEFINE FIELD OVERWRITE follow_symlinks ON intent TYPE bool DEFAULT false;
+	DEFINE FIELD OVERWRITE source_subpath ON intent TYPE option<string>;
+	DEFINE FIELD OVERWRITE on_conflict ON intent TYPE string DEFAULT 'review';
+	DEFINE FIELD OVERWRITE overwrite_policy ON intent TYPE string DEFAULT 'always';
+	DEFINE FIELD OVERWRITE copy_strategy ON intent TYPE string DEFAULT 'copy';
+	DEFINE FIELD OVERWRITE write_manifest ON intent TYPE bool DEFAULT false;
 	DEFINE FIELD OVERWRITE initial_sync_complete ON intent TYPE bool DEFAULT false;
 ";
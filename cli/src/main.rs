@@ -83,6 +83,12 @@ enum Commands {
 	/// Clear all data and re-import from backup-tool config
 	ResetAndImport,
 
+	/// Re-hash a destination's files against its .kip-manifest.json
+	VerifyManifest {
+		/// Directory holding the manifest (a destination's root)
+		dir: std::path::PathBuf,
+	},
+
 	/// Show real-time backup progress
 	Status {
 		/// Clear status file (after backup completes)
@@ -175,6 +181,11 @@ async fn main() -> Result<()> {
 			println!("\n✅ Reset and import complete!");
 		}
 
+		Commands::VerifyManifest { dir } => {
+			println!("🔍 Verifying {} against its manifest...\n", dir.display());
+			verify_manifest(&dir)?;
+		}
+
 		Commands::Status { clear } => {
 			if clear {
 				status::BackupStatus::clear()?;
@@ -188,6 +199,30 @@ async fn main() -> Result<()> {
 	Ok(())
 }
 
+/// Check a destination against its manifest; any drift exits non-zero
+fn verify_manifest(dir: &std::path::Path) -> Result<()> {
+	use daemon::engine::manifest;
+
+	let check = manifest::verify_manifest(dir)?;
+	for path in &check.mismatched {
+		println!("   ❌ changed: {}", path);
+	}
+	for path in &check.missing {
+		println!("   ❌ missing: {}", path);
+	}
+
+	if !check.is_clean() {
+		eprintln!(
+			"\n❌ {} of {} file(s) don't match the manifest",
+			check.mismatched.len() + check.missing.len(),
+			check.checked
+		);
+		std::process::exit(1);
+	}
+	println!("✅ All {} file(s) match the manifest", check.checked);
+	Ok(())
+}
+
 /// List configured folders
 fn list_configs(sort_priority: bool, filter: Option<&str>) -> Result<()> {
 	let app_configs = config::load_app_configs()?;
@@ -1,6 +1,11 @@
 //! Database initialization
 
-use std::path::{Path, PathBuf};
+use std::{
+	ffi::OsString,
+	fmt,
+	path::{Path, PathBuf},
+	time::Duration,
+};
 
 use surrealdb::{
 	engine::local::{Db, Mem, SurrealKv},
@@ -9,23 +14,87 @@ use surrealdb::{
 
 use crate::db::{DbHandle, SCHEMA_V1};
 
+/// Attempts at opening a locked database before giving up. A previous
+/// instance that is just exiting usually releases the lock within a second.
+const LOCK_RETRIES: u32 = 5;
+/// Delay before the first retry; doubles on each attempt after.
+const LOCK_RETRY_BASE_DELAY: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub enum InitError {
+	/// Another process holds the database lock, even after retrying.
+	Locked(String),
+	Other(String),
+}
+
+impl fmt::Display for InitError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			InitError::Locked(s) => write!(f, "database is locked by another process: {}", s),
+			InitError::Other(s) => write!(f, "database init failed: {}", s),
+		}
+	}
+}
+
+impl std::error::Error for InitError {}
+
 /// Initialize the database: connect, select ns/db, run migrations, bootstrap machine.
-pub async fn init() -> Result<DbHandle, Box<dyn std::error::Error>> {
+pub async fn init() -> Result<DbHandle, InitError> {
 	let path = db_path();
 	init_with_path(&path).await
 }
 
 /// Initialize the database at a specific path
-pub async fn init_with_path(path: &Path) -> Result<DbHandle, Box<dyn std::error::Error>> {
-	let db = Surreal::new::<SurrealKv>(path).await?;
-	db.use_ns("kip").use_db("kip").await?;
+pub async fn init_with_path(path: &Path) -> Result<DbHandle, InitError> {
+	let db = open_with_retry(path).await?;
+	let other = |e: Box<dyn std::error::Error>| InitError::Other(e.to_string());
 
-	run_migrations(&db).await?;
-	bootstrap_local_machine(&db).await?;
+	db.use_ns("kip")
+		.use_db("kip")
+		.await
+		.map_err(|e| InitError::Other(e.to_string()))?;
+
+	run_migrations(&db).await.map_err(other)?;
+	bootstrap_local_machine(&db).await.map_err(other)?;
+	recover_interrupted_jobs(&db).await.map_err(other)?;
+	recover_interrupted_scans(&db).await.map_err(other)?;
 
 	Ok(DbHandle { db })
 }
 
+/// Open the on-disk store, retrying with backoff while it's locked.
+async fn open_with_retry(path: &Path) -> Result<Surreal<Db>, InitError> {
+	let mut delay = LOCK_RETRY_BASE_DELAY;
+	let mut attempt = 1;
+	loop {
+		match Surreal::new::<SurrealKv>(path).await {
+			Ok(db) => return Ok(db),
+			Err(e) if !is_lock_error(&e.to_string()) => return Err(InitError::Other(e.to_string())),
+			Err(e) if attempt >= LOCK_RETRIES => {
+				tracing::error!("Database still locked after {} attempts: {}", attempt, e);
+				return Err(InitError::Locked(e.to_string()));
+			}
+			Err(e) => {
+				tracing::warn!(
+					"Database locked (attempt {}/{}), retrying in {:?}: {}",
+					attempt,
+					LOCK_RETRIES,
+					delay,
+					e
+				);
+				tokio::time::sleep(delay).await;
+				delay *= 2;
+				attempt += 1;
+			}
+		}
+	}
+}
+
+fn is_lock_error(message: &str) -> bool {
+	let message = message.to_lowercase();
+	message.contains("locked") || message.contains("lock file")
+}
+
 /// Initialize an in-memory database for testing
 /// Each call creates a completely isolated instance with no filesystem access
 pub async fn init_memory() -> Result<DbHandle, Box<dyn std::error::Error>> {
@@ -39,14 +108,27 @@ pub async fn init_memory() -> Result<DbHandle, Box<dyn std::error::Error>> {
 	Ok(DbHandle { db })
 }
 
-/// Resolve the database file path.
-/// ~/Library/Application Support/kip/kip.db
+/// Kip's data directory: `KIP_DATA_DIR` if set, otherwise `Kip` under the
+/// platform data dir (`~/Library/Application Support` on macOS,
+/// `~/.local/share` on Linux).
+pub fn data_dir() -> PathBuf {
+	data_dir_from(std::env::var_os("KIP_DATA_DIR"))
+}
+
+/// `data_dir` given the value of `KIP_DATA_DIR`; an empty one counts as unset.
+fn data_dir_from(env_override: Option<OsString>) -> PathBuf {
+	if let Some(dir) = env_override.filter(|d| !d.is_empty()) {
+		return PathBuf::from(dir);
+	}
+	dirs::data_dir()
+		.or_else(|| dirs::home_dir().map(|h| h.join(".local").join("share")))
+		.expect("no home directory")
+		.join("Kip")
+}
+
+/// Resolve the database file path: `<data_dir>/kip.db`.
 fn db_path() -> PathBuf {
-	let home = std::env::var("HOME").expect("HOME not set");
-	let path = PathBuf::from(home)
-		.join("Library")
-		.join("Application Support")
-		.join("Kip");
+	let path = data_dir();
 	std::fs::create_dir_all(&path).expect("Failed to create Kip data directory");
 	path.join("kip.db")
 }
@@ -63,7 +145,7 @@ async fn bootstrap_local_machine(db: &Surreal<Db>) -> Result<(), Box<dyn std::er
 	tracing::info!("Bootstrapping local machine with hostname: {}", hostname);
 	let mut resp = db
 		.query(
-			"UPSERT machine:local CONTENT {
+			"UPSERT machine:local MERGE {
             name: $name,
             kind: 'local',
             hostname: $hostname,
@@ -84,9 +166,147 @@ async fn bootstrap_local_machine(db: &Surreal<Db>) -> Result<(), Box<dyn std::er
 	Ok(())
 }
 
+/// Clean up after a crash mid-copy: any job still marked `transferring` was
+/// interrupted by the previous session, so its destination file is partial.
+/// Jobs with recorded progress keep their partial file for the copier to
+/// resume (it re-checks the prefix); jobs without any have it deleted,
+/// unless the destination is on a remote machine, where the same path on
+/// this disk is some other file. All are reset to `pending`.
+async fn recover_interrupted_jobs(db: &Surreal<Db>) -> Result<(), Box<dyn std::error::Error>> {
+	let mut resp = db
+		.query(
+			"SELECT id, dest_path, bytes_transferred, destination.machine.kind = 'remote' AS remote
+             FROM transfer_job WHERE status = 'transferring'",
+		)
+		.await?;
+	let rows: Vec<serde_json::Value> = resp.take(0)?;
+
+	if rows.is_empty() {
+		return Ok(());
+	}
+
+	let mut removed = 0usize;
+	let mut resumable = 0usize;
+	for row in &rows {
+		if row["bytes_transferred"].as_i64().unwrap_or(0) > 0 {
+			resumable += 1;
+			continue;
+		}
+		if row["remote"].as_bool() == Some(true) {
+			continue;
+		}
+		let Some(dest_path) = row["dest_path"].as_str() else {
+			continue;
+		};
+		let dest_path = kip_core::util::decode_path(dest_path);
+		match std::fs::remove_file(&dest_path) {
+			Ok(()) => {
+				removed += 1;
+				tracing::info!("Removed partial file from interrupted transfer: {}", dest_path.display());
+			}
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+			Err(e) => tracing::warn!("Failed to remove partial file {}: {}", dest_path.display(), e),
+		}
+	}
+
+	db.query("UPDATE transfer_job SET status = 'pending', started_at = NONE WHERE status = 'transferring'")
+		.await?
+		.check()?;
+
+	tracing::info!(
+		"Recovered {} interrupted transfer jobs ({} partial files removed, {} resumable)",
+		rows.len(),
+		removed,
+		resumable
+	);
+	Ok(())
+}
+
+/// An intent still `scanning` was cut off mid-scan. Its jobs are written in
+/// one transaction, so it has none from that scan; it goes back to `idle`
+/// to be scanned again rather than staying claimed forever. That scan
+/// resumes the walk from the intent's `scan_cursor`.
+async fn recover_interrupted_scans(db: &Surreal<Db>) -> Result<(), Box<dyn std::error::Error>> {
+	let mut resp = db
+		.query("UPDATE intent SET status = 'idle', updated_at = time::now() WHERE status = 'scanning' RETURN id")
+		.await?;
+	let rows: Vec<serde_json::Value> = resp.take(0)?;
+	if !rows.is_empty() {
+		tracing::info!("Reset {} intent(s) interrupted mid-scan", rows.len());
+	}
+	Ok(())
+}
+
 fn get_hostname() -> String {
 	std::process::Command::new("hostname")
 		.output()
 		.map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
 		.unwrap_or_else(|_| "unknown".to_string())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lock_errors_are_recognised() {
+		assert!(is_lock_error("Database already locked by another process"));
+		assert!(!is_lock_error("Permission denied (os error 13)"));
+	}
+
+	#[tokio::test]
+	async fn interrupted_jobs_lose_their_partial_local_files() {
+		let db = init_memory().await.unwrap();
+		let dst = tempfile::tempdir().unwrap();
+		let (local, remote, resumable) =
+			(dst.path().join("local.bin"), dst.path().join("remote.bin"), dst.path().join("resumable.bin"));
+		for partial in [&local, &remote, &resumable] {
+			std::fs::write(partial, "half").unwrap();
+		}
+		db.db
+			.query(
+				"CREATE machine:nas CONTENT {
+                     name: 'NAS', kind: 'remote', hostname: 'nas', is_current: false, online: true,
+                     last_seen: time::now(),
+                 };
+                 CREATE location:dst CONTENT { path: $root, available: true };
+                 CREATE location:nas CONTENT { path: $root, machine: machine:nas, available: true };
+                 CREATE transfer_job CONTENT {
+                     intent: intent:x, destination: location:dst, source_path: '/src/local.bin',
+                     dest_path: $local, size: 8, status: 'transferring',
+                 };
+                 CREATE transfer_job CONTENT {
+                     intent: intent:x, destination: location:nas, source_path: '/src/remote.bin',
+                     dest_path: $remote, size: 8, status: 'transferring',
+                 };
+                 CREATE transfer_job CONTENT {
+                     intent: intent:x, destination: location:dst, source_path: '/src/resumable.bin',
+                     dest_path: $resumable, size: 8, bytes_transferred: 4, status: 'transferring',
+                 };",
+			)
+			.bind(("root", dst.path().to_str().unwrap().to_string()))
+			.bind(("local", local.to_str().unwrap().to_string()))
+			.bind(("remote", remote.to_str().unwrap().to_string()))
+			.bind(("resumable", resumable.to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		recover_interrupted_jobs(&db.db).await.unwrap();
+
+		assert!(!local.exists(), "a partial local copy with no progress should be removed");
+		assert!(remote.exists(), "a remote job's path on this disk isn't its partial file");
+		assert!(resumable.exists(), "a copy with progress is kept to resume");
+		let mut resp = db.db.query("SELECT VALUE status FROM transfer_job").await.unwrap();
+		let statuses: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(statuses, vec!["pending".to_string(); 3]);
+	}
+
+	#[test]
+	fn data_dir_env_override_wins() {
+		assert_eq!(data_dir_from(Some("/tmp/kip-test-data".into())), PathBuf::from("/tmp/kip-test-data"));
+		assert!(data_dir_from(Some(OsString::new())).ends_with("Kip"));
+		assert!(data_dir_from(None).ends_with("Kip"));
+	}
+}
@@ -6,4 +6,4 @@ mod schema;
 
 pub use handle::DbHandle;
 pub use schema::SCHEMA_V1;
-pub use init::{init, init_memory, init_with_path};
+pub use init::{data_dir, init, init_memory, init_with_path, InitError};
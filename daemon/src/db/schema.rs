@@ -11,23 +11,30 @@ pub const SCHEMA_V1: &str = "
     DEFINE FIELD OVERWRITE ssh_proxy ON machine TYPE option<string>;
     DEFINE FIELD OVERWRITE last_seen ON machine TYPE datetime;
     DEFINE FIELD OVERWRITE online ON machine TYPE bool DEFAULT false;
+    DEFINE FIELD OVERWRITE color ON machine TYPE option<string>;
 
     DEFINE TABLE OVERWRITE drive SCHEMAFULL;
     DEFINE FIELD OVERWRITE name ON drive TYPE string;
     DEFINE FIELD OVERWRITE uuid ON drive TYPE string;
     DEFINE FIELD OVERWRITE filesystem ON drive TYPE option<string>;
+    DEFINE FIELD OVERWRITE kind ON drive TYPE string DEFAULT 'local';
     DEFINE FIELD OVERWRITE capacity_bytes ON drive TYPE option<int>;
+    DEFINE FIELD OVERWRITE free_bytes ON drive TYPE option<int>;
     DEFINE FIELD OVERWRITE mount_point ON drive TYPE option<string>;
     DEFINE FIELD OVERWRITE connected ON drive TYPE bool DEFAULT false;
     DEFINE FIELD OVERWRITE last_seen ON drive TYPE datetime;
+    DEFINE FIELD OVERWRITE color ON drive TYPE option<string>;
     DEFINE FIELD OVERWRITE limitations ON drive TYPE option<object>;
     DEFINE FIELD OVERWRITE limitations.max_file_size ON drive TYPE option<int>;
+    DEFINE FIELD OVERWRITE measured_write_mbps ON drive TYPE option<float>;
+    DEFINE FIELD OVERWRITE benchmarked_at ON drive TYPE option<datetime>;
     DEFINE INDEX OVERWRITE idx_drive_uuid ON drive FIELDS uuid UNIQUE;
 
     DEFINE TABLE OVERWRITE location SCHEMALESS;
     DEFINE FIELD OVERWRITE path ON location TYPE string;
     DEFINE FIELD OVERWRITE label ON location TYPE option<string>;
     DEFINE FIELD OVERWRITE available ON location TYPE bool DEFAULT false;
+    DEFINE FIELD OVERWRITE is_dir ON location TYPE option<bool>;
     DEFINE FIELD OVERWRITE graph_x ON location TYPE option<float>;
     DEFINE FIELD OVERWRITE graph_y ON location TYPE option<float>;
 
@@ -39,16 +46,37 @@ pub const SCHEMA_V1: &str = "
     DEFINE FIELD OVERWRITE kind ON intent TYPE string;
     DEFINE FIELD OVERWRITE speed_mode ON intent TYPE string;
     DEFINE FIELD OVERWRITE priority ON intent TYPE int DEFAULT 0;
+    DEFINE FIELD OVERWRITE concurrency ON intent TYPE option<int>;
     DEFINE FIELD OVERWRITE created_at ON intent TYPE datetime DEFAULT time::now();
     DEFINE FIELD OVERWRITE updated_at ON intent TYPE datetime DEFAULT time::now();
     DEFINE FIELD OVERWRITE total_files ON intent TYPE int DEFAULT 0;
     DEFINE FIELD OVERWRITE total_bytes ON intent TYPE int DEFAULT 0;
     DEFINE FIELD OVERWRITE completed_files ON intent TYPE int DEFAULT 0;
     DEFINE FIELD OVERWRITE completed_bytes ON intent TYPE int DEFAULT 0;
+    DEFINE FIELD OVERWRITE bytes_per_sec ON intent TYPE option<int>;
+    DEFINE FIELD OVERWRITE skipped_entries ON intent TYPE int DEFAULT 0;
+    DEFINE FIELD OVERWRITE skip_log ON intent TYPE array<object> DEFAULT [];
     DEFINE FIELD OVERWRITE include_patterns ON intent TYPE option<array<string>>;
     DEFINE FIELD OVERWRITE exclude_patterns ON intent TYPE option<array<string>>;
     DEFINE FIELD OVERWRITE bidirectional ON intent TYPE bool DEFAULT false;
+    DEFINE FIELD OVERWRITE follow_symlinks ON intent TYPE bool DEFAULT false;
+    DEFINE FIELD OVERWRITE source_subpath ON intent TYPE option<string>;
     DEFINE FIELD OVERWRITE initial_sync_complete ON intent TYPE bool DEFAULT false;
+    DEFINE FIELD OVERWRITE scan_started_at ON intent TYPE option<datetime>;
+    DEFINE FIELD OVERWRITE scan_cursor ON intent TYPE option<object>;
+    DEFINE FIELD OVERWRITE last_synced_at ON intent TYPE option<datetime>;
+    DEFINE FIELD OVERWRITE retryable_errors ON intent TYPE array<string> DEFAULT ['io_error'];
+    DEFINE FIELD OVERWRITE job_order ON intent TYPE string DEFAULT 'as_found';
+    DEFINE FIELD OVERWRITE verify_mode ON intent TYPE string DEFAULT 'hash';
+    DEFINE FIELD OVERWRITE on_conflict ON intent TYPE string DEFAULT 'review';
+    DEFINE FIELD OVERWRITE overwrite_policy ON intent TYPE string DEFAULT 'always';
+    DEFINE FIELD OVERWRITE copy_strategy ON intent TYPE string DEFAULT 'copy';
+    DEFINE FIELD OVERWRITE write_manifest ON intent TYPE bool DEFAULT false;
+
+    DEFINE TABLE OVERWRITE intent_template SCHEMALESS;
+    DEFINE FIELD OVERWRITE name ON intent_template TYPE string;
+    DEFINE FIELD OVERWRITE settings ON intent_template TYPE object;
+    DEFINE FIELD OVERWRITE created_at ON intent_template TYPE datetime DEFAULT time::now();
 
     DEFINE TABLE OVERWRITE transfer_job SCHEMAFULL;
     DEFINE FIELD OVERWRITE intent ON transfer_job TYPE string;
@@ -56,6 +84,7 @@ pub const SCHEMA_V1: &str = "
     DEFINE FIELD OVERWRITE dest_path ON transfer_job TYPE string;
     DEFINE FIELD OVERWRITE destination ON transfer_job TYPE string;
     DEFINE FIELD OVERWRITE size ON transfer_job TYPE int;
+    DEFINE FIELD OVERWRITE source_mtime_ns ON transfer_job TYPE option<int>;
     DEFINE FIELD OVERWRITE bytes_transferred ON transfer_job TYPE int DEFAULT 0;
     DEFINE FIELD OVERWRITE status ON transfer_job TYPE string;
     DEFINE FIELD OVERWRITE attempts ON transfer_job TYPE int DEFAULT 0;
@@ -64,10 +93,21 @@ pub const SCHEMA_V1: &str = "
     DEFINE FIELD OVERWRITE error_kind ON transfer_job TYPE option<string>;
     DEFINE FIELD OVERWRITE source_hash ON transfer_job TYPE option<string>;
     DEFINE FIELD OVERWRITE dest_hash ON transfer_job TYPE option<string>;
+    DEFINE FIELD OVERWRITE next_retry_at ON transfer_job TYPE option<datetime>;
+    DEFINE FIELD OVERWRITE overwrite_approved ON transfer_job TYPE bool DEFAULT false;
+    DEFINE FIELD OVERWRITE is_dir ON transfer_job TYPE bool DEFAULT false;
     DEFINE FIELD OVERWRITE started_at ON transfer_job TYPE option<datetime>;
     DEFINE FIELD OVERWRITE completed_at ON transfer_job TYPE option<datetime>;
     DEFINE FIELD OVERWRITE created_at ON transfer_job TYPE datetime DEFAULT time::now();
 
+    DEFINE TABLE OVERWRITE scan_entry SCHEMAFULL;
+    DEFINE FIELD OVERWRITE intent ON scan_entry TYPE string;
+    DEFINE FIELD OVERWRITE seq ON scan_entry TYPE int;
+    DEFINE FIELD OVERWRITE path ON scan_entry TYPE string;
+    DEFINE FIELD OVERWRITE size ON scan_entry TYPE int;
+    DEFINE FIELD OVERWRITE mtime_ns ON scan_entry TYPE option<int>;
+    DEFINE INDEX OVERWRITE idx_intent ON scan_entry FIELDS intent;
+
     DEFINE TABLE OVERWRITE file_record SCHEMAFULL;
     DEFINE FIELD OVERWRITE hash ON file_record TYPE string;
     DEFINE FIELD OVERWRITE size ON file_record TYPE int;
@@ -75,7 +115,7 @@ pub const SCHEMA_V1: &str = "
     DEFINE INDEX OVERWRITE idx_hash ON file_record FIELDS hash;
     DEFINE INDEX OVERWRITE idx_size ON file_record FIELDS size;
 
-    DEFINE TABLE OVERWRITE exists_at SCHEMAFULL;
+    DEFINE TABLE OVERWRITE exists_at TYPE RELATION IN file_record OUT location SCHEMAFULL;
     DEFINE FIELD OVERWRITE path ON exists_at TYPE string;
     DEFINE FIELD OVERWRITE modified_at ON exists_at TYPE datetime;
     DEFINE FIELD OVERWRITE verified_at ON exists_at TYPE datetime;
@@ -98,4 +138,8 @@ pub const SCHEMA_V1: &str = "
     DEFINE FIELD OVERWRITE dest_size ON review_item TYPE option<int>;
     DEFINE FIELD OVERWRITE dest_hash ON review_item TYPE option<string>;
     DEFINE FIELD OVERWRITE dest_modified ON review_item TYPE option<datetime>;
+
+    DEFINE TABLE OVERWRITE picker_state SCHEMAFULL;
+    DEFINE FIELD OVERWRITE last_dir ON picker_state TYPE string;
+    DEFINE FIELD OVERWRITE updated_at ON picker_state TYPE datetime DEFAULT time::now();
 ";
@@ -0,0 +1,1237 @@
+//! DB-independent copy-and-verify pipeline.
+//!
+//! Takes paths and options, returns a `CopyResult` or `CopyError`. The
+//! transfer engine wraps this with job status transitions; anything else
+//! (tests, the CLI) can call it directly without a database. `copy_file`
+//! works on local paths; `copy_between` runs the same pipeline across any
+//! pair of `transport::FileSystem`s.
+
+use std::{
+	fmt, fs,
+	io::{self, Read, Seek, SeekFrom, Write},
+	path::Path,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant, SystemTime},
+};
+
+use kip_core::models::intent::{CopyStrategy, SpeedMode, VerifyMode, DEFAULT_RETRYABLE_ERRORS};
+
+use crate::engine::transport::{FileStat, FileSystem, LocalFs};
+
+pub const CHUNK_SIZE: usize = SpeedMode::Normal.profile().chunk_size; // 256KB
+
+/// Smallest buffer `chunk_size_for` picks, however small the file.
+const MIN_CHUNK_SIZE: usize = 64 * 1024;
+/// Buffer for large files under `SpeedMode::Blast`; fast disks copy
+/// noticeably quicker with megabyte-sized reads.
+const BLAST_CHUNK_SIZE: usize = SpeedMode::Blast.profile().chunk_size;
+
+/// Bytes copied between progress reports, whatever the chunk size.
+pub const PROGRESS_STEP: u64 = 1024 * 1024;
+
+/// Read/write buffer size for a file of `file_size` bytes: no bigger than
+/// the file needs, up to the speed mode's chunk size.
+pub fn chunk_size_for(file_size: u64, speed_mode: &SpeedMode) -> usize {
+	let max = speed_mode.profile().chunk_size;
+	usize::try_from(file_size)
+		.unwrap_or(usize::MAX)
+		.checked_next_power_of_two()
+		.unwrap_or(max)
+		.clamp(MIN_CHUNK_SIZE, max)
+}
+
+/// Hex length of an xxh3-128 digest; blake3's is 64.
+const XXH3_HEX_LEN: usize = 32;
+
+/// Slack allowed when comparing mtimes, for filesystems that round them
+/// (FAT keeps two-second resolution).
+const MTIME_TOLERANCE: Duration = Duration::from_secs(2);
+
+/// First retry delay for a retryable failure; doubles per attempt.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+/// Longest a job waits between retries.
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// Delay before retrying a job that has already failed `attempts` times:
+/// `RETRY_BASE_DELAY * 2^attempts`, capped at `RETRY_MAX_DELAY`.
+pub fn backoff_delay(attempts: u32) -> Duration {
+	RETRY_BASE_DELAY
+		.checked_mul(2u32.saturating_pow(attempts))
+		.map_or(RETRY_MAX_DELAY, |d| d.min(RETRY_MAX_DELAY))
+}
+
+#[derive(Debug)]
+pub enum CopyError {
+	JobNotFound(String),
+	SourceNotFound(String),
+	PermissionDenied(String),
+	DiskFull(String),
+	IoError(String),
+	HashMismatch {
+		source_hash: String,
+		dest_hash: String,
+	},
+	/// The destination's size or mtime isn't what was just written.
+	MetadataMismatch(String),
+	/// The source is a FIFO, socket, device node or directory. Reading one
+	/// could block forever or never end, so it isn't attempted.
+	UnsupportedFileType(String),
+	/// The destination already holds a different file and the intent's
+	/// overwrite policy kept it.
+	DestinationExists(String),
+	/// The destination is on a remote machine whose record doesn't say how
+	/// to reach it.
+	RemoteMisconfigured(String),
+	/// `CopyOptions::cancel` was raised mid-copy. The partial destination is
+	/// left in place for the caller to resume from or remove.
+	Cancelled,
+	DbError(String),
+}
+
+impl fmt::Display for CopyError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CopyError::JobNotFound(s) => write!(f, "job not found: {}", s),
+			CopyError::SourceNotFound(s) => write!(f, "source file not found: {}", s),
+			CopyError::PermissionDenied(s) => write!(f, "permission denied: {}", s),
+			CopyError::DiskFull(s) => write!(f, "disk full: {}", s),
+			CopyError::IoError(s) => write!(f, "I/O error: {}", s),
+			CopyError::HashMismatch { source_hash, dest_hash } => {
+				write!(f, "hash mismatch: source={}, dest={}", source_hash, dest_hash)
+			}
+			CopyError::MetadataMismatch(s) => write!(f, "destination doesn't match after copy: {}", s),
+			CopyError::UnsupportedFileType(s) => write!(f, "not a regular file: {}", s),
+			CopyError::DestinationExists(s) => write!(f, "destination already exists and differs: {}", s),
+			CopyError::RemoteMisconfigured(s) => write!(f, "can't connect to the destination machine: {}", s),
+			CopyError::Cancelled => write!(f, "copy cancelled"),
+			CopyError::DbError(s) => write!(f, "database error: {}", s),
+		}
+	}
+}
+
+impl std::error::Error for CopyError {}
+
+impl CopyError {
+	/// Whether this error is retryable under the default policy
+	/// (transient I/O only) vs needs immediate review.
+	pub fn is_retryable(&self) -> bool {
+		DEFAULT_RETRYABLE_ERRORS.contains(&classify_error(self))
+	}
+
+	/// Whether this error is retryable under an intent's policy, given as a
+	/// list of error kinds (`io_error`, `permission_denied`, ...).
+	pub fn is_retryable_for(&self, retryable_kinds: &[String]) -> bool {
+		let kind = classify_error(self);
+		retryable_kinds.iter().any(|k| k == kind)
+	}
+}
+
+/// Stable string for an error, as stored in `transfer_job.error_kind`.
+pub fn classify_error(err: &CopyError) -> &'static str {
+	match err {
+		CopyError::SourceNotFound(_) => "source_missing",
+		CopyError::PermissionDenied(_) => "permission_denied",
+		CopyError::DiskFull(_) => "disk_full",
+		CopyError::HashMismatch { .. } => "hash_mismatch",
+		CopyError::MetadataMismatch(_) => "verify_failed",
+		CopyError::UnsupportedFileType(_) => "unsupported_file_type",
+		CopyError::DestinationExists(_) => "conflict",
+		CopyError::RemoteMisconfigured(_) => "remote_misconfigured",
+		CopyError::Cancelled => "cancelled",
+		CopyError::IoError(_) => "io_error",
+		CopyError::JobNotFound(_) | CopyError::DbError(_) => "internal",
+	}
+}
+
+#[derive(Debug, Clone)]
+pub struct CopyResult {
+	pub bytes_copied: u64,
+	pub source_hash: String,
+	pub dest_hash: String,
+	pub verified: bool,
+}
+
+/// Digest a copy is hashed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlgorithm {
+	Blake3,
+	/// xxh3-128: non-cryptographic, several times faster.
+	Xxh3,
+}
+
+impl HashAlgorithm {
+	/// What the copy hashes with under `mode`. Only `FastHash` trades blake3
+	/// away; the other modes keep it so dedup still recognises the content.
+	pub fn for_mode(mode: VerifyMode) -> Self {
+		match mode {
+			VerifyMode::FastHash => HashAlgorithm::Xxh3,
+			VerifyMode::Hash | VerifyMode::Paranoid | VerifyMode::SizeMtime | VerifyMode::None => {
+				HashAlgorithm::Blake3
+			}
+		}
+	}
+
+	/// The algorithm that produced a stored hex digest, told apart by length.
+	pub fn of_digest(hex: &str) -> Self {
+		if hex.len() == XXH3_HEX_LEN {
+			HashAlgorithm::Xxh3
+		} else {
+			HashAlgorithm::Blake3
+		}
+	}
+}
+
+/// Streaming hasher for either algorithm.
+enum Hasher {
+	Blake3(Box<blake3::Hasher>),
+	Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+	fn new(algorithm: HashAlgorithm) -> Self {
+		match algorithm {
+			HashAlgorithm::Blake3 => Hasher::Blake3(Box::default()),
+			HashAlgorithm::Xxh3 => Hasher::Xxh3(Box::default()),
+		}
+	}
+
+	fn update(&mut self, data: &[u8]) {
+		match self {
+			Hasher::Blake3(h) => {
+				h.update(data);
+			}
+			Hasher::Xxh3(h) => h.update(data),
+		}
+	}
+
+	fn finalize_hex(&self) -> String {
+		match self {
+			Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+			Hasher::Xxh3(h) => format!("{:032x}", h.digest128()),
+		}
+	}
+}
+
+/// Knobs for a single file copy.
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+	/// Read/write buffer size.
+	pub chunk_size: usize,
+	/// How the destination is checked after writing, and so which hash the
+	/// copy is recorded with.
+	pub verify: VerifyMode,
+	/// Bytes a previous, interrupted attempt already wrote. If the partial
+	/// destination checks out, copying continues from there.
+	pub resume_from: u64,
+	/// Source mtime captured at scan time, applied to the destination after
+	/// the copy. `None` falls back to the source's current mtime.
+	pub source_modified: Option<SystemTime>,
+	/// Checked after every chunk; once set, the copy stops with
+	/// `CopyError::Cancelled`.
+	pub cancel: Option<Arc<AtomicBool>>,
+	/// Whether `copy_file` may reflink or hardlink instead of copying when
+	/// both ends are on the same filesystem.
+	pub strategy: CopyStrategy,
+	/// Bytes per second to hold the copy to; `None` doesn't throttle.
+	pub rate_limit: Option<u64>,
+}
+
+impl Default for CopyOptions {
+	fn default() -> Self {
+		Self {
+			chunk_size: CHUNK_SIZE,
+			verify: VerifyMode::Hash,
+			resume_from: 0,
+			source_modified: None,
+			cancel: None,
+			strategy: CopyStrategy::Copy,
+			rate_limit: None,
+		}
+	}
+}
+
+/// Holds a copy to a byte rate by sleeping whenever it gets ahead of it.
+struct Throttle {
+	rate: u64,
+	started: Instant,
+	sent: u64,
+}
+
+impl Throttle {
+	fn new(rate: u64) -> Self {
+		Self { rate: rate.max(1), started: Instant::now(), sent: 0 }
+	}
+
+	/// Account for `n` more bytes, waiting until the rate allows them.
+	fn pace(&mut self, n: usize) {
+		self.sent += n as u64;
+		let due = Duration::from_secs_f64(self.sent as f64 / self.rate as f64);
+		if let Some(ahead) = due.checked_sub(self.started.elapsed()) {
+			std::thread::sleep(ahead);
+		}
+	}
+}
+
+/// Core copy pipeline: read source → hash → write dest → verify → copy
+/// mode bits and mtime.
+///
+/// The destination is written in place rather than to a temp file renamed
+/// over it: the partial file is what an interrupted copy resumes from, and
+/// there's no rename to fail with `EXDEV` when the destination is on another
+/// filesystem.
+///
+/// With a `strategy` other than `Copy`, a fresh copy on the same filesystem
+/// is cloned or hardlinked into place instead; see `link_or_clone`.
+///
+/// `on_progress` is called with the running byte count every
+/// `PROGRESS_STEP` bytes or so, and once more when the copy ends or stops.
+/// This is synchronous and should run on spawn_blocking.
+pub fn copy_file(
+	source: impl AsRef<Path>,
+	dest: impl AsRef<Path>,
+	options: &CopyOptions,
+	mut on_progress: impl FnMut(u64),
+) -> Result<CopyResult, CopyError> {
+	let (source, dest) = (source.as_ref(), dest.as_ref());
+	if options.strategy != CopyStrategy::Copy && options.resume_from == 0 {
+		if let Some(result) = link_or_clone(source, dest, options)? {
+			on_progress(result.bytes_copied);
+			return Ok(result);
+		}
+	}
+	copy_between(&LocalFs, source, &LocalFs, dest, options, on_progress)
+}
+
+/// Put `dest` in place without writing its bytes, when it's on the same
+/// filesystem as `source`: a reflink, or for `CopyStrategy::Link` a hardlink
+/// if the filesystem can't clone. `None` means neither worked and the bytes
+/// have to be copied.
+///
+/// The source is still hashed once, so the job is recorded like any other
+/// copy. A clone or link holds exactly the source's blocks, so it counts as
+/// verified without reading it back.
+pub(crate) fn link_or_clone(
+	source: &Path,
+	dest: &Path,
+	options: &CopyOptions,
+) -> Result<Option<CopyResult>, CopyError> {
+	let source_stat = LocalFs.stat(source).map_err(|e| map_io_error(e, source))?;
+	if !source_stat.is_file {
+		return Err(CopyError::UnsupportedFileType(source.to_string_lossy().to_string()));
+	}
+	if let Some(parent) = dest.parent() {
+		fs::create_dir_all(parent).map_err(|e| map_io_error(e, dest))?;
+	}
+	if !same_filesystem(source, dest) {
+		return Ok(None);
+	}
+
+	let linked = if reflink(source, dest).is_ok() {
+		// A clone is a new file, so it takes the source's mode and mtime like a copy
+		let modified = options.source_modified.or(source_stat.modified);
+		preserve_metadata(&LocalFs, &source_stat, dest, modified)?;
+		true
+	} else if options.strategy == CopyStrategy::Link {
+		// A link can't be made over an existing file
+		let _ = fs::remove_file(dest);
+		fs::hard_link(source, dest).is_ok()
+	} else {
+		false
+	};
+	if !linked {
+		return Ok(None);
+	}
+
+	let source_hash = hash_file(source, HashAlgorithm::for_mode(options.verify))?;
+	Ok(Some(CopyResult {
+		bytes_copied: source_stat.len,
+		dest_hash: source_hash.clone(),
+		source_hash,
+		verified: options.verify != VerifyMode::None,
+	}))
+}
+
+/// Whether `dest`'s directory is on the same filesystem as `source`.
+#[cfg(unix)]
+fn same_filesystem(source: &Path, dest: &Path) -> bool {
+	use std::os::unix::fs::MetadataExt;
+
+	let dir = dest.parent().unwrap_or(dest);
+	match (fs::metadata(source), fs::metadata(dir)) {
+		(Ok(s), Ok(d)) => s.dev() == d.dev(),
+		_ => false,
+	}
+}
+
+#[cfg(not(unix))]
+fn same_filesystem(_source: &Path, _dest: &Path) -> bool {
+	false
+}
+
+/// Clone `source`'s blocks into a new `dest` with `FICLONE` (Btrfs, XFS).
+#[cfg(target_os = "linux")]
+fn reflink(source: &Path, dest: &Path) -> io::Result<()> {
+	use std::os::fd::AsRawFd;
+
+	// _IOW(0x94, 9, int)
+	const FICLONE: libc::c_ulong = 0x4004_9409;
+
+	let src = fs::File::open(source)?;
+	let dst = fs::File::create(dest)?;
+	if unsafe { libc::ioctl(dst.as_raw_fd(), FICLONE as _, src.as_raw_fd()) } != 0 {
+		let err = io::Error::last_os_error();
+		drop(dst);
+		let _ = fs::remove_file(dest);
+		return Err(err);
+	}
+	Ok(())
+}
+
+/// Clone `source` into a new `dest` with `clonefile` (APFS).
+#[cfg(target_os = "macos")]
+fn reflink(source: &Path, dest: &Path) -> io::Result<()> {
+	use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+	let to_c = |p: &Path| {
+		CString::new(p.as_os_str().as_bytes()).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+	};
+	let (src, dst) = (to_c(source)?, to_c(dest)?);
+	// clonefile won't replace an existing file
+	let _ = fs::remove_file(dest);
+	if unsafe { libc::clonefile(src.as_ptr(), dst.as_ptr(), 0) } != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn reflink(_source: &Path, _dest: &Path) -> io::Result<()> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "reflinks not supported on this platform"))
+}
+
+/// `copy_file` from a path on `source_fs` to one on `dest_fs`, e.g. onto a
+/// remote machine. Both ends are hashed the same way either way.
+pub fn copy_between<S: FileSystem, D: FileSystem>(
+	source_fs: &S,
+	source_path: &Path,
+	dest_fs: &D,
+	dest_path: &Path,
+	options: &CopyOptions,
+	mut on_progress: impl FnMut(u64),
+) -> Result<CopyResult, CopyError> {
+	// Opening a FIFO blocks until a writer shows up, so check before opening
+	let source_kind = source_fs.stat(source_path).map_err(|e| map_io_error(e, source_path))?;
+	if !source_kind.is_file {
+		return Err(CopyError::UnsupportedFileType(source_path.to_string_lossy().to_string()));
+	}
+
+	// Create destination parent directories
+	if let Some(parent) = dest_path.parent() {
+		dest_fs.create_dir_all(parent).map_err(|e| map_io_error(e, dest_path))?;
+	}
+
+	// Open source
+	let mut source = source_fs.open(source_path).map_err(|e| map_io_error(e, source_path))?;
+	let mut buf = vec![0u8; options.chunk_size.max(1)];
+	let algorithm = HashAlgorithm::for_mode(options.verify);
+
+	// Pick up where an interrupted attempt left off, or start fresh
+	let resumed = if options.resume_from > 0 {
+		let ends = ResumeEnds { source_fs, source_path, dest_fs, dest_path };
+		try_resume(&ends, &mut source, options.resume_from, algorithm, &mut buf)?
+	} else {
+		None
+	};
+
+	let (mut dest, mut hasher, mut bytes_copied) = match resumed {
+		Some(state) => state,
+		None => {
+			source
+				.seek(SeekFrom::Start(0))
+				.map_err(|e| map_io_error(e, source_path))?;
+			// Open dest (create/truncate)
+			let dest = dest_fs.create(dest_path).map_err(|e| map_io_error(e, dest_path))?;
+			(dest, Hasher::new(algorithm), 0)
+		}
+	};
+
+	// Single-pass: read → hash → write
+	let mut reported = bytes_copied;
+	let mut throttle = options.rate_limit.map(Throttle::new);
+	loop {
+		let n = source
+			.read(&mut buf)
+			.map_err(|e| map_io_error(e, source_path))?;
+		if n == 0 {
+			break;
+		}
+
+		hasher.update(&buf[..n]);
+		dest.write_all(&buf[..n])
+			.map_err(|e| map_io_error(e, dest_path))?;
+
+		bytes_copied += n as u64;
+		if let Some(throttle) = &mut throttle {
+			throttle.pace(n);
+		}
+		if bytes_copied - reported >= PROGRESS_STEP {
+			on_progress(bytes_copied);
+			reported = bytes_copied;
+		}
+
+		if options.cancel.as_ref().is_some_and(|c| c.load(Ordering::Relaxed)) {
+			// Leave what was written intact, so a resume can pick it up
+			dest.flush().map_err(|e| map_io_error(e, dest_path))?;
+			if bytes_copied > reported {
+				on_progress(bytes_copied);
+			}
+			return Err(CopyError::Cancelled);
+		}
+	}
+	if bytes_copied > reported {
+		on_progress(bytes_copied);
+	}
+
+	dest.flush().map_err(|e| map_io_error(e, dest_path))?;
+	if options.verify != VerifyMode::None {
+		dest_fs.sync(&mut dest).map_err(|e| map_io_error(e, dest_path))?;
+	}
+	drop(dest);
+
+	let source_hash = hasher.finalize_hex();
+
+	let reread = match options.verify {
+		VerifyMode::Paranoid => true,
+		VerifyMode::Hash | VerifyMode::FastHash => !dest_fs.trusts_writes(),
+		VerifyMode::SizeMtime | VerifyMode::None => false,
+	};
+	let result = if reread {
+		// Verify: re-read dest, compute hash
+		let dest_hash = hash_file_on(dest_fs, dest_path, algorithm)?;
+		if source_hash != dest_hash {
+			return Err(CopyError::HashMismatch { source_hash, dest_hash });
+		}
+		CopyResult { bytes_copied, source_hash, dest_hash, verified: true }
+	} else {
+		// Nothing re-read: the dest hash is what we wrote, and a clean fsync
+		// is taken to mean that's what's on disk
+		let verified = options.verify != VerifyMode::None;
+		CopyResult { bytes_copied, dest_hash: source_hash.clone(), source_hash, verified }
+	};
+
+	let source_stat = source_fs.stat(source_path).map_err(|e| map_io_error(e, source_path))?;
+	let modified = options.source_modified.or(source_stat.modified);
+	preserve_metadata(dest_fs, &source_stat, dest_path, modified)?;
+
+	if options.verify == VerifyMode::SizeMtime {
+		let dest_stat = dest_fs.stat(dest_path).map_err(|e| map_io_error(e, dest_path))?;
+		check_size_and_mtime(&dest_stat, bytes_copied, modified)?;
+	}
+
+	Ok(result)
+}
+
+/// `VerifyMode::SizeMtime`'s check: the destination is as long as what was
+/// written, and carries the mtime just applied to it.
+fn check_size_and_mtime(dest: &FileStat, len: u64, modified: Option<SystemTime>) -> Result<(), CopyError> {
+	if dest.len != len {
+		return Err(CopyError::MetadataMismatch(format!("{} bytes, expected {len}", dest.len)));
+	}
+	let (Some(expected), Some(actual)) = (modified, dest.modified) else {
+		return Ok(());
+	};
+	let drift = actual.duration_since(expected).unwrap_or_else(|e| e.duration());
+	if drift > MTIME_TOLERANCE {
+		return Err(CopyError::MetadataMismatch(format!("mtime is {}s off", drift.as_secs())));
+	}
+	Ok(())
+}
+
+/// Give the destination the source's permissions and modification time, so
+/// change detection sees an unchanged file and scripts stay executable.
+fn preserve_metadata(
+	dest_fs: &impl FileSystem,
+	source_stat: &FileStat,
+	dest_path: &Path,
+	modified: Option<SystemTime>,
+) -> Result<(), CopyError> {
+	dest_fs
+		.set_metadata(dest_path, source_stat.mode, modified)
+		.map_err(|e| map_io_error(e, dest_path))
+}
+
+/// Both sides of a copy, for `try_resume`.
+struct ResumeEnds<'a, S, D> {
+	source_fs: &'a S,
+	source_path: &'a Path,
+	dest_fs: &'a D,
+	dest_path: &'a Path,
+}
+
+/// Validate a partial destination left by an interrupted copy.
+///
+/// Compares the first `min(resume_from, dest_len)` bytes of source and dest.
+/// On a match, returns the dest opened for writing at that offset, an
+/// `algorithm` hasher primed with the prefix, and the offset; `source` is
+/// left positioned there.
+/// Returns `None` (caller re-copies from scratch) if the dest is missing,
+/// larger than the source, or its prefix differs.
+fn try_resume<S: FileSystem, D: FileSystem>(
+	ends: &ResumeEnds<'_, S, D>,
+	source: &mut S::File,
+	resume_from: u64,
+	algorithm: HashAlgorithm,
+	buf: &mut [u8],
+) -> Result<Option<(D::File, Hasher, u64)>, CopyError> {
+	let (source_path, dest_path) = (ends.source_path, ends.dest_path);
+	let Ok(dest_stat) = ends.dest_fs.stat(dest_path) else {
+		return Ok(None);
+	};
+	let source_len = ends
+		.source_fs
+		.stat(source_path)
+		.map_err(|e| map_io_error(e, source_path))?
+		.len;
+	if dest_stat.len > source_len {
+		tracing::warn!("partial dest larger than source, re-copying: {}", dest_path.display());
+		return Ok(None);
+	}
+
+	// Progress is recorded before the OS flushes, so the file may be shorter
+	let offset = resume_from.min(dest_stat.len);
+	if offset == 0 {
+		return Ok(None);
+	}
+
+	let mut dest = ends.dest_fs.open_rw(dest_path).map_err(|e| map_io_error(e, dest_path))?;
+
+	let mut hasher = Hasher::new(algorithm);
+	let mut dest_buf = vec![0u8; buf.len()];
+	let mut remaining = offset;
+	while remaining > 0 {
+		let n = remaining.min(buf.len() as u64) as usize;
+		source
+			.read_exact(&mut buf[..n])
+			.map_err(|e| map_io_error(e, source_path))?;
+		dest.read_exact(&mut dest_buf[..n])
+			.map_err(|e| map_io_error(e, dest_path))?;
+		if buf[..n] != dest_buf[..n] {
+			tracing::warn!("partial dest prefix differs from source, re-copying: {}", dest_path.display());
+			return Ok(None);
+		}
+		hasher.update(&buf[..n]);
+		remaining -= n as u64;
+	}
+
+	// Drop anything past the verified prefix and continue from there
+	ends.dest_fs
+		.set_len(&mut dest, offset)
+		.map_err(|e| map_io_error(e, dest_path))?;
+	dest.seek(SeekFrom::Start(offset))
+		.map_err(|e| map_io_error(e, dest_path))?;
+
+	tracing::info!("resuming {} at {} bytes", dest_path.display(), offset);
+	Ok(Some((dest, hasher, offset)))
+}
+
+/// Hash a file with `algorithm` in 256KB chunks.
+pub fn hash_file(path: impl AsRef<Path>, algorithm: HashAlgorithm) -> Result<String, CopyError> {
+	hash_file_on(&LocalFs, path.as_ref(), algorithm)
+}
+
+/// `hash_file` for a path on any filesystem.
+pub fn hash_file_on(filesystem: &impl FileSystem, path: &Path, algorithm: HashAlgorithm) -> Result<String, CopyError> {
+	let mut file = filesystem.open(path).map_err(|e| map_io_error(e, path))?;
+	let mut hasher = Hasher::new(algorithm);
+	let mut buf = vec![0u8; CHUNK_SIZE];
+
+	loop {
+		let n = file.read(&mut buf).map_err(|e| map_io_error(e, path))?;
+		if n == 0 {
+			break;
+		}
+		hasher.update(&buf[..n]);
+	}
+
+	Ok(hasher.finalize_hex())
+}
+
+/// Size and free space of a filesystem, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FsUsage {
+	pub total: u64,
+	/// Free space usable by non-root writers.
+	pub available: u64,
+}
+
+/// Bytes available to unprivileged writers on the filesystem holding `path`.
+///
+/// `path` may not exist yet (a destination about to be created); the nearest
+/// existing ancestor is measured instead.
+pub fn available_space(path: impl AsRef<Path>) -> io::Result<u64> {
+	let mut path = path.as_ref();
+	while !path.exists() {
+		path = path
+			.parent()
+			.ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no existing ancestor"))?;
+	}
+	Ok(filesystem_usage(path)?.available)
+}
+
+/// `statvfs` the filesystem mounted at (or containing) `path`.
+#[cfg(unix)]
+#[allow(clippy::unnecessary_cast)] // statvfs field widths differ between macOS and Linux
+pub fn filesystem_usage(path: impl AsRef<Path>) -> io::Result<FsUsage> {
+	use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+	let path_c = CString::new(path.as_ref().as_os_str().as_bytes())
+		.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+	let mut stat = unsafe { std::mem::zeroed::<libc::statvfs>() };
+	if unsafe { libc::statvfs(path_c.as_ptr(), &mut stat) } != 0 {
+		return Err(io::Error::last_os_error());
+	}
+	// Block counts are in f_frsize units; f_bavail excludes root-reserved blocks
+	let block = stat.f_frsize as u64;
+	Ok(FsUsage { total: stat.f_blocks as u64 * block, available: stat.f_bavail as u64 * block })
+}
+
+#[cfg(not(unix))]
+pub fn filesystem_usage(_path: impl AsRef<Path>) -> io::Result<FsUsage> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "free space check not supported on this platform"))
+}
+
+/// How `move_to_trash` disposed of a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disposal {
+	/// Moved to the OS trash — recoverable from Finder/Trash.
+	Trashed,
+	/// The filesystem has no trash (network shares, some external drives),
+	/// so the path was deleted permanently.
+	Removed,
+}
+
+/// Delete a file or directory by moving it to the OS trash, falling back to
+/// a permanent delete where the volume doesn't support trash. Deletions of
+/// user files (location removal) go through here rather than `fs::remove_*`.
+pub fn move_to_trash(path: impl AsRef<Path>) -> Result<Disposal, CopyError> {
+	let path = path.as_ref();
+
+	match trash::delete(path) {
+		Ok(()) => {
+			tracing::info!("moved to trash: {}", path.display());
+			Ok(Disposal::Trashed)
+		}
+		Err(e) => {
+			tracing::warn!("trash unavailable for {} ({}), deleting permanently", path.display(), e);
+			let removed = if path.is_dir() {
+				fs::remove_dir_all(path)
+			} else {
+				fs::remove_file(path)
+			};
+			removed.map_err(|e| map_io_error(e, path))?;
+			tracing::info!("permanently deleted: {}", path.display());
+			Ok(Disposal::Removed)
+		}
+	}
+}
+
+/// Map an I/O error to a `CopyError`. The path is only for the message, so
+/// it's converted lossily.
+pub(crate) fn map_io_error(err: io::Error, path: &Path) -> CopyError {
+	let path = path.to_string_lossy();
+	match err.kind() {
+		io::ErrorKind::NotFound => CopyError::SourceNotFound(path.to_string()),
+		io::ErrorKind::PermissionDenied => CopyError::PermissionDenied(path.to_string()),
+		io::ErrorKind::StorageFull => CopyError::DiskFull(path.to_string()),
+		_ => CopyError::IoError(format!("{path}: {err}")),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn copy_and_verify_small_file() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.txt");
+		let dst = tmp.path().join("dest.txt");
+		fs::write(&src, "hello world").unwrap();
+
+		// Copy manually using the same pipeline as copy_file
+		let mut source = fs::File::open(&src).unwrap();
+		let mut dest = fs::File::create(&dst).unwrap();
+		let mut hasher = blake3::Hasher::new();
+		let mut buf = vec![0u8; CHUNK_SIZE];
+		loop {
+			let n = source.read(&mut buf).unwrap();
+			if n == 0 {
+				break;
+			}
+			hasher.update(&buf[..n]);
+			dest.write_all(&buf[..n]).unwrap();
+		}
+		drop(dest);
+
+		let source_hash = hasher.finalize().to_hex().to_string();
+		let dest_hash = hash_file(dst.to_str().unwrap(), HashAlgorithm::Blake3).unwrap();
+
+		assert_eq!(source_hash, dest_hash);
+		assert_eq!(fs::read_to_string(&dst).unwrap(), "hello world");
+	}
+
+	#[test]
+	fn copy_file_creates_parents_and_verifies() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.bin");
+		let dst = tmp.path().join("nested/deeper/dest.bin");
+		let data = vec![7u8; CHUNK_SIZE * 2 + 17];
+		fs::write(&src, &data).unwrap();
+
+		let mut progress = Vec::new();
+		let result = copy_file(
+			src.to_str().unwrap(),
+			dst.to_str().unwrap(),
+			&CopyOptions::default(),
+			|bytes| progress.push(bytes),
+		)
+		.unwrap();
+
+		assert!(result.verified);
+		assert_eq!(result.bytes_copied, data.len() as u64);
+		assert_eq!(result.source_hash, blake3::hash(&data).to_hex().to_string());
+		assert_eq!(fs::read(&dst).unwrap(), data);
+		assert_eq!(progress.last().copied(), Some(data.len() as u64));
+	}
+
+	#[test]
+	fn progress_is_reported_per_step_not_per_chunk() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.bin");
+		let dst = tmp.path().join("dest.bin");
+		let data = vec![9u8; PROGRESS_STEP as usize * 3 + 5];
+		fs::write(&src, &data).unwrap();
+
+		let options = CopyOptions { chunk_size: MIN_CHUNK_SIZE, ..Default::default() };
+		let mut progress = Vec::new();
+		copy_file(&src, &dst, &options, |bytes| progress.push(bytes)).unwrap();
+
+		assert_eq!(
+			progress,
+			vec![PROGRESS_STEP, PROGRESS_STEP * 2, PROGRESS_STEP * 3, data.len() as u64]
+		);
+	}
+
+	#[test]
+	fn chunk_size_follows_file_size_and_speed() {
+		assert_eq!(chunk_size_for(100, &SpeedMode::Normal), MIN_CHUNK_SIZE);
+		assert_eq!(chunk_size_for(100_000, &SpeedMode::Blast), 128 * 1024);
+		assert_eq!(chunk_size_for(1 << 30, &SpeedMode::Normal), CHUNK_SIZE);
+		assert_eq!(chunk_size_for(1 << 30, &SpeedMode::Blast), BLAST_CHUNK_SIZE);
+		assert_eq!(chunk_size_for(u64::MAX, &SpeedMode::Ninja), CHUNK_SIZE);
+	}
+
+	#[test]
+	fn rate_limit_slows_the_copy() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.bin");
+		let dst = tmp.path().join("dest.bin");
+		fs::write(&src, vec![3u8; 1024 * 1024]).unwrap();
+
+		// A mebibyte at 4 MiB/s takes at least a quarter second
+		let options = CopyOptions { rate_limit: Some(4 * 1024 * 1024), ..Default::default() };
+		let started = Instant::now();
+		copy_file(&src, &dst, &options, |_| {}).unwrap();
+		assert!(started.elapsed() >= Duration::from_millis(240), "took {:?}", started.elapsed());
+	}
+
+	/// Compares the default buffer with `BLAST_CHUNK_SIZE` on a large file.
+	/// Run with `cargo test -p daemon --release -- --ignored --nocapture`.
+	#[test]
+	#[ignore = "benchmark"]
+	fn bench_chunk_sizes() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("large.bin");
+		let data: Vec<u8> = (0..256 * 1024 * 1024).map(|i: usize| (i % 251) as u8).collect();
+		fs::write(&src, &data).unwrap();
+
+		for chunk_size in [CHUNK_SIZE, BLAST_CHUNK_SIZE] {
+			let dst = tmp.path().join(format!("copy-{chunk_size}.bin"));
+			let options = CopyOptions { chunk_size, ..Default::default() };
+			let started = std::time::Instant::now();
+			copy_file(&src, &dst, &options, |_| {}).unwrap();
+			let secs = started.elapsed().as_secs_f64();
+			println!(
+				"{:>5} KiB chunks: {:.2}s ({:.0} MiB/s)",
+				chunk_size / 1024,
+				secs,
+				data.len() as f64 / (1024.0 * 1024.0) / secs
+			);
+		}
+	}
+
+	#[test]
+	fn copy_file_resumes_from_partial_dest() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.bin");
+		let dst = tmp.path().join("dest.bin");
+		let data: Vec<u8> = (0..CHUNK_SIZE * 3).map(|i| (i % 251) as u8).collect();
+		fs::write(&src, &data).unwrap();
+		fs::write(&dst, &data[..CHUNK_SIZE + 100]).unwrap();
+
+		let options = CopyOptions { resume_from: CHUNK_SIZE as u64 + 100, ..Default::default() };
+		let mut progress = Vec::new();
+		let result = copy_file(&src, &dst, &options, |bytes| progress.push(bytes)).unwrap();
+
+		assert!(result.verified);
+		assert_eq!(result.source_hash, blake3::hash(&data).to_hex().to_string());
+		assert_eq!(fs::read(&dst).unwrap(), data);
+		// No chunk was re-sent from the start
+		assert!(progress.iter().all(|&b| b > CHUNK_SIZE as u64 + 100));
+	}
+
+	#[test]
+	fn copy_file_recopies_when_partial_prefix_differs() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.bin");
+		let dst = tmp.path().join("dest.bin");
+		let data = vec![1u8; CHUNK_SIZE * 2];
+		fs::write(&src, &data).unwrap();
+		fs::write(&dst, vec![9u8; 1000]).unwrap();
+
+		let options = CopyOptions { resume_from: 1000, ..Default::default() };
+		let result = copy_file(&src, &dst, &options, |_| {}).unwrap();
+
+		assert!(result.verified);
+		assert_eq!(fs::read(&dst).unwrap(), data);
+	}
+
+	#[test]
+	fn copy_file_recopies_when_partial_larger_than_source() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.txt");
+		let dst = tmp.path().join("dest.txt");
+		fs::write(&src, "short").unwrap();
+		fs::write(&dst, "short but with stale trailing bytes").unwrap();
+
+		let options = CopyOptions { resume_from: 5, ..Default::default() };
+		let result = copy_file(&src, &dst, &options, |_| {}).unwrap();
+
+		assert!(result.verified);
+		assert_eq!(fs::read_to_string(&dst).unwrap(), "short");
+	}
+
+	#[test]
+	fn copy_file_missing_source() {
+		let tmp = tempfile::tempdir().unwrap();
+		let dst = tmp.path().join("dest.txt");
+
+		let err = copy_file(
+			"/tmp/kip_definitely_not_real.txt",
+			dst.to_str().unwrap(),
+			&CopyOptions::default(),
+			|_| {},
+		)
+		.unwrap_err();
+		assert!(matches!(err, CopyError::SourceNotFound(_)));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn link_strategy_skips_the_byte_copy_on_one_filesystem() {
+		use std::os::unix::fs::MetadataExt;
+
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("source.bin");
+		let dst = tmp.path().join("nested/dest.bin");
+		fs::write(&src, "same filesystem").unwrap();
+		fs::create_dir_all(dst.parent().unwrap()).unwrap();
+		fs::write(&dst, "stale").unwrap();
+
+		let options = CopyOptions { strategy: CopyStrategy::Link, ..Default::default() };
+		let result = copy_file(&src, &dst, &options, |_| {}).unwrap();
+
+		assert!(result.verified);
+		assert_eq!(result.source_hash, hash_file(&src, HashAlgorithm::Blake3).unwrap());
+		assert_eq!(fs::read_to_string(&dst).unwrap(), "same filesystem");
+		// Cloned if the filesystem can, else the very same file
+		let (s, d) = (fs::metadata(&src).unwrap(), fs::metadata(&dst).unwrap());
+		assert!(s.ino() == d.ino() || s.mtime() == d.mtime());
+	}
+
+	// APFS refuses names that aren't valid UTF-8
+	#[cfg(target_os = "linux")]
+	#[test]
+	fn copy_file_non_utf8_name() {
+		use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join(OsStr::from_bytes(b"src-\xff\xfe.bin"));
+		let dst = tmp.path().join("out").join(OsStr::from_bytes(b"dst-\xff\xfe.bin"));
+		fs::write(&src, "odd name").unwrap();
+
+		let result = copy_file(&src, &dst, &CopyOptions::default(), |_| {}).unwrap();
+
+		assert!(result.verified);
+		assert_eq!(fs::read_to_string(&dst).unwrap(), "odd name");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn copy_file_preserves_mode_and_mtime() {
+		use std::os::unix::fs::PermissionsExt;
+
+		use filetime::FileTime;
+
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("script.sh");
+		let dst = tmp.path().join("out/script.sh");
+		fs::write(&src, "#!/bin/sh\necho hi\n").unwrap();
+		fs::set_permissions(&src, fs::Permissions::from_mode(0o754)).unwrap();
+		let mtime = FileTime::from_unix_time(1_600_000_000, 123_456_789);
+		filetime::set_file_mtime(&src, mtime).unwrap();
+
+		copy_file(&src, &dst, &CopyOptions::default(), |_| {}).unwrap();
+
+		let meta = fs::metadata(&dst).unwrap();
+		assert_eq!(meta.permissions().mode() & 0o777, 0o754);
+		assert_eq!(FileTime::from_last_modification_time(&meta), mtime);
+
+		// A scan-time mtime takes precedence over re-reading the source
+		let scanned = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+		let options = CopyOptions { source_modified: Some(scanned), ..Default::default() };
+		copy_file(&src, &dst, &options, |_| {}).unwrap();
+		assert_eq!(fs::metadata(&dst).unwrap().modified().unwrap(), scanned);
+	}
+
+	#[test]
+	fn copies_zero_byte_file() {
+		let tmp = tempfile::tempdir().unwrap();
+		let src = tmp.path().join("empty.txt");
+		let dst = tmp.path().join("out/empty.txt");
+		fs::write(&src, "").unwrap();
+
+		let result = copy_file(&src, &dst, &CopyOptions::default(), |_| {}).unwrap();
+
+		assert_eq!(result.bytes_copied, 0);
+		assert!(result.verified);
+		assert_eq!(result.source_hash, blake3::hash(b"").to_hex().to_string());
+		assert_eq!(fs::metadata(&dst).unwrap().len(), 0);
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn fifo_source_is_refused_without_reading() {
+		let tmp = tempfile::tempdir().unwrap();
+		let fifo = tmp.path().join("pipe");
+		let path = std::ffi::CString::new(fifo.to_str().unwrap()).unwrap();
+		assert_eq!(unsafe { libc::mkfifo(path.as_ptr(), 0o644) }, 0);
+
+		// Opening the FIFO would hang this test
+		let err = copy_file(&fifo, tmp.path().join("out"), &CopyOptions::default(), |_| {}).unwrap_err();
+
+		assert!(matches!(err, CopyError::UnsupportedFileType(_)));
+		assert_eq!(classify_error(&err), "unsupported_file_type");
+		assert!(!tmp.path().join("out").exists());
+	}
+
+	#[test]
+	fn hash_empty_file() {
+		let tmp = tempfile::tempdir().unwrap();
+		let f = tmp.path().join("empty.txt");
+		fs::write(&f, "").unwrap();
+
+		let hash = hash_file(f.to_str().unwrap(), HashAlgorithm::Blake3).unwrap();
+		assert_eq!(hash.len(), 64); // blake3 hex
+	}
+
+	#[test]
+	fn hash_multichunk_file() {
+		let tmp = tempfile::tempdir().unwrap();
+		let f = tmp.path().join("big.bin");
+		let data = vec![42u8; CHUNK_SIZE * 3 + 1000];
+		fs::write(&f, &data).unwrap();
+
+		let hash = hash_file(f.to_str().unwrap(), HashAlgorithm::Blake3).unwrap();
+
+		// Must match blake3 computed in one shot
+		let expected = blake3::hash(&data).to_hex().to_string();
+		assert_eq!(hash, expected);
+	}
+
+	#[test]
+	fn hash_file_not_found() {
+		let err = hash_file("/tmp/kip_definitely_not_real.txt", HashAlgorithm::Blake3).unwrap_err();
+		assert!(matches!(err, CopyError::SourceNotFound(_)));
+	}
+
+	#[test]
+	fn fast_hash_copies_with_xxh3() {
+		let tmp = tempfile::tempdir().unwrap();
+		let (src, dst) = (tmp.path().join("source.bin"), tmp.path().join("dest.bin"));
+		let data = vec![3u8; CHUNK_SIZE + 5];
+		fs::write(&src, &data).unwrap();
+
+		let options = CopyOptions { verify: VerifyMode::FastHash, ..Default::default() };
+		let result = copy_file(&src, &dst, &options, |_| {}).unwrap();
+
+		let expected = format!("{:032x}", xxhash_rust::xxh3::xxh3_128(&data));
+		assert!(result.verified);
+		assert_eq!(result.source_hash, expected);
+		assert_eq!(hash_file(&dst, HashAlgorithm::Xxh3).unwrap(), expected);
+		assert_eq!(HashAlgorithm::of_digest(&result.source_hash), HashAlgorithm::Xxh3);
+	}
+
+	/// `LocalFs` that counts reads and can pretend to be remote.
+	struct CountingFs {
+		opens: std::cell::Cell<u32>,
+		trusted: bool,
+	}
+
+	impl FileSystem for CountingFs {
+		type File = fs::File;
+
+		fn open(&self, path: &Path) -> io::Result<fs::File> {
+			self.opens.set(self.opens.get() + 1);
+			LocalFs.open(path)
+		}
+		fn create(&self, path: &Path) -> io::Result<fs::File> {
+			LocalFs.create(path)
+		}
+		fn open_rw(&self, path: &Path) -> io::Result<fs::File> {
+			LocalFs.open_rw(path)
+		}
+		fn set_len(&self, file: &mut fs::File, len: u64) -> io::Result<()> {
+			LocalFs.set_len(file, len)
+		}
+		fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+			LocalFs.create_dir_all(path)
+		}
+		fn stat(&self, path: &Path) -> io::Result<FileStat> {
+			LocalFs.stat(path)
+		}
+		fn set_metadata(&self, path: &Path, mode: Option<u32>, modified: Option<SystemTime>) -> io::Result<()> {
+			LocalFs.set_metadata(path, mode, modified)
+		}
+		fn sync(&self, file: &mut fs::File) -> io::Result<()> {
+			LocalFs.sync(file)
+		}
+		fn trusts_writes(&self) -> bool {
+			self.trusted
+		}
+	}
+
+	#[test]
+	fn trusted_writes_are_not_read_back_unless_paranoid() {
+		let tmp = tempfile::tempdir().unwrap();
+		let (src, dst) = (tmp.path().join("source.txt"), tmp.path().join("dest.txt"));
+		fs::write(&src, "hello world").unwrap();
+
+		let reads = |verify, trusted| {
+			let dest_fs = CountingFs { opens: std::cell::Cell::new(0), trusted };
+			let options = CopyOptions { verify, ..Default::default() };
+			let result = copy_between(&LocalFs, &src, &dest_fs, &dst, &options, |_| {}).unwrap();
+			assert!(result.verified);
+			assert_eq!(result.dest_hash, blake3::hash(b"hello world").to_hex().to_string());
+			dest_fs.opens.get()
+		};
+
+		assert_eq!(reads(VerifyMode::Hash, true), 0);
+		assert_eq!(reads(VerifyMode::Hash, false), 1, "remote copies are still read back");
+		assert_eq!(reads(VerifyMode::Paranoid, true), 1);
+	}
+
+	#[test]
+	fn cancel_stops_at_the_next_chunk() {
+		let tmp = tempfile::tempdir().unwrap();
+		let (src, dst) = (tmp.path().join("source.bin"), tmp.path().join("dest.bin"));
+		fs::write(&src, vec![9u8; PROGRESS_STEP as usize * 4]).unwrap();
+
+		// Raised at the first progress report, a step into the file
+		let cancel = Arc::new(AtomicBool::new(false));
+		let options = CopyOptions { chunk_size: 1024, cancel: Some(cancel.clone()), ..Default::default() };
+		let err = copy_file(&src, &dst, &options, |_| cancel.store(true, Ordering::Relaxed)).unwrap_err();
+
+		assert!(matches!(err, CopyError::Cancelled));
+		assert!(!err.is_retryable());
+		assert_eq!(fs::metadata(&dst).unwrap().len(), PROGRESS_STEP, "the partial copy is kept for resuming");
+	}
+
+	#[test]
+	fn hash_algorithm_per_mode() {
+		assert_eq!(HashAlgorithm::for_mode(VerifyMode::Hash), HashAlgorithm::Blake3);
+		assert_eq!(HashAlgorithm::for_mode(VerifyMode::Paranoid), HashAlgorithm::Blake3);
+		assert_eq!(HashAlgorithm::for_mode(VerifyMode::FastHash), HashAlgorithm::Xxh3);
+		assert_eq!(HashAlgorithm::for_mode(VerifyMode::SizeMtime), HashAlgorithm::Blake3);
+		assert_eq!(HashAlgorithm::for_mode(VerifyMode::None), HashAlgorithm::Blake3);
+		assert_eq!(HashAlgorithm::of_digest(&"0".repeat(64)), HashAlgorithm::Blake3);
+	}
+
+	#[test]
+	fn unverified_modes_skip_the_reread() {
+		let tmp = tempfile::tempdir().unwrap();
+		let (src, dst) = (tmp.path().join("source.txt"), tmp.path().join("dest.txt"));
+		fs::write(&src, "hello world").unwrap();
+
+		let size_mtime = CopyOptions { verify: VerifyMode::SizeMtime, ..Default::default() };
+		assert!(copy_file(&src, &dst, &size_mtime, |_| {}).unwrap().verified);
+		let none = CopyOptions { verify: VerifyMode::None, ..Default::default() };
+		assert!(!copy_file(&src, &dst, &none, |_| {}).unwrap().verified);
+		assert_eq!(fs::read_to_string(&dst).unwrap(), "hello world");
+	}
+
+	#[test]
+	fn size_mtime_check_catches_drift() {
+		let now = SystemTime::now();
+		let stat = |len, modified| FileStat { len, is_file: true, mode: None, modified: Some(modified) };
+
+		assert!(check_size_and_mtime(&stat(10, now), 10, Some(now)).is_ok());
+		// FAT rounds mtimes to two seconds
+		assert!(check_size_and_mtime(&stat(10, now + Duration::from_secs(1)), 10, Some(now)).is_ok());
+		let short = check_size_and_mtime(&stat(7, now), 10, Some(now)).unwrap_err();
+		assert_eq!(classify_error(&short), "verify_failed");
+		assert!(check_size_and_mtime(&stat(10, now - Duration::from_secs(60)), 10, Some(now)).is_err());
+	}
+
+	#[test]
+	fn error_classification() {
+		assert!(CopyError::IoError("tmp".into()).is_retryable());
+		assert!(!CopyError::SourceNotFound("x".into()).is_retryable());
+		assert!(!CopyError::PermissionDenied("x".into()).is_retryable());
+		assert!(!CopyError::DiskFull("x".into()).is_retryable());
+		assert!(!CopyError::HashMismatch { source_hash: "a".into(), dest_hash: "b".into() }.is_retryable());
+	}
+
+	#[test]
+	fn available_space_measures_nearest_existing_dir() {
+		let tmp = tempfile::tempdir().unwrap();
+		let here = available_space(tmp.path()).unwrap();
+		assert!(here > 0);
+		// A not-yet-created destination measures its parent's filesystem
+		assert!(available_space(tmp.path().join("not/yet/created")).unwrap() > 0);
+
+		let usage = filesystem_usage(tmp.path()).unwrap();
+		assert!(usage.total >= usage.available);
+	}
+
+	#[test]
+	fn backoff_doubles_then_caps() {
+		assert_eq!(backoff_delay(0), Duration::from_secs(2));
+		assert_eq!(backoff_delay(1), Duration::from_secs(4));
+		assert_eq!(backoff_delay(3), Duration::from_secs(16));
+		assert_eq!(backoff_delay(10), RETRY_MAX_DELAY);
+		assert_eq!(backoff_delay(u32::MAX), RETRY_MAX_DELAY);
+	}
+
+	#[test]
+	fn retry_policy_per_intent() {
+		let policy = vec!["io_error".to_string(), "permission_denied".to_string()];
+		assert!(CopyError::IoError("tmp".into()).is_retryable_for(&policy));
+		assert!(CopyError::PermissionDenied("x".into()).is_retryable_for(&policy));
+		assert!(!CopyError::DiskFull("x".into()).is_retryable_for(&policy));
+		assert!(!CopyError::IoError("tmp".into()).is_retryable_for(&[]));
+	}
+}
@@ -0,0 +1,260 @@
+//! Content-addressed dedup via `file_record` and `exists_at`.
+//!
+//! A `file_record` is one unique blob (hash + size). Each verified copy of it
+//! is an `exists_at` edge to the location holding it, with the path on disk.
+//! `copy_job` consults these before writing, so re-running an unchanged sync
+//! doesn't re-copy anything. `dedup_report` sums up what the tables know:
+//! how much content is stored more than once, and what dedup spared.
+
+use surrealdb::types::RecordId;
+
+use crate::{db::DbHandle, engine::copier::CopyError};
+
+/// Find a live copy of `hash`/`size` at `location`, returning its path.
+pub async fn find_existing(
+	db: &DbHandle,
+	hash: &str,
+	size: u64,
+	location: &RecordId,
+) -> Result<Option<String>, CopyError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT VALUE path FROM exists_at
+             WHERE out = $location AND stale = false
+               AND in.hash = $hash AND in.size = $size
+             LIMIT 1",
+		)
+		.bind(("location", location.clone()))
+		.bind(("hash", hash.to_string()))
+		.bind(("size", size as i64))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	let paths: Vec<String> = response
+		.take(0)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	Ok(paths.into_iter().next())
+}
+
+/// Record that the blob `hash`/`size` now lives at `path` inside `location`,
+/// creating its `file_record` on first sight. Re-recording the same path
+/// refreshes `verified_at` instead of adding a second edge.
+pub async fn record_file_at(
+	db: &DbHandle,
+	hash: &str,
+	size: u64,
+	location: &RecordId,
+	path: &str,
+) -> Result<(), CopyError> {
+	db.db
+		.query(
+			"LET $found = (SELECT VALUE id FROM file_record WHERE hash = $hash AND size = $size LIMIT 1);
+             LET $record = IF array::len($found) > 0 {
+                 $found[0]
+             } ELSE {
+                 (CREATE ONLY file_record CONTENT { hash: $hash, size: $size, first_seen: time::now() }).id
+             };
+             DELETE exists_at WHERE in = $record AND out = $location AND path = $path;
+             RELATE $record->exists_at->$location CONTENT {
+                 path: $path,
+                 modified_at: time::now(),
+                 verified_at: time::now(),
+                 stale: false,
+             };",
+		)
+		.bind(("hash", hash.to_string()))
+		.bind(("size", size as i64))
+		.bind(("location", location.clone()))
+		.bind(("path", path.to_string()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	Ok(())
+}
+
+/// Flag the copy recorded at `path` inside `location` as no longer holding
+/// its content, so lookups stop offering it.
+pub async fn mark_stale(db: &DbHandle, location: &RecordId, path: &str) -> Result<(), CopyError> {
+	db.db
+		.query("UPDATE exists_at SET stale = true WHERE out = $location AND path = $path")
+		.bind(("location", location.clone()))
+		.bind(("path", path.to_string()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	Ok(())
+}
+
+/// Duplicate content across tracked locations, from `file_record` and
+/// `exists_at`, and the copying dedup has already spared.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DedupStats {
+	/// Distinct blobs with at least one live copy.
+	pub unique_files: u64,
+	/// Blobs with live copies in more than one place.
+	pub duplicated_files: u64,
+	/// Space taken by every copy beyond each blob's first: what removing the
+	/// duplicates would free.
+	pub duplicate_bytes: u64,
+	/// Size of the completed jobs dedup satisfied without writing a byte.
+	pub saved_bytes: u64,
+}
+
+/// Count live copies per blob and the jobs dedup completed without copying.
+pub async fn dedup_report(db: &DbHandle) -> Result<DedupStats, CopyError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT in, count() AS copies, math::max(in.size) AS size FROM exists_at
+             WHERE stale = false GROUP BY in;
+             SELECT math::sum(size) AS total FROM transfer_job
+             WHERE status = 'complete' AND bytes_transferred = 0 AND size > 0 GROUP ALL;",
+		)
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	let blobs: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	let saved: Option<serde_json::Value> = response
+		.take(1)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	let mut stats = DedupStats {
+		unique_files: blobs.len() as u64,
+		saved_bytes: saved.and_then(|r| r["total"].as_u64()).unwrap_or(0),
+		..Default::default()
+	};
+	for blob in &blobs {
+		let copies = blob["copies"].as_u64().unwrap_or(0);
+		if copies > 1 {
+			stats.duplicated_files += 1;
+			stats.duplicate_bytes += blob["size"].as_u64().unwrap_or(0) * (copies - 1);
+		}
+	}
+	Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::init_memory;
+
+	async fn setup() -> (DbHandle, RecordId) {
+		let db = init_memory().await.unwrap();
+		let location = RecordId::new("location", "dest");
+		db.db
+			.query("CREATE $id CONTENT { path: '/backup', available: true }")
+			.bind(("id", location.clone()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		(db, location)
+	}
+
+	#[tokio::test]
+	async fn lookup_finds_recorded_copy() {
+		let (db, location) = setup().await;
+		record_file_at(&db, "abc", 42, &location, "/backup/a.txt").await.unwrap();
+
+		let found = find_existing(&db, "abc", 42, &location).await.unwrap();
+		assert_eq!(found.as_deref(), Some("/backup/a.txt"));
+	}
+
+	#[tokio::test]
+	async fn lookup_requires_matching_size_and_location() {
+		let (db, location) = setup().await;
+		record_file_at(&db, "abc", 42, &location, "/backup/a.txt").await.unwrap();
+
+		assert!(find_existing(&db, "abc", 43, &location).await.unwrap().is_none());
+		assert!(find_existing(&db, "xyz", 42, &location).await.unwrap().is_none());
+		let elsewhere = RecordId::new("location", "other");
+		assert!(find_existing(&db, "abc", 42, &elsewhere).await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn lookup_ignores_stale_copies() {
+		let (db, location) = setup().await;
+		record_file_at(&db, "abc", 42, &location, "/backup/a.txt").await.unwrap();
+		db.db.query("UPDATE exists_at SET stale = true").await.unwrap();
+
+		assert!(find_existing(&db, "abc", 42, &location).await.unwrap().is_none());
+	}
+
+	#[tokio::test]
+	async fn marking_stale_hides_only_that_copy() {
+		let (db, location) = setup().await;
+		record_file_at(&db, "abc", 42, &location, "/backup/a.txt").await.unwrap();
+		record_file_at(&db, "abc", 42, &location, "/backup/b.txt").await.unwrap();
+		mark_stale(&db, &location, "/backup/a.txt").await.unwrap();
+
+		let found = find_existing(&db, "abc", 42, &location).await.unwrap();
+		assert_eq!(found.as_deref(), Some("/backup/b.txt"));
+	}
+
+	#[tokio::test]
+	async fn recording_twice_reuses_record_and_edge() {
+		let (db, location) = setup().await;
+		record_file_at(&db, "abc", 42, &location, "/backup/a.txt").await.unwrap();
+		record_file_at(&db, "abc", 42, &location, "/backup/a.txt").await.unwrap();
+		record_file_at(&db, "abc", 42, &location, "/backup/copy.txt").await.unwrap();
+
+		let mut resp = db
+			.db
+			.query("SELECT VALUE id FROM file_record; SELECT VALUE id FROM exists_at;")
+			.await
+			.unwrap();
+		let records: Vec<RecordId> = resp.take(0).unwrap();
+		let edges: Vec<RecordId> = resp.take(1).unwrap();
+		assert_eq!(records.len(), 1);
+		assert_eq!(edges.len(), 2);
+	}
+
+	#[tokio::test]
+	async fn report_counts_blobs_stored_more_than_once() {
+		let (db, location) = setup().await;
+		let other = RecordId::new("location", "other");
+		record_file_at(&db, "abc", 100, &location, "/backup/a.txt").await.unwrap();
+		record_file_at(&db, "abc", 100, &other, "/mirror/a.txt").await.unwrap();
+		record_file_at(&db, "abc", 100, &location, "/backup/copy.txt").await.unwrap();
+		record_file_at(&db, "xyz", 7, &location, "/backup/x.txt").await.unwrap();
+		db.db
+			.query(
+				"CREATE transfer_job CONTENT {
+                    intent: intent:x, destination: location:dest, source_path: '/src/a', dest_path: '/backup/copy.txt',
+                    size: 100, bytes_transferred: 0, status: 'complete',
+                };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let stats = dedup_report(&db).await.unwrap();
+		assert_eq!(
+			stats,
+			DedupStats { unique_files: 2, duplicated_files: 1, duplicate_bytes: 200, saved_bytes: 100 }
+		);
+	}
+
+	#[tokio::test]
+	async fn report_ignores_stale_copies() {
+		let (db, location) = setup().await;
+		let other = RecordId::new("location", "other");
+		record_file_at(&db, "abc", 100, &location, "/backup/a.txt").await.unwrap();
+		record_file_at(&db, "abc", 100, &other, "/mirror/a.txt").await.unwrap();
+		db.db.query("UPDATE exists_at SET stale = true WHERE out = location:other").await.unwrap();
+
+		let stats = dedup_report(&db).await.unwrap();
+		assert_eq!(stats.duplicated_files, 0);
+		assert_eq!(stats.duplicate_bytes, 0);
+	}
+}
@@ -0,0 +1,316 @@
+//! Pausing and resuming intents as their drives come and go.
+//!
+//! The frontend's drive poller reports drives that disappeared or came back.
+//! A transferring intent that reads from or writes to a vanished drive is
+//! parked as `waiting_for_device`, so its jobs don't burn through retries
+//! against a missing mount, and is picked back up when the drive returns.
+//! Starting an intent whose drive isn't plugged in parks it the same way.
+
+use surrealdb::types::RecordId;
+use tracing::{info, warn};
+
+use crate::{
+	db::DbHandle,
+	engine::{
+		scanner,
+		scheduler::{self, SchedulerError},
+	},
+	events::{self, EngineEvent},
+	graph_store::rid_string,
+};
+
+/// An intent affected by a drive change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AffectedIntent {
+	pub id: RecordId,
+	pub name: Option<String>,
+}
+
+/// Park every transferring intent whose source or a destination is on
+/// `drive`, and emit a `DriveDisconnected` event naming them. Jobs already
+/// in flight stop at their next chunk and go back to `pending`.
+pub async fn pause_intents_for_drive(
+	db: &DbHandle,
+	drive: &RecordId,
+	drive_name: &str,
+) -> Result<Vec<AffectedIntent>, SchedulerError> {
+	let paused = update_intents_on_drive(db, drive, "transferring", "waiting_for_device").await?;
+	if !paused.is_empty() {
+		events::emit(EngineEvent::DriveDisconnected {
+			drive: drive_name.to_string(),
+			intents: paused.iter().map(display_name).collect(),
+		});
+	}
+	Ok(paused)
+}
+
+/// Pick intents waiting on `drive` back up now that it's connected again.
+///
+/// Intents whose other drives are still missing keep waiting. An intent that
+/// was parked mid-transfer goes back to `transferring` and its remaining jobs
+/// run; one that never got to scan goes back to `idle` and is scanned first.
+pub async fn resume_intents_for_drive(db: &DbHandle, drive: &RecordId) -> Result<Vec<AffectedIntent>, SchedulerError> {
+	let ready = ready_to_resume(db, drive).await?;
+	let mut resumed = Vec::with_capacity(ready.len());
+
+	for intent in ready {
+		let scanned = has_jobs(db, &intent.id).await?;
+		let next_status = if scanned { "transferring" } else { "idle" };
+		let mut response = db
+			.db
+			.query(
+				"UPDATE $id SET status = $status, updated_at = time::now()
+                 WHERE status = 'waiting_for_device' RETURN id",
+			)
+			.bind(("id", intent.id.clone()))
+			.bind(("status", next_status.to_string()))
+			.await
+			.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+		let claimed: Vec<serde_json::Value> = response
+			.take(0)
+			.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+		if claimed.is_empty() {
+			continue;
+		}
+
+		info!("resuming {} after its drive reconnected", display_name(&intent));
+		let db = db.clone();
+		let id = intent.id.clone();
+		tokio::spawn(async move {
+			if !scanned {
+				if let Err(e) = scanner::scan_intent(&db, &id).await {
+					warn!("rescanning resumed intent {:?} failed: {}", id, e);
+					return;
+				}
+			}
+			if let Err(e) = scheduler::run_intent(&db, &id).await {
+				warn!("resumed intent {:?} failed: {}", id, e);
+			}
+		});
+		resumed.push(intent);
+	}
+
+	Ok(resumed)
+}
+
+/// Names of disconnected drives holding the intent's source or destinations.
+pub async fn missing_drives(db: &DbHandle, intent_id: &RecordId) -> Result<Vec<String>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"LET $intent = (SELECT source, destinations FROM ONLY $id);
+             LET $drives = array::concat(
+                 [(<record> $intent.source).drive],
+                 ($intent.destinations ?? []).map(|$d| (<record> $d).drive)
+             );
+             SELECT VALUE name FROM drive WHERE connected = false AND id IN $drives;",
+		)
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	response
+		.take(2)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))
+}
+
+/// Waiting intents with a source or destination location on `drive` and no
+/// other drive still missing.
+async fn ready_to_resume(db: &DbHandle, drive: &RecordId) -> Result<Vec<AffectedIntent>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"LET $locations = (SELECT VALUE id FROM location WHERE drive = $drive);
+             SELECT id, name FROM intent
+             WHERE status = 'waiting_for_device'
+               AND (<record> source IN $locations
+                    OR array::len(array::intersect(destinations.map(|$d| <record> $d), $locations)) > 0);",
+		)
+		.bind(("drive", drive.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(1)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let mut ready = Vec::new();
+	for intent in rows.iter().filter_map(parse_affected) {
+		if missing_drives(db, &intent.id).await?.is_empty() {
+			ready.push(intent);
+		}
+	}
+	Ok(ready)
+}
+
+async fn has_jobs(db: &DbHandle, intent_id: &RecordId) -> Result<bool, SchedulerError> {
+	let mut response = db
+		.db
+		.query("SELECT VALUE id FROM transfer_job WHERE intent = $intent_id LIMIT 1")
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let ids: Vec<RecordId> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	Ok(!ids.is_empty())
+}
+
+async fn update_intents_on_drive(
+	db: &DbHandle,
+	drive: &RecordId,
+	from: &str,
+	to: &str,
+) -> Result<Vec<AffectedIntent>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"UPDATE intent SET status = $to, updated_at = time::now()
+             WHERE status = $from
+               AND ((<record> source).drive = $drive OR $drive IN destinations.map(|$d| (<record> $d).drive))
+             RETURN id, name",
+		)
+		.bind(("drive", drive.clone()))
+		.bind(("from", from.to_string()))
+		.bind(("to", to.to_string()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	Ok(rows.iter().filter_map(parse_affected).collect())
+}
+
+fn parse_affected(row: &serde_json::Value) -> Option<AffectedIntent> {
+	let id = serde_json::from_value::<RecordId>(row["id"].clone()).ok()?;
+	let name = row["name"].as_str().map(String::from);
+	Some(AffectedIntent { id, name })
+}
+
+fn display_name(intent: &AffectedIntent) -> String {
+	intent.name.clone().unwrap_or_else(|| rid_string(&intent.id))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::init_memory;
+
+	async fn setup() -> DbHandle {
+		let db = init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE drive:usb CONTENT { name: 'USB', uuid: 'u-1', connected: false, last_seen: time::now() };
+                 CREATE location:home CONTENT { path: '/home/me', available: true };
+                 CREATE location:usb CONTENT { path: '/media/usb/backup', drive: drive:usb, available: true };
+                 CREATE intent:to_usb CONTENT {
+                     name: 'Backup', source: 'location:home', destinations: ['location:usb'],
+                     status: 'transferring', kind: 'one_shot', speed_mode: 'normal',
+                 };
+                 CREATE intent:from_usb CONTENT {
+                     source: 'location:usb', destinations: ['location:home'],
+                     status: 'transferring', kind: 'one_shot', speed_mode: 'normal',
+                 };
+                 CREATE intent:idle CONTENT {
+                     source: 'location:home', destinations: ['location:usb'],
+                     status: 'idle', kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		db
+	}
+
+	async fn status(db: &DbHandle, key: &str) -> String {
+		let mut resp = db
+			.db
+			.query("SELECT VALUE status FROM type::record('intent', $key)")
+			.bind(("key", key.to_string()))
+			.await
+			.unwrap();
+		let statuses: Vec<String> = resp.take(0).unwrap();
+		statuses.into_iter().next().unwrap()
+	}
+
+	#[tokio::test]
+	async fn disconnect_parks_transferring_intents_on_either_side() {
+		let db = setup().await;
+
+		let paused = pause_intents_for_drive(&db, &RecordId::new("drive", "usb"), "USB").await.unwrap();
+
+		assert_eq!(paused.len(), 2);
+		assert_eq!(status(&db, "to_usb").await, "waiting_for_device");
+		assert_eq!(status(&db, "from_usb").await, "waiting_for_device");
+		assert_eq!(status(&db, "idle").await, "idle");
+	}
+
+	async fn set_waiting(db: &DbHandle, keys: &[&str]) {
+		for key in keys {
+			db.db
+				.query("UPDATE type::record('intent', $key) SET status = 'waiting_for_device'")
+				.bind(("key", key.to_string()))
+				.await
+				.unwrap();
+		}
+	}
+
+	#[tokio::test]
+	async fn missing_drives_names_disconnected_ones() {
+		let db = setup().await;
+		let missing = missing_drives(&db, &RecordId::new("intent", "to_usb")).await.unwrap();
+		assert_eq!(missing, vec!["USB".to_string()]);
+
+		db.db.query("UPDATE drive:usb SET connected = true").await.unwrap();
+		assert!(missing_drives(&db, &RecordId::new("intent", "to_usb")).await.unwrap().is_empty());
+	}
+
+	#[tokio::test]
+	async fn reconnect_finds_intents_on_either_side() {
+		let db = setup().await;
+		set_waiting(&db, &["to_usb", "from_usb"]).await;
+		db.db.query("UPDATE drive:usb SET connected = true").await.unwrap();
+
+		let mut ready: Vec<String> = ready_to_resume(&db, &RecordId::new("drive", "usb"))
+			.await
+			.unwrap()
+			.into_iter()
+			.map(|i| rid_string(&i.id))
+			.collect();
+		ready.sort();
+		assert_eq!(ready, vec!["intent:from_usb", "intent:to_usb"]);
+	}
+
+	#[tokio::test]
+	async fn reconnect_waits_for_every_drive() {
+		let db = setup().await;
+		db.db
+			.query(
+				"UPDATE drive:usb SET connected = true;
+                 CREATE drive:ssd CONTENT { name: 'SSD', uuid: 'u-2', connected: false, last_seen: time::now() };
+                 CREATE location:ssd CONTENT { path: '/media/ssd', drive: drive:ssd, available: true };
+                 UPDATE intent:to_usb SET destinations = ['location:usb', 'location:ssd'];",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		set_waiting(&db, &["to_usb"]).await;
+
+		let resumed = resume_intents_for_drive(&db, &RecordId::new("drive", "usb")).await.unwrap();
+		assert!(resumed.is_empty());
+		assert_eq!(status(&db, "to_usb").await, "waiting_for_device");
+	}
+
+	#[tokio::test]
+	async fn unrelated_drive_leaves_intents_alone() {
+		let db = setup().await;
+		let paused = pause_intents_for_drive(&db, &RecordId::new("drive", "other"), "Other").await.unwrap();
+		assert!(paused.is_empty());
+		assert_eq!(status(&db, "to_usb").await, "transferring");
+	}
+}
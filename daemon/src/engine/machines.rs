@@ -0,0 +1,134 @@
+//! Keeping remote machines' `online` flag current.
+//!
+//! Nothing else tells Kip whether a remote is reachable, so `MachineWatcher`
+//! periodically opens a TCP connection to each one's SSH port. A machine
+//! that answers is marked `online` with a fresh `last_seen`; one that
+//! doesn't is marked offline, and the graph greys its container out the way
+//! it does an unplugged drive.
+
+use std::time::Duration;
+
+use surrealdb::types::RecordId;
+use tokio::{net::TcpStream, task::JoinHandle};
+use tracing::{info, warn};
+
+use crate::{
+	db::DbHandle,
+	engine::{scheduler::SchedulerError, transport::RemoteMachine},
+	graph_store::rid_string,
+};
+
+/// How often remote machines are probed.
+pub const MACHINE_POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// A machine that doesn't accept a connection this quickly counts as offline.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Probes every remote machine on an interval until stopped.
+pub struct MachineWatcher {
+	handle: JoinHandle<()>,
+}
+
+impl MachineWatcher {
+	pub fn start(db: DbHandle, interval: Duration) -> Self {
+		let handle = tokio::spawn(async move {
+			loop {
+				if let Err(e) = probe_machines(&db).await {
+					warn!("machine probe failed: {}", e);
+				}
+				tokio::time::sleep(interval).await;
+			}
+		});
+		MachineWatcher { handle }
+	}
+
+	pub fn stop(self) {
+		self.handle.abort();
+	}
+}
+
+/// Probe each remote machine once and record whether it answered. Returns
+/// the machines whose status changed.
+pub async fn probe_machines(db: &DbHandle) -> Result<Vec<RecordId>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT id, online, kind AS m_kind, hostname AS m_hostname, ssh_user AS m_ssh_user,
+                    ssh_key_path AS m_ssh_key_path, ssh_proxy AS m_ssh_proxy
+             FROM machine WHERE kind = 'remote'",
+		)
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let mut changed = Vec::new();
+	for row in &rows {
+		let Ok(id) = serde_json::from_value::<RecordId>(row["id"].clone()) else {
+			continue;
+		};
+		// A remote without a hostname can't be reached at all
+		let online = match RemoteMachine::from_row(row, "m") {
+			Ok(Some(machine)) => reachable(&machine).await,
+			Ok(None) | Err(_) => false,
+		};
+		if row["online"].as_bool() != Some(online) {
+			info!("{} is now {}", rid_string(&id), if online { "online" } else { "offline" });
+			changed.push(id.clone());
+		}
+
+		db.db
+			.query("UPDATE $id SET online = $online, last_seen = IF $online { time::now() } ELSE { last_seen }")
+			.bind(("id", id))
+			.bind(("online", online))
+			.await
+			.map_err(|e| SchedulerError::DbError(e.to_string()))?
+			.check()
+			.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	}
+	Ok(changed)
+}
+
+async fn reachable(machine: &RemoteMachine) -> bool {
+	let address = machine.probe_address();
+	matches!(tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect(address)).await, Ok(Ok(_)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::init_memory;
+
+	async fn online(db: &DbHandle) -> bool {
+		let mut resp = db.db.query("SELECT VALUE online FROM machine:nas").await.unwrap();
+		let online: Vec<bool> = resp.take(0).unwrap();
+		online[0]
+	}
+
+	#[tokio::test]
+	async fn probe_tracks_whether_the_ssh_port_answers() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let address = listener.local_addr().unwrap().to_string();
+		let db = init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE machine:nas CONTENT {
+                    name: 'NAS', kind: 'remote', hostname: $address, is_current: false,
+                    online: false, last_seen: time::now(),
+                };",
+			)
+			.bind(("address", address))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let changed = probe_machines(&db).await.unwrap();
+		assert_eq!(changed, vec![RecordId::new("machine", "nas")]);
+		assert!(online(&db).await);
+
+		drop(listener);
+		probe_machines(&db).await.unwrap();
+		assert!(!online(&db).await);
+	}
+}
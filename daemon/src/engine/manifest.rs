@@ -0,0 +1,254 @@
+//! Checksum manifests written at a destination's root.
+//!
+//! For an intent with `write_manifest` set, finishing a run writes
+//! `MANIFEST_FILE` into each local destination: every file the intent has
+//! copied there, by path relative to the destination root, with its size and
+//! blake3 hash. It's plain JSON, so a backup can be checked without Kip, and
+//! `verify_manifest` (`kip verify-manifest <dir>`) re-hashes the files
+//! against it:
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "algorithm": "blake3",
+//!   "created_at": "2026-10-14T09:30:00+00:00",
+//!   "files": [
+//!     { "path": "photos/a.jpg", "size": 1048576, "blake3": "af1349b9f5f9a1a6a0404dea36dcc949..." }
+//!   ]
+//! }
+//! ```
+//!
+//! Paths are `/`-separated and sorted. A name that isn't valid UTF-8 is
+//! written the way `encode_path` stores it, which `decode_path` reverses.
+
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs,
+	path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use surrealdb::types::RecordId;
+use tracing::info;
+
+use kip_core::util::{decode_path, encode_path};
+
+use crate::{
+	db::DbHandle,
+	engine::copier::{self, CopyError, HashAlgorithm},
+};
+
+/// Name of the manifest at a destination's root.
+pub const MANIFEST_FILE: &str = ".kip-manifest.json";
+
+/// The manifest format version this build writes and reads.
+pub const MANIFEST_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+	pub version: u32,
+	/// Always "blake3" for this version.
+	pub algorithm: String,
+	/// When the manifest was written, RFC 3339.
+	pub created_at: String,
+	pub files: Vec<ManifestEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+	/// Relative to the directory holding the manifest.
+	pub path: String,
+	pub size: u64,
+	pub blake3: String,
+}
+
+/// Outcome of checking a directory against its manifest.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManifestCheck {
+	pub checked: u64,
+	/// Files whose size or hash no longer match, by manifest path.
+	pub mismatched: Vec<String>,
+	/// Files listed in the manifest that are gone or can't be read.
+	pub missing: Vec<String>,
+}
+
+impl ManifestCheck {
+	pub fn is_clean(&self) -> bool {
+		self.mismatched.is_empty() && self.missing.is_empty()
+	}
+}
+
+/// Write a manifest into each local destination of the intent, listing the
+/// files its completed jobs copied there. A file copied again by a later run
+/// is listed as the latest copy left it. Returns the manifests written.
+pub async fn write_manifests(db: &DbHandle, intent_id: &RecordId) -> Result<Vec<PathBuf>, CopyError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT destination.path AS root, destination.machine.kind = 'remote' AS remote,
+                    dest_path, size, source_hash
+             FROM transfer_job
+             WHERE intent = $intent_id AND status = 'complete' AND source_hash != NONE
+             ORDER BY completed_at",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	let jobs: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	// Per destination root, the latest copy of each relative path
+	let mut roots: HashMap<String, BTreeMap<String, (String, u64, String)>> = HashMap::new();
+	for job in &jobs {
+		// A remote destination's root can't be written from here
+		let (Some(root), false) = (job["root"].as_str(), job["remote"].as_bool() == Some(true)) else {
+			continue;
+		};
+		let dest_path = job["dest_path"].as_str().unwrap_or_default();
+		let Ok(relative) = decode_path(dest_path).strip_prefix(decode_path(root)).map(Path::to_path_buf) else {
+			continue;
+		};
+		let hash = job["source_hash"].as_str().unwrap_or_default().to_string();
+		let size = job["size"].as_u64().unwrap_or(0);
+		roots
+			.entry(root.to_string())
+			.or_default()
+			.insert(manifest_path(&relative), (dest_path.to_string(), size, hash));
+	}
+
+	let mut written = Vec::new();
+	for (root, files) in roots {
+		let path = tokio::task::spawn_blocking(move || write_manifest(&decode_path(&root), files))
+			.await
+			.map_err(|e| CopyError::IoError(format!("task join error: {e}")))??;
+		info!("wrote checksum manifest {}", path.display());
+		written.push(path);
+	}
+	Ok(written)
+}
+
+/// Write `root`'s manifest from its files' (dest path, size, hash). A copy
+/// that was fast-hashed in xxh3 is hashed again in blake3 from the file.
+fn write_manifest(root: &Path, files: BTreeMap<String, (String, u64, String)>) -> Result<PathBuf, CopyError> {
+	let mut entries = Vec::with_capacity(files.len());
+	for (path, (dest_path, size, hash)) in files {
+		let blake3 = match HashAlgorithm::of_digest(&hash) {
+			HashAlgorithm::Blake3 => hash,
+			HashAlgorithm::Xxh3 => copier::hash_file(decode_path(&dest_path), HashAlgorithm::Blake3)?,
+		};
+		entries.push(ManifestEntry { path, size, blake3 });
+	}
+	let manifest = Manifest {
+		version: MANIFEST_VERSION,
+		algorithm: "blake3".to_string(),
+		created_at: chrono::Utc::now().to_rfc3339(),
+		files: entries,
+	};
+	let json = serde_json::to_string_pretty(&manifest).map_err(|e| CopyError::IoError(e.to_string()))?;
+
+	// Written beside and renamed over, so a reader never sees half a manifest
+	let path = root.join(MANIFEST_FILE);
+	let partial = root.join(format!("{MANIFEST_FILE}.partial"));
+	fs::write(&partial, json).map_err(|e| copier::map_io_error(e, &partial))?;
+	fs::rename(&partial, &path).map_err(|e| copier::map_io_error(e, &path))?;
+	Ok(path)
+}
+
+/// Re-hash every file `dir`'s manifest lists and compare it to the size
+/// and hash recorded there. Nothing is written.
+pub fn verify_manifest(dir: &Path) -> Result<ManifestCheck, CopyError> {
+	let path = dir.join(MANIFEST_FILE);
+	let text = fs::read_to_string(&path).map_err(|e| copier::map_io_error(e, &path))?;
+	let manifest: Manifest = serde_json::from_str(&text)
+		.map_err(|e| CopyError::IoError(format!("{} is not a Kip manifest: {e}", path.display())))?;
+	if manifest.version != MANIFEST_VERSION || manifest.algorithm != "blake3" {
+		return Err(CopyError::IoError(format!(
+			"{}: unsupported manifest version {} ({})",
+			path.display(),
+			manifest.version,
+			manifest.algorithm
+		)));
+	}
+
+	let mut check = ManifestCheck::default();
+	for entry in &manifest.files {
+		check.checked += 1;
+		let file = dir.join(decode_path(&entry.path));
+		let Ok(metadata) = fs::metadata(&file) else {
+			check.missing.push(entry.path.clone());
+			continue;
+		};
+		if metadata.len() != entry.size {
+			check.mismatched.push(entry.path.clone());
+			continue;
+		}
+		match copier::hash_file(&file, HashAlgorithm::Blake3) {
+			Ok(hash) if hash == entry.blake3 => {}
+			Ok(_) => check.mismatched.push(entry.path.clone()),
+			Err(_) => check.missing.push(entry.path.clone()),
+		}
+	}
+	Ok(check)
+}
+
+/// A relative path as the manifest writes it: `/`-separated on every
+/// platform.
+fn manifest_path(relative: &Path) -> String {
+	encode_path(relative).replace(std::path::MAIN_SEPARATOR, "/")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::init_memory;
+
+	#[tokio::test]
+	async fn manifest_lists_copies_and_verifies_them() {
+		let dst = tempfile::tempdir().unwrap();
+		fs::create_dir_all(dst.path().join("sub")).unwrap();
+		fs::write(dst.path().join("a.txt"), "alpha").unwrap();
+		fs::write(dst.path().join("sub/b.txt"), "bravo").unwrap();
+		let hash = |rel: &str| copier::hash_file(dst.path().join(rel), HashAlgorithm::Blake3).unwrap();
+
+		let db = init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:dst CONTENT { path: $root, available: true };
+                 CREATE transfer_job CONTENT {
+                     intent: intent:archive, destination: location:dst, source_path: '/src/a.txt',
+                     dest_path: $root + '/a.txt', size: 5, status: 'complete', source_hash: $a,
+                 };
+                 CREATE transfer_job CONTENT {
+                     intent: intent:archive, destination: location:dst, source_path: '/src/sub/b.txt',
+                     dest_path: $root + '/sub/b.txt', size: 5, status: 'complete', source_hash: $b,
+                 };
+                 CREATE transfer_job CONTENT {
+                     intent: intent:archive, destination: location:dst, source_path: '/src/c.txt',
+                     dest_path: $root + '/c.txt', size: 5, status: 'needs_review',
+                 };",
+			)
+			.bind(("root", dst.path().to_str().unwrap().to_string()))
+			.bind(("a", hash("a.txt")))
+			.bind(("b", hash("sub/b.txt")))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let written = write_manifests(&db, &RecordId::new("intent", "archive")).await.unwrap();
+		assert_eq!(written, vec![dst.path().join(MANIFEST_FILE)]);
+		let manifest: Manifest = serde_json::from_str(&fs::read_to_string(&written[0]).unwrap()).unwrap();
+		let paths: Vec<&str> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+		assert_eq!(paths, vec!["a.txt", "sub/b.txt"]);
+
+		assert!(verify_manifest(dst.path()).unwrap().is_clean());
+
+		fs::write(dst.path().join("a.txt"), "omega").unwrap();
+		fs::remove_file(dst.path().join("sub/b.txt")).unwrap();
+		let check = verify_manifest(dst.path()).unwrap();
+		assert_eq!(check.checked, 2);
+		assert_eq!(check.mismatched, vec!["a.txt".to_string()]);
+		assert_eq!(check.missing, vec!["sub/b.txt".to_string()]);
+	}
+}
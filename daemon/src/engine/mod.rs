@@ -1,9 +1,19 @@
 //! Engine modules - Core transfer logic
 
+pub mod copier;
+pub mod dedup;
+pub mod drives;
+pub mod machines;
+pub mod manifest;
 pub mod scanner;
 pub mod scheduler;
+pub mod shutdown;
 pub mod transfer;
+pub mod transport;
+pub mod verify;
+pub mod watcher;
 
+pub use copier::*;
 pub use transfer::*;
 pub use scanner::*;
 pub use scheduler::*;
@@ -1,17 +1,61 @@
-use std::{fmt, path::Path, time::SystemTime};
+use std::{
+	collections::{BTreeSet, HashMap},
+	fmt, fs,
+	cell::RefCell,
+	ffi::OsStr,
+	path::{Component, Path, PathBuf},
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, LazyLock, Mutex,
+	},
+	time::{Duration, SystemTime},
+};
 
-use surrealdb::types::RecordId;
+use glob::{MatchOptions, Pattern};
+use ignore::{
+	gitignore::{Gitignore, GitignoreBuilder},
+	Match,
+};
+use kip_core::{
+	graph_types::path_contains,
+	models::intent::ConflictPolicy,
+	util::{decode_path, encode_path},
+};
+use surrealdb::types::{RecordId, SurrealValue};
+use tokio::task::JoinHandle;
 use walkdir::WalkDir;
 
-use crate::db::DbHandle;
+use crate::{
+	db::DbHandle,
+	engine::{
+		copier::{self, HashAlgorithm},
+		drives, transfer,
+	},
+	events::{self, intent_label, EngineEvent},
+	graph_store::rid_string,
+};
 
 #[derive(Debug)]
 pub enum ScanError {
 	IntentNotFound(String),
+	AlreadyRunning(String),
 	SourceLocationNotFound(String),
 	DestLocationNotFound(String),
 	SourcePathNotExists(String),
 	SourcePathNotDir(String),
+	/// A drive the intent needs isn't connected; the intent now waits for it.
+	DeviceUnavailable(String),
+	/// Remote machines can only be copied to; scanning one (as a source or
+	/// the far side of a two-way sync) isn't supported.
+	RemoteUnsupported(String),
+	InvalidPattern(String),
+	/// The intent's `source_subpath` is absolute or climbs out of its location.
+	InvalidSubpath(String),
+	/// A destination is the source directory, or one is inside the other, so
+	/// the copy would read what it writes.
+	OverlappingPaths(String),
+	/// `cancel_scan` stopped the scan before it wrote its jobs.
+	Cancelled,
 	WalkError(walkdir::Error),
 	DbError(String),
 }
@@ -20,10 +64,17 @@ impl fmt::Display for ScanError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			ScanError::IntentNotFound(s) => write!(f, "intent not found: {}", s),
+			ScanError::AlreadyRunning(s) => write!(f, "intent already running: {}", s),
 			ScanError::SourceLocationNotFound(s) => write!(f, "source location not found: {}", s),
 			ScanError::DestLocationNotFound(s) => write!(f, "destination location not found: {}", s),
 			ScanError::SourcePathNotExists(s) => write!(f, "source path does not exist: {}", s),
 			ScanError::SourcePathNotDir(s) => write!(f, "source path is not a directory: {}", s),
+			ScanError::DeviceUnavailable(s) => write!(f, "waiting for {} to be connected", s),
+			ScanError::RemoteUnsupported(s) => write!(f, "can't scan a remote machine yet: {}", s),
+			ScanError::InvalidPattern(s) => write!(f, "invalid include/exclude pattern: {}", s),
+			ScanError::InvalidSubpath(s) => write!(f, "source subpath must stay inside its location: {}", s),
+			ScanError::OverlappingPaths(s) => write!(f, "source and destination overlap: {}", s),
+			ScanError::Cancelled => write!(f, "scan cancelled"),
 			ScanError::WalkError(e) => write!(f, "filesystem walk error: {}", e),
 			ScanError::DbError(s) => write!(f, "database error: {}", s),
 		}
@@ -51,67 +102,385 @@ pub struct ScanResult {
 	pub total_bytes: u64,
 	pub jobs_created: u64,
 	pub skipped_entries: u64,
+	/// The first `SKIP_LOG_LIMIT` skipped entries, relative to the source.
+	pub skipped: Vec<(PathBuf, SkipReason)>,
 }
 
-#[derive(Debug)]
+/// Most skipped entries a scan names; `skipped_entries` counts all of them.
+pub const SKIP_LOG_LIMIT: usize = 100;
+
+/// Why a walk left an entry out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+	/// A symlink, with `follow_symlinks` off.
+	Symlink,
+	/// A followed symlink back to one of its own ancestors.
+	SymlinkLoop,
+	PermissionDenied,
+	/// Couldn't be read or stat'ed for any other reason.
+	Unreadable,
+	/// Left out by the intent's include/exclude patterns.
+	Excluded,
+	/// Matched by a `.kipignore`.
+	Ignored,
+}
+
+impl SkipReason {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			SkipReason::Symlink => "symlink",
+			SkipReason::SymlinkLoop => "symlink_loop",
+			SkipReason::PermissionDenied => "permission_denied",
+			SkipReason::Unreadable => "unreadable",
+			SkipReason::Excluded => "excluded",
+			SkipReason::Ignored => "ignored",
+		}
+	}
+
+	fn parse(s: &str) -> Option<Self> {
+		[
+			SkipReason::Symlink,
+			SkipReason::SymlinkLoop,
+			SkipReason::PermissionDenied,
+			SkipReason::Unreadable,
+			SkipReason::Excluded,
+			SkipReason::Ignored,
+		]
+		.into_iter()
+		.find(|r| r.as_str() == s)
+	}
+
+	fn of_walk_error(err: &walkdir::Error) -> Self {
+		if err.loop_ancestor().is_some() {
+			SkipReason::SymlinkLoop
+		} else if err.io_error().map(|e| e.kind()) == Some(std::io::ErrorKind::PermissionDenied) {
+			SkipReason::PermissionDenied
+		} else {
+			SkipReason::Unreadable
+		}
+	}
+}
+
+/// What a walk skipped: every entry counted, the first `SKIP_LOG_LIMIT` named.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct SkipLog {
+	count: u64,
+	entries: Vec<(PathBuf, SkipReason)>,
+	/// Every entry permission kept the walk out of, uncapped: each one is
+	/// filed for review rather than left out of the backup unnoticed.
+	denied: Vec<PathBuf>,
+}
+
+impl SkipLog {
+	fn record(&mut self, path: PathBuf, reason: SkipReason) {
+		self.count += 1;
+		if reason == SkipReason::PermissionDenied {
+			self.denied.push(path.clone());
+		}
+		if self.entries.len() < SKIP_LOG_LIMIT {
+			self.entries.push((path, reason));
+		}
+	}
+
+	fn append(&mut self, other: SkipLog) {
+		self.count += other.count;
+		let room = SKIP_LOG_LIMIT.saturating_sub(self.entries.len());
+		self.entries.extend(other.entries.into_iter().take(room));
+		self.denied.extend(other.denied);
+	}
+
+	/// As saved in a scan checkpoint; paths are `encode_path`ed.
+	fn to_json(&self) -> serde_json::Value {
+		let entries: Vec<serde_json::Value> = self
+			.entries
+			.iter()
+			.map(|(path, reason)| serde_json::json!({ "path": encode_path(path), "reason": reason.as_str() }))
+			.collect();
+		let denied: Vec<String> = self.denied.iter().map(|p| encode_path(p)).collect();
+		serde_json::json!({ "count": self.count, "entries": entries, "denied": denied })
+	}
+
+	fn from_json(value: &serde_json::Value) -> Self {
+		let paths = |v: &serde_json::Value| v.as_array().cloned().unwrap_or_default();
+		let entries = paths(&value["entries"])
+			.iter()
+			.filter_map(|e| Some((decode_path(e["path"].as_str()?), SkipReason::parse(e["reason"].as_str()?)?)))
+			.collect();
+		let denied = paths(&value["denied"]).iter().filter_map(|p| p.as_str().map(decode_path)).collect();
+		SkipLog { count: value["count"].as_u64().unwrap_or(0), entries, denied }
+	}
+}
+
+#[derive(Debug, Clone)]
 struct FileEntry {
-	relative_path: String,
+	/// Kept as a `PathBuf` so non-UTF-8 names survive to the copier.
+	relative_path: PathBuf,
 	size: u64,
-	#[allow(dead_code)]
 	modified: SystemTime,
 }
 
+impl FileEntry {
+	/// Modification time as stored in `transfer_job.source_mtime_ns`.
+	fn mtime_ns(&self) -> Option<i64> {
+		let since_epoch = self.modified.duration_since(SystemTime::UNIX_EPOCH).ok()?;
+		i64::try_from(since_epoch.as_nanos()).ok()
+	}
+}
+
 /// Loaded intent fields needed for scanning.
 struct IntentData {
+	name: Option<String>,
 	source: RecordId,
 	destinations: Vec<RecordId>,
+	include_patterns: Vec<String>,
+	exclude_patterns: Vec<String>,
+	/// Changes flow both ways between source and each destination.
+	bidirectional: bool,
+	/// Walk into symlinked files and directories instead of skipping them.
+	follow_symlinks: bool,
+	/// Folder under the source location to transfer instead of all of it.
+	source_subpath: Option<String>,
+	/// Which side wins when a file changed on both since the last sync.
+	on_conflict: ConflictPolicy,
+	/// Scan start of the last pass that completed cleanly; edits after this
+	/// count as "changed since last sync".
+	last_synced_at: Option<SystemTime>,
+}
+
+/// A resolved destination location.
+struct Destination {
+	id: RecordId,
+	path: String,
+	/// Per-file size limit of the drive the location lives on (FAT32: 4 GiB - 1).
+	max_file_size: Option<u64>,
+	/// On a remote machine; jobs copy there over SFTP.
+	remote: bool,
+}
+
+/// A location's path, and whether it's on a remote machine rather than
+/// this one.
+struct ResolvedLocation {
+	path: String,
+	remote: bool,
+}
+
+impl ResolvedLocation {
+	/// The path, for callers that read it from the local disk.
+	fn local_path(self) -> Result<String, ScanError> {
+		// Walking, and the checks that stat what was walked, all read this
+		// machine's disk; only copying to a remote goes over SFTP
+		if self.remote {
+			return Err(ScanError::RemoteUnsupported(self.path));
+		}
+		Ok(self.path)
+	}
+}
+
+/// Compiled include/exclude globs, matched against paths relative to the
+/// source root.
+///
+/// A pattern without a `/` matches a file or directory *name* anywhere in the
+/// tree (`*.tmp`, `node_modules`); one with a `/` matches the whole relative
+/// path (`src/**`). Excludes win over includes, and an empty include list
+/// means everything.
+#[derive(Debug, Clone, Default)]
+struct PathFilter {
+	include: Vec<Pattern>,
+	exclude: Vec<Pattern>,
+}
+
+/// Pending jobs bound per statement when a scan queues its transfers.
+const JOB_BATCH_SIZE: usize = 1000;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+	case_sensitive: true,
+	require_literal_separator: true,
+	require_literal_leading_dot: false,
+};
+
+impl PathFilter {
+	fn new(include: &[String], exclude: &[String]) -> Result<Self, ScanError> {
+		let compile = |patterns: &[String]| {
+			patterns
+				.iter()
+				.map(|p| Pattern::new(p).map_err(|e| ScanError::InvalidPattern(format!("{p}: {e}"))))
+				.collect::<Result<Vec<_>, _>>()
+		};
+		Ok(Self { include: compile(include)?, exclude: compile(exclude)? })
+	}
+
+	fn matches(pattern: &Pattern, relative: &Path) -> bool {
+		if pattern.as_str().contains('/') {
+			pattern.matches_path_with(relative, MATCH_OPTIONS)
+		} else {
+			relative
+				.file_name()
+				.is_some_and(|name| pattern.matches_path_with(Path::new(name), MATCH_OPTIONS))
+		}
+	}
+
+	fn is_excluded(&self, relative: &Path) -> bool {
+		self.exclude.iter().any(|p| Self::matches(p, relative))
+	}
+
+	/// Whether a file at `relative` should get a transfer job.
+	fn accepts(&self, relative: &Path) -> bool {
+		if self.is_excluded(relative) {
+			return false;
+		}
+		self.include.is_empty() || self.include.iter().any(|p| Self::matches(p, relative))
+	}
+
+	/// Like `accepts`, but also rejects files inside an excluded directory.
+	/// The walker gets this for free by pruning; single changed paths don't.
+	fn accepts_nested(&self, relative: &Path) -> bool {
+		let mut dir = relative.parent();
+		while let Some(d) = dir.filter(|d| !d.as_os_str().is_empty()) {
+			if self.is_excluded(d) {
+				return false;
+			}
+			dir = d.parent();
+		}
+		self.accepts(relative)
+	}
+}
+
+/// How often a scan waiting on the walk checks whether it was cancelled.
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Files a scan walks between checkpoints.
+const CHECKPOINT_EVERY: usize = 5_000;
+/// A checkpoint older than this is walked again from the start: too much
+/// may have changed under the part it covers.
+const CHECKPOINT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Cancel flags of the scans in progress, by intent.
+static SCANS: LazyLock<Mutex<HashMap<RecordId, Arc<AtomicBool>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Keeps a scan registered for `cancel_scan` until dropped.
+struct TrackedScan(RecordId);
+
+impl TrackedScan {
+	fn start(intent_id: &RecordId) -> (Self, Arc<AtomicBool>) {
+		let cancel = Arc::new(AtomicBool::new(false));
+		SCANS.lock().unwrap().insert(intent_id.clone(), cancel.clone());
+		(TrackedScan(intent_id.clone()), cancel)
+	}
+}
+
+impl Drop for TrackedScan {
+	fn drop(&mut self) {
+		SCANS.lock().unwrap().remove(&self.0);
+	}
+}
+
+/// Stop the intent's scan, if one is running. It returns
+/// `ScanError::Cancelled` without writing any jobs and leaves the intent
+/// `idle`. Returns whether there was a scan to stop.
+pub fn cancel_scan(intent_id: &RecordId) -> bool {
+	match SCANS.lock().unwrap().get(intent_id) {
+		Some(cancel) => {
+			cancel.store(true, Ordering::Relaxed);
+			true
+		}
+		None => false,
+	}
 }
 
 /// Scan an intent's source, create transfer_jobs for all destinations.
 ///
-/// State transitions: idle → scanning → transferring (or complete if empty).
+/// State transitions: idle → scanning → transferring (or complete if empty,
+/// or waiting_for_device if one of its drives is unplugged).
+///
+/// The transition into `scanning` is a compare-and-set on `status`, so a
+/// second start while the intent is active returns `ScanError::AlreadyRunning`
+/// instead of creating duplicate jobs. Until its jobs are written, the scan
+/// can be stopped with `cancel_scan`. The walk checkpoints as it goes, so a
+/// scan that was stopped or cut off picks up where it was next time.
 pub async fn scan_intent(db: &DbHandle, intent_id: &RecordId) -> Result<ScanResult, ScanError> {
 	// 1. Load intent fields we need
 	let intent = load_intent(db, intent_id).await?;
 
-	// 2. Transition to scanning
-	db.db
-		.query("UPDATE $id SET status = 'scanning', updated_at = time::now()")
-		.bind(("id", intent_id.clone()))
-		.await
-		.map_err(|e| ScanError::DbError(e.to_string()))?
-		.check()
-		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	// 2. Transition to scanning (only if not already active)
+	claim_intent(db, intent_id).await?;
+	let (_tracked, cancel) = TrackedScan::start(intent_id);
 
-	// 3. Resolve source path
-	let source_path = resolve_location_path(db, &intent.source, true).await?;
+	let (id, name) = (rid_string(intent_id), intent_label(intent_id, intent.name.as_deref()));
+	events::emit(EngineEvent::ScanStarted { intent: id.clone(), name: name.clone() });
 
-	// 4. Walk filesystem (blocking — offload to thread pool)
-	let (entries, skipped) = tokio::task::spawn_blocking({
-		let source_path = source_path.clone();
-		move || walk_source(&source_path)
-	})
-	.await
-	.map_err(|e| ScanError::DbError(format!("task join error: {e}")))??;
+	let result = scan_claimed(db, intent_id, &intent, &cancel).await;
+	events::emit(match &result {
+		Ok(r) => EngineEvent::ScanFinished { intent: id, name, files: r.files_found, jobs: r.jobs_created },
+		Err(e) => EngineEvent::ScanFailed { intent: id, name, error: e.to_string() },
+	});
+	if result.is_err() {
+		// Release the claim so a failed or cancelled scan doesn't block future starts
+		let _ = db
+			.db
+			.query("UPDATE $id SET status = 'idle', updated_at = time::now() WHERE status = 'scanning'")
+			.bind(("id", intent_id.clone()))
+			.await;
+	}
+	result
+}
 
-	// 5. Resolve destination paths
-	let mut destinations = Vec::with_capacity(intent.destinations.len());
-	for dest_id in &intent.destinations {
-		let dest_path = resolve_location_path(db, dest_id, false).await?;
-		destinations.push((dest_id.clone(), dest_path));
+/// Steps 3–7 of `scan_intent`, run once the intent has been claimed.
+async fn scan_claimed(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	intent: &IntentData,
+	cancel: &Arc<AtomicBool>,
+) -> Result<ScanResult, ScanError> {
+	// Park instead of failing if a drive is unplugged; reconnecting resumes it
+	let missing = drives::missing_drives(db, intent_id)
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	if !missing.is_empty() {
+		db.db
+			.query("UPDATE $id SET status = 'waiting_for_device', updated_at = time::now()")
+			.bind(("id", intent_id.clone()))
+			.await
+			.map_err(|e| ScanError::DbError(e.to_string()))?;
+		return Err(ScanError::DeviceUnavailable(missing.join(", ")));
 	}
 
+	let plan = plan_scan(db, intent_id, intent, cancel, true).await?;
+
 	// 6. Create transfer jobs
-	let jobs_created = create_transfer_jobs(db, intent_id, &source_path, &entries, &destinations).await?;
+	let jobs_created = match &plan.source_as_dest {
+		// The source is also a destination for changes made on the other side
+		Some(source_as_dest) => {
+			let mut created = 0;
+			for (dest, sync) in plan.destinations.iter().zip(&plan.sync) {
+				created +=
+					create_sync_jobs(db, intent_id, &plan.source_path, source_as_dest, dest, sync, cancel).await?;
+			}
+			created
+		}
+		None => {
+			let (entries, dests, done) = (&plan.entries, &plan.destinations, &plan.up_to_date);
+			create_transfer_jobs(db, intent_id, &plan.source_path, entries, dests, done, cancel).await?
+		}
+	};
+	// Whatever the walk couldn't read goes to review instead of missing silently
+	let source_root = decode_path(&plan.source_path);
+	let unreadable = file_unreadable(db, intent_id, &source_root, &plan.destinations, &plan.skipped.denied).await?;
+	let jobs_created = jobs_created + unreadable;
+	let (total_jobs, total_bytes) = (plan.job_count() + unreadable, plan.queued_bytes());
 
-	// 7. Update intent totals and transition
-	let total_bytes: u64 = entries.iter().map(|e| e.size).sum();
-	let total_jobs = entries.len() as u64 * destinations.len() as u64;
+	// 7. Update intent totals and transition, now that every job is written.
+	// What the walk skipped is kept so the intent can say which and why
 	let next_status = if total_jobs == 0 {
 		"complete"
 	} else {
 		"transferring"
 	};
+	let skip_log: Vec<serde_json::Value> = plan
+		.skipped
+		.entries
+		.iter()
+		.map(|(path, reason)| serde_json::json!({ "path": encode_path(path), "reason": reason.as_str() }))
+		.collect();
 
 	db.db
 		.query(
@@ -119,246 +488,2311 @@ pub async fn scan_intent(db: &DbHandle, intent_id: &RecordId) -> Result<ScanResu
                 status = $status,
                 total_files = $total_files,
                 total_bytes = $total_bytes,
+                skipped_entries = $skipped_entries,
+                skip_log = $skip_log,
                 updated_at = time::now()",
 		)
 		.bind(("id", intent_id.clone()))
 		.bind(("status", next_status.to_string()))
 		.bind(("total_files", total_jobs as i64))
-		.bind(("total_bytes", total_bytes as i64 * destinations.len() as i64))
+		.bind(("total_bytes", total_bytes as i64))
+		.bind(("skipped_entries", plan.skipped.count as i64))
+		.bind(("skip_log", skip_log))
 		.await
 		.map_err(|e| ScanError::DbError(e.to_string()))?
 		.check()
 		.map_err(|e| ScanError::DbError(e.to_string()))?;
 
-	Ok(ScanResult {
-		files_found: entries.len() as u64,
-		total_bytes,
-		jobs_created,
-		skipped_entries: skipped,
-	})
+	// Every job is written, so there's nothing left to resume
+	clear_checkpoint(db, intent_id).await?;
+	Ok(plan.result(jobs_created))
 }
 
-/// Load the intent fields needed for scanning via raw query + JSON.
-async fn load_intent(db: &DbHandle, intent_id: &RecordId) -> Result<IntentData, ScanError> {
-	let mut response = db
-		.db
-		.query("SELECT source, destinations FROM $id")
-		.bind(("id", intent_id.clone()))
+/// Walk an intent's source and work out the jobs a scan would create,
+/// without writing anything or touching the intent's status. The returned
+/// `jobs_created` is the number of jobs `scan_intent` would create now.
+pub async fn scan_preview(db: &DbHandle, intent_id: &RecordId) -> Result<ScanResult, ScanError> {
+	let intent = load_intent(db, intent_id).await?;
+	let missing = drives::missing_drives(db, intent_id)
 		.await
 		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	if !missing.is_empty() {
+		return Err(ScanError::DeviceUnavailable(missing.join(", ")));
+	}
 
-	let row: Option<serde_json::Value> = response
-		.take(0)
-		.map_err(|e| ScanError::DbError(e.to_string()))?;
-
-	let row = row.ok_or_else(|| ScanError::IntentNotFound(format!("{:?}", intent_id)))?;
-
-	let source: RecordId = serde_json::from_value(row["source"].clone())
-		.map_err(|e| ScanError::DbError(format!("failed to parse intent.source: {e}")))?;
-
-	let destinations: Vec<RecordId> = serde_json::from_value(row["destinations"].clone())
-		.map_err(|e| ScanError::DbError(format!("failed to parse intent.destinations: {e}")))?;
-
-	Ok(IntentData { source, destinations })
+	let plan = plan_scan(db, intent_id, &intent, &Arc::default(), false).await?;
+	Ok(plan.result(plan.job_count()))
 }
 
-/// Resolve a location record ID to its absolute filesystem path.
-async fn resolve_location_path(db: &DbHandle, location_id: &RecordId, is_source: bool) -> Result<String, ScanError> {
-	let mut response = db
-		.db
-		.query("SELECT path FROM $id")
-		.bind(("id", location_id.clone()))
-		.await
-		.map_err(|e| ScanError::DbError(e.to_string()))?;
-
-	let path: Option<String> = response
-		.take("path")
-		.map_err(|e| ScanError::DbError(e.to_string()))?;
+/// Everything a scan decides before it writes jobs.
+struct ScanPlan {
+	source_path: String,
+	entries: Vec<FileEntry>,
+	skipped: SkipLog,
+	destinations: Vec<Destination>,
+	/// Set for bidirectional intents, whose source also receives changes.
+	source_as_dest: Option<Destination>,
+	/// One plan per destination, for bidirectional intents.
+	sync: Vec<SyncPlan>,
+	/// Per destination, the entries an earlier run already copied unchanged.
+	/// Empty for bidirectional intents, which compare both sides instead.
+	up_to_date: Vec<BTreeSet<PathBuf>>,
+}
 
-	path.ok_or_else(|| {
-		let id_str = format!("{:?}", location_id);
-		if is_source {
-			ScanError::SourceLocationNotFound(id_str)
+impl ScanPlan {
+	fn job_count(&self) -> u64 {
+		if self.source_as_dest.is_some() {
+			self.sync.iter().map(SyncPlan::job_count).sum()
 		} else {
-			ScanError::DestLocationNotFound(id_str)
+			let entries = self.entries.len() as u64;
+			self.up_to_date.iter().map(|done| entries - done.len() as u64).sum()
 		}
-	})
-}
-
-fn walk_source(source_path: &str) -> Result<(Vec<FileEntry>, u64), ScanError> {
-	let root = Path::new(source_path);
+	}
 
-	if !root.exists() {
-		return Err(ScanError::SourcePathNotExists(source_path.to_string()));
+	/// Bytes the queued jobs will copy, across all destinations.
+	fn queued_bytes(&self) -> u64 {
+		if self.source_as_dest.is_some() {
+			self.sync.iter().map(SyncPlan::queued_bytes).sum()
+		} else {
+			self.up_to_date
+				.iter()
+				.map(|done| {
+					let queued = self.entries.iter().filter(|e| !done.contains(&e.relative_path));
+					queued.map(|e| e.size).sum::<u64>()
+				})
+				.sum()
+		}
 	}
-	if !root.is_dir() {
-		return Err(ScanError::SourcePathNotDir(source_path.to_string()));
+
+	fn source_bytes(&self) -> u64 {
+		self.entries.iter().map(|e| e.size).sum()
 	}
 
-	let mut entries = Vec::new();
-	let mut skipped = 0u64;
+	fn result(&self, jobs_created: u64) -> ScanResult {
+		ScanResult {
+			files_found: self.entries.len() as u64,
+			total_bytes: if self.source_as_dest.is_some() {
+				self.queued_bytes()
+			} else {
+				self.source_bytes()
+			},
+			jobs_created,
+			skipped_entries: self.skipped.count,
+			skipped: self.skipped.entries.clone(),
+		}
+	}
+}
 
-	for result in WalkDir::new(root).follow_links(false) {
-		let entry = match result {
-			Ok(e) => e,
-			Err(_) => {
-				skipped += 1;
-				continue;
-			}
-		};
+fn check_cancelled(cancel: &AtomicBool) -> Result<(), ScanError> {
+	if cancel.load(Ordering::Relaxed) {
+		return Err(ScanError::Cancelled);
+	}
+	Ok(())
+}
 
-		if entry.file_type().is_dir() || entry.file_type().is_symlink() {
-			if entry.file_type().is_symlink() {
-				skipped += 1;
+/// Await a blocking scan step, giving up on it as soon as `cancel` is
+/// raised. A walk stuck on a hung network mount may not get to check the
+/// flag for a long time; its thread is left to finish on its own.
+async fn unless_cancelled<T>(cancel: &AtomicBool, mut task: JoinHandle<Result<T, ScanError>>) -> Result<T, ScanError> {
+	let mut ticker = tokio::time::interval(CANCEL_POLL_INTERVAL);
+	loop {
+		tokio::select! {
+			joined = &mut task => {
+				return joined.map_err(|e| ScanError::DbError(format!("task join error: {e}")))?;
 			}
-			continue;
+			_ = ticker.tick() => check_cancelled(cancel)?,
 		}
-
-		let metadata = match entry.metadata() {
-			Ok(m) => m,
-			Err(_) => {
-				skipped += 1;
-				continue;
-			}
-		};
-
-		let relative = entry
-			.path()
-			.strip_prefix(root)
-			.expect("walkdir entry must be under root")
-			.to_string_lossy()
-			.to_string();
-
-		entries.push(FileEntry {
-			relative_path: relative,
-			size: metadata.len(),
-			modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
-		});
 	}
-
-	Ok((entries, skipped))
 }
 
-async fn create_transfer_jobs(
+/// Steps 3–5 of `scan_intent`: walk the source, resolve destinations and
+/// compare each one against the source — by two-way sync state for
+/// bidirectional intents, else by what earlier runs already copied. With
+/// `checkpoint` set, the walk saves its progress as it goes and resumes an
+/// interrupted one; see `walk_checkpointed`.
+async fn plan_scan(
 	db: &DbHandle,
 	intent_id: &RecordId,
-	source_base_path: &str,
-	entries: &[FileEntry],
-	destinations: &[(RecordId, String)],
-) -> Result<u64, ScanError> {
-	let mut jobs_created = 0u64;
-	let source_base = source_base_path.trim_end_matches('/');
-
-	for (dest_id, dest_base_path) in destinations {
-		let dest_base = dest_base_path.trim_end_matches('/');
-
-		for entry in entries {
-			let source_full = format!("{source_base}/{}", entry.relative_path);
-			let dest_full = format!("{dest_base}/{}", entry.relative_path);
-
-			db.db
-				.query(
-					"CREATE transfer_job CONTENT {
-                        intent: $intent_id,
-                        source_path: $source_path,
-                        dest_path: $dest_path,
-                        destination: $dest_id,
-                        size: $size,
-                        bytes_transferred: 0,
-                        status: 'pending',
-                        attempts: 0,
-                        max_attempts: 3,
-                        last_error: NONE,
-                        error_kind: NONE,
-                        source_hash: NONE,
-                        dest_hash: NONE,
-                        started_at: NONE,
-                        completed_at: NONE,
-                        created_at: time::now(),
-                    }",
-				)
-				.bind(("intent_id", intent_id.clone()))
-				.bind(("source_path", source_full))
-				.bind(("dest_path", dest_full))
-				.bind(("dest_id", dest_id.clone()))
-				.bind(("size", entry.size as i64))
-				.await
-				.map_err(|e| ScanError::DbError(e.to_string()))?
-				.check()
-				.map_err(|e| ScanError::DbError(e.to_string()))?;
-
-			jobs_created += 1;
+	intent: &IntentData,
+	cancel: &Arc<AtomicBool>,
+	checkpoint: bool,
+) -> Result<ScanPlan, ScanError> {
+	// 3. Resolve source and destination paths
+	let source_path = resolve_source_root(db, intent).await?;
+	let mut destinations = Vec::with_capacity(intent.destinations.len());
+	for dest_id in &intent.destinations {
+		let dest = resolve_destination(db, dest_id).await?;
+		if !dest.remote {
+			check_overlap(&source_path, &dest.path)?;
 		}
+		destinations.push(dest);
 	}
 
-	Ok(jobs_created)
-}
+	// 4. Walk filesystem (blocking — offload to thread pool)
+	let filter = PathFilter::new(&intent.include_patterns, &intent.exclude_patterns)?;
+	let (entries, skipped) = if checkpoint {
+		walk_checkpointed(db, intent_id, intent, &source_path, &filter, cancel).await?
+	} else {
+		let walk = tokio::task::spawn_blocking({
+			let source_path = source_path.clone();
+			let filter = filter.clone();
+			let follow_symlinks = intent.follow_symlinks;
+			let cancel = cancel.clone();
+			move || walk_source_until(&source_path, &filter, follow_symlinks, &cancel)
+		});
+		unless_cancelled(cancel, walk).await?
+	};
 
-#[cfg(test)]
-mod tests {
-	use std::fs;
+	let mut plan = ScanPlan {
+		source_path,
+		entries,
+		skipped,
+		destinations,
+		source_as_dest: None,
+		sync: Vec::new(),
+		up_to_date: Vec::new(),
+	};
+	if !intent.bidirectional {
+		for dest in &plan.destinations {
+			plan.up_to_date.push(up_to_date_entries(db, intent_id, &plan.entries, dest).await?);
+		}
+		return Ok(plan);
+	}
 
-	use super::*;
+	// Changes flow back into the same subfolder that was walked
+	let mut source_as_dest = resolve_destination(db, &intent.source).await?;
+	source_as_dest.path = plan.source_path.clone();
+	plan.source_as_dest = Some(source_as_dest);
+	for dest in &plan.destinations {
+		// Two-way sync walks the destination too, which only works locally
+		if dest.remote {
+			return Err(ScanError::RemoteUnsupported(format!("two-way sync with {}", dest.path)));
+		}
+		let compare = tokio::task::spawn_blocking({
+			let (source_path, dest_path) = (plan.source_path.clone(), dest.path.clone());
+			let (entries, filter) = (plan.entries.clone(), filter.clone());
+			let last_synced_at = intent.last_synced_at;
+			move || plan_bidirectional(&source_path, &entries, &dest_path, &filter, last_synced_at)
+		});
+		let mut sync = unless_cancelled(cancel, compare).await?;
+		sync.settle_conflicts(intent.on_conflict);
+		plan.sync.push(sync);
+	}
+	Ok(plan)
+}
 
-	fn setup_tree(dir: &Path) {
-		fs::create_dir_all(dir.join("subdir/deep")).unwrap();
-		fs::write(dir.join("root.txt"), "hello").unwrap();
-		fs::write(dir.join("subdir/mid.txt"), "ab").unwrap();
-		fs::write(dir.join("subdir/deep/bottom.txt"), "abcdefghij").unwrap();
+/// Walk an intent's source for `plan_scan`, saving a checkpoint every
+/// `CHECKPOINT_EVERY` files: the files found so far (as `scan_entry` rows)
+/// and the intent's `scan_cursor`, the last file walked. A scan cut off by
+/// a crash, a failure or `cancel_scan` leaves its checkpoint behind, and
+/// the next one walks only what comes after the cursor.
+///
+/// A checkpoint is only picked up by a scan of the same source with the
+/// same filters, and not once it's older than `CHECKPOINT_MAX_AGE`. Files
+/// added before the cursor in between are found by the scan after that.
+async fn walk_checkpointed(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	intent: &IntentData,
+	source_path: &str,
+	filter: &PathFilter,
+	cancel: &Arc<AtomicBool>,
+) -> Result<(Vec<FileEntry>, SkipLog), ScanError> {
+	let key = checkpoint_key(intent, source_path);
+	let resumed = load_checkpoint(db, intent_id, &key).await?;
+	if let Some((cursor, entries, _)) = &resumed {
+		let found = entries.len();
+		tracing::info!("resuming scan of {} after {} ({} files found before)", source_path, cursor.display(), found);
 	}
 
-	#[test]
-	fn walks_nested_dirs() {
-		let tmp = tempfile::tempdir().unwrap();
-		setup_tree(tmp.path());
+	let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+	let mut walk = tokio::task::spawn_blocking({
+		let source_path = source_path.to_string();
+		let filter = filter.clone();
+		let follow_symlinks = intent.follow_symlinks;
+		let cancel = cancel.clone();
+		let resume_after = resumed.as_ref().map(|(cursor, _, _)| cursor.clone());
+		move || {
+			let on_checkpoint = |checkpoint: WalkCheckpoint| {
+				let _ = tx.send(checkpoint);
+			};
+			walk_resuming(&source_path, &filter, follow_symlinks, &cancel, resume_after.as_deref(), on_checkpoint)
+		}
+	});
 
-		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap()).unwrap();
+	let mut saved = resumed.as_ref().map_or(0, |(_, entries, _)| entries.len());
+	let mut ticker = tokio::time::interval(CANCEL_POLL_INTERVAL);
+	let (entries, skipped) = loop {
+		tokio::select! {
+			joined = &mut walk => {
+				break joined.map_err(|e| ScanError::DbError(format!("task join error: {e}")))??;
+			}
+			Some(checkpoint) = rx.recv() => {
+				saved += save_checkpoint(db, intent_id, &key, saved, checkpoint).await?;
+			}
+			_ = ticker.tick() => check_cancelled(cancel)?,
+		}
+	};
 
-		assert_eq!(skipped, 0);
-		assert_eq!(entries.len(), 3);
+	// What the earlier scan found comes first, as it did in walk order
+	let Some((_, mut all_entries, mut all_skipped)) = resumed else {
+		return Ok((entries, skipped));
+	};
+	all_entries.extend(entries);
+	all_skipped.append(skipped);
+	Ok((all_entries, all_skipped))
+}
 
-		let mut paths: Vec<&str> = entries.iter().map(|e| e.relative_path.as_str()).collect();
-		paths.sort();
-		assert_eq!(paths, vec!["root.txt", "subdir/deep/bottom.txt", "subdir/mid.txt"]);
-	}
+/// What a checkpoint has to have been made with to be resumed: the walked
+/// directory and everything that decides what's found in it.
+fn checkpoint_key(intent: &IntentData, source_path: &str) -> String {
+	serde_json::json!([source_path, intent.include_patterns, intent.exclude_patterns, intent.follow_symlinks])
+		.to_string()
+}
 
-	#[test]
-	fn reports_correct_sizes() {
-		let tmp = tempfile::tempdir().unwrap();
-		setup_tree(tmp.path());
+/// The cursor, files and skips of `intent_id`'s checkpoint, if it has one
+/// made with `key` recently enough to resume. Any other is cleared.
+async fn load_checkpoint(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	key: &str,
+) -> Result<Option<(PathBuf, Vec<FileEntry>, SkipLog)>, ScanError> {
+	let mut response = db
+		.db
+		.query("SELECT VALUE scan_cursor FROM ONLY $id")
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let cursor: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
 
-		let (entries, _) = walk_source(tmp.path().to_str().unwrap()).unwrap();
+	let fresh = |saved_at: &str| {
+		let Ok(saved_at) = chrono::DateTime::parse_from_rfc3339(saved_at) else {
+			return false;
+		};
+		let age = chrono::Utc::now() - saved_at.with_timezone(&chrono::Utc);
+		age.to_std().is_ok_and(|age| age < CHECKPOINT_MAX_AGE)
+	};
+	let usable = cursor.filter(|c| c["key"] == key && c["saved_at"].as_str().is_some_and(fresh));
+	let Some((path, skipped)) =
+		usable.and_then(|c| Some((decode_path(c["path"].as_str()?), SkipLog::from_json(&c["skipped"]))))
+	else {
+		clear_checkpoint(db, intent_id).await?;
+		return Ok(None);
+	};
 
-		let total: u64 = entries.iter().map(|e| e.size).sum();
-		// "hello" (5) + "ab" (2) + "abcdefghij" (10)
-		assert_eq!(total, 17);
-	}
+	let mut response = db
+		.db
+		.query("SELECT path, size, mtime_ns FROM scan_entry WHERE intent = $id ORDER BY seq")
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let entries = rows
+		.iter()
+		.filter_map(|row| {
+			let mtime_ns = row["mtime_ns"].as_u64().unwrap_or(0);
+			Some(FileEntry {
+				relative_path: decode_path(row["path"].as_str()?),
+				size: row["size"].as_u64().unwrap_or(0),
+				modified: SystemTime::UNIX_EPOCH + Duration::from_nanos(mtime_ns),
+			})
+		})
+		.collect();
 
-	#[test]
-	fn skips_symlinks() {
-		let tmp = tempfile::tempdir().unwrap();
-		setup_tree(tmp.path());
-		std::os::unix::fs::symlink(tmp.path().join("root.txt"), tmp.path().join("link.txt")).unwrap();
+	Ok(Some((path, entries, skipped)))
+}
 
-		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap()).unwrap();
+/// One file of a checkpoint, as bound into its insert. `seq` keeps walk order.
+#[derive(Debug, Clone, SurrealValue)]
+struct SavedEntry {
+	seq: i64,
+	path: String,
+	size: i64,
+	mtime_ns: Option<i64>,
+}
 
-		assert_eq!(entries.len(), 3); // symlink not counted as a file
-		assert_eq!(skipped, 1);
-	}
+/// Save a checkpoint's files and move the cursor past them, in one
+/// transaction. `saved` files were saved before it. Returns the number
+/// saved now.
+async fn save_checkpoint(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	key: &str,
+	saved: usize,
+	checkpoint: WalkCheckpoint,
+) -> Result<usize, ScanError> {
+	let entries: Vec<SavedEntry> = checkpoint
+		.entries
+		.iter()
+		.enumerate()
+		.map(|(i, entry)| SavedEntry {
+			seq: (saved + i) as i64,
+			path: encode_path(&entry.relative_path),
+			size: entry.size as i64,
+			mtime_ns: entry.mtime_ns(),
+		})
+		.collect();
+	let cursor = serde_json::json!({
+		"key": key,
+		"path": encode_path(&checkpoint.cursor),
+		"skipped": checkpoint.skipped.to_json(),
+		"saved_at": chrono::Utc::now().to_rfc3339(),
+	});
+
+	db.db
+		.query(
+			"BEGIN TRANSACTION;
+             FOR $entry IN $entries {
+                 CREATE scan_entry CONTENT {
+                     intent: $id, seq: $entry.seq, path: $entry.path, size: $entry.size, mtime_ns: $entry.mtime_ns,
+                 };
+             };
+             UPDATE $id SET scan_cursor = $cursor;
+             COMMIT TRANSACTION;",
+		)
+		.bind(("id", intent_id.clone()))
+		.bind(("entries", entries))
+		.bind(("cursor", cursor))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	Ok(checkpoint.entries.len())
+}
+
+/// Drop an intent's scan checkpoint, so its next scan walks from the start.
+async fn clear_checkpoint(db: &DbHandle, intent_id: &RecordId) -> Result<(), ScanError> {
+	db.db
+		.query("DELETE scan_entry WHERE intent = $id; UPDATE $id SET scan_cursor = NONE;")
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	Ok(())
+}
+
+/// Resolve an intent's source location to its directory on disk.
+pub async fn intent_source_root(db: &DbHandle, intent_id: &RecordId) -> Result<PathBuf, ScanError> {
+	let intent = load_intent(db, intent_id).await?;
+	let source_path = resolve_source_root(db, &intent).await?;
+	Ok(decode_path(&source_path))
+}
+
+/// The directory an intent transfers from: its source location, or the
+/// `source_subpath` folder under it.
+async fn resolve_source_root(db: &DbHandle, intent: &IntentData) -> Result<String, ScanError> {
+	let location_path = resolve_location_path(db, &intent.source, true).await?.local_path()?;
+	match &intent.source_subpath {
+		Some(subpath) => join_subpath(&location_path, subpath),
+		None => Ok(location_path),
+	}
+}
+
+/// `base` joined with the relative `subpath`, refusing absolute paths and
+/// `..` so an intent can't reach outside its location.
+fn join_subpath(base: &str, subpath: &str) -> Result<String, ScanError> {
+	let mut joined = decode_path(base);
+	for component in Path::new(subpath).components() {
+		match component {
+			Component::Normal(part) => joined.push(part),
+			Component::CurDir => {}
+			Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+				return Err(ScanError::InvalidSubpath(subpath.to_string()));
+			}
+		}
+	}
+	Ok(encode_path(&joined))
+}
+
+/// Create transfer jobs for specific files under an intent's source, e.g.
+/// ones a filesystem watcher saw change. Paths are absolute; anything
+/// outside the source, filtered out, or no longer a regular file is ignored.
+///
+/// A still-pending job for the same file is replaced rather than duplicated,
+/// so a burst of saves queues one copy. Returns the number of jobs created.
+pub async fn enqueue_changed_files(db: &DbHandle, intent_id: &RecordId, changed: &[PathBuf]) -> Result<u64, ScanError> {
+	let intent = load_intent(db, intent_id).await?;
+	let source_path = resolve_source_root(db, &intent).await?;
+	let root = decode_path(&source_path);
+	let filter = PathFilter::new(&intent.include_patterns, &intent.exclude_patterns)?;
+	let mut ignores = IgnoreFiles::default();
+
+	let mut entries = Vec::new();
+	for path in changed {
+		let Ok(relative) = path.strip_prefix(&root) else {
+			continue;
+		};
+		if !filter.accepts_nested(relative) || ignores.is_ignored_nested(&root, path) {
+			continue;
+		}
+		// Gone again, or a directory (or a symlink, unless those are followed) — nothing to copy
+		let metadata = if intent.follow_symlinks { fs::metadata(path) } else { fs::symlink_metadata(path) };
+		let Ok(metadata) = metadata else {
+			continue;
+		};
+		if !metadata.is_file() {
+			continue;
+		}
+		entries.push(FileEntry {
+			relative_path: relative.to_path_buf(),
+			size: metadata.len(),
+			modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+		});
+	}
+
+	if entries.is_empty() {
+		return Ok(0);
+	}
+
+	let mut destinations = Vec::with_capacity(intent.destinations.len());
+	for dest_id in &intent.destinations {
+		destinations.push(resolve_destination(db, dest_id).await?);
+	}
+
+	// A pending job for the same file is replaced, and already counted in the totals
+	let (mut replaced_jobs, mut replaced_bytes) = (0u64, 0u64);
+	for entry in &entries {
+		let mut response = db
+			.db
+			.query(
+				"DELETE transfer_job WHERE intent = $intent_id AND source_path = $source_path AND status = 'pending'
+                 RETURN BEFORE",
+			)
+			.bind(("intent_id", intent_id.clone()))
+			.bind(("source_path", encode_path(&root.join(&entry.relative_path))))
+			.await
+			.map_err(|e| ScanError::DbError(e.to_string()))?;
+		let replaced: Vec<serde_json::Value> = response.take(0).map_err(|e| ScanError::DbError(e.to_string()))?;
+		replaced_jobs += replaced.len() as u64;
+		replaced_bytes += replaced.iter().filter_map(|job| job["size"].as_u64()).sum::<u64>();
+	}
+
+	// The watcher saw these change, so nothing is up to date
+	let nothing_done = vec![BTreeSet::new(); destinations.len()];
+	let not_cancelled = AtomicBool::new(false);
+	let jobs_created =
+		create_transfer_jobs(db, intent_id, &source_path, &entries, &destinations, &nothing_done, &not_cancelled)
+			.await?;
+
+	let added_bytes: u64 = entries.iter().map(|e| e.size).sum::<u64>() * destinations.len() as u64;
+	db.db
+		.query(
+			"UPDATE $id SET
+                total_files += $files,
+                total_bytes += $bytes,
+                updated_at = time::now()",
+		)
+		.bind(("id", intent_id.clone()))
+		.bind(("files", jobs_created as i64 - replaced_jobs as i64))
+		.bind(("bytes", added_bytes as i64 - replaced_bytes as i64))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	Ok(jobs_created)
+}
+
+/// Queue another copy of every file a completed job wrote that's no longer
+/// at its destination, e.g. deleted by hand after a sync. Only each path's
+/// latest copy is requeued, and only while its source is still there.
+/// Remote destinations aren't checked. Returns the number of jobs requeued.
+pub async fn requeue_missing_copies(db: &DbHandle, intent_id: &RecordId) -> Result<u64, ScanError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT id, source_path, dest_path FROM transfer_job
+             WHERE intent = $intent_id AND status = 'complete' AND destination.machine.kind != 'remote'
+             ORDER BY completed_at ASC",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	// Later rows win, so each path maps to its most recent copy
+	let latest: HashMap<&str, &serde_json::Value> =
+		rows.iter().filter_map(|r| Some((r["dest_path"].as_str()?, r))).collect();
+	let missing: Vec<RecordId> = latest
+		.into_iter()
+		.filter(|(dest_path, row)| {
+			let source_path = row["source_path"].as_str().unwrap_or_default();
+			fs::symlink_metadata(decode_path(dest_path)).is_err() && decode_path(source_path).is_file()
+		})
+		.filter_map(|(_, row)| serde_json::from_value(row["id"].clone()).ok())
+		.collect();
+	if missing.is_empty() {
+		return Ok(0);
+	}
+
+	db.db
+		.query(
+			"UPDATE $ids SET status = 'pending', attempts = 0, bytes_transferred = 0, next_retry_at = NONE,
+                last_error = NONE, error_kind = NONE",
+		)
+		.bind(("ids", missing.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	Ok(missing.len() as u64)
+}
+
+/// Atomically move the intent into `scanning`. The WHERE clause makes this a
+/// compare-and-set: if another caller got there first, no row is returned.
+async fn claim_intent(db: &DbHandle, intent_id: &RecordId) -> Result<(), ScanError> {
+	let mut response = db
+		.db
+		.query(
+			"UPDATE $id SET status = 'scanning', scan_started_at = time::now(), updated_at = time::now()
+                WHERE status NOT IN ['scanning', 'transferring', 'verifying']
+                RETURN id",
+		)
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let claimed: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	if claimed.is_empty() {
+		return Err(ScanError::AlreadyRunning(format!("{:?}", intent_id)));
+	}
+
+	Ok(())
+}
+
+/// Load the intent fields needed for scanning via raw query + JSON.
+async fn load_intent(db: &DbHandle, intent_id: &RecordId) -> Result<IntentData, ScanError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT name, source, destinations, include_patterns, exclude_patterns, bidirectional, follow_symlinks,
+                    source_subpath, on_conflict, last_synced_at
+             FROM $id",
+		)
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let row = row.ok_or_else(|| ScanError::IntentNotFound(format!("{:?}", intent_id)))?;
+
+	let source: RecordId = serde_json::from_value(row["source"].clone())
+		.map_err(|e| ScanError::DbError(format!("failed to parse intent.source: {e}")))?;
+
+	let destinations: Vec<RecordId> = serde_json::from_value(row["destinations"].clone())
+		.map_err(|e| ScanError::DbError(format!("failed to parse intent.destinations: {e}")))?;
+
+	// NONE/null means "no patterns"
+	let include_patterns: Vec<String> = serde_json::from_value(row["include_patterns"].clone()).unwrap_or_default();
+	let exclude_patterns: Vec<String> = serde_json::from_value(row["exclude_patterns"].clone()).unwrap_or_default();
+
+	let name = row["name"].as_str().map(String::from);
+	let bidirectional = row["bidirectional"].as_bool().unwrap_or(false);
+	let follow_symlinks = row["follow_symlinks"].as_bool().unwrap_or(false);
+	let source_subpath = row["source_subpath"].as_str().filter(|s| !s.is_empty()).map(String::from);
+	let on_conflict = serde_json::from_value(row["on_conflict"].clone()).unwrap_or_default();
+	let last_synced_at = row["last_synced_at"]
+		.as_str()
+		.and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+		.map(SystemTime::from);
+
+	Ok(IntentData {
+		name,
+		source,
+		destinations,
+		include_patterns,
+		exclude_patterns,
+		bidirectional,
+		follow_symlinks,
+		source_subpath,
+		on_conflict,
+		last_synced_at,
+	})
+}
+
+/// Resolve a location record ID to its absolute path and which machine
+/// kind holds it, so callers know whether it's reachable locally.
+async fn resolve_location_path(
+	db: &DbHandle,
+	location_id: &RecordId,
+	is_source: bool,
+) -> Result<ResolvedLocation, ScanError> {
+	let mut response = db
+		.db
+		.query("SELECT path, machine.kind = 'remote' AS remote FROM $id")
+		.bind(("id", location_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let Some(path) = row.as_ref().and_then(|r| r["path"].as_str()) else {
+		let id_str = format!("{:?}", location_id);
+		return Err(if is_source {
+			ScanError::SourceLocationNotFound(id_str)
+		} else {
+			ScanError::DestLocationNotFound(id_str)
+		});
+	};
+	let remote = row.as_ref().is_some_and(|r| r["remote"].as_bool() == Some(true));
+	Ok(ResolvedLocation { path: path.to_string(), remote })
+}
+
+/// Resolve a destination location's path and its drive's file size limit.
+async fn resolve_destination(db: &DbHandle, location_id: &RecordId) -> Result<Destination, ScanError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT path, drive.limitations.max_file_size AS max_file_size, machine.kind = 'remote' AS remote
+             FROM $id",
+		)
+		.bind(("id", location_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	let row = row.ok_or_else(|| ScanError::DestLocationNotFound(format!("{:?}", location_id)))?;
+	let path = row["path"]
+		.as_str()
+		.ok_or_else(|| ScanError::DestLocationNotFound(format!("{:?}", location_id)))?
+		.to_string();
+
+	Ok(Destination {
+		id: location_id.clone(),
+		path,
+		max_file_size: row["max_file_size"].as_u64(),
+		remote: row["remote"].as_bool() == Some(true),
+	})
+}
+
+/// Reject copying `source` to `dest` on the same machine when they're the
+/// same directory or either holds the other. Paths are compared after
+/// resolving symlinks where they exist.
+pub fn check_overlap(source: &str, dest: &str) -> Result<(), ScanError> {
+	let (source_real, dest_real) = (real_path(source), real_path(dest));
+	if source_real == dest_real || path_contains(&source_real, &dest_real) || path_contains(&dest_real, &source_real) {
+		return Err(ScanError::OverlappingPaths(format!("{source} and {dest}")));
+	}
+	Ok(())
+}
+
+/// `path` with symlinks resolved as far as it exists; a destination may not
+/// have been created yet.
+fn real_path(path: &str) -> String {
+	let path = decode_path(path);
+	for existing in path.ancestors() {
+		if let Ok(real) = fs::canonicalize(existing) {
+			return match path.strip_prefix(existing) {
+				Ok(rest) if !rest.as_os_str().is_empty() => encode_path(&real.join(rest)),
+				_ => encode_path(&real),
+			};
+		}
+	}
+	encode_path(&path)
+}
+
+/// Per-directory ignore file, in gitignore syntax, kept with the data.
+const IGNORE_FILE: &str = ".kipignore";
+
+/// The `.kipignore` files met during one walk, each read once. A file
+/// applies to its directory's subtree, and a deeper file overrides a
+/// shallower one (including `!` re-includes), as with `.gitignore`.
+#[derive(Default)]
+struct IgnoreFiles {
+	by_dir: HashMap<PathBuf, Option<Gitignore>>,
+}
+
+impl IgnoreFiles {
+	fn is_ignored(&mut self, root: &Path, path: &Path, is_dir: bool) -> bool {
+		let Some(parent) = path.parent() else {
+			return false;
+		};
+		// Closest directory first
+		for dir in parent.ancestors().take_while(|d| d.starts_with(root)) {
+			let rules = self.by_dir.entry(dir.to_path_buf()).or_insert_with(|| load_ignore_file(dir));
+			match rules.as_ref().map(|r| r.matched(path, is_dir)) {
+				Some(Match::Ignore(_)) => return true,
+				Some(Match::Whitelist(_)) => return false,
+				Some(Match::None) | None => {}
+			}
+		}
+		false
+	}
+
+	/// Record that the walk is about to list `dir`. Until its `.kipignore`
+	/// turns up (`found_in`) it has no rules, so nothing stats for one.
+	fn listing(&mut self, dir: &Path) {
+		self.by_dir.entry(dir.to_path_buf()).or_insert(None);
+	}
+
+	/// The walk listed a `.kipignore` in `dir`: read its rules.
+	fn found_in(&mut self, dir: &Path) {
+		self.by_dir.insert(dir.to_path_buf(), load_ignore_file(dir));
+	}
+
+	/// Like `is_ignored` for a file found without walking down to it, so
+	/// the directories above it have to be checked too.
+	fn is_ignored_nested(&mut self, root: &Path, path: &Path) -> bool {
+		let dirs: Vec<&Path> = path.ancestors().skip(1).take_while(|d| d.starts_with(root) && *d != root).collect();
+		dirs.iter().rev().any(|dir| self.is_ignored(root, dir, true)) || self.is_ignored(root, path, false)
+	}
+}
+
+/// Rules from `dir`'s `.kipignore`, if it has one. Lines that don't parse
+/// are logged and left out rather than failing the scan.
+fn load_ignore_file(dir: &Path) -> Option<Gitignore> {
+	let file = dir.join(IGNORE_FILE);
+	if !file.is_file() {
+		return None;
+	}
+	let mut builder = GitignoreBuilder::new(dir);
+	if let Some(e) = builder.add(&file) {
+		tracing::warn!("{}: {}", file.display(), e);
+	}
+	builder
+		.build()
+		.map_err(|e| tracing::warn!("ignoring {}: {}", file.display(), e))
+		.ok()
+}
+
+/// Walk `source_path` for regular files the filter accepts. Symlinks are
+/// skipped unless `follow_symlinks` is set; then their targets are walked
+/// as if they lived here, and a link back to one of its own ancestors is
+/// skipped rather than looped through. Anything a `.kipignore` matches is
+/// left out like an excluded path. Skipped entries are logged by path,
+/// relative to the source, with the reason.
+fn walk_source(
+	source_path: &str,
+	filter: &PathFilter,
+	follow_symlinks: bool,
+) -> Result<(Vec<FileEntry>, SkipLog), ScanError> {
+	walk_source_until(source_path, filter, follow_symlinks, &AtomicBool::new(false))
+}
+
+/// `walk_source`, returning `ScanError::Cancelled` at the next entry once
+/// `cancel` is raised.
+fn walk_source_until(
+	source_path: &str,
+	filter: &PathFilter,
+	follow_symlinks: bool,
+	cancel: &AtomicBool,
+) -> Result<(Vec<FileEntry>, SkipLog), ScanError> {
+	walk_resuming(source_path, filter, follow_symlinks, cancel, None, |_| {})
+}
+
+/// How far a walk has got, handed out every `CHECKPOINT_EVERY` files.
+struct WalkCheckpoint {
+	/// The last file walked; everything before it in walk order is done.
+	cursor: PathBuf,
+	/// Files found since the last checkpoint.
+	entries: Vec<FileEntry>,
+	/// Everything skipped so far.
+	skipped: SkipLog,
+}
+
+/// Where a name sorts among its siblings in walk order: a directory's
+/// `.kipignore` first, then by name.
+fn walk_key(name: &OsStr) -> (bool, &OsStr) {
+	(name != IGNORE_FILE, name)
+}
+
+/// Whether a walk in walk order reaches `path` no later than `cursor`,
+/// leaving out the directories the cursor is inside.
+fn walked_before(path: &Path, cursor: &Path) -> bool {
+	if cursor.starts_with(path) {
+		return path == cursor;
+	}
+	path.components().map(|c| walk_key(c.as_os_str())).lt(cursor.components().map(|c| walk_key(c.as_os_str())))
+}
+
+/// `walk_source_until`, leaving out everything up to and including the
+/// relative `resume_after` (an earlier walk's checkpoint cursor) and
+/// passing a `WalkCheckpoint` to `checkpoint` every `CHECKPOINT_EVERY`
+/// files found. Only what this walk found is returned.
+fn walk_resuming(
+	source_path: &str,
+	filter: &PathFilter,
+	follow_symlinks: bool,
+	cancel: &AtomicBool,
+	resume_after: Option<&Path>,
+	mut checkpoint: impl FnMut(WalkCheckpoint),
+) -> Result<(Vec<FileEntry>, SkipLog), ScanError> {
+	let root_buf = decode_path(source_path);
+	let root = root_buf.as_path();
+
+	// One stat for both checks; on a network mount each is a round trip
+	match fs::metadata(root) {
+		Err(_) => return Err(ScanError::SourcePathNotExists(source_path.to_string())),
+		Ok(m) if !m.is_dir() => return Err(ScanError::SourcePathNotDir(source_path.to_string())),
+		Ok(_) => {}
+	}
+
+	let relative_to_root = |path: &Path| path.strip_prefix(root).unwrap_or(path).to_path_buf();
+	let mut entries = Vec::new();
+	let mut skipped = SkipLog::default();
+
+	// Excluded and ignored directories are pruned without descending into
+	// them; each counts as one skipped entry, as does each ignored file
+	let pruned = RefCell::new(SkipLog::default());
+	let mut ignores = IgnoreFiles::default();
+	// Each directory's `.kipignore` is sorted ahead of its siblings, so its
+	// rules are read before they're needed and a directory without one costs
+	// nothing beyond its listing. The rest go by name, so the order is the
+	// same every time and a checkpoint's cursor says what's been walked
+	let in_walk_order =
+		|a: &walkdir::DirEntry, b: &walkdir::DirEntry| walk_key(a.file_name()).cmp(&walk_key(b.file_name()));
+	// walkdir checks followed links against the directories above them and
+	// reports a cycle as an error entry, which is counted as skipped below.
+	// Entry types come from the directory listing; only files get a stat
+	let walker = WalkDir::new(root)
+		.follow_links(follow_symlinks)
+		.sort_by(in_walk_order)
+		.into_iter()
+		.filter_entry(|e| {
+			if e.depth() == 0 {
+				ignores.listing(root);
+				return true;
+			}
+			let is_dir = e.file_type().is_dir();
+			if !is_dir && e.file_name() == IGNORE_FILE {
+				if let Some(dir) = e.path().parent() {
+					ignores.found_in(dir);
+				}
+			}
+			// Walked, and counted, by the scan this one resumes
+			let relative = e.path().strip_prefix(root).unwrap_or(e.path());
+			if resume_after.is_some_and(|cursor| walked_before(relative, cursor)) {
+				return false;
+			}
+			let reason = if is_dir && e.path().strip_prefix(root).is_ok_and(|rel| filter.is_excluded(rel)) {
+				SkipReason::Excluded
+			} else if ignores.is_ignored(root, e.path(), is_dir) {
+				SkipReason::Ignored
+			} else {
+				if is_dir {
+					ignores.listing(e.path());
+				}
+				return true;
+			};
+			pruned.borrow_mut().record(relative_to_root(e.path()), reason);
+			false
+		});
+	let mut checkpointed = 0;
+
+	for result in walker {
+		check_cancelled(cancel)?;
+		let entry = match result {
+			Ok(e) => e,
+			Err(e) => {
+				let path = e.path().map(relative_to_root).unwrap_or_default();
+				let reason = SkipReason::of_walk_error(&e);
+				if reason != SkipReason::SymlinkLoop {
+					tracing::warn!("scan of {} skipped {}: {}", source_path, path.display(), e);
+				}
+				skipped.record(path, reason);
+				continue;
+			}
+		};
+
+		if entry.file_type().is_dir() || entry.file_type().is_symlink() {
+			if entry.file_type().is_symlink() {
+				skipped.record(relative_to_root(entry.path()), SkipReason::Symlink);
+			}
+			continue;
+		}
+
+		let relative = entry
+			.path()
+			.strip_prefix(root)
+			.expect("walkdir entry must be under root")
+			.to_path_buf();
+
+		let metadata = match entry.metadata() {
+			Ok(m) => m,
+			Err(e) => {
+				tracing::warn!("scan of {} skipped {}: {}", source_path, relative.display(), e);
+				skipped.record(relative, SkipReason::of_walk_error(&e));
+				continue;
+			}
+		};
+
+		if !filter.accepts(&relative) {
+			skipped.record(relative, SkipReason::Excluded);
+			continue;
+		}
+
+		entries.push(FileEntry {
+			relative_path: relative,
+			size: metadata.len(),
+			modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+		});
+
+		// Only at a file: a directory as the cursor would leave out all of it
+		if entries.len() - checkpointed >= CHECKPOINT_EVERY {
+			let mut so_far = skipped.clone();
+			so_far.append(pruned.borrow().clone());
+			checkpoint(WalkCheckpoint {
+				cursor: entries[entries.len() - 1].relative_path.clone(),
+				entries: entries[checkpointed..].to_vec(),
+				skipped: so_far,
+			});
+			checkpointed = entries.len();
+		}
+	}
+
+	skipped.append(pruned.into_inner());
+	Ok((entries, skipped))
+}
+
+/// Entries whose last completed job to `dest` copied the same size and
+/// mtime they have now, and whose copy is still there. Re-running an
+/// unchanged intent then queues nothing. Remote copies can't be checked
+/// cheaply and are trusted.
+async fn up_to_date_entries(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	entries: &[FileEntry],
+	dest: &Destination,
+) -> Result<BTreeSet<PathBuf>, ScanError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT dest_path, size, source_mtime_ns FROM transfer_job
+             WHERE intent = $intent_id AND destination = $dest_id AND status = 'complete'
+             ORDER BY completed_at ASC",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.bind(("dest_id", dest.id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	// Later rows win, so each path maps to its most recent copy
+	let copied: HashMap<&str, (Option<u64>, Option<i64>)> = rows
+		.iter()
+		.filter_map(|r| Some((r["dest_path"].as_str()?, (r["size"].as_u64(), r["source_mtime_ns"].as_i64()))))
+		.collect();
+	if copied.is_empty() {
+		return Ok(BTreeSet::new());
+	}
+
+	let dest_base = decode_path(&dest.path);
+	let mut done = BTreeSet::new();
+	for entry in entries {
+		let dest_full = dest_base.join(&entry.relative_path);
+		let Some(&(size, mtime_ns)) = copied.get(encode_path(&dest_full).as_str()) else {
+			continue;
+		};
+		if size != Some(entry.size) || mtime_ns.is_none() || mtime_ns != entry.mtime_ns() {
+			continue;
+		}
+		let still_there = dest.remote || fs::metadata(&dest_full).is_ok_and(|m| m.len() == entry.size);
+		if still_there {
+			done.insert(entry.relative_path.clone());
+		}
+	}
+	Ok(done)
+}
+
+/// Queue a job per entry for each destination, leaving out those the
+/// destination's `up_to_date` set already holds. Nothing pending is written
+/// once `cancel` is raised.
+async fn create_transfer_jobs(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	source_base_path: &str,
+	entries: &[FileEntry],
+	destinations: &[Destination],
+	up_to_date: &[BTreeSet<PathBuf>],
+	cancel: &AtomicBool,
+) -> Result<u64, ScanError> {
+	let mut jobs_created = 0u64;
+	let mut pending = Vec::new();
+	let source_base = decode_path(source_base_path);
+
+	for (dest, done) in destinations.iter().zip(up_to_date) {
+		let dest_id = &dest.id;
+		let dest_base = decode_path(&dest.path);
+
+		for entry in entries.iter().filter(|e| !done.contains(&e.relative_path)) {
+			check_cancelled(cancel)?;
+			let source_full = encode_path(&source_base.join(&entry.relative_path));
+			let dest_full = encode_path(&dest_base.join(&entry.relative_path));
+
+			// Never attempt a write the destination filesystem can't hold
+			if let Some(max) = dest.max_file_size.filter(|&max| entry.size > max) {
+				let job = ReviewJob {
+					source_path: &source_full,
+					dest_path: &dest_full,
+					destination: dest_id,
+					size: entry.size,
+					dest_size: None,
+					source_modified: Some(entry.modified),
+					dest_modified: None,
+					error_kind: "file_too_large",
+					message: format!(
+						"file is {} bytes; destination drive allows at most {max} bytes per file",
+						entry.size
+					),
+					options: &["skip"],
+					is_dir: false,
+				};
+				create_review_job(db, intent_id, job).await?;
+				jobs_created += 1;
+				continue;
+			}
+
+			pending.push(NewJob {
+				source_path: source_full,
+				dest_path: dest_full,
+				destination: dest_id.clone(),
+				size: entry.size as i64,
+				source_mtime_ns: entry.mtime_ns(),
+			});
+		}
+	}
+
+	check_cancelled(cancel)?;
+	if !pending.is_empty() {
+		insert_pending_jobs(db, intent_id, &pending).await?;
+		jobs_created += pending.len() as u64;
+	}
+
+	Ok(jobs_created)
+}
+
+/// One `pending` job, as bound into a batch insert.
+#[derive(Debug, Clone, SurrealValue)]
+struct NewJob {
+	source_path: String,
+	dest_path: String,
+	destination: RecordId,
+	size: i64,
+	source_mtime_ns: Option<i64>,
+}
+
+/// Create pending jobs in one transaction, `JOB_BATCH_SIZE` per statement,
+/// so a scan that's interrupted or fails partway leaves none of them behind
+/// for the scheduler to run as if the scan were complete.
+async fn insert_pending_jobs(db: &DbHandle, intent_id: &RecordId, jobs: &[NewJob]) -> Result<(), ScanError> {
+	let batches: Vec<&[NewJob]> = jobs.chunks(JOB_BATCH_SIZE).collect();
+	let mut sql = String::from("BEGIN TRANSACTION;");
+	for i in 0..batches.len() {
+		sql.push_str(&format!("FOR $job IN $batch{i} {CREATE_JOB};"));
+	}
+	sql.push_str("COMMIT TRANSACTION;");
+
+	let mut query = db.db.query(sql).bind(("intent_id", intent_id.clone()));
+	for (i, batch) in batches.into_iter().enumerate() {
+		query = query.bind((format!("batch{i}"), batch.to_vec()));
+	}
+	query
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	Ok(())
+}
+
+/// Loop body creating the pending job bound as `$job`.
+const CREATE_JOB: &str = "{
+                CREATE transfer_job CONTENT {
+                    intent: $intent_id,
+                    source_path: $job.source_path,
+                    dest_path: $job.dest_path,
+                    destination: $job.destination,
+                    size: $job.size,
+                    source_mtime_ns: $job.source_mtime_ns,
+                    bytes_transferred: 0,
+                    status: 'pending',
+                    attempts: 0,
+                    max_attempts: 3,
+                    last_error: NONE,
+                    error_kind: NONE,
+                    source_hash: NONE,
+                    dest_hash: NONE,
+                    started_at: NONE,
+                    completed_at: NONE,
+                    created_at: time::now(),
+                };
+            }";
+
+/// What a bidirectional scan does with one relative path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncAction {
+	/// Both sides already match.
+	Skip,
+	/// Copy source → destination.
+	ToDest,
+	/// Copy destination → source.
+	ToSource,
+	/// Both sides changed since the last sync; the intent's `on_conflict`
+	/// policy or a human picks.
+	Conflict,
+}
+
+/// Decide which way a file flows between the two endpoints of a
+/// bidirectional intent.
+///
+/// Matching size and mtime (the copier preserves mtime) means in sync; same
+/// size with different mtimes falls back to `same_content`, which hashes.
+/// Otherwise a side "changed" if its mtime is after `last_sync`; exactly one
+/// changed side wins, both changed is a conflict. With no previous sync the
+/// newer file wins. A file missing on one side is copied to it — deletions
+/// aren't propagated.
+fn plan_sync(
+	source: Option<&FileEntry>,
+	dest: Option<&FileEntry>,
+	last_sync: Option<SystemTime>,
+	same_content: impl FnOnce() -> bool,
+) -> SyncAction {
+	let (s, d) = match (source, dest) {
+		(Some(s), Some(d)) => (s, d),
+		(Some(_), None) => return SyncAction::ToDest,
+		(None, Some(_)) => return SyncAction::ToSource,
+		(None, None) => return SyncAction::Skip,
+	};
+
+	if s.size == d.size && (s.modified == d.modified || same_content()) {
+		return SyncAction::Skip;
+	}
+
+	match last_sync {
+		Some(t) => match (s.modified > t, d.modified > t) {
+			(true, false) => SyncAction::ToDest,
+			(false, true) => SyncAction::ToSource,
+			// Both edited, or they differ without either being touched
+			_ => SyncAction::Conflict,
+		},
+		None => match s.modified.cmp(&d.modified) {
+			std::cmp::Ordering::Greater => SyncAction::ToDest,
+			std::cmp::Ordering::Less => SyncAction::ToSource,
+			std::cmp::Ordering::Equal => SyncAction::Conflict,
+		},
+	}
+}
+
+/// Jobs a bidirectional scan wants for one source/destination pair.
+#[derive(Debug, Default)]
+struct SyncPlan {
+	to_dest: Vec<FileEntry>,
+	to_source: Vec<FileEntry>,
+	/// (source side, destination side)
+	conflicts: Vec<(FileEntry, FileEntry)>,
+}
+
+impl SyncPlan {
+	fn job_count(&self) -> u64 {
+		(self.to_dest.len() + self.to_source.len() + self.conflicts.len()) as u64
+	}
+
+	fn queued_bytes(&self) -> u64 {
+		self.to_dest.iter().chain(&self.to_source).map(|e| e.size).sum()
+	}
+
+	/// Copy the winning side of each conflict the policy decides; the rest
+	/// stay conflicts and go to review.
+	fn settle_conflicts(&mut self, policy: ConflictPolicy) {
+		for (s, d) in std::mem::take(&mut self.conflicts) {
+			match policy.source_wins(Some(s.modified), Some(d.modified)) {
+				Some(true) => self.to_dest.push(s),
+				Some(false) => self.to_source.push(d),
+				None => self.conflicts.push((s, d)),
+			}
+		}
+	}
+}
+
+/// Walk the destination and compare it against the already-walked source.
+/// A destination that doesn't exist yet is treated as empty.
+fn plan_bidirectional(
+	source_path: &str,
+	source_entries: &[FileEntry],
+	dest_path: &str,
+	filter: &PathFilter,
+	last_sync: Option<SystemTime>,
+) -> Result<SyncPlan, ScanError> {
+	// Destinations hold the copies Kip wrote, which are never symlinks
+	let dest_entries = match walk_source(dest_path, filter, false) {
+		Ok((entries, _)) => entries,
+		Err(ScanError::SourcePathNotExists(_)) => Vec::new(),
+		Err(e) => return Err(e),
+	};
+
+	let source_root = decode_path(source_path);
+	let dest_root = decode_path(dest_path);
+	let by_path = |entries: &[FileEntry]| -> HashMap<PathBuf, FileEntry> {
+		entries.iter().map(|e| (e.relative_path.clone(), e.clone())).collect()
+	};
+	let (sources, dests) = (by_path(source_entries), by_path(&dest_entries));
+	let all_paths: BTreeSet<&PathBuf> = sources.keys().chain(dests.keys()).collect();
+
+	let mut plan = SyncPlan::default();
+	for rel in all_paths {
+		let (s, d) = (sources.get(rel), dests.get(rel));
+		let same_content = || {
+			let hash = |root: &Path| copier::hash_file(root.join(rel), HashAlgorithm::Blake3).ok();
+			matches!((hash(&source_root), hash(&dest_root)), (Some(a), Some(b)) if a == b)
+		};
+		match plan_sync(s, d, last_sync, same_content) {
+			SyncAction::Skip => {}
+			SyncAction::ToDest => plan.to_dest.extend(s.cloned()),
+			SyncAction::ToSource => plan.to_source.extend(d.cloned()),
+			SyncAction::Conflict => {
+				if let (Some(s), Some(d)) = (s, d) {
+					plan.conflicts.push((s.clone(), d.clone()));
+				}
+			}
+		}
+	}
+
+	Ok(plan)
+}
+
+/// Turn a `SyncPlan` into jobs. Reverse-direction jobs copy from the
+/// destination into the source location. Returns the number of jobs created.
+async fn create_sync_jobs(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	source_path: &str,
+	source_as_dest: &Destination,
+	dest: &Destination,
+	plan: &SyncPlan,
+	cancel: &AtomicBool,
+) -> Result<u64, ScanError> {
+	// The sync plan already left out files that match on both sides
+	let nothing_done = [BTreeSet::new()];
+	let to_dest = std::slice::from_ref(dest);
+	let mut created =
+		create_transfer_jobs(db, intent_id, source_path, &plan.to_dest, to_dest, &nothing_done, cancel).await?;
+	let to_source = std::slice::from_ref(source_as_dest);
+	created +=
+		create_transfer_jobs(db, intent_id, &dest.path, &plan.to_source, to_source, &nothing_done, cancel).await?;
+
+	let (source_root, dest_root) = (decode_path(source_path), decode_path(&dest.path));
+	for (s, d) in &plan.conflicts {
+		let source_full = encode_path(&source_root.join(&s.relative_path));
+		let dest_full = encode_path(&dest_root.join(&d.relative_path));
+		let job = ReviewJob {
+			source_path: &source_full,
+			dest_path: &dest_full,
+			destination: &dest.id,
+			size: s.size,
+			dest_size: Some(d.size),
+			source_modified: Some(s.modified),
+			dest_modified: Some(d.modified),
+			error_kind: "conflict",
+			message: "changed on both sides since the last sync".to_string(),
+			options: &["keep_source", "keep_dest", "skip"],
+			is_dir: false,
+		};
+		create_review_job(db, intent_id, job).await?;
+		created += 1;
+	}
+
+	Ok(created)
+}
+
+/// A job that goes straight to `needs_review` instead of being attempted.
+struct ReviewJob<'a> {
+	source_path: &'a str,
+	dest_path: &'a str,
+	destination: &'a RecordId,
+	size: u64,
+	dest_size: Option<u64>,
+	source_modified: Option<SystemTime>,
+	dest_modified: Option<SystemTime>,
+	error_kind: &'static str,
+	message: String,
+	options: &'static [&'static str],
+	/// Stands for a whole source directory rather than one file.
+	is_dir: bool,
+}
+
+/// File a `permission_denied` review job, per destination, for each entry
+/// under `source_root` the walk couldn't read, so a subtree it couldn't
+/// enter isn't just missing from the backup. Retrying one re-walks it with
+/// `retry_unreadable`. Returns the number of jobs filed.
+async fn file_unreadable(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	source_root: &Path,
+	destinations: &[Destination],
+	denied: &[PathBuf],
+) -> Result<u64, ScanError> {
+	let mut filed = 0;
+	for relative in denied {
+		let source_full = source_root.join(relative);
+		let source_path = encode_path(&source_full);
+		let message = format!("can't read {}: permission denied, so nothing in it was copied", source_full.display());
+		for dest in destinations {
+			let dest_path = encode_path(&decode_path(&dest.path).join(relative));
+			let job = ReviewJob {
+				source_path: &source_path,
+				dest_path: &dest_path,
+				destination: &dest.id,
+				size: 0,
+				dest_size: None,
+				source_modified: None,
+				dest_modified: None,
+				error_kind: "permission_denied",
+				message: message.clone(),
+				options: &["retry", "skip"],
+				is_dir: true,
+			};
+			create_review_job(db, intent_id, job).await?;
+			filed += 1;
+		}
+	}
+	Ok(filed)
+}
+
+/// Retry a job `file_unreadable` filed: walk its directory again and queue
+/// what's there now, filing whatever is still unreadable anew. The job
+/// itself is removed. Returns `None`, touching nothing, for any other job.
+pub async fn retry_unreadable(db: &DbHandle, job_id: &RecordId) -> Result<Option<u64>, ScanError> {
+	let mut response = db
+		.db
+		.query("SELECT intent, destination, source_path, is_dir FROM ONLY $job")
+		.bind(("job", job_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let job: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	let Some(job) = job.filter(|j| j["is_dir"] == true) else {
+		return Ok(None);
+	};
+	let (Ok(intent_id), Ok(dest_id)) = (
+		serde_json::from_value::<RecordId>(job["intent"].clone()),
+		serde_json::from_value::<RecordId>(job["destination"].clone()),
+	) else {
+		return Ok(None);
+	};
+
+	let intent = load_intent(db, &intent_id).await?;
+	let root = decode_path(&resolve_source_root(db, &intent).await?);
+	let dir = decode_path(job["source_path"].as_str().unwrap_or_default());
+	let follow_symlinks = intent.follow_symlinks;
+	let (files, denied) = tokio::task::spawn_blocking({
+		let dir = dir.clone();
+		move || {
+			if dir.is_file() {
+				return (vec![dir], Vec::new());
+			}
+			// Gone since, or no longer a directory: nothing left to copy
+			match walk_source(&encode_path(&dir), &PathFilter::default(), follow_symlinks) {
+				Ok((entries, skipped)) => {
+					let files = entries.iter().map(|e| dir.join(&e.relative_path)).collect();
+					(files, skipped.denied)
+				}
+				Err(_) => (Vec::new(), Vec::new()),
+			}
+		}
+	})
+	.await
+	.map_err(|e| ScanError::DbError(format!("task join error: {e}")))?;
+
+	// The intent's filters apply from its source root, so queue by full path
+	let queued = enqueue_changed_files(db, &intent_id, &files).await?;
+	let relative = dir.strip_prefix(&root).unwrap_or(&dir).to_path_buf();
+	let still_denied: Vec<PathBuf> = denied.iter().map(|d| relative.join(d)).collect();
+	let destination = resolve_destination(db, &dest_id).await?;
+	let filed = file_unreadable(db, &intent_id, &root, std::slice::from_ref(&destination), &still_denied).await?;
+
+	// The job stood for one file in the intent's totals; its replacements are counted instead
+	db.db
+		.query("UPDATE $intent SET total_files += $filed - 1, updated_at = time::now(); DELETE $job")
+		.bind(("intent", intent_id))
+		.bind(("filed", filed as i64))
+		.bind(("job", job_id.clone()))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+	Ok(Some(queued + filed))
+}
+
+/// Record a job in `needs_review` along with its review item.
+async fn create_review_job(db: &DbHandle, intent_id: &RecordId, job: ReviewJob<'_>) -> Result<(), ScanError> {
+	let options: Vec<String> = job.options.iter().map(|o| o.to_string()).collect();
+
+	db.db
+		.query(
+			"LET $job = CREATE ONLY transfer_job CONTENT {
+                intent: $intent_id,
+                source_path: $source_path,
+                dest_path: $dest_path,
+                destination: $dest_id,
+                size: $size,
+                bytes_transferred: 0,
+                status: 'needs_review',
+                attempts: 0,
+                max_attempts: 3,
+                last_error: $message,
+                error_kind: $error_kind,
+                is_dir: $is_dir,
+                created_at: time::now(),
+            };
+            CREATE review_item CONTENT {
+                job: $job.id,
+                intent: $intent_id,
+                error_kind: $error_kind,
+                error_message: $message,
+                source_path: $source_path,
+                dest_path: $dest_path,
+                options: $options,
+                source_size: $size,
+                source_modified: IF $source_modified { <datetime>$source_modified } ELSE { NONE },
+                dest_size: $dest_size,
+                dest_modified: IF $dest_modified { <datetime>$dest_modified } ELSE { NONE },
+                created_at: time::now(),
+            };",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.bind(("source_path", job.source_path.to_string()))
+		.bind(("dest_path", job.dest_path.to_string()))
+		.bind(("dest_id", job.destination.clone()))
+		.bind(("size", job.size as i64))
+		.bind(("dest_size", job.dest_size.map(|s| s as i64)))
+		.bind(("source_modified", job.source_modified.map(transfer::rfc3339)))
+		.bind(("dest_modified", job.dest_modified.map(transfer::rfc3339)))
+		.bind(("error_kind", job.error_kind.to_string()))
+		.bind(("message", job.message))
+		.bind(("options", options))
+		.bind(("is_dir", job.is_dir))
+		.await
+		.map_err(|e| ScanError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| ScanError::DbError(e.to_string()))?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	fn setup_tree(dir: &Path) {
+		fs::create_dir_all(dir.join("subdir/deep")).unwrap();
+		fs::write(dir.join("root.txt"), "hello").unwrap();
+		fs::write(dir.join("subdir/mid.txt"), "ab").unwrap();
+		fs::write(dir.join("subdir/deep/bottom.txt"), "abcdefghij").unwrap();
+	}
+
+	#[test]
+	fn walks_nested_dirs() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+
+		assert_eq!(skipped.count, 0);
+		assert_eq!(entries.len(), 3);
+
+		let mut paths: Vec<&str> = entries.iter().map(|e| e.relative_path.to_str().unwrap()).collect();
+		paths.sort();
+		assert_eq!(paths, vec!["root.txt", "subdir/deep/bottom.txt", "subdir/mid.txt"]);
+	}
+
+	#[test]
+	fn reports_correct_sizes() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+
+		let (entries, _) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+
+		let total: u64 = entries.iter().map(|e| e.size).sum();
+		// "hello" (5) + "ab" (2) + "abcdefghij" (10)
+		assert_eq!(total, 17);
+	}
+
+	#[test]
+	fn skips_symlinks() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		std::os::unix::fs::symlink(tmp.path().join("root.txt"), tmp.path().join("link.txt")).unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+
+		assert_eq!(entries.len(), 3); // symlink not counted as a file
+		assert_eq!(skipped.count, 1);
+	}
+
+	#[test]
+	fn skip_log_names_each_entry_and_why() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		std::os::unix::fs::symlink(tmp.path().join("root.txt"), tmp.path().join("link.txt")).unwrap();
+		fs::write(tmp.path().join("scratch.tmp"), "x").unwrap();
+
+		let (_, skipped) = walk_source(tmp.path().to_str().unwrap(), &filter(&[], &["*.tmp"]), false).unwrap();
+
+		let mut logged = skipped.entries.clone();
+		logged.sort_by(|a, b| a.0.cmp(&b.0));
+		assert_eq!(
+			logged,
+			vec![(PathBuf::from("link.txt"), SkipReason::Symlink), (PathBuf::from("scratch.tmp"), SkipReason::Excluded)]
+		);
+	}
+
+	#[test]
+	fn skip_log_is_capped_but_counts_everything() {
+		let mut log = SkipLog::default();
+		for i in 0..SKIP_LOG_LIMIT + 5 {
+			log.record(PathBuf::from(format!("f{i}")), SkipReason::Unreadable);
+		}
+		assert_eq!(log.count, SKIP_LOG_LIMIT as u64 + 5);
+		assert_eq!(log.entries.len(), SKIP_LOG_LIMIT);
+	}
+
+	#[test]
+	fn skip_log_keeps_every_denied_path() {
+		let mut log = SkipLog::default();
+		for i in 0..SKIP_LOG_LIMIT + 50 {
+			log.record(PathBuf::from(format!("d{i}")), SkipReason::PermissionDenied);
+		}
+		log.record(PathBuf::from("link"), SkipReason::Symlink);
+		assert_eq!(log.entries.len(), SKIP_LOG_LIMIT);
+		assert_eq!(log.denied.len(), SKIP_LOG_LIMIT + 50, "each one is filed for review");
+	}
+
+	#[test]
+	fn follows_symlinks_when_asked() {
+		let tmp = tempfile::tempdir().unwrap();
+		let elsewhere = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		fs::write(elsewhere.path().join("shared.txt"), "shared").unwrap();
+		std::os::unix::fs::symlink(tmp.path().join("root.txt"), tmp.path().join("link.txt")).unwrap();
+		std::os::unix::fs::symlink(elsewhere.path(), tmp.path().join("linked_dir")).unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), true).unwrap();
+
+		assert_eq!(skipped.count, 0);
+		let mut paths: Vec<&str> = entries.iter().map(|e| e.relative_path.to_str().unwrap()).collect();
+		paths.sort();
+		assert_eq!(
+			paths,
+			vec!["link.txt", "linked_dir/shared.txt", "root.txt", "subdir/deep/bottom.txt", "subdir/mid.txt"]
+		);
+		let link = entries.iter().find(|e| e.relative_path.to_str() == Some("link.txt")).unwrap();
+		assert_eq!(link.size, 5, "size is the target's");
+	}
+
+	#[test]
+	fn symlink_cycle_terminates() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		std::os::unix::fs::symlink(tmp.path(), tmp.path().join("subdir/back_to_root")).unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), true).unwrap();
+
+		assert_eq!(entries.len(), 3);
+		assert_eq!(skipped.count, 1, "the looping link is skipped once");
+	}
+
+	#[test]
+	fn preserves_non_utf8_names() {
+		use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+		let tmp = tempfile::tempdir().unwrap();
+		let name = OsStr::from_bytes(b"caf\xe9.txt");
+		fs::write(tmp.path().join(name), "latin-1").unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+
+		assert_eq!(skipped.count, 0);
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].relative_path.as_os_str(), name);
+
+		// The job path round-trips through its DB encoding to the real file
+		let stored = encode_path(&tmp.path().join(&entries[0].relative_path));
+		assert_eq!(fs::read_to_string(decode_path(&stored)).unwrap(), "latin-1");
+	}
 
 	#[test]
 	fn empty_dir_returns_zero() {
 		let tmp = tempfile::tempdir().unwrap();
 
-		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap()).unwrap();
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
 
 		assert_eq!(entries.len(), 0);
-		assert_eq!(skipped, 0);
+		assert_eq!(skipped.count, 0);
+	}
+
+	fn filter(include: &[&str], exclude: &[&str]) -> PathFilter {
+		let owned = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+		PathFilter::new(&owned(include), &owned(exclude)).unwrap()
+	}
+
+	fn sorted_paths(entries: &[FileEntry]) -> Vec<String> {
+		let mut paths: Vec<String> = entries
+			.iter()
+			.map(|e| e.relative_path.to_string_lossy().to_string())
+			.collect();
+		paths.sort();
+		paths
+	}
+
+	#[test]
+	fn exclude_tmp_files() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		fs::write(tmp.path().join("scratch.tmp"), "x").unwrap();
+		fs::write(tmp.path().join("subdir/deep/cache.tmp"), "y").unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &filter(&[], &["*.tmp"]), false).unwrap();
+
+		assert_eq!(sorted_paths(&entries), vec!["root.txt", "subdir/deep/bottom.txt", "subdir/mid.txt"]);
+		assert_eq!(skipped.count, 2);
+	}
+
+	#[test]
+	fn include_subtree_only() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &filter(&["subdir/**"], &[]), false).unwrap();
+		assert_eq!(sorted_paths(&entries), vec!["subdir/deep/bottom.txt", "subdir/mid.txt"]);
+		assert_eq!(skipped.count, 1);
+
+		// No `src/` in the fixture, so nothing matches
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &filter(&["src/**"], &[]), false).unwrap();
+		assert!(entries.is_empty());
+		assert_eq!(skipped.count, 3);
+	}
+
+	#[test]
+	fn kipignore_prunes_node_modules() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		fs::create_dir_all(tmp.path().join("app/node_modules/left-pad")).unwrap();
+		fs::write(tmp.path().join("app/node_modules/left-pad/index.js"), "pad").unwrap();
+		fs::write(tmp.path().join("app/main.js"), "main").unwrap();
+		fs::write(tmp.path().join(".kipignore"), "node_modules/\n").unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+
+		assert_eq!(
+			sorted_paths(&entries),
+			vec![".kipignore", "app/main.js", "root.txt", "subdir/deep/bottom.txt", "subdir/mid.txt"]
+		);
+		assert_eq!(skipped.count, 1, "the pruned directory counts once");
+	}
+
+	#[test]
+	fn nested_kipignore_applies_to_its_subtree() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		fs::write(tmp.path().join("top.log"), "log").unwrap();
+		fs::write(tmp.path().join("subdir/build.log"), "log").unwrap();
+		fs::write(tmp.path().join("subdir/keep.log"), "log").unwrap();
+		fs::write(tmp.path().join("subdir/.kipignore"), "*.log\n!keep.log\n").unwrap();
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+
+		assert_eq!(
+			sorted_paths(&entries),
+			vec![
+				"root.txt",
+				"subdir/.kipignore",
+				"subdir/deep/bottom.txt",
+				"subdir/keep.log",
+				"subdir/mid.txt",
+				"top.log"
+			]
+		);
+		assert_eq!(skipped.count, 1);
+	}
+
+	#[test]
+	fn walked_before_follows_walk_order() {
+		let before = |path: &str, cursor: &str| walked_before(Path::new(path), Path::new(cursor));
+		assert!(before("a.txt", "b.txt"));
+		assert!(!before("b.txt", "a.txt"));
+		assert!(before("b.txt", "b.txt"), "the cursor itself was walked");
+		assert!(!before("sub", "sub/x.txt"), "the cursor's directories are still being walked");
+		assert!(before("sub/a.txt", "sub/x.txt"));
+		assert!(before("sub/z.txt", "subdir/a.txt"));
+		assert!(before(".kipignore", "!first"), ".kipignore comes first among its siblings");
+	}
+
+	#[test]
+	fn resumed_walk_leaves_out_what_was_walked() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+		let cancel = AtomicBool::new(false);
+		let after = Path::new("subdir/deep/bottom.txt");
+
+		let (entries, skipped) =
+			walk_resuming(tmp.path().to_str().unwrap(), &PathFilter::default(), false, &cancel, Some(after), |_| {})
+				.unwrap();
+		assert_eq!(sorted_paths(&entries), vec!["subdir/mid.txt"]);
+		assert_eq!(skipped.count, 0);
+	}
+
+	#[test]
+	fn kipignore_applies_to_siblings_listed_before_it() {
+		let tmp = tempfile::tempdir().unwrap();
+		// Whichever order the directory lists them in, the rules cover every sibling
+		fs::write(tmp.path().join(".kipignore"), "*.log\n").unwrap();
+		for i in 0..20 {
+			fs::write(tmp.path().join(format!("{i}.log")), "log").unwrap();
+			fs::write(tmp.path().join(format!("{i}.txt")), "txt").unwrap();
+		}
+
+		let (entries, skipped) = walk_source(tmp.path().to_str().unwrap(), &PathFilter::default(), false).unwrap();
+		assert_eq!(entries.len(), 21, "every .txt and the .kipignore");
+		assert!(entries.iter().all(|e| e.relative_path.extension().is_none_or(|ext| ext != "log")));
+		assert_eq!(skipped.count, 20);
+	}
+
+	#[test]
+	fn exclude_wins_over_include() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+
+		let (entries, _) =
+			walk_source(tmp.path().to_str().unwrap(), &filter(&["subdir/**"], &["deep"]), false).unwrap();
+
+		assert_eq!(sorted_paths(&entries), vec!["subdir/mid.txt"]);
+	}
+
+	fn entry(path: &str, size: u64, secs: u64) -> FileEntry {
+		FileEntry {
+			relative_path: PathBuf::from(path),
+			size,
+			modified: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs),
+		}
+	}
+
+	fn at(secs: u64) -> Option<SystemTime> {
+		Some(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+	}
+
+	#[test]
+	fn plan_sync_copies_missing_side() {
+		let e = entry("a", 1, 100);
+		assert_eq!(plan_sync(Some(&e), None, None, || unreachable!()), SyncAction::ToDest);
+		assert_eq!(plan_sync(None, Some(&e), None, || unreachable!()), SyncAction::ToSource);
+	}
+
+	#[test]
+	fn plan_sync_matching_files_skip() {
+		let (s, d) = (entry("a", 5, 100), entry("a", 5, 100));
+		assert_eq!(plan_sync(Some(&s), Some(&d), at(50), || unreachable!()), SyncAction::Skip);
+		// Same size, different mtime: decided by content
+		let d = entry("a", 5, 200);
+		assert_eq!(plan_sync(Some(&s), Some(&d), at(50), || true), SyncAction::Skip);
+	}
+
+	#[test]
+	fn plan_sync_one_side_changed() {
+		let (old, new) = (entry("a", 5, 100), entry("a", 6, 300));
+		assert_eq!(plan_sync(Some(&new), Some(&old), at(200), || false), SyncAction::ToDest);
+		assert_eq!(plan_sync(Some(&old), Some(&new), at(200), || false), SyncAction::ToSource);
+	}
+
+	#[test]
+	fn plan_sync_both_changed_conflicts() {
+		let (s, d) = (entry("a", 5, 300), entry("a", 6, 400));
+		assert_eq!(plan_sync(Some(&s), Some(&d), at(200), || false), SyncAction::Conflict);
+		// Without a baseline the newer side wins
+		assert_eq!(plan_sync(Some(&s), Some(&d), None, || false), SyncAction::ToSource);
+	}
+
+	/// Source and destination trees that were identical at t=1000 (the last
+	/// sync), with `a.txt`, `b.txt` and `c.txt` in both.
+	fn synced_trees() -> (tempfile::TempDir, tempfile::TempDir) {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		for dir in [src.path(), dst.path()] {
+			for name in ["a.txt", "b.txt", "c.txt"] {
+				let path = dir.join(name);
+				fs::write(&path, "original").unwrap();
+				filetime::set_file_mtime(&path, filetime::FileTime::from_unix_time(1000, 0)).unwrap();
+			}
+		}
+		(src, dst)
+	}
+
+	fn edit_file(path: &Path, contents: &str, secs: i64) {
+		fs::write(path, contents).unwrap();
+		filetime::set_file_mtime(path, filetime::FileTime::from_unix_time(secs, 0)).unwrap();
+	}
+
+	fn plan_trees(src: &Path, dst: &Path) -> SyncPlan {
+		let none = PathFilter::default();
+		let (entries, _) = walk_source(src.to_str().unwrap(), &none, false).unwrap();
+		plan_bidirectional(src.to_str().unwrap(), &entries, dst.to_str().unwrap(), &none, at(1500)).unwrap()
+	}
+
+	#[test]
+	fn bidirectional_source_edit_flows_to_dest() {
+		let (src, dst) = synced_trees();
+		edit_file(&src.path().join("a.txt"), "edited on source", 2000);
+
+		let plan = plan_trees(src.path(), dst.path());
+		assert_eq!(sorted_paths(&plan.to_dest), vec!["a.txt"]);
+		assert!(plan.to_source.is_empty() && plan.conflicts.is_empty());
+	}
+
+	#[test]
+	fn bidirectional_dest_edit_and_new_file_flow_back() {
+		let (src, dst) = synced_trees();
+		edit_file(&dst.path().join("b.txt"), "edited on dest", 2000);
+		edit_file(&dst.path().join("new.txt"), "only on dest", 2000);
+
+		let plan = plan_trees(src.path(), dst.path());
+		assert!(plan.to_dest.is_empty() && plan.conflicts.is_empty());
+		assert_eq!(sorted_paths(&plan.to_source), vec!["b.txt", "new.txt"]);
+	}
+
+	#[test]
+	fn bidirectional_both_edited_is_conflict() {
+		let (src, dst) = synced_trees();
+		edit_file(&src.path().join("c.txt"), "source version", 2000);
+		edit_file(&dst.path().join("c.txt"), "dest version!", 2100);
+
+		let plan = plan_trees(src.path(), dst.path());
+		assert!(plan.to_dest.is_empty() && plan.to_source.is_empty());
+		assert_eq!(plan.conflicts.len(), 1);
+		assert_eq!(plan.conflicts[0].0.relative_path, PathBuf::from("c.txt"));
+	}
+
+	#[test]
+	fn conflict_policy_settles_both_edited_files() {
+		let (src, dst) = synced_trees();
+		edit_file(&src.path().join("c.txt"), "source version", 2000);
+		edit_file(&dst.path().join("c.txt"), "dest version!", 2100);
+
+		let mut plan = plan_trees(src.path(), dst.path());
+		plan.settle_conflicts(ConflictPolicy::Review);
+		assert_eq!(plan.conflicts.len(), 1, "review keeps the conflict");
+
+		plan.settle_conflicts(ConflictPolicy::NewerWins);
+		assert!(plan.conflicts.is_empty() && plan.to_dest.is_empty());
+		assert_eq!(sorted_paths(&plan.to_source), vec!["c.txt"]);
+
+		let mut plan = plan_trees(src.path(), dst.path());
+		plan.settle_conflicts(ConflictPolicy::SourceWins);
+		assert!(plan.conflicts.is_empty() && plan.to_source.is_empty());
+		assert_eq!(sorted_paths(&plan.to_dest), vec!["c.txt"]);
+	}
+
+	#[test]
+	fn bidirectional_touch_without_change_skips() {
+		let (src, dst) = synced_trees();
+		// Same bytes, newer mtime on one side: hashes match, nothing to do
+		edit_file(&src.path().join("a.txt"), "original", 2000);
+
+		let plan = plan_trees(src.path(), dst.path());
+		assert!(plan.to_dest.is_empty() && plan.to_source.is_empty() && plan.conflicts.is_empty());
+	}
+
+	#[test]
+	fn nested_paths_respect_excluded_dirs() {
+		let f = filter(&[], &["node_modules", "*.tmp"]);
+		assert!(f.accepts_nested(Path::new("src/main.rs")));
+		assert!(!f.accepts_nested(Path::new("web/node_modules/pkg/index.js")));
+		assert!(!f.accepts_nested(Path::new("notes.tmp")));
+	}
+
+	#[tokio::test]
+	async fn preview_counts_jobs_without_writing_them() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:preview CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let preview = scan_preview(&db, &RecordId::new("intent", "preview")).await.unwrap();
+
+		assert_eq!((preview.files_found, preview.total_bytes, preview.jobs_created), (3, 17, 3));
+		let mut resp = db
+			.db
+			.query("SELECT VALUE id FROM transfer_job; SELECT VALUE status FROM intent:preview")
+			.await
+			.unwrap();
+		let jobs: Vec<RecordId> = resp.take(0).unwrap();
+		let statuses: Vec<String> = resp.take(1).unwrap();
+		assert!(jobs.is_empty());
+		assert_eq!(statuses, vec!["idle".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn re_enqueuing_a_pending_file_keeps_the_totals() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:watched CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'complete',
+                     kind: 'sync', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "watched");
+		let changed = vec![src.path().join("root.txt")];
+
+		assert_eq!(enqueue_changed_files(&db, &intent_id, &changed).await.unwrap(), 1);
+		assert_eq!(enqueue_changed_files(&db, &intent_id, &changed).await.unwrap(), 1);
+
+		let mut resp = db
+			.db
+			.query("SELECT VALUE id FROM transfer_job; SELECT total_files, total_bytes FROM ONLY intent:watched")
+			.await
+			.unwrap();
+		let jobs: Vec<RecordId> = resp.take(0).unwrap();
+		let totals: Option<serde_json::Value> = resp.take(1).unwrap();
+		let totals = totals.unwrap();
+		assert_eq!(jobs.len(), 1);
+		assert_eq!((totals["total_files"].as_u64(), totals["total_bytes"].as_u64()), (Some(1), Some(5)));
+	}
+
+	#[tokio::test]
+	async fn batched_jobs_cross_chunk_boundaries_intact() {
+		let db = crate::db::init_memory().await.unwrap();
+		let entries: Vec<FileEntry> = (0..=JOB_BATCH_SIZE)
+			.map(|i| FileEntry {
+				relative_path: PathBuf::from(format!("f{i}.txt")),
+				size: i as u64,
+				modified: SystemTime::UNIX_EPOCH,
+			})
+			.collect();
+		let destinations: Vec<Destination> = ["a", "b"]
+			.iter()
+			.map(|key| Destination {
+				id: RecordId::new("location", *key),
+				path: format!("/dst/{key}"),
+				max_file_size: None,
+				remote: false,
+			})
+			.collect();
+		let intent_id = RecordId::new("intent", "big");
+
+		let nothing_done = vec![BTreeSet::new(); destinations.len()];
+		let not_cancelled = AtomicBool::new(false);
+		let created = create_transfer_jobs(&db, &intent_id, "/src", &entries, &destinations, &nothing_done, &not_cancelled)
+			.await
+			.unwrap();
+
+		let expected = 2 * (JOB_BATCH_SIZE + 1);
+		assert_eq!(created, expected as u64);
+		let mut resp = db.db.query("SELECT VALUE dest_path FROM transfer_job").await.unwrap();
+		let paths: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(paths.len(), expected);
+		let unique: BTreeSet<&String> = paths.iter().collect();
+		assert_eq!(unique.len(), expected, "no job is written twice");
+		assert!(unique.contains(&format!("/dst/b/f{JOB_BATCH_SIZE}.txt")));
+	}
+
+	#[tokio::test]
+	async fn source_subpath_roots_the_scan() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:part CONTENT {
+                     source: location:src, source_subpath: 'subdir', destinations: [location:dst],
+                     status: 'idle', kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let result = scan_intent(&db, &RecordId::new("intent", "part")).await.unwrap();
+
+		assert_eq!(result.jobs_created, 2);
+		let mut resp = db.db.query("SELECT VALUE dest_path FROM transfer_job ORDER BY dest_path").await.unwrap();
+		let paths: Vec<String> = resp.take(0).unwrap();
+		let dst = dst.path().to_str().unwrap();
+		assert_eq!(paths, vec![format!("{dst}/deep/bottom.txt"), format!("{dst}/mid.txt")]);
+	}
+
+	#[test]
+	fn overlapping_source_and_destination_are_rejected() {
+		let root = tempfile::tempdir().unwrap();
+		fs::create_dir_all(root.path().join("photos/backup")).unwrap();
+		fs::create_dir_all(root.path().join("photos-old")).unwrap();
+		let path = |rel: &str| root.path().join(rel).to_str().unwrap().to_string();
+
+		let same = check_overlap(&path("photos"), &format!("{}/", path("photos")));
+		assert!(matches!(same, Err(ScanError::OverlappingPaths(_))));
+		let dest_inside = check_overlap(&path("photos"), &path("photos/backup"));
+		assert!(matches!(dest_inside, Err(ScanError::OverlappingPaths(_))));
+		let source_inside = check_overlap(&path("photos/backup"), &path("photos"));
+		assert!(matches!(source_inside, Err(ScanError::OverlappingPaths(_))));
+		// A destination that doesn't exist yet still counts
+		assert!(check_overlap(&path("photos"), &path("photos/new")).is_err());
+
+		assert!(check_overlap(&path("photos"), &path("photos-old")).is_ok());
+	}
+
+	#[tokio::test]
+	async fn scan_refuses_a_destination_inside_its_source() {
+		let src = tempfile::tempdir().unwrap();
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:inside CONTENT { path: $inside, available: true };
+                 CREATE intent:nested CONTENT {
+                     source: location:src, destinations: [location:inside],
+                     status: 'idle', kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("inside", src.path().join("subdir").to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let result = scan_intent(&db, &RecordId::new("intent", "nested")).await;
+
+		assert!(matches!(result, Err(ScanError::OverlappingPaths(_))));
+		let mut resp = db
+			.db
+			.query("SELECT VALUE id FROM transfer_job; SELECT VALUE status FROM intent:nested;")
+			.await
+			.unwrap();
+		let jobs: Vec<RecordId> = resp.take(0).unwrap();
+		let statuses: Vec<String> = resp.take(1).unwrap();
+		assert!(jobs.is_empty());
+		assert_eq!(statuses, vec!["idle".to_string()]);
+	}
+
+	#[test]
+	fn subpath_cannot_escape_its_location() {
+		assert_eq!(join_subpath("/data", "photos/./2024").unwrap(), "/data/photos/2024");
+		assert!(matches!(join_subpath("/data", "../etc"), Err(ScanError::InvalidSubpath(_))));
+		assert!(matches!(join_subpath("/data", "photos/../../etc"), Err(ScanError::InvalidSubpath(_))));
+		assert!(matches!(join_subpath("/data", "/etc"), Err(ScanError::InvalidSubpath(_))));
+	}
+
+	#[tokio::test]
+	async fn files_over_the_drive_limit_go_to_review() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE drive:fat CONTENT {
+                     name: 'FAT stick', uuid: 'u-fat', filesystem: 'msdos', connected: true,
+                     last_seen: time::now(), limitations: { max_file_size: 4 },
+                 };
+                 CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, drive: drive:fat, available: true };
+                 CREATE intent:limited CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "limited");
+
+		scan_intent(&db, &intent_id).await.unwrap();
+		let result = crate::engine::scheduler::run_intent(&db, &intent_id).await.unwrap();
+
+		// Only the 2-byte file fits
+		assert_eq!((result.completed, result.needs_review), (1, 2));
+		assert!(dst.path().join("subdir/mid.txt").exists());
+		assert!(!dst.path().join("root.txt").exists());
+		let mut resp = db
+			.db
+			.query("SELECT error_kind, options FROM review_item ORDER BY source_path")
+			.await
+			.unwrap();
+		let items: Vec<serde_json::Value> = resp.take(0).unwrap();
+		assert_eq!(items.len(), 2);
+		for item in &items {
+			assert_eq!(item["error_kind"], "file_too_large");
+			assert_eq!(item["options"], serde_json::json!(["skip"]));
+		}
+	}
+
+	#[tokio::test]
+	async fn rescan_skips_files_already_copied() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:again CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "again");
+
+		let first = scan_intent(&db, &intent_id).await.unwrap();
+		assert_eq!(first.jobs_created, 3);
+
+		// Stand in for the scheduler: copy everything and mark it done
+		setup_tree(dst.path());
+		db.db
+			.query(
+				"UPDATE transfer_job SET status = 'complete', completed_at = time::now();
+                 UPDATE intent:again SET status = 'complete';",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let second = scan_intent(&db, &intent_id).await.unwrap();
+		assert_eq!((second.files_found, second.jobs_created), (3, 0));
+
+		fs::write(src.path().join("subdir/mid.txt"), "changed").unwrap();
+		fs::remove_file(dst.path().join("root.txt")).unwrap();
+		db.db.query("UPDATE intent:again SET status = 'complete'").await.unwrap();
+		let third = scan_intent(&db, &intent_id).await.unwrap();
+		assert_eq!(third.jobs_created, 2, "the edited file and the deleted copy are queued again");
+	}
+
+	#[tokio::test]
+	async fn copy_deleted_from_destination_is_recreated() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:mirror CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "mirror");
+
+		scan_intent(&db, &intent_id).await.unwrap();
+		crate::engine::scheduler::run_intent(&db, &intent_id).await.unwrap();
+		assert_eq!(requeue_missing_copies(&db, &intent_id).await.unwrap(), 0, "nothing is missing yet");
+
+		let deleted = dst.path().join("subdir/mid.txt");
+		fs::remove_file(&deleted).unwrap();
+		assert_eq!(requeue_missing_copies(&db, &intent_id).await.unwrap(), 1);
+
+		let result = crate::engine::scheduler::run_intent(&db, &intent_id).await.unwrap();
+		assert_eq!(result.completed, 3);
+		assert_eq!(fs::read_to_string(&deleted).unwrap(), "ab");
+	}
+
+	#[tokio::test]
+	async fn remote_destination_scans_but_remote_source_is_refused() {
+		let src = tempfile::tempdir().unwrap();
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE machine:nas CONTENT {
+                     name: 'NAS', kind: 'remote', hostname: 'nas.local', is_current: false, last_seen: time::now(),
+                 };
+                 CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:nas CONTENT { path: '/srv/backup', machine: machine:nas, available: true };
+                 CREATE intent:push CONTENT {
+                     source: location:src, destinations: [location:nas], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };
+                 CREATE intent:pull CONTENT {
+                     source: location:nas, destinations: [location:src], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let push = scan_preview(&db, &RecordId::new("intent", "push")).await.unwrap();
+		assert_eq!(push.jobs_created, 3);
+
+		let err = scan_preview(&db, &RecordId::new("intent", "pull")).await.unwrap_err();
+		assert!(matches!(err, ScanError::RemoteUnsupported(_)));
+	}
+
+	#[test]
+	fn invalid_pattern_errors() {
+		let err = PathFilter::new(&["[".to_string()], &[]).unwrap_err();
+		assert!(matches!(err, ScanError::InvalidPattern(_)));
 	}
 
 	#[test]
 	fn nonexistent_path_errors() {
-		let err = walk_source("/tmp/kip_definitely_not_real").unwrap_err();
+		let err = walk_source("/tmp/kip_definitely_not_real", &PathFilter::default(), false).unwrap_err();
 		assert!(matches!(err, ScanError::SourcePathNotExists(_)));
 	}
 
@@ -368,7 +2802,168 @@ mod tests {
 		let file = tmp.path().join("afile.txt");
 		fs::write(&file, "x").unwrap();
 
-		let err = walk_source(file.to_str().unwrap()).unwrap_err();
+		let err = walk_source(file.to_str().unwrap(), &PathFilter::default(), false).unwrap_err();
 		assert!(matches!(err, ScanError::SourcePathNotDir(_)));
 	}
+
+	#[test]
+	fn cancelled_walk_stops() {
+		let tmp = tempfile::tempdir().unwrap();
+		setup_tree(tmp.path());
+
+		let err = walk_source_until(tmp.path().to_str().unwrap(), &PathFilter::default(), false, &AtomicBool::new(true))
+			.unwrap_err();
+		assert!(matches!(err, ScanError::Cancelled));
+	}
+
+	#[tokio::test]
+	async fn cancelled_scan_writes_no_jobs() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:big CONTENT {
+                     source: location:src, destinations: [location:dst],
+                     status: 'idle', kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "big");
+		assert!(!cancel_scan(&intent_id), "nothing to cancel before the scan starts");
+
+		let intent = load_intent(&db, &intent_id).await.unwrap();
+		claim_intent(&db, &intent_id).await.unwrap();
+		let result = scan_claimed(&db, &intent_id, &intent, &Arc::new(AtomicBool::new(true))).await;
+
+		assert!(matches!(result, Err(ScanError::Cancelled)));
+		let mut resp = db.db.query("SELECT VALUE id FROM transfer_job").await.unwrap();
+		let jobs: Vec<RecordId> = resp.take(0).unwrap();
+		assert!(jobs.is_empty());
+	}
+
+	#[tokio::test]
+	async fn unreadable_dir_is_filed_for_review_and_retried() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:locked CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'needs_review',
+                     kind: 'one_shot', speed_mode: 'normal', total_files: 1,
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "locked");
+		let destination = resolve_destination(&db, &RecordId::new("location", "dst")).await.unwrap();
+
+		// Running as root, nothing can be made unreadable, so file one by hand
+		let denied = [PathBuf::from("subdir")];
+		let filed = file_unreadable(&db, &intent_id, src.path(), &[destination], &denied).await.unwrap();
+		assert_eq!(filed, 1);
+		let mut resp = db
+			.db
+			.query(
+				"SELECT VALUE id FROM transfer_job WHERE status = 'needs_review' AND is_dir = true;
+                 SELECT VALUE options FROM review_item WHERE error_kind = 'permission_denied';",
+			)
+			.await
+			.unwrap();
+		let jobs: Vec<RecordId> = resp.take(0).unwrap();
+		let options: Vec<Vec<String>> = resp.take(1).unwrap();
+		assert_eq!(jobs.len(), 1);
+		assert_eq!(options, vec![vec!["retry".to_string(), "skip".to_string()]]);
+
+		// Readable now: its two files are queued and the review job goes away
+		assert_eq!(retry_unreadable(&db, &jobs[0]).await.unwrap(), Some(2));
+		let mut resp = db
+			.db
+			.query("SELECT VALUE source_path FROM transfer_job ORDER BY source_path")
+			.await
+			.unwrap();
+		let queued: Vec<String> = resp.take(0).unwrap();
+		let expected: Vec<String> = ["subdir/deep/bottom.txt", "subdir/mid.txt"]
+			.iter()
+			.map(|rel| src.path().join(rel).to_str().unwrap().to_string())
+			.collect();
+		assert_eq!(queued, expected);
+
+		let mut resp = db.db.query("SELECT VALUE id FROM transfer_job LIMIT 1").await.unwrap();
+		let file_jobs: Vec<RecordId> = resp.take(0).unwrap();
+		assert_eq!(retry_unreadable(&db, &file_jobs[0]).await.unwrap(), None, "a file job is left alone");
+	}
+
+	#[tokio::test]
+	async fn scan_resumes_from_its_checkpoint() {
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		setup_tree(src.path());
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:huge CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "huge");
+		let intent = load_intent(&db, &intent_id).await.unwrap();
+		let key = checkpoint_key(&intent, src.path().to_str().unwrap());
+
+		// An earlier scan got as far as root.txt, which was 999 bytes then
+		let walked = FileEntry { relative_path: PathBuf::from("root.txt"), size: 999, modified: SystemTime::now() };
+		let checkpoint =
+			WalkCheckpoint { cursor: PathBuf::from("root.txt"), entries: vec![walked], skipped: SkipLog::default() };
+		save_checkpoint(&db, &intent_id, &key, 0, checkpoint).await.unwrap();
+
+		let result = scan_intent(&db, &intent_id).await.unwrap();
+		assert_eq!((result.files_found, result.jobs_created), (3, 3));
+		assert_eq!(result.total_bytes, 999 + 2 + 10, "root.txt comes from the checkpoint, not a second walk");
+
+		let mut resp = db
+			.db
+			.query("SELECT VALUE scan_cursor FROM ONLY intent:huge; SELECT VALUE id FROM scan_entry;")
+			.await
+			.unwrap();
+		let cursor: Option<serde_json::Value> = resp.take(0).unwrap();
+		let saved: Vec<RecordId> = resp.take(1).unwrap();
+		assert!(cursor.is_none() && saved.is_empty(), "a finished scan leaves nothing to resume");
+	}
+
+	#[tokio::test]
+	async fn checkpoint_with_other_filters_is_dropped() {
+		let db = crate::db::init_memory().await.unwrap();
+		db.db.query("CREATE intent:huge CONTENT { status: 'idle' }").await.unwrap().check().unwrap();
+		let intent_id = RecordId::new("intent", "huge");
+		let walked = FileEntry { relative_path: PathBuf::from("a.txt"), size: 1, modified: SystemTime::now() };
+		let checkpoint =
+			WalkCheckpoint { cursor: PathBuf::from("a.txt"), entries: vec![walked], skipped: SkipLog::default() };
+		save_checkpoint(&db, &intent_id, "before", 0, checkpoint).await.unwrap();
+
+		assert!(load_checkpoint(&db, &intent_id, "before").await.unwrap().is_some());
+		assert!(load_checkpoint(&db, &intent_id, "after").await.unwrap().is_none());
+		assert!(load_checkpoint(&db, &intent_id, "before").await.unwrap().is_none(), "and cleared");
+	}
 }
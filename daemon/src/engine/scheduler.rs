@@ -1,11 +1,130 @@
-use std::{fmt, sync::Arc};
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	fmt,
+	future::Future,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
 
-use surrealdb::types::RecordId;
-use tokio::sync::Semaphore;
+use kip_core::models::intent::{JobOrder, SpeedMode};
+use surrealdb::types::{RecordId, SurrealValue};
+use tokio::sync::{mpsc, Semaphore};
+use tracing::info;
 
-use crate::{db::DbHandle, engine::transfer};
+use kip_core::util::decode_path;
 
-const MAX_CONCURRENCY: usize = 4;
+use crate::{
+	db::DbHandle,
+	engine::{copier, manifest, shutdown, transfer, watcher},
+	events::{self, intent_label, EngineEvent},
+	graph_store::rid_string,
+};
+
+/// Concurrent copies per `Normal` intent when neither the intent nor
+/// `KIP_MAX_CONCURRENCY` says otherwise.
+pub const DEFAULT_CONCURRENCY: usize = SpeedMode::Normal.profile().concurrency;
+
+/// How often a running intent's `completed_bytes` and `bytes_per_sec` are
+/// refreshed from its jobs.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples older than this don't count toward `bytes_per_sec`.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How often copies' running byte counts are written to their jobs.
+const JOB_PROGRESS_FLUSH: Duration = Duration::from_millis(500);
+
+/// Copies holding a dispatch permit right now, across every running loop.
+static COPYING: AtomicUsize = AtomicUsize::new(0);
+/// Permits across every batch being dispatched: the sum of their concurrency.
+static SLOTS: AtomicUsize = AtomicUsize::new(0);
+/// Jobs handed to a running batch that haven't got a permit yet.
+static QUEUED: AtomicUsize = AtomicUsize::new(0);
+
+/// What the dispatch loops are doing right now, for the UI to poll. A full
+/// set of slots with jobs still queued means transfers are waiting on the
+/// concurrency limit; nothing copying with jobs queued means they're waiting
+/// on something else, like a retry backoff.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DispatchStatus {
+	pub copying: usize,
+	pub slots: usize,
+	pub queued: usize,
+}
+
+pub fn dispatch_status() -> DispatchStatus {
+	DispatchStatus {
+		copying: COPYING.load(Ordering::Relaxed),
+		slots: SLOTS.load(Ordering::Relaxed),
+		queued: QUEUED.load(Ordering::Relaxed),
+	}
+}
+
+/// A share of one of the `DispatchStatus` counters, given back when dropped
+/// so a cancelled batch or a panicking copy can't leave it skewed.
+struct Held(&'static AtomicUsize, usize);
+
+impl Held {
+	fn new(counter: &'static AtomicUsize, n: usize) -> Self {
+		counter.fetch_add(n, Ordering::Relaxed);
+		Held(counter, n)
+	}
+
+	fn release_one(&mut self) {
+		if self.1 > 0 {
+			self.0.fetch_sub(1, Ordering::Relaxed);
+			self.1 -= 1;
+		}
+	}
+}
+
+impl Drop for Held {
+	fn drop(&mut self) {
+		self.0.fetch_sub(self.1, Ordering::Relaxed);
+	}
+}
+
+/// Global concurrency default: `KIP_MAX_CONCURRENCY`, else `DEFAULT_CONCURRENCY`.
+pub fn default_concurrency() -> usize {
+	std::env::var("KIP_MAX_CONCURRENCY")
+		.ok()
+		.and_then(|v| v.trim().parse::<usize>().ok())
+		.filter(|&n| n > 0)
+		.unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+/// Upper bound on concurrent writes to a drive with the given benchmarked
+/// write speed. Slow media (spinning disks, USB sticks) thrash on seeks when
+/// several files are written at once.
+fn drive_concurrency_cap(write_mbps: f64) -> usize {
+	if write_mbps < 60.0 {
+		1
+	} else if write_mbps < 250.0 {
+		2
+	} else {
+		usize::MAX
+	}
+}
+
+/// Effective concurrency for an intent: its own setting, or else its speed
+/// mode's (the global default for `Normal`), capped by the slowest
+/// benchmarked destination drive.
+fn effective_concurrency(configured: Option<usize>, speed_mode: &SpeedMode, dest_write_mbps: &[f64]) -> usize {
+	let mode_default = || match speed_mode {
+		SpeedMode::Normal => default_concurrency(),
+		SpeedMode::Ninja | SpeedMode::Blast => speed_mode.profile().concurrency,
+	};
+	let requested = configured.filter(|&n| n > 0).unwrap_or_else(mode_default);
+	let cap = dest_write_mbps
+		.iter()
+		.map(|&mbps| drive_concurrency_cap(mbps))
+		.min()
+		.unwrap_or(usize::MAX);
+	requested.min(cap).max(1)
+}
 
 #[derive(Debug)]
 pub enum SchedulerError {
@@ -31,29 +150,109 @@ pub struct RunResult {
 	pub needs_review: u64,
 }
 
-/// Run all pending jobs for an intent with bounded concurrency.
-/// Returns when all jobs are complete, failed, or need review.
-pub async fn run_intent(db: &DbHandle, intent_id: &RecordId) -> Result<RunResult, SchedulerError> {
-	// Verify intent exists
+/// Jobs of one intent that didn't go through cleanly, so a list of intents
+/// can flag the unhealthy ones.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct JobHealth {
+	pub needs_review: u64,
+	pub failed: u64,
+	/// Failed at least once and waiting for another attempt.
+	pub retrying: u64,
+}
+
+impl JobHealth {
+	pub fn is_healthy(&self) -> bool {
+		*self == Self::default()
+	}
+}
+
+/// Job counts for one destination of an intent, so a fan-out intent can show
+/// each target's progress on its own.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DestinationResult {
+	pub total: u64,
+	pub completed: u64,
+	pub needs_review: u64,
+	pub failed: u64,
+	/// Still waiting for or in the middle of a copy.
+	pub remaining: u64,
+}
+
+/// Per-status job counts, shared by `compute_result`, `job_health` and
+/// `destination_results`.
+const JOB_COUNTS: &str = "math::sum(IF status = 'complete' THEN 1 ELSE 0 END) AS completed,
+                math::sum(IF status = 'needs_review' THEN 1 ELSE 0 END) AS needs_review,
+                math::sum(IF status = 'failed' THEN 1 ELSE 0 END) AS failed,
+                math::sum(IF status = 'pending' AND attempts > 0 THEN 1 ELSE 0 END) AS retrying";
+
+/// An intent's job order and effective concurrency. Errors if the intent
+/// doesn't exist.
+async fn dispatch_settings(db: &DbHandle, intent_id: &RecordId) -> Result<(JobOrder, usize), SchedulerError> {
 	let mut response = db
 		.db
-		.query("SELECT id FROM $id")
+		.query(
+			"SELECT id, job_order, concurrency, speed_mode,
+                    (destinations ?? []).map(|$d| (<record> $d).drive.measured_write_mbps) AS dest_write_mbps
+             FROM $id",
+		)
 		.bind(("id", intent_id.clone()))
 		.await
 		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
 
-	let exists: Option<serde_json::Value> = response
+	let intent: Option<serde_json::Value> = response
 		.take(0)
 		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
 
-	if exists.is_none() {
+	let Some(intent) = intent else {
 		return Err(SchedulerError::IntentNotFound(format!("{:?}", intent_id)));
-	}
+	};
+	let job_order: JobOrder = serde_json::from_value(intent["job_order"].clone()).unwrap_or_default();
+	// Undetected or unbenchmarked drives come back as null and don't cap anything
+	let dest_write_mbps: Vec<f64> = intent["dest_write_mbps"]
+		.as_array()
+		.map(|speeds| speeds.iter().filter_map(|v| v.as_f64()).collect())
+		.unwrap_or_default();
+	let speed_mode: SpeedMode = serde_json::from_value(intent["speed_mode"].clone()).unwrap_or_default();
+	let concurrency = effective_concurrency(
+		intent["concurrency"].as_u64().map(|n| n as usize),
+		&speed_mode,
+		&dest_write_mbps,
+	);
+	Ok((job_order, concurrency))
+}
+
+/// Move an intent to `transferring` unless a scan or run already holds it.
+/// Like the scanner's claim, it's a compare-and-set on `status`, so of two
+/// callers racing to start the same intent only one gets `true`; the other
+/// should leave the work to it.
+pub async fn claim_intent(db: &DbHandle, intent_id: &RecordId) -> Result<bool, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"UPDATE $id SET status = 'transferring', updated_at = time::now()
+                WHERE status NOT IN ['scanning', 'transferring', 'verifying']
+                RETURN id",
+		)
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let claimed: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	Ok(!claimed.is_empty())
+}
+
+/// Run all pending jobs for an intent with bounded concurrency.
+/// Returns when all jobs are complete, failed, or need review.
+pub async fn run_intent(db: &DbHandle, intent_id: &RecordId) -> Result<RunResult, SchedulerError> {
+	let (job_order, concurrency) = dispatch_settings(db, intent_id).await?;
 
-	// Recovery: reset any jobs stuck in 'transferring' from a previous crash
+	// Recovery: reset any jobs stuck in 'transferring' from a previous crash.
+	// bytes_transferred is kept so the copier can resume the partial file.
 	db.db
 		.query(
-			"UPDATE transfer_job SET status = 'pending', bytes_transferred = 0
+			"UPDATE transfer_job SET status = 'pending'
              WHERE intent = $intent_id AND status = 'transferring'",
 		)
 		.bind(("intent_id", intent_id.clone()))
@@ -62,40 +261,54 @@ pub async fn run_intent(db: &DbHandle, intent_id: &RecordId) -> Result<RunResult
 		.check()
 		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
 
-	let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENCY));
+	// Park work that can't fit before any file is touched
+	precheck_free_space(db, intent_id).await?;
+
+	let _progress = ProgressReporter::spawn(db.clone(), intent_id.clone());
+	let job_progress = JobProgress::spawn(db.clone());
 
 	// Main dispatch loop: keep pulling pending jobs until none remain
 	loop {
-		let job_ids = get_pending_jobs(db, intent_id).await?;
-
-		if job_ids.is_empty() {
-			break;
+		// The app is quitting: the intent stays `transferring` and is
+		// resumed on the next launch
+		if shutdown::is_requested() {
+			info!("stopping dispatch for {:?}: shutting down", intent_id);
+			return compute_result(db, intent_id).await;
 		}
 
-		// Spawn concurrent copy tasks
-		let mut handles = Vec::with_capacity(job_ids.len());
+		// A drive went away (or the user paused): leave the rest for later
+		if let Some(status) = halted_status(db, intent_id).await? {
+			info!("stopping dispatch for {:?}: intent is {}", intent_id, status);
+			clear_throughput(db, intent_id).await?;
+			return compute_result(db, intent_id).await;
+		}
 
-		for job_id in job_ids {
-			let permit = semaphore.clone().acquire_owned().await.unwrap();
-			let db = db.clone();
+		let job_ids = get_pending_jobs(db, intent_id, job_order).await?;
 
-			handles.push(tokio::spawn(async move {
-				let result = transfer::copy_job(&db, &job_id).await;
-				drop(permit);
-				(job_id, result)
-			}));
+		if job_ids.is_empty() {
+			// Only backed-off retries left: wait for the soonest one
+			match soonest_retry(db, intent_id).await? {
+				Some(delay) => {
+					tokio::time::sleep(delay).await;
+					continue;
+				}
+				None => break,
+			}
 		}
 
-		// Wait for all tasks in this batch
-		for handle in handles {
-			// Ignore join errors (panics in copy tasks) — the job stays
-			// in 'transferring' and will be recovered on next loop iteration
-			let _ = handle.await;
-		}
+		run_bounded(job_ids, concurrency, |job_id| {
+			let db = db.clone();
+			let progress = job_progress.sender();
+			async move {
+				let _ = transfer::copy_job(&db, &job_id, &progress).await;
+			}
+		})
+		.await;
 
 		// After batch completes, loop back to check for any jobs that
 		// were retried (set back to 'pending' by the transfer engine)
 	}
+	job_progress.finish().await;
 
 	// All jobs processed — compute final counts and update intent
 	let result = compute_result(db, intent_id).await?;
@@ -104,11 +317,451 @@ pub async fn run_intent(db: &DbHandle, intent_id: &RecordId) -> Result<RunResult
 	Ok(result)
 }
 
-/// Query all pending job IDs for an intent.
-async fn get_pending_jobs(db: &DbHandle, intent_id: &RecordId) -> Result<Vec<RecordId>, SchedulerError> {
+/// Bytes/sec over the last `THROUGHPUT_WINDOW`, from periodic samples of an
+/// intent's running byte count.
+#[derive(Debug, Default)]
+struct Throughput {
+	samples: VecDeque<(Instant, u64)>,
+}
+
+impl Throughput {
+	/// Record the byte count at `now` and return the rate across the window,
+	/// once there are two samples to compare.
+	fn sample(&mut self, now: Instant, bytes: u64) -> Option<u64> {
+		self.samples.push_back((now, bytes));
+		while self
+			.samples
+			.front()
+			.is_some_and(|&(at, _)| now.duration_since(at) > THROUGHPUT_WINDOW)
+		{
+			self.samples.pop_front();
+		}
+
+		let &(first_at, first_bytes) = self.samples.front()?;
+		let elapsed = now.duration_since(first_at).as_secs_f64();
+		if elapsed <= 0.0 {
+			return None;
+		}
+		// A failed job restarting can shrink the total; that's not negative speed
+		Some((bytes.saturating_sub(first_bytes) as f64 / elapsed) as u64)
+	}
+}
+
+/// Hands a running copy's byte count to `JobProgress`. Sending never blocks,
+/// so copy threads can report after every chunk without touching the runtime.
+#[derive(Debug, Clone)]
+pub struct ProgressSender(mpsc::UnboundedSender<(RecordId, u64)>);
+
+impl ProgressSender {
+	pub fn report(&self, job_id: &RecordId, bytes: u64) {
+		// The writer only goes away after the last copy finishes
+		let _ = self.0.send((job_id.clone(), bytes));
+	}
+}
+
+/// Writes the byte counts copies report to `transfer_job.bytes_transferred`.
+/// Reports are coalesced to the latest per job and written in one query
+/// every `JOB_PROGRESS_FLUSH`. Only jobs still `transferring` are updated,
+/// so a late report can't undo a job's completion or cancellation.
+struct JobProgress {
+	tx: mpsc::UnboundedSender<(RecordId, u64)>,
+	writer: tokio::task::JoinHandle<()>,
+}
+
+#[derive(Debug, SurrealValue)]
+struct JobBytes {
+	id: RecordId,
+	bytes: i64,
+}
+
+impl JobProgress {
+	fn spawn(db: DbHandle) -> Self {
+		let (tx, mut rx) = mpsc::unbounded_channel::<(RecordId, u64)>();
+		let writer = tokio::spawn(async move {
+			let mut latest = HashMap::new();
+			let mut ticker = tokio::time::interval(JOB_PROGRESS_FLUSH);
+			loop {
+				tokio::select! {
+					report = rx.recv() => match report {
+						Some((job_id, bytes)) => {
+							latest.insert(job_id, bytes);
+						}
+						None => break,
+					},
+					_ = ticker.tick() => flush_job_progress(&db, &mut latest).await,
+				}
+			}
+			flush_job_progress(&db, &mut latest).await;
+		});
+		Self { tx, writer }
+	}
+
+	fn sender(&self) -> ProgressSender {
+		ProgressSender(self.tx.clone())
+	}
+
+	/// Write what's still buffered once every sender is gone. Dropping this
+	/// instead leaves the writer to finish on its own.
+	async fn finish(self) {
+		let Self { tx, writer } = self;
+		drop(tx);
+		let _ = writer.await;
+	}
+}
+
+async fn flush_job_progress(db: &DbHandle, latest: &mut HashMap<RecordId, u64>) {
+	if latest.is_empty() {
+		return;
+	}
+	let updates: Vec<JobBytes> = latest
+		.drain()
+		.map(|(id, bytes)| JobBytes { id, bytes: bytes as i64 })
+		.collect();
+	// Best-effort, like the intent-level progress
+	if let Err(e) = db
+		.db
+		.query(
+			"FOR $update IN $updates {
+                 UPDATE $update.id SET bytes_transferred = $update.bytes WHERE status = 'transferring';
+             }",
+		)
+		.bind(("updates", updates))
+		.await
+	{
+		tracing::debug!("job progress update failed: {}", e);
+	}
+}
+
+/// Keeps the intent's progress fields fresh while `run_intent` dispatches.
+/// Stops when dropped.
+struct ProgressReporter(tokio::task::JoinHandle<()>);
+
+impl ProgressReporter {
+	fn spawn(db: DbHandle, intent_id: RecordId) -> Self {
+		Self(tokio::spawn(async move {
+			let mut throughput = Throughput::default();
+			let mut ticker = tokio::time::interval(PROGRESS_INTERVAL);
+			loop {
+				ticker.tick().await;
+				// Errors are skipped: progress is best-effort, like per-job updates
+				if let Ok(None) = report_progress(&db, &intent_id, &mut throughput).await {
+					break;
+				}
+			}
+		}))
+	}
+}
+
+impl Drop for ProgressReporter {
+	fn drop(&mut self) {
+		self.0.abort();
+	}
+}
+
+/// Sum the intent's job progress into `completed_bytes`/`completed_files`,
+/// update `bytes_per_sec` and emit a `TransferProgress` event. Returns the
+/// byte total, or `None` if the intent is gone.
+async fn report_progress(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	throughput: &mut Throughput,
+) -> Result<Option<u64>, SchedulerError> {
+	let (bytes, files) = job_progress(db, intent_id).await?;
+	let rate = throughput.sample(Instant::now(), bytes);
+
+	let mut response = db
+		.db
+		.query(
+			"UPDATE $id SET completed_bytes = $bytes, completed_files = $files, bytes_per_sec = $rate
+             RETURN name, total_files",
+		)
+		.bind(("id", intent_id.clone()))
+		.bind(("bytes", bytes as i64))
+		.bind(("files", files as i64))
+		.bind(("rate", rate.map(|r| r as i64)))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let updated: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	let Some(row) = updated else {
+		return Ok(None);
+	};
+
+	events::emit(EngineEvent::TransferProgress {
+		intent: rid_string(intent_id),
+		name: intent_label(intent_id, row["name"].as_str()),
+		completed: files,
+		total: row["total_files"].as_u64().unwrap_or(0),
+	});
+	Ok(Some(bytes))
+}
+
+/// (bytes written, completed jobs) across the intent's jobs.
+async fn job_progress(db: &DbHandle, intent_id: &RecordId) -> Result<(u64, u64), SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT
+                math::sum(bytes_transferred ?? 0) AS bytes,
+                math::sum(IF status = 'complete' THEN 1 ELSE 0 END) AS files
+             FROM transfer_job WHERE intent = $intent_id GROUP ALL",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	Ok(row.map_or((0, 0), |r| (r["bytes"].as_u64().unwrap_or(0), r["files"].as_u64().unwrap_or(0))))
+}
+
+/// The intent's status if it was parked, cancelled or deleted mid-run, so
+/// dispatch should stop without finalizing.
+async fn halted_status(db: &DbHandle, intent_id: &RecordId) -> Result<Option<String>, SchedulerError> {
+	let mut response = db
+		.db
+		.query("SELECT VALUE status FROM $id")
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let status: Vec<String> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let Some(status) = status.into_iter().next() else {
+		return Ok(Some("deleted".to_string()));
+	};
+	Ok(Some(status).filter(|s| matches!(s.as_str(), "waiting_for_device" | "paused" | "cancelled")))
+}
+
+/// Compare each destination's pending bytes against its free space. If a
+/// destination can't take everything, its pending jobs go straight to
+/// `needs_review` as `disk_full` and an `InsufficientSpace` event is emitted.
+/// Destinations whose free space can't be read (unmounted, or on a remote
+/// machine) are left to fail normally. Returns the number of jobs parked.
+async fn precheck_free_space(db: &DbHandle, intent_id: &RecordId) -> Result<u64, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT destination, destination.path AS location_path, size, bytes_transferred
+             FROM transfer_job
+             WHERE intent = $intent_id AND status = 'pending' AND destination.machine.kind != 'remote'",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	// destination -> (location path, bytes still to write, job count)
+	let mut needed: Vec<(RecordId, String, u64, u64)> = Vec::new();
+	for row in rows {
+		let (Ok(dest), Some(path)) = (
+			serde_json::from_value::<RecordId>(row["destination"].clone()),
+			row["location_path"].as_str(),
+		) else {
+			continue;
+		};
+		let remaining = row["size"]
+			.as_u64()
+			.unwrap_or(0)
+			.saturating_sub(row["bytes_transferred"].as_u64().unwrap_or(0));
+		match needed.iter_mut().find(|(id, ..)| *id == dest) {
+			Some(entry) => {
+				entry.2 += remaining;
+				entry.3 += 1;
+			}
+			None => needed.push((dest, path.to_string(), remaining, 1)),
+		}
+	}
+
+	let mut parked = 0;
+	for (dest, path, bytes, jobs) in needed {
+		let Ok(available) = copier::available_space(decode_path(&path)) else {
+			continue;
+		};
+		if bytes <= available {
+			continue;
+		}
+
+		let message = format!("needs {bytes} bytes but only {available} bytes are free");
+		db.db
+			.query(
+				"LET $jobs = (UPDATE transfer_job SET
+                    status = 'needs_review',
+                    error_kind = 'disk_full',
+                    last_error = $message
+                 WHERE intent = $intent_id AND destination = $dest AND status = 'pending'
+                 RETURN AFTER);
+                 FOR $job IN $jobs {
+                    CREATE review_item CONTENT {
+                        job: $job.id,
+                        intent: $job.intent,
+                        error_kind: 'disk_full',
+                        error_message: $message,
+                        source_path: $job.source_path,
+                        dest_path: $job.dest_path,
+                        options: ['retry', 'skip'],
+                        source_size: $job.size,
+                        created_at: time::now(),
+                    };
+                 };",
+			)
+			.bind(("intent_id", intent_id.clone()))
+			.bind(("dest", dest))
+			.bind(("message", message))
+			.await
+			.map_err(|e| SchedulerError::DbError(e.to_string()))?
+			.check()
+			.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+		parked += jobs;
+		events::emit(EngineEvent::InsufficientSpace {
+			destination: kip_core::util::display_path(&path),
+			needed: bytes,
+			available,
+			jobs,
+		});
+	}
+
+	Ok(parked)
+}
+
+/// Run pending jobs across every intent, highest priority first. Each
+/// intent goes through `run_intent`, so it keeps its own concurrency, drive
+/// cap, job order and free-space check. Intents are picked one at a time,
+/// so work queued meanwhile for a higher-priority intent goes next.
+pub async fn run_all_pending(db: &DbHandle) -> Result<RunResult, SchedulerError> {
+	let mut total = RunResult { completed: 0, failed: 0, needs_review: 0 };
+	// An intent that stopped with jobs left (paused, unplugged) isn't retried
+	let mut done = HashSet::new();
+
+	while !shutdown::is_requested() {
+		let pending = pending_intents(db).await?;
+		let Some(intent_id) = pending.into_iter().find(|id| !done.contains(id)) else {
+			break;
+		};
+		done.insert(intent_id.clone());
+
+		let result = run_intent(db, &intent_id).await?;
+		total.completed += result.completed;
+		total.failed += result.failed;
+		total.needs_review += result.needs_review;
+	}
+
+	Ok(total)
+}
+
+/// Intents with pending jobs, highest priority first, oldest first within a
+/// priority.
+async fn pending_intents(db: &DbHandle) -> Result<Vec<RecordId>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT VALUE id FROM intent
+             WHERE (SELECT VALUE id FROM transfer_job WHERE intent = $parent.id AND status = 'pending' LIMIT 1) != []
+             ORDER BY priority DESC, created_at ASC",
+		)
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))
+}
+
+/// Time until the intent's earliest backed-off pending job becomes due, or
+/// `None` if nothing is waiting.
+async fn soonest_retry(db: &DbHandle, intent_id: &RecordId) -> Result<Option<Duration>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT next_retry_at FROM transfer_job
+             WHERE intent = $intent_id AND status = 'pending' AND next_retry_at > time::now()
+             ORDER BY next_retry_at ASC LIMIT 1",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let due = row
+		.and_then(|r| r["next_retry_at"].as_str().map(str::to_string))
+		.and_then(|s| chrono::DateTime::parse_from_rfc3339(&s).ok());
+
+	Ok(due.map(|at| {
+		(at.with_timezone(&chrono::Utc) - chrono::Utc::now())
+			.to_std()
+			.unwrap_or(Duration::ZERO)
+	}))
+}
+
+/// Run `task` for every item, at most `concurrency` at a time, and wait for
+/// all of them. Items are started in order. While it runs, its permits and
+/// waiting items count toward `dispatch_status`.
+async fn run_bounded<T, F, Fut>(items: Vec<T>, concurrency: usize, task: F)
+where
+	T: Send + 'static,
+	F: Fn(T) -> Fut,
+	Fut: Future<Output = ()> + Send + 'static,
+{
+	let concurrency = concurrency.max(1);
+	let semaphore = Arc::new(Semaphore::new(concurrency));
+	let mut handles = Vec::with_capacity(items.len());
+	let _slots = Held::new(&SLOTS, concurrency);
+	let mut queued = Held::new(&QUEUED, items.len());
+
+	for item in items {
+		let permit = semaphore.clone().acquire_owned().await.unwrap();
+		queued.release_one();
+		let copying = Held::new(&COPYING, 1);
+		let fut = task(item);
+
+		handles.push(tokio::spawn(async move {
+			fut.await;
+			drop(copying);
+			drop(permit);
+		}));
+	}
+
+	for handle in handles {
+		// Ignore join errors (panics in copy tasks) — the job stays
+		// in 'transferring' and will be recovered on next loop iteration
+		let _ = handle.await;
+	}
+}
+
+/// Query all pending job IDs for an intent, in the intent's `job_order`.
+async fn get_pending_jobs(
+	db: &DbHandle,
+	intent_id: &RecordId,
+	order: JobOrder,
+) -> Result<Vec<RecordId>, SchedulerError> {
+	let query = match order {
+		JobOrder::SmallestFirst => {
+			"SELECT id, size FROM transfer_job WHERE intent = $intent_id AND status = 'pending' AND (next_retry_at IS NONE OR next_retry_at <= time::now()) ORDER BY size ASC"
+		}
+		JobOrder::LargestFirst => {
+			"SELECT id, size FROM transfer_job WHERE intent = $intent_id AND status = 'pending' AND (next_retry_at IS NONE OR next_retry_at <= time::now()) ORDER BY size DESC"
+		}
+		JobOrder::AsFound => {
+			"SELECT id, created_at FROM transfer_job WHERE intent = $intent_id AND status = 'pending' AND (next_retry_at IS NONE OR next_retry_at <= time::now()) ORDER BY created_at ASC"
+		}
+	};
+
 	let mut response = db
 		.db
-		.query("SELECT id FROM transfer_job WHERE intent = $intent_id AND status = 'pending'")
+		.query(query)
 		.bind(("intent_id", intent_id.clone()))
 		.await
 		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
@@ -131,13 +784,7 @@ async fn get_pending_jobs(db: &DbHandle, intent_id: &RecordId) -> Result<Vec<Rec
 async fn compute_result(db: &DbHandle, intent_id: &RecordId) -> Result<RunResult, SchedulerError> {
 	let mut response = db
 		.db
-		.query(
-			"SELECT
-                math::sum(IF status = 'complete' THEN 1 ELSE 0 END) AS completed,
-                math::sum(IF status = 'needs_review' THEN 1 ELSE 0 END) AS needs_review,
-                math::sum(IF status = 'failed' THEN 1 ELSE 0 END) AS failed
-             FROM transfer_job WHERE intent = $intent_id GROUP ALL",
-		)
+		.query(format!("SELECT {JOB_COUNTS} FROM transfer_job WHERE intent = $intent_id GROUP ALL"))
 		.bind(("intent_id", intent_id.clone()))
 		.await
 		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
@@ -156,7 +803,109 @@ async fn compute_result(db: &DbHandle, intent_id: &RecordId) -> Result<RunResult
 	}
 }
 
-/// Update the intent's final status based on job results.
+/// An intent's jobs that went to review or failed for good, counted by
+/// `error_kind`, most common first. Its report export lists each of them.
+pub async fn failure_counts(db: &DbHandle, intent_id: &RecordId) -> Result<Vec<(String, u64)>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT error_kind ?? 'unknown' AS kind, count() AS count FROM transfer_job
+             WHERE intent = $intent_id AND status IN ['needs_review', 'failed']
+             GROUP BY kind",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let mut counts: Vec<(String, u64)> = rows
+		.iter()
+		.filter_map(|r| Some((r["kind"].as_str()?.to_string(), r["count"].as_u64()?)))
+		.collect();
+	counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+	Ok(counts)
+}
+
+/// Health of every intent that has jobs, in one grouped query. Intents with
+/// no jobs are left out.
+pub async fn job_health(db: &DbHandle) -> Result<HashMap<RecordId, JobHealth>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(format!("SELECT intent, {JOB_COUNTS} FROM transfer_job GROUP BY intent"))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	Ok(rows
+		.iter()
+		.filter_map(|r| {
+			let intent = serde_json::from_value::<RecordId>(r["intent"].clone()).ok()?;
+			let health = JobHealth {
+				needs_review: r["needs_review"].as_u64().unwrap_or(0),
+				failed: r["failed"].as_u64().unwrap_or(0),
+				retrying: r["retrying"].as_u64().unwrap_or(0),
+			};
+			Some((intent, health))
+		})
+		.collect())
+}
+
+/// Job counts for every (intent, destination) pair that has jobs. Jobs a
+/// two-way sync copies back into the source are counted under the source.
+pub async fn destination_results(
+	db: &DbHandle,
+) -> Result<HashMap<(RecordId, RecordId), DestinationResult>, SchedulerError> {
+	let mut response = db
+		.db
+		.query(format!(
+			"SELECT intent, destination, count() AS total, {JOB_COUNTS},
+                math::sum(IF status IN ['pending', 'transferring'] THEN 1 ELSE 0 END) AS remaining
+             FROM transfer_job GROUP BY intent, destination"
+		))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	Ok(rows
+		.iter()
+		.filter_map(|r| {
+			let intent = serde_json::from_value::<RecordId>(r["intent"].clone()).ok()?;
+			let destination = serde_json::from_value::<RecordId>(r["destination"].clone()).ok()?;
+			let result = DestinationResult {
+				total: r["total"].as_u64().unwrap_or(0),
+				completed: r["completed"].as_u64().unwrap_or(0),
+				needs_review: r["needs_review"].as_u64().unwrap_or(0),
+				failed: r["failed"].as_u64().unwrap_or(0),
+				remaining: r["remaining"].as_u64().unwrap_or(0),
+			};
+			Some(((intent, destination), result))
+		})
+		.collect())
+}
+
+/// Nothing is moving anymore; drop the stale rate so the UI stops showing one.
+async fn clear_throughput(db: &DbHandle, intent_id: &RecordId) -> Result<(), SchedulerError> {
+	db.db
+		.query("UPDATE $id SET bytes_per_sec = NONE")
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	Ok(())
+}
+
+/// Update the intent's final status based on job results and tell the UI
+/// how the run went.
 async fn finalize_intent(db: &DbHandle, intent_id: &RecordId, result: &RunResult) -> Result<(), SchedulerError> {
 	let status = if result.needs_review > 0 {
 		"needs_review"
@@ -164,21 +913,627 @@ async fn finalize_intent(db: &DbHandle, intent_id: &RecordId, result: &RunResult
 		"complete"
 	};
 
-	// Also update completed_files and completed_bytes from actual job data
-	db.db
+	// Also update completed_files and completed_bytes from actual job data.
+	// Finishing a pass means a sync intent's initial sync is done; a clean
+	// pass becomes the baseline for bidirectional change detection.
+	let (completed_bytes, _) = job_progress(db, intent_id).await?;
+	let mut response = db
+		.db
 		.query(
 			"UPDATE $id SET
                 status = $status,
                 completed_files = $completed,
-                updated_at = time::now()",
+                completed_bytes = $completed_bytes,
+                bytes_per_sec = NONE,
+                initial_sync_complete = true,
+                last_synced_at = IF $status = 'complete' { scan_started_at } ELSE { last_synced_at },
+                updated_at = time::now()
+             RETURN kind, name, write_manifest",
 		)
 		.bind(("id", intent_id.clone()))
 		.bind(("status", status.to_string()))
 		.bind(("completed", result.completed as i64))
+		.bind(("completed_bytes", completed_bytes as i64))
 		.await
-		.map_err(|e| SchedulerError::DbError(e.to_string()))?
-		.check()
 		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
 
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	let Some(row) = row else {
+		return Ok(());
+	};
+
+	let (intent, name) = (rid_string(intent_id), intent_label(intent_id, row["name"].as_str()));
+	events::emit(if result.needs_review > 0 {
+		EngineEvent::NeedsReview {
+			intent,
+			name,
+			count: result.needs_review,
+			copied: result.completed,
+			failures: failure_counts(db, intent_id).await?,
+		}
+	} else {
+		EngineEvent::TransferComplete { intent, name, files: result.completed }
+	});
+
+	// A failed manifest doesn't undo the copies it would have listed
+	if row["write_manifest"] == true {
+		if let Err(e) = manifest::write_manifests(db, intent_id).await {
+			tracing::warn!("couldn't write the checksum manifest for {}: {}", rid_string(intent_id), e);
+		}
+	}
+
+	// Sync intents keep following the source from here on
+	if row["kind"] == "sync" {
+		watcher::spawn_sync_watcher(db.clone(), intent_id.clone());
+	}
+
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	/// Runs 6 sleeping tasks and reports the most that were in flight at once.
+	async fn peak_in_flight(concurrency: usize) -> usize {
+		let current = Arc::new(AtomicUsize::new(0));
+		let peak = Arc::new(AtomicUsize::new(0));
+
+		run_bounded((0..6).collect::<Vec<_>>(), concurrency, |_| {
+			let (current, peak) = (current.clone(), peak.clone());
+			async move {
+				let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+				peak.fetch_max(now, Ordering::SeqCst);
+				tokio::time::sleep(Duration::from_millis(10)).await;
+				current.fetch_sub(1, Ordering::SeqCst);
+			}
+		})
+		.await;
+
+		peak.load(Ordering::SeqCst)
+	}
+
+	#[tokio::test]
+	async fn dispatch_status_counts_running_copies() {
+		// Other tests dispatch in parallel, so only lower bounds hold
+		let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+		run_bounded((0..3).collect::<Vec<_>>(), 2, |_| {
+			let seen = seen.clone();
+			async move {
+				seen.lock().unwrap().push(dispatch_status());
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await;
+
+		let seen = seen.lock().unwrap();
+		assert_eq!(seen.len(), 3);
+		assert!(seen.iter().all(|s| s.copying >= 1 && s.slots >= 2));
+		// The first two start together while the third waits for a permit
+		assert!(seen[0].queued >= 1);
+	}
+
+	#[tokio::test]
+	async fn concurrency_one_serializes_jobs() {
+		assert_eq!(peak_in_flight(1).await, 1);
+	}
+
+	#[tokio::test]
+	async fn concurrency_bounds_parallel_jobs() {
+		let peak = peak_in_flight(3).await;
+		assert!(peak > 1 && peak <= 3, "peak was {peak}");
+	}
+
+	#[tokio::test]
+	async fn failures_are_counted_by_kind_for_the_outcome() {
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"FOR $job IN [
+                    ['a', 'complete', NONE], ['b', 'complete', NONE], ['c', 'complete', NONE],
+                    ['d', 'needs_review', 'hash_mismatch'], ['e', 'needs_review', 'verify_failed'],
+                    ['f', 'failed', 'permission_denied'],
+                ] {
+                    CREATE transfer_job CONTENT {
+                        intent: intent:big, destination: location:dst,
+                        source_path: '/src/' + $job[0], dest_path: '/dst/' + $job[0],
+                        size: 1, status: $job[1], error_kind: $job[2],
+                    };
+                };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "big");
+
+		let failures = failure_counts(&db, &intent_id).await.unwrap();
+		assert_eq!(failures.iter().map(|(_, n)| n).sum::<u64>(), 3);
+		let result = compute_result(&db, &intent_id).await.unwrap();
+		assert_eq!(
+			events::outcome_summary(result.completed, &failures),
+			"3 copied, 2 failed verification, 1 permission denied"
+		);
+	}
+
+	async fn create_intent_with_job(db: &DbHandle, key: &str, priority: i64) {
+		db.db
+			.query(
+				"CREATE type::record('intent', $key) CONTENT {
+                    source: 'location:src', destinations: [], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal', priority: $priority,
+                };
+                CREATE transfer_job CONTENT {
+                    intent: type::record('intent', $key),
+                    destination: type::record('location', 'dst'),
+                    source_path: '/src/' + $key, dest_path: '/dst/' + $key,
+                    size: 1, status: 'pending',
+                };",
+			)
+			.bind(("key", key.to_string()))
+			.bind(("priority", priority))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+	}
+
+	#[tokio::test]
+	async fn pending_jobs_follow_intent_priority() {
+		let db = crate::db::init_memory().await.unwrap();
+		// The low-priority (nightly) job is queued first
+		create_intent_with_job(&db, "nightly", 10).await;
+		create_intent_with_job(&db, "urgent", 900).await;
+
+		let intents = pending_intents(&db).await.unwrap();
+
+		assert_eq!(intents, vec![RecordId::new("intent", "urgent"), RecordId::new("intent", "nightly")]);
+	}
+
+	#[tokio::test]
+	async fn run_all_pending_checks_each_intent_like_run_intent() {
+		let db = crate::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		fs::write(src.path().join("small.txt"), "alpha").unwrap();
+		fs::write(src.path().join("huge.bin"), "tiny on disk, huge on paper").unwrap();
+
+		db.db
+			.query(
+				"CREATE location:dst CONTENT { path: $dst_root, available: true };
+                CREATE intent:small CONTENT {
+                    source: 'location:src', destinations: ['location:dst'], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal',
+                };
+                CREATE intent:big CONTENT {
+                    source: 'location:src', destinations: ['location:dst'], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal', priority: 5,
+                };
+                CREATE transfer_job CONTENT {
+                    intent: intent:small, destination: location:dst,
+                    source_path: $src_root + '/small.txt', dest_path: $dst_root + '/small.txt',
+                    size: 5, status: 'pending',
+                };
+                CREATE transfer_job CONTENT {
+                    intent: intent:big, destination: location:dst,
+                    source_path: $src_root + '/huge.bin', dest_path: $dst_root + '/huge.bin',
+                    size: 9000000000000000000, status: 'pending',
+                };",
+			)
+			.bind(("src_root", src.path().to_str().unwrap().to_string()))
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let result = run_all_pending(&db).await.unwrap();
+
+		assert_eq!((result.completed, result.needs_review), (1, 1));
+		assert_eq!(fs::read_to_string(dst.path().join("small.txt")).unwrap(), "alpha");
+		assert!(!dst.path().join("huge.bin").exists(), "the free-space check should have parked it");
+		let mut resp = db.db.query("SELECT VALUE error_kind FROM review_item").await.unwrap();
+		let kinds: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(kinds, vec!["disk_full".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn job_progress_keeps_the_latest_count_for_running_jobs() {
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE transfer_job:running CONTENT {
+                    intent: intent:a, destination: location:dst, source_path: '/src/a', dest_path: '/dst/a',
+                    size: 1000, status: 'transferring',
+                };
+                CREATE transfer_job:done CONTENT {
+                    intent: intent:a, destination: location:dst, source_path: '/src/b', dest_path: '/dst/b',
+                    size: 1, status: 'complete', bytes_transferred: 1,
+                };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let (running, done) = (RecordId::new("transfer_job", "running"), RecordId::new("transfer_job", "done"));
+
+		let progress = JobProgress::spawn(db.clone());
+		let sender = progress.sender();
+		for bytes in [100, 200, 300] {
+			sender.report(&running, bytes);
+		}
+		// A report that lands after the job finished
+		sender.report(&done, 50);
+		drop(sender);
+		progress.finish().await;
+
+		let mut resp = db
+			.db
+			.query("SELECT VALUE bytes_transferred FROM [$running, $done]")
+			.bind(("running", running))
+			.bind(("done", done))
+			.await
+			.unwrap();
+		let bytes: Vec<i64> = resp.take(0).unwrap();
+		assert_eq!(bytes, vec![300, 1]);
+	}
+
+	#[tokio::test]
+	async fn job_health_counts_each_intent_separately() {
+		let db = crate::db::init_memory().await.unwrap();
+		create_intent_with_job(&db, "clean", 0).await;
+		create_intent_with_job(&db, "shaky", 0).await;
+		db.db
+			.query(
+				"UPDATE transfer_job SET status = 'complete' WHERE intent = intent:clean;
+                 UPDATE transfer_job SET attempts = 1 WHERE intent = intent:shaky;
+                 CREATE transfer_job CONTENT {
+                     intent: intent:shaky, destination: location:dst, source_path: '/src/x', dest_path: '/dst/x',
+                     size: 1, status: 'needs_review',
+                 };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let health = job_health(&db).await.unwrap();
+
+		assert!(health[&RecordId::new("intent", "clean")].is_healthy());
+		assert_eq!(
+			health[&RecordId::new("intent", "shaky")],
+			JobHealth { needs_review: 1, failed: 0, retrying: 1 }
+		);
+	}
+
+	#[tokio::test]
+	async fn destination_results_split_a_fan_out_intent() {
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"FOR $job IN [
+                     { dest: location:a, path: '/a/1', status: 'complete' },
+                     { dest: location:a, path: '/a/2', status: 'complete' },
+                     { dest: location:b, path: '/b/1', status: 'complete' },
+                     { dest: location:b, path: '/b/2', status: 'pending' },
+                 ] {
+                     CREATE transfer_job CONTENT {
+                         intent: intent:fan, destination: $job.dest, source_path: '/src', dest_path: $job.path,
+                         size: 1, status: $job.status,
+                     };
+                 };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let results = destination_results(&db).await.unwrap();
+		let result = |dest: &str| results[&(RecordId::new("intent", "fan"), RecordId::new("location", dest))];
+
+		assert_eq!(result("a"), DestinationResult { total: 2, completed: 2, ..Default::default() });
+		assert_eq!(result("b"), DestinationResult { total: 2, completed: 1, remaining: 1, ..Default::default() });
+	}
+
+	#[tokio::test]
+	async fn insufficient_space_parks_jobs_before_copying() {
+		let db = crate::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let source_file = src.path().join("huge.bin");
+		fs::write(&source_file, "tiny on disk, huge on paper").unwrap();
+		let dest_file = dst.path().join("huge.bin");
+
+		db.db
+			.query(
+				"CREATE intent:big CONTENT {
+                    source: 'location:src', destinations: [], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal',
+                };
+                CREATE location:dst CONTENT { path: $dst_root, available: true };
+                CREATE transfer_job CONTENT {
+                    intent: intent:big, destination: location:dst,
+                    source_path: $src_path, dest_path: $dst_path,
+                    size: 9000000000000000000, status: 'pending',
+                };",
+			)
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.bind(("src_path", source_file.to_str().unwrap().to_string()))
+			.bind(("dst_path", dest_file.to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let result = run_intent(&db, &RecordId::new("intent", "big")).await.unwrap();
+
+		assert_eq!(result.needs_review, 1);
+		assert_eq!(result.completed, 0);
+		assert!(!dest_file.exists(), "no file should be written");
+
+		let mut resp = db.db.query("SELECT VALUE error_kind FROM review_item").await.unwrap();
+		let kinds: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(kinds, vec!["disk_full".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn slow_destination_drive_caps_a_run() {
+		let db = crate::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let source_file = src.path().join("a.txt");
+		fs::write(&source_file, "alpha").unwrap();
+		let dest_file = dst.path().join("a.txt");
+
+		db.db
+			.query(
+				"CREATE drive:stick CONTENT {
+                    name: 'Stick', uuid: 'u-stick', connected: true, last_seen: time::now(),
+                    measured_write_mbps: 20.0,
+                };
+                CREATE location:dst CONTENT { path: $dst_root, drive: drive:stick, available: true };
+                CREATE intent:usb CONTENT {
+                    source: 'location:src', destinations: ['location:dst'], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal', concurrency: 8,
+                };
+                CREATE transfer_job CONTENT {
+                    intent: intent:usb, destination: location:dst,
+                    source_path: $src_path, dest_path: $dst_path,
+                    size: 5, status: 'pending',
+                };",
+			)
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.bind(("src_path", source_file.to_str().unwrap().to_string()))
+			.bind(("dst_path", dest_file.to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "usb");
+
+		let (_, concurrency) = dispatch_settings(&db, &intent_id).await.unwrap();
+		assert_eq!(concurrency, 1, "the stick's write speed should cap the intent's 8");
+
+		let result = run_intent(&db, &intent_id).await.unwrap();
+		assert_eq!(result.completed, 1);
+		assert_eq!(fs::read_to_string(&dest_file).unwrap(), "alpha");
+	}
+
+	#[tokio::test]
+	async fn remote_destination_without_hostname_fails_the_job() {
+		let db = crate::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let source_file = src.path().join("a.txt");
+		fs::write(&source_file, "alpha").unwrap();
+		let dest_file = dst.path().join("a.txt");
+
+		db.db
+			.query(
+				"CREATE intent:offsite CONTENT {
+                    source: 'location:src', destinations: [], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal',
+                };
+                CREATE machine:nas CONTENT {
+                    name: 'NAS', kind: 'remote', is_current: false, online: false, last_seen: time::now(),
+                };
+                CREATE location:nas CONTENT { path: $dst_root, machine: machine:nas, available: true };
+                CREATE transfer_job CONTENT {
+                    intent: intent:offsite, destination: location:nas,
+                    source_path: $src_path, dest_path: $dst_path,
+                    size: 5, status: 'pending',
+                };",
+			)
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.bind(("src_path", source_file.to_str().unwrap().to_string()))
+			.bind(("dst_path", dest_file.to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let result = run_intent(&db, &RecordId::new("intent", "offsite")).await.unwrap();
+
+		assert_eq!(result.needs_review, 1);
+		assert!(!dest_file.exists(), "nothing should be written to the local disk");
+		let mut resp = db.db.query("SELECT VALUE error_kind FROM review_item").await.unwrap();
+		let kinds: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(kinds, vec!["remote_misconfigured".to_string()]);
+	}
+
+	/// Run a one-file intent to `dest_name` after recording the source's
+	/// content at `recorded_name` in the destination, where a file of the
+	/// same size but different content actually sits.
+	async fn copy_with_stale_record(recorded_name: &str, dest_name: &str) -> (DbHandle, tempfile::TempDir) {
+		let db = crate::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let source_file = src.path().join("photo.jpg");
+		fs::write(&source_file, "the source bytes").unwrap();
+		fs::write(dst.path().join(recorded_name), "an edited copy!!").unwrap();
+		let hash = copier::hash_file(&source_file, copier::HashAlgorithm::Blake3).unwrap();
+
+		db.db
+			.query(
+				"CREATE intent:photos CONTENT {
+                    source: 'location:src', destinations: [], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal',
+                };
+                CREATE location:dst CONTENT { path: $dst_root, available: true };
+                CREATE transfer_job CONTENT {
+                    intent: intent:photos, destination: location:dst,
+                    source_path: $src_path, dest_path: $dst_path,
+                    size: 16, status: 'pending',
+                };",
+			)
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.bind(("src_path", source_file.to_str().unwrap().to_string()))
+			.bind(("dst_path", dst.path().join(dest_name).to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let recorded = dst.path().join(recorded_name).to_str().unwrap().to_string();
+		let location = RecordId::new("location", "dst");
+		crate::engine::dedup::record_file_at(&db, &hash, 16, &location, &recorded).await.unwrap();
+
+		let result = run_intent(&db, &RecordId::new("intent", "photos")).await.unwrap();
+		assert_eq!(result.completed, 1);
+		(db, dst)
+	}
+
+	#[tokio::test]
+	async fn dedup_rechecks_a_same_size_file_at_the_dest_path() {
+		let (db, dst) = copy_with_stale_record("photo.jpg", "photo.jpg").await;
+
+		assert_eq!(fs::read_to_string(dst.path().join("photo.jpg")).unwrap(), "the source bytes");
+		let mut resp = db.db.query("SELECT VALUE bytes_transferred FROM transfer_job").await.unwrap();
+		let copied: Vec<i64> = resp.take(0).unwrap();
+		assert_eq!(copied, vec![16], "the bytes should have been copied, not skipped");
+	}
+
+	#[tokio::test]
+	async fn dedup_marks_a_changed_copy_elsewhere_stale() {
+		let (db, dst) = copy_with_stale_record("older.jpg", "photo.jpg").await;
+
+		assert_eq!(fs::read_to_string(dst.path().join("photo.jpg")).unwrap(), "the source bytes");
+		assert_eq!(fs::read_to_string(dst.path().join("older.jpg")).unwrap(), "an edited copy!!");
+		let older = dst.path().join("older.jpg").to_str().unwrap().to_string();
+		let mut resp = db
+			.db
+			.query("SELECT VALUE stale FROM exists_at WHERE path = $path")
+			.bind(("path", older))
+			.await
+			.unwrap();
+		let stale: Vec<bool> = resp.take(0).unwrap();
+		assert_eq!(stale, vec![true]);
+	}
+
+	/// Run a one-file intent whose destination already holds a different file.
+	async fn copy_over_existing(overwrite_policy: &str) -> (DbHandle, tempfile::TempDir) {
+		let db = crate::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		fs::write(src.path().join("notes.txt"), "from the source").unwrap();
+		fs::write(dst.path().join("notes.txt"), "edited at the destination").unwrap();
+
+		db.db
+			.query(
+				"CREATE intent:notes CONTENT {
+                    source: 'location:src', destinations: [], status: 'transferring',
+                    kind: 'one_shot', speed_mode: 'normal', overwrite_policy: $policy,
+                };
+                CREATE location:dst CONTENT { path: $dst_root, available: true };
+                CREATE transfer_job CONTENT {
+                    intent: intent:notes, destination: location:dst,
+                    source_path: $src_path, dest_path: $dst_path,
+                    size: 15, status: 'pending',
+                };",
+			)
+			.bind(("policy", overwrite_policy.to_string()))
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.bind(("src_path", src.path().join("notes.txt").to_str().unwrap().to_string()))
+			.bind(("dst_path", dst.path().join("notes.txt").to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		run_intent(&db, &RecordId::new("intent", "notes")).await.unwrap();
+		(db, dst)
+	}
+
+	#[tokio::test]
+	async fn overwrite_policy_keeps_a_different_destination() {
+		let (db, dst) = copy_over_existing("never").await;
+		assert_eq!(fs::read_to_string(dst.path().join("notes.txt")).unwrap(), "edited at the destination");
+		let mut resp = db.db.query("SELECT VALUE status FROM transfer_job").await.unwrap();
+		let statuses: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(statuses, vec!["skipped".to_string()]);
+
+		let (db, dst) = copy_over_existing("review").await;
+		assert_eq!(fs::read_to_string(dst.path().join("notes.txt")).unwrap(), "edited at the destination");
+		let mut resp = db.db.query("SELECT VALUE error_kind FROM review_item").await.unwrap();
+		let kinds: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(kinds, vec!["conflict".to_string()]);
+
+		let (_db, dst) = copy_over_existing("always").await;
+		assert_eq!(fs::read_to_string(dst.path().join("notes.txt")).unwrap(), "from the source");
+	}
+
+	#[test]
+	fn throughput_needs_two_samples() {
+		let start = Instant::now();
+		let mut throughput = Throughput::default();
+		assert_eq!(throughput.sample(start, 0), None);
+		assert_eq!(throughput.sample(start + Duration::from_secs(2), 4_000_000), Some(2_000_000));
+	}
+
+	#[test]
+	fn throughput_forgets_old_samples() {
+		let start = Instant::now();
+		let mut throughput = Throughput::default();
+		throughput.sample(start, 0);
+		// Fast burst early on, then a slow stretch
+		throughput.sample(start + Duration::from_secs(1), 50_000_000);
+		let rate = throughput.sample(start + Duration::from_secs(21), 50_000_000 + 10_000_000);
+		// Both earlier samples fell out of the window
+		assert_eq!(rate, None);
+
+		let rate = throughput.sample(start + Duration::from_secs(26), 70_000_000);
+		assert_eq!(rate, Some(2_000_000));
+	}
+
+	#[test]
+	fn throughput_never_goes_negative() {
+		let start = Instant::now();
+		let mut throughput = Throughput::default();
+		throughput.sample(start, 1_000);
+		assert_eq!(throughput.sample(start + Duration::from_secs(1), 500), Some(0));
+	}
+
+	#[test]
+	fn intent_setting_overrides_default() {
+		assert_eq!(effective_concurrency(Some(16), &SpeedMode::Normal, &[]), 16);
+		assert_eq!(effective_concurrency(Some(0), &SpeedMode::Normal, &[]), default_concurrency());
+		assert_eq!(effective_concurrency(Some(3), &SpeedMode::Ninja, &[]), 3);
+	}
+
+	#[test]
+	fn slowest_destination_caps_concurrency() {
+		assert_eq!(effective_concurrency(Some(16), &SpeedMode::Normal, &[2000.0]), 16);
+		assert_eq!(effective_concurrency(Some(16), &SpeedMode::Normal, &[2000.0, 120.0]), 2);
+		assert_eq!(effective_concurrency(Some(16), &SpeedMode::Normal, &[35.0]), 1);
+		assert_eq!(effective_concurrency(None, &SpeedMode::Blast, &[120.0]), 2);
+	}
+
+	#[test]
+	fn speed_modes_differ_in_concurrency_and_throttling() {
+		let (normal, ninja, blast) = (SpeedMode::Normal.profile(), SpeedMode::Ninja.profile(), SpeedMode::Blast.profile());
+		assert_eq!((normal.concurrency, normal.rate_limit), (DEFAULT_CONCURRENCY, None));
+		assert!(ninja.concurrency < normal.concurrency && ninja.rate_limit.is_some());
+		assert!(blast.concurrency > normal.concurrency && blast.rate_limit.is_none());
+		assert!(blast.chunk_size > normal.chunk_size);
+		assert_eq!(effective_concurrency(None, &SpeedMode::Ninja, &[]), ninja.concurrency);
+		assert_eq!(effective_concurrency(None, &SpeedMode::Blast, &[2000.0]), blast.concurrency);
+	}
+}
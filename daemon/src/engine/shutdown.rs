@@ -0,0 +1,211 @@
+//! Stopping transfers cleanly when the app quits.
+//!
+//! `shutdown` raises a process-wide flag, so the scheduler stops dispatching
+//! and no new copy starts, and stops every copy still running. Each stopped
+//! job goes back to `pending` with its progress checkpointed, from the copy
+//! itself if it gets to settle within the grace period, or from here if not:
+//! whatever awaits a copy may not run again before the process exits. On the
+//! next launch `resume_interrupted_intents` picks the intents back up and the
+//! copier resumes each partial file.
+
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc, LazyLock, Mutex,
+	},
+	time::{Duration, Instant},
+};
+
+use surrealdb::types::RecordId;
+use tracing::{info, warn};
+
+use crate::{
+	db::DbHandle,
+	engine::{
+		scheduler::{self, SchedulerError},
+		transfer,
+	},
+};
+
+/// How long quitting waits for running copies to settle on their own.
+pub const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
+
+/// How often `wait_for_copies` checks for jobs still transferring.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// Copies in flight, by job, with their cancel flag and bytes copied so far.
+static RUNNING: LazyLock<Mutex<HashMap<RecordId, RunningCopy>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+struct RunningCopy {
+	cancel: Arc<AtomicBool>,
+	copied: Arc<AtomicU64>,
+}
+
+/// Keeps a copy registered for `shutdown` to stop until dropped.
+pub(crate) struct Tracked(RecordId);
+
+impl Drop for Tracked {
+	fn drop(&mut self) {
+		RUNNING.lock().unwrap().remove(&self.0);
+	}
+}
+
+/// Register a running copy so quitting can stop and checkpoint it.
+pub(crate) fn track(job_id: RecordId, cancel: Arc<AtomicBool>, copied: Arc<AtomicU64>) -> Tracked {
+	RUNNING
+		.lock()
+		.unwrap()
+		.insert(job_id.clone(), RunningCopy { cancel, copied });
+	Tracked(job_id)
+}
+
+/// Whether the app is quitting. Nothing new should start once it is.
+pub fn is_requested() -> bool {
+	SHUTTING_DOWN.load(Ordering::Relaxed)
+}
+
+/// Stop every running copy and put its job back to `pending`, waiting up to
+/// `grace` for the copies to do so themselves.
+pub async fn shutdown(db: &DbHandle, grace: Duration) {
+	info!("shutting down: stopping running copies");
+	SHUTTING_DOWN.store(true, Ordering::Relaxed);
+	for copy in RUNNING.lock().unwrap().values() {
+		copy.cancel.store(true, Ordering::Relaxed);
+	}
+
+	if wait_for_copies(db, grace).await {
+		return;
+	}
+	let stragglers: Vec<(RecordId, u64)> = RUNNING
+		.lock()
+		.unwrap()
+		.iter()
+		.map(|(id, copy)| (id.clone(), copy.copied.load(Ordering::Relaxed)))
+		.collect();
+	for (job_id, copied) in stragglers {
+		if let Err(e) = transfer::checkpoint(db, &job_id, copied).await {
+			warn!("couldn't checkpoint {:?} at shutdown: {}", job_id, e);
+		}
+	}
+}
+
+/// `shutdown` for callers outside any async context, like a window's close
+/// handler. Runs on its own thread so it never blocks inside a runtime.
+pub fn shutdown_blocking(db: &DbHandle, grace: Duration) {
+	let db = db.clone();
+	let _ = std::thread::spawn(move || {
+		match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+			Ok(rt) => rt.block_on(shutdown(&db, grace)),
+			Err(e) => warn!("can't run shutdown: {}", e),
+		}
+	})
+	.join();
+}
+
+/// Wait until no job is `transferring`, for at most `grace`.
+async fn wait_for_copies(db: &DbHandle, grace: Duration) -> bool {
+	let deadline = Instant::now() + grace;
+	loop {
+		match transferring_jobs(db).await {
+			Ok(0) => return true,
+			Ok(_) if Instant::now() >= deadline => return false,
+			Ok(_) => tokio::time::sleep(WAIT_POLL_INTERVAL).await,
+			Err(e) => {
+				warn!("couldn't check for running copies at shutdown: {}", e);
+				return false;
+			}
+		}
+	}
+}
+
+async fn transferring_jobs(db: &DbHandle) -> Result<u64, SchedulerError> {
+	let mut response = db
+		.db
+		.query("SELECT count() AS count FROM transfer_job WHERE status = 'transferring' GROUP ALL")
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	Ok(row.and_then(|r| r["count"].as_u64()).unwrap_or(0))
+}
+
+/// Resolves on Ctrl-C, or SIGTERM on unix, so a quit from the terminal or the
+/// OS can go through `shutdown` too.
+pub async fn wait_for_signal() {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{signal, SignalKind};
+		match signal(SignalKind::terminate()) {
+			Ok(mut terminate) => {
+				tokio::select! {
+					_ = tokio::signal::ctrl_c() => {}
+					_ = terminate.recv() => {}
+				}
+			}
+			Err(e) => {
+				warn!("can't listen for SIGTERM: {}", e);
+				let _ = tokio::signal::ctrl_c().await;
+			}
+		}
+	}
+	#[cfg(not(unix))]
+	let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Run intents that were still transferring when the app last quit. Their
+/// jobs were put back to `pending`, so each picks up where it stopped.
+pub async fn resume_interrupted_intents(db: &DbHandle) -> Result<Vec<RecordId>, SchedulerError> {
+	let mut response = db
+		.db
+		.query("SELECT VALUE id FROM intent WHERE status = 'transferring'")
+		.await
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+	let intents: Vec<RecordId> = response
+		.take(0)
+		.map_err(|e| SchedulerError::DbError(e.to_string()))?;
+
+	for intent_id in &intents {
+		info!("resuming {:?} after the last quit", intent_id);
+		let (db, intent_id) = (db.clone(), intent_id.clone());
+		tokio::spawn(async move {
+			if let Err(e) = scheduler::run_intent(&db, &intent_id).await {
+				warn!("resumed intent {:?} failed: {}", intent_id, e);
+			}
+		});
+	}
+	Ok(intents)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::db::init_memory;
+
+	// Requesting a shutdown would stop copies in every other test in this
+	// process, so only the waiting is tested here
+
+	#[tokio::test]
+	async fn waiting_ends_once_no_copy_is_running() {
+		let db = init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE transfer_job:a CONTENT {
+                    intent: intent:x, destination: location:dst, source_path: '/src/a', dest_path: '/dst/a',
+                    size: 1, status: 'transferring',
+                };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		assert!(!wait_for_copies(&db, Duration::from_millis(250)).await, "a copy is still running");
+
+		db.db.query("UPDATE transfer_job:a SET status = 'pending'").await.unwrap();
+		assert!(wait_for_copies(&db, Duration::from_millis(250)).await);
+	}
+}
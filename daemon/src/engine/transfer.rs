@@ -1,76 +1,78 @@
 use std::{
-	fmt, fs,
-	io::{self, Read, Write},
 	path::Path,
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc,
+	},
+	time::{Duration, SystemTime},
 };
 
 use surrealdb::types::RecordId;
 
-use crate::db::DbHandle;
-
-const CHUNK_SIZE: usize = 256 * 1024; // 256KB
-const PROGRESS_INTERVAL: usize = 4; // update DB every 4 chunks (~1MB)
-
-#[derive(Debug)]
-pub enum CopyError {
-	JobNotFound(String),
-	SourceNotFound(String),
-	PermissionDenied(String),
-	DiskFull(String),
-	IoError(String),
-	HashMismatch {
-		source_hash: String,
-		dest_hash: String,
-	},
-	DbError(String),
-}
-
-impl fmt::Display for CopyError {
-	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		match self {
-			CopyError::JobNotFound(s) => write!(f, "job not found: {}", s),
-			CopyError::SourceNotFound(s) => write!(f, "source file not found: {}", s),
-			CopyError::PermissionDenied(s) => write!(f, "permission denied: {}", s),
-			CopyError::DiskFull(s) => write!(f, "disk full: {}", s),
-			CopyError::IoError(s) => write!(f, "I/O error: {}", s),
-			CopyError::HashMismatch { source_hash, dest_hash } => {
-				write!(f, "hash mismatch: source={}, dest={}", source_hash, dest_hash)
-			}
-			CopyError::DbError(s) => write!(f, "database error: {}", s),
-		}
-	}
-}
-
-impl std::error::Error for CopyError {}
+use kip_core::{
+	models::intent::{ConflictPolicy, CopyStrategy, OverwritePolicy, SpeedMode, VerifyMode, DEFAULT_RETRYABLE_ERRORS},
+	util::decode_path,
+};
 
-impl CopyError {
-	/// Whether this error is retryable (transient I/O) vs needs immediate review.
-	pub fn is_retryable(&self) -> bool {
-		matches!(self, CopyError::IoError(_))
-	}
-}
+use crate::{
+	db::DbHandle,
+	engine::{
+		copier::{self, classify_error, CopyError, CopyOptions, CopyResult, HashAlgorithm},
+		dedup,
+		scheduler::ProgressSender,
+		shutdown,
+		transport::{LocalFs, RemoteMachine, SftpFs},
+	},
+};
 
-#[derive(Debug, Clone)]
-pub struct CopyResult {
-	pub bytes_copied: u64,
-	pub source_hash: String,
-	pub dest_hash: String,
-	pub verified: bool,
-}
+/// How often a running copy checks whether its intent was paused or
+/// cancelled.
+const HALT_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 /// Data we need from a transfer_job record.
 struct JobData {
 	intent: serde_json::Value,
+	/// Destination location; `None` for jobs created before it was recorded.
+	destination: Option<RecordId>,
 	source_path: String,
 	dest_path: String,
+	size: u64,
 	attempts: i64,
 	max_attempts: i64,
+	bytes_transferred: u64,
+	retryable_errors: Vec<String>,
+	source_modified: Option<SystemTime>,
+	verify_mode: VerifyMode,
+	/// Picks the copy's buffer size and bandwidth limit.
+	speed_mode: SpeedMode,
+	/// Settles hash mismatches instead of sending them to review.
+	on_conflict: ConflictPolicy,
+	/// Whether an existing, different destination file may be replaced.
+	overwrite_policy: OverwritePolicy,
+	/// Replacing the destination was approved in review, whatever the policy.
+	overwrite_approved: bool,
+	/// Whether a same-filesystem copy may be a reflink or hardlink.
+	copy_strategy: CopyStrategy,
+	/// Set when the destination location is on a remote machine; the copy
+	/// then goes over SFTP.
+	dest_remote: Option<RemoteMachine>,
+	/// Why the destination's remote machine can't be connected to, if its
+	/// record is missing what that takes.
+	dest_misconfigured: Option<String>,
 }
 
 /// Execute a single transfer job: copy file, hash, verify.
 ///
-/// Handles DB status transitions and error classification.
-pub async fn copy_job(db: &DbHandle, job_id: &RecordId) -> Result<CopyResult, CopyError> {
+/// Handles DB status transitions and error classification. Pausing or
+/// cancelling the intent stops the copy within a chunk, with
+/// `CopyError::Cancelled`, as does quitting the app, which also refuses to
+/// start new copies. The running byte count goes to `progress` as the copy
+/// goes, about every `copier::PROGRESS_STEP` bytes.
+pub async fn copy_job(db: &DbHandle, job_id: &RecordId, progress: &ProgressSender) -> Result<CopyResult, CopyError> {
+	if shutdown::is_requested() {
+		return Err(CopyError::Cancelled);
+	}
+
 	// 1. Load job data
 	let job = load_job(db, job_id).await?;
 
@@ -83,317 +85,632 @@ pub async fn copy_job(db: &DbHandle, job_id: &RecordId) -> Result<CopyResult, Co
 		.check()
 		.map_err(|e| CopyError::DbError(e.to_string()))?;
 
-	// 3. Run the copy pipeline (blocking I/O on dedicated thread)
-	let source = job.source_path.clone();
-	let dest = job.dest_path.clone();
-	let db_clone = db.clone();
+	// A remote destination we can't reach mustn't fall through to a local write
+	if let Some(why) = &job.dest_misconfigured {
+		return fail_job(db, job_id, &job, CopyError::RemoteMisconfigured(why.clone())).await;
+	}
+
+	// 3. Skip the write entirely if the destination already holds this content
+	if let Some(result) = try_dedup(db, &job).await? {
+		mark_complete(db, job_id, &result).await?;
+		return Ok(result);
+	}
+
+	// 4. Leave a different file already at the destination alone if the intent says so
+	guard_overwrite(db, job_id, &job).await?;
+
+	// 5. Run the copy pipeline (blocking I/O on dedicated thread)
+	let source = decode_path(&job.source_path);
+	let dest = decode_path(&job.dest_path);
+	let progress = progress.clone();
 	let job_id_clone = job_id.clone();
 
-	let result = tokio::task::spawn_blocking(move || copy_and_hash(&source, &dest, &db_clone, &job_id_clone))
-		.await
-		.map_err(|e| CopyError::IoError(format!("task join error: {e}")))?;
+	let cancel = Arc::new(AtomicBool::new(false));
+	let copied = Arc::new(AtomicU64::new(job.bytes_transferred));
+	let _halt_watch = HaltWatch::spawn(db.clone(), job_id.clone(), cancel.clone());
+	let _running = shutdown::track(job_id.clone(), cancel.clone(), copied.clone());
+	let options = CopyOptions {
+		chunk_size: copier::chunk_size_for(job.size, &job.speed_mode),
+		resume_from: job.bytes_transferred,
+		source_modified: job.source_modified,
+		verify: job.verify_mode,
+		cancel: Some(cancel),
+		strategy: job.copy_strategy,
+		rate_limit: job.speed_mode.profile().rate_limit,
+		..Default::default()
+	};
+
+	let dest_remote = job.dest_remote.clone();
+
+	let result = tokio::task::spawn_blocking(move || {
+		let on_progress = |bytes_copied| {
+			copied.store(bytes_copied, Ordering::Relaxed);
+			progress.report(&job_id_clone, bytes_copied);
+		};
+		match &dest_remote {
+			Some(machine) => {
+				let remote = SftpFs::shared(machine).map_err(|e| copier::map_io_error(e, &dest))?;
+				let result = copier::copy_between(&LocalFs, &source, &*remote, &dest, &options, on_progress);
+				// The session may be the thing that broke; the next copy reconnects
+				if matches!(result, Err(CopyError::IoError(_))) {
+					SftpFs::forget(machine);
+				}
+				result
+			}
+			None => copier::copy_file(&source, &dest, &options, on_progress),
+		}
+	})
+	.await
+	.map_err(|e| CopyError::IoError(format!("task join error: {e}")))?;
 
 	match result {
+		Err(CopyError::Cancelled) => {
+			stop_job(db, job_id, &job, copied.load(Ordering::Relaxed)).await?;
+			Err(CopyError::Cancelled)
+		}
 		Ok(copy_result) => {
-			// 4. Mark complete
-			db.db
-				.query(
-					"UPDATE $id SET
-                        status = 'complete',
-                        source_hash = $source_hash,
-                        dest_hash = $dest_hash,
-                        bytes_transferred = $bytes,
-                        completed_at = time::now()",
+			// 6. Mark complete and remember where this content now lives. Dedup
+			// looks content up by blake3, so fast-hashed copies aren't recorded
+			mark_complete(db, job_id, &copy_result).await?;
+			let blake3 = HashAlgorithm::of_digest(&copy_result.source_hash) == HashAlgorithm::Blake3;
+			if let Some(location) = job.destination.as_ref().filter(|_| blake3) {
+				if let Err(e) = dedup::record_file_at(
+					db,
+					&copy_result.source_hash,
+					copy_result.bytes_copied,
+					location,
+					&job.dest_path,
 				)
-				.bind(("id", job_id.clone()))
-				.bind(("source_hash", copy_result.source_hash.clone()))
-				.bind(("dest_hash", copy_result.dest_hash.clone()))
-				.bind(("bytes", copy_result.bytes_copied as i64))
 				.await
-				.map_err(|e| CopyError::DbError(e.to_string()))?
-				.check()
-				.map_err(|e| CopyError::DbError(e.to_string()))?;
+				{
+					tracing::warn!("failed to record {} for dedup: {}", job.dest_path, e);
+				}
+			}
 
 			Ok(copy_result)
 		}
-		Err(err) => {
-			// 5. Handle error: retryable vs needs_review
-			let new_attempts = job.attempts + 1;
-			let (new_status, error_kind) = if err.is_retryable() && new_attempts < job.max_attempts {
-				("pending", classify_error(&err))
-			} else {
-				("needs_review", classify_error(&err))
-			};
-
-			db.db
-				.query(
-					"UPDATE $id SET
-                        status = $status,
-                        attempts = $attempts,
-                        last_error = $error,
-                        error_kind = $error_kind",
-				)
-				.bind(("id", job_id.clone()))
-				.bind(("status", new_status.to_string()))
-				.bind(("attempts", new_attempts))
-				.bind(("error", err.to_string()))
-				.bind(("error_kind", error_kind.to_string()))
-				.await
-				.map_err(|e| CopyError::DbError(e.to_string()))?
-				.check()
-				.map_err(|e| CopyError::DbError(e.to_string()))?;
-
-			// Create review item for non-retryable failures
-			if new_status == "needs_review" {
-				let options = resolution_options(error_kind);
-				let _ = db
-					.db
-					.query(
-						"CREATE review_item CONTENT {
-                            job: $job_id,
-                            intent: $intent_id,
-                            error_kind: $error_kind,
-                            error_message: $error_msg,
-                            source_path: $source_path,
-                            dest_path: $dest_path,
-                            options: $options,
-                            created_at: time::now(),
-                        }",
-					)
-					.bind(("job_id", job_id.clone()))
-					.bind(("intent_id", job.intent.clone()))
-					.bind(("error_kind", error_kind.to_string()))
-					.bind(("error_msg", err.to_string()))
-					.bind(("source_path", job.source_path.clone()))
-					.bind(("dest_path", job.dest_path.clone()))
-					.bind(("options", options))
-					.await;
-			}
-
-			Err(err)
+		Err(CopyError::HashMismatch { source_hash, dest_hash }) if settle_mismatch(&job) == Some(false) => {
+			// The intent keeps whatever the destination holds
+			let kept = CopyResult { bytes_copied: job.size, source_hash, dest_hash, verified: false };
+			mark_complete(db, job_id, &kept).await?;
+			Ok(kept)
 		}
+		Err(err) => fail_job(db, job_id, &job, err).await,
 	}
 }
 
-fn resolution_options(error_kind: &str) -> Vec<String> {
-	match error_kind {
-		"source_missing" => vec!["skip".into(), "rescan".into()],
-		"permission_denied" => vec!["retry".into(), "skip".into()],
-		"disk_full" => vec!["retry".into(), "skip".into()],
-		"hash_mismatch" => vec!["retry".into(), "skip".into(), "accept".into()],
-		"io_error" => vec!["retry".into(), "skip".into()],
-		_ => vec!["skip".into()],
+/// Record a failed copy: back to `pending` for another attempt if the
+/// intent retries this kind of error and attempts remain, else
+/// `needs_review` with a review item. Returns `err`.
+async fn fail_job(db: &DbHandle, job_id: &RecordId, job: &JobData, err: CopyError) -> Result<CopyResult, CopyError> {
+	// A mismatch the policy settles for the source is copied again
+	let new_attempts = job.attempts + 1;
+	let retryable = err.is_retryable_for(&job.retryable_errors)
+		|| (matches!(err, CopyError::HashMismatch { .. }) && settle_mismatch(job) == Some(true));
+	let (new_status, error_kind) = if retryable && new_attempts < job.max_attempts {
+		("pending", classify_error(&err))
+	} else {
+		("needs_review", classify_error(&err))
+	};
+
+	// Retries wait out an exponential backoff instead of hammering a flaky mount
+	let next_retry_at = (new_status == "pending").then(|| {
+		let delay = copier::backoff_delay(job.attempts.max(0) as u32);
+		(chrono::Utc::now() + delay).to_rfc3339()
+	});
+
+	db.db
+		.query(
+			"UPDATE $id SET
+                status = $status,
+                attempts = $attempts,
+                last_error = $error,
+                error_kind = $error_kind,
+                next_retry_at = IF $next_retry_at { <datetime>$next_retry_at } ELSE { NONE }",
+		)
+		.bind(("id", job_id.clone()))
+		.bind(("status", new_status.to_string()))
+		.bind(("attempts", new_attempts))
+		.bind(("next_retry_at", next_retry_at))
+		.bind(("error", err.to_string()))
+		.bind(("error_kind", error_kind.to_string()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	// Create review item for non-retryable failures, with both sides
+	// as they are now so the card can compare them
+	if new_status == "needs_review" {
+		let (source_hash, dest_hash) = match &err {
+			CopyError::HashMismatch { source_hash, dest_hash } => (Some(source_hash.clone()), Some(dest_hash.clone())),
+			_ => (None, None),
+		};
+		// A remote destination can't be stat'ed from here
+		let dest = if job.dest_remote.is_some() || job.dest_misconfigured.is_some() {
+			FileSnapshot { hash: dest_hash, ..Default::default() }
+		} else {
+			FileSnapshot::stat(&decode_path(&job.dest_path), dest_hash)
+		};
+		let item = NewReviewItem {
+			job: job_id,
+			intent: job.intent.clone(),
+			error_kind,
+			message: err.to_string(),
+			source_path: &job.source_path,
+			dest_path: &job.dest_path,
+			source: FileSnapshot::stat(&decode_path(&job.source_path), source_hash),
+			dest,
+		};
+		let _ = create_review_item(db, item).await;
 	}
+
+	Err(err)
 }
 
-fn classify_error(err: &CopyError) -> &'static str {
-	match err {
-		CopyError::SourceNotFound(_) => "source_missing",
-		CopyError::PermissionDenied(_) => "permission_denied",
-		CopyError::DiskFull(_) => "disk_full",
-		CopyError::HashMismatch { .. } => "hash_mismatch",
-		CopyError::IoError(_) => "io_error",
-		CopyError::JobNotFound(_) | CopyError::DbError(_) => "internal",
-	}
+/// Why a running copy should stop before it finishes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Halt {
+	/// The intent was paused or lost a drive; the job resumes later.
+	Pause,
+	/// The job or its intent was cancelled or deleted.
+	Cancel,
 }
 
-async fn load_job(db: &DbHandle, job_id: &RecordId) -> Result<JobData, CopyError> {
+/// Whether the job's copy should stop, from its own and its intent's status.
+async fn halt_reason(db: &DbHandle, job_id: &RecordId) -> Result<Option<Halt>, CopyError> {
 	let mut response = db
 		.db
-		.query("SELECT intent, source_path, dest_path, attempts, max_attempts FROM $id")
+		.query("SELECT status, intent.status AS intent_status FROM $id")
 		.bind(("id", job_id.clone()))
 		.await
 		.map_err(|e| CopyError::DbError(e.to_string()))?;
-
 	let row: Option<serde_json::Value> = response
 		.take(0)
 		.map_err(|e| CopyError::DbError(e.to_string()))?;
 
-	let row = row.ok_or_else(|| CopyError::JobNotFound(format!("{:?}", job_id)))?;
-
-	Ok(JobData {
-		intent: row["intent"].clone(),
-		source_path: row["source_path"].as_str().unwrap_or_default().to_string(),
-		dest_path: row["dest_path"].as_str().unwrap_or_default().to_string(),
-		attempts: row["attempts"].as_i64().unwrap_or(0),
-		max_attempts: row["max_attempts"].as_i64().unwrap_or(3),
+	let Some(row) = row else {
+		return Ok(Some(Halt::Cancel));
+	};
+	if row["status"].as_str() == Some("cancelled") {
+		return Ok(Some(Halt::Cancel));
+	}
+	Ok(match row["intent_status"].as_str() {
+		Some("paused" | "waiting_for_device") => Some(Halt::Pause),
+		Some("cancelled" | "error") | None => Some(Halt::Cancel),
+		Some(_) => None,
 	})
 }
 
-/// Core copy pipeline: read source → hash → write dest → verify.
-/// This is synchronous and should run on spawn_blocking.
-fn copy_and_hash(
-	source_path: &str,
-	dest_path: &str,
-	db: &DbHandle,
-	job_id: &RecordId,
-) -> Result<CopyResult, CopyError> {
-	// Create destination parent directories
-	if let Some(parent) = Path::new(dest_path).parent() {
-		fs::create_dir_all(parent).map_err(|e| map_io_error(e, dest_path))?;
+/// Raises a copy's cancel flag once `halt_reason` says to stop. Stops
+/// polling when dropped.
+struct HaltWatch(tokio::task::JoinHandle<()>);
+
+impl HaltWatch {
+	fn spawn(db: DbHandle, job_id: RecordId, cancel: Arc<AtomicBool>) -> Self {
+		Self(tokio::spawn(async move {
+			let mut ticker = tokio::time::interval(HALT_POLL_INTERVAL);
+			loop {
+				ticker.tick().await;
+				// A failed check just waits for the next tick
+				if let Ok(Some(_)) = halt_reason(&db, &job_id).await {
+					cancel.store(true, Ordering::Relaxed);
+					break;
+				}
+			}
+		}))
 	}
+}
 
-	// Open source
-	let mut source = fs::File::open(source_path).map_err(|e| map_io_error(e, source_path))?;
-
-	// Open dest (create/truncate)
-	let mut dest = fs::File::create(dest_path).map_err(|e| map_io_error(e, dest_path))?;
-
-	// Single-pass: read → hash → write
-	let mut hasher = blake3::Hasher::new();
-	let mut buf = vec![0u8; CHUNK_SIZE];
-	let mut bytes_copied: u64 = 0;
-	let mut chunks_since_progress = 0usize;
+impl Drop for HaltWatch {
+	fn drop(&mut self) {
+		self.0.abort();
+	}
+}
 
-	loop {
-		let n = source
-			.read(&mut buf)
-			.map_err(|e| map_io_error(e, source_path))?;
-		if n == 0 {
-			break;
+/// Settle a job whose copy was stopped mid-file after `copied` bytes. A
+/// paused job goes back to `pending` with its partial file kept, and resumes
+/// from it; a cancelled one has its partial file removed.
+async fn stop_job(db: &DbHandle, job_id: &RecordId, job: &JobData, copied: u64) -> Result<(), CopyError> {
+	match halt_reason(db, job_id).await? {
+		Some(Halt::Cancel) => {
+			// Remote partials are left for the next copy over them to truncate
+			if job.dest_remote.is_none() {
+				let _ = std::fs::remove_file(decode_path(&job.dest_path));
+			}
+			db.db
+				.query("UPDATE $id SET status = 'cancelled', bytes_transferred = 0")
+				.bind(("id", job_id.clone()))
+				.await
+				.map_err(|e| CopyError::DbError(e.to_string()))?;
 		}
+		Some(Halt::Pause) | None => checkpoint(db, job_id, copied).await?,
+	}
+	tracing::info!("stopped copying {} mid-file", job.dest_path);
+	Ok(())
+}
 
-		hasher.update(&buf[..n]);
-		dest.write_all(&buf[..n])
-			.map_err(|e| map_io_error(e, dest_path))?;
+/// Put a stopped job back to `pending`, recording how far its copy got so
+/// the next attempt resumes from there.
+pub(crate) async fn checkpoint(db: &DbHandle, job_id: &RecordId, copied: u64) -> Result<(), CopyError> {
+	db.db
+		.query("UPDATE $id SET status = 'pending', bytes_transferred = $bytes WHERE status = 'transferring'")
+		.bind(("id", job_id.clone()))
+		.bind(("bytes", copied as i64))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	Ok(())
+}
 
-		bytes_copied += n as u64;
-		chunks_since_progress += 1;
+/// One side of a failed copy, as shown in the review queue.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub(crate) struct FileSnapshot {
+	pub size: Option<u64>,
+	pub hash: Option<String>,
+	pub modified: Option<SystemTime>,
+}
 
-		if chunks_since_progress >= PROGRESS_INTERVAL {
-			chunks_since_progress = 0;
-			update_progress(db, job_id, bytes_copied);
+impl FileSnapshot {
+	/// Stat `path` now. A missing file leaves size and mtime empty.
+	pub(crate) fn stat(path: &Path, hash: Option<String>) -> Self {
+		let metadata = std::fs::metadata(path).ok();
+		Self {
+			size: metadata.as_ref().map(|m| m.len()),
+			hash,
+			modified: metadata.and_then(|m| m.modified().ok()),
 		}
 	}
+}
 
-	dest.flush().map_err(|e| map_io_error(e, dest_path))?;
-	drop(dest);
+/// A review item to file for a job that needs a human.
+pub(crate) struct NewReviewItem<'a> {
+	pub job: &'a RecordId,
+	pub intent: serde_json::Value,
+	pub error_kind: &'a str,
+	pub message: String,
+	pub source_path: &'a str,
+	pub dest_path: &'a str,
+	pub source: FileSnapshot,
+	pub dest: FileSnapshot,
+}
 
-	let source_hash = hasher.finalize().to_hex().to_string();
+/// File a review item for a job, offering the resolutions that fit `error_kind`.
+pub(crate) async fn create_review_item(db: &DbHandle, item: NewReviewItem<'_>) -> Result<(), CopyError> {
+	db.db
+		.query(
+			"CREATE review_item CONTENT {
+                job: $job_id,
+                intent: $intent_id,
+                error_kind: $error_kind,
+                error_message: $error_msg,
+                source_path: $source_path,
+                dest_path: $dest_path,
+                options: $options,
+                source_size: $source_size,
+                source_hash: $source_hash,
+                source_modified: IF $source_modified { <datetime>$source_modified } ELSE { NONE },
+                dest_size: $dest_size,
+                dest_hash: $dest_hash,
+                dest_modified: IF $dest_modified { <datetime>$dest_modified } ELSE { NONE },
+                created_at: time::now(),
+            }",
+		)
+		.bind(("job_id", item.job.clone()))
+		.bind(("intent_id", item.intent))
+		.bind(("error_kind", item.error_kind.to_string()))
+		.bind(("error_msg", item.message))
+		.bind(("source_path", item.source_path.to_string()))
+		.bind(("dest_path", item.dest_path.to_string()))
+		.bind(("options", resolution_options(item.error_kind)))
+		.bind(("source_size", item.source.size.map(|s| s as i64)))
+		.bind(("source_hash", item.source.hash))
+		.bind(("source_modified", item.source.modified.map(rfc3339)))
+		.bind(("dest_size", item.dest.size.map(|s| s as i64)))
+		.bind(("dest_hash", item.dest.hash))
+		.bind(("dest_modified", item.dest.modified.map(rfc3339)))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
 
-	// Verify: re-read dest, compute hash
-	let dest_hash = hash_file(dest_path)?;
+	Ok(())
+}
 
-	let verified = source_hash == dest_hash;
-	if !verified {
-		return Err(CopyError::HashMismatch { source_hash, dest_hash });
-	}
+/// A file time in the form SurrealDB's `<datetime>` cast accepts.
+pub(crate) fn rfc3339(time: SystemTime) -> String {
+	chrono::DateTime::<chrono::Utc>::from(time).to_rfc3339()
+}
 
-	Ok(CopyResult { bytes_copied, source_hash, dest_hash, verified })
+/// What `try_dedup` could do with a recorded copy of the source's content.
+enum Reuse {
+	/// The job's own dest path already holds it.
+	InPlace,
+	/// Another copy in the location was cloned into place.
+	Cloned(CopyResult),
+	/// The recorded file no longer holds the content.
+	Stale,
+	/// The content checks out but can't be cloned; copy the source instead.
+	Unavailable,
 }
 
-/// Hash a file using blake3 in 256KB chunks.
-pub fn hash_file(path: &str) -> Result<String, CopyError> {
-	let mut file = fs::File::open(path).map_err(|e| map_io_error(e, path))?;
-	let mut hasher = blake3::Hasher::new();
-	let mut buf = vec![0u8; CHUNK_SIZE];
+/// Look the source's content up in the destination's `exists_at` edges.
+///
+/// An edge is only a lead: the file it names may have been edited since,
+/// or never read back after it was written, so it's hashed again before
+/// anything is reused and marked stale if it no longer matches. If the
+/// job's own dest path holds the content, nothing is written; if another
+/// path in the location does, it's reflinked into place, so the two stay
+/// independent copies. Returns `None` when the content has to be copied
+/// (or the source is unreadable — the copy reports that).
+async fn try_dedup(db: &DbHandle, job: &JobData) -> Result<Option<CopyResult>, CopyError> {
+	let Some(location) = &job.destination else {
+		return Ok(None);
+	};
+	// A partial copy is already underway; resuming beats re-hashing. Remote
+	// destinations can't be cloned into from here.
+	if job.bytes_transferred > 0 || job.dest_remote.is_some() {
+		return Ok(None);
+	}
 
-	loop {
-		let n = file.read(&mut buf).map_err(|e| map_io_error(e, path))?;
-		if n == 0 {
-			break;
+	let source = decode_path(&job.source_path);
+	let hashed = tokio::task::spawn_blocking(move || {
+		let size = std::fs::metadata(&source).ok()?.len();
+		let hash = copier::hash_file(&source, HashAlgorithm::Blake3).ok()?;
+		Some((hash, size))
+	})
+	.await
+	.map_err(|e| CopyError::IoError(format!("task join error: {e}")))?;
+	let Some((hash, size)) = hashed else {
+		return Ok(None);
+	};
+
+	let Some(existing) = dedup::find_existing(db, &hash, size, location).await? else {
+		return Ok(None);
+	};
+
+	let dest = decode_path(&job.dest_path);
+	let existing_path = decode_path(&existing);
+	let expected = hash.clone();
+	let source_modified = job.source_modified;
+	let reuse = tokio::task::spawn_blocking(move || {
+		let holds_content = |path: &Path| {
+			std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() == size)
+				&& copier::hash_file(path, HashAlgorithm::Blake3).is_ok_and(|h| h == expected)
+		};
+		if existing_path == dest {
+			return if holds_content(&dest) { Reuse::InPlace } else { Reuse::Stale };
 		}
-		hasher.update(&buf[..n]);
+		if !std::fs::metadata(&existing_path).is_ok_and(|m| m.is_file() && m.len() == size) {
+			return Reuse::Stale;
+		}
+		// A clone replaces whatever is at dest, which is for the overwrite
+		// policy to decide
+		if dest.exists() {
+			return Reuse::Unavailable;
+		}
+		let options = CopyOptions {
+			verify: VerifyMode::Hash,
+			source_modified,
+			strategy: CopyStrategy::Reflink,
+			..Default::default()
+		};
+		match copier::link_or_clone(&existing_path, &dest, &options) {
+			// The clone holds exactly the blocks just hashed
+			Ok(Some(result)) if result.source_hash == expected => Reuse::Cloned(result),
+			Ok(Some(_)) => {
+				let _ = std::fs::remove_file(&dest);
+				Reuse::Stale
+			}
+			Ok(None) | Err(_) => Reuse::Unavailable,
+		}
+	})
+	.await
+	.map_err(|e| CopyError::IoError(format!("task join error: {e}")))?;
+
+	match reuse {
+		Reuse::InPlace => {
+			tracing::info!("already at destination, skipping copy: {}", job.dest_path);
+			Ok(Some(CopyResult { bytes_copied: 0, dest_hash: hash.clone(), source_hash: hash, verified: true }))
+		}
+		Reuse::Cloned(result) => {
+			tracing::info!("cloned {} from existing copy {}", job.dest_path, existing);
+			dedup::record_file_at(db, &hash, size, location, &job.dest_path).await?;
+			Ok(Some(CopyResult { bytes_copied: 0, ..result }))
+		}
+		Reuse::Stale => {
+			tracing::info!("recorded copy {} no longer matches, copying instead", existing);
+			dedup::mark_stale(db, location, &existing).await?;
+			Ok(None)
+		}
+		Reuse::Unavailable => Ok(None),
 	}
+}
 
-	Ok(hasher.finalize().to_hex().to_string())
+async fn mark_complete(db: &DbHandle, job_id: &RecordId, result: &CopyResult) -> Result<(), CopyError> {
+	db.db
+		.query(
+			"UPDATE $id SET
+                status = 'complete',
+                source_hash = $source_hash,
+                dest_hash = $dest_hash,
+                bytes_transferred = $bytes,
+                completed_at = time::now()",
+		)
+		.bind(("id", job_id.clone()))
+		.bind(("source_hash", result.source_hash.clone()))
+		.bind(("dest_hash", result.dest_hash.clone()))
+		.bind(("bytes", result.bytes_copied as i64))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?
+		.check()
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	Ok(())
 }
 
-fn map_io_error(err: io::Error, path: &str) -> CopyError {
-	match err.kind() {
-		io::ErrorKind::NotFound => CopyError::SourceNotFound(path.to_string()),
-		io::ErrorKind::PermissionDenied => CopyError::PermissionDenied(path.to_string()),
-		io::ErrorKind::StorageFull => CopyError::DiskFull(path.to_string()),
-		_ => CopyError::IoError(format!("{path}: {err}")),
+/// Apply the intent's `overwrite_policy` to a destination that already holds
+/// a different file. A file it keeps is skipped, or filed for review with
+/// both sides' size, hash and mtime, and `CopyError::DestinationExists` is
+/// returned. Resumed copies are our own partial files, and a remote
+/// destination can't be stat'ed from here, so both go ahead.
+async fn guard_overwrite(db: &DbHandle, job_id: &RecordId, job: &JobData) -> Result<(), CopyError> {
+	if job.overwrite_policy == OverwritePolicy::Always
+		|| job.overwrite_approved
+		|| job.bytes_transferred > 0
+		|| job.dest_remote.is_some()
+	{
+		return Ok(());
 	}
-}
 
-/// Fire-and-forget progress update. Errors are silently ignored
-/// (progress is best-effort, not critical).
-fn update_progress(db: &DbHandle, job_id: &RecordId, bytes: u64) {
-	let db = db.clone();
-	let job_id = job_id.clone();
-	tokio::task::block_in_place(move || {
-		tokio::runtime::Handle::current().block_on(async {
-			let _ = db
-				.db
-				.query("UPDATE $id SET bytes_transferred = $bytes")
-				.bind(("id", job_id))
-				.bind(("bytes", bytes as i64))
-				.await;
-		});
-	});
+	let (source, dest) = (decode_path(&job.source_path), decode_path(&job.dest_path));
+	let hash_always = job.overwrite_policy == OverwritePolicy::Review;
+	let existing = tokio::task::spawn_blocking(move || differing_dest(&source, &dest, hash_always))
+		.await
+		.map_err(|e| CopyError::IoError(format!("task join error: {e}")))?;
+	let Some((source, dest)) = existing else {
+		return Ok(());
+	};
+
+	let err = CopyError::DestinationExists(job.dest_path.clone());
+	match job.overwrite_policy.overwrites(source.modified, dest.modified) {
+		Some(true) => return Ok(()),
+		Some(false) => {
+			db.db
+				.query("UPDATE $id SET status = 'skipped', last_error = $error, error_kind = 'conflict'")
+				.bind(("id", job_id.clone()))
+				.bind(("error", format!("{err}; left as it is")))
+				.await
+				.map_err(|e| CopyError::DbError(e.to_string()))?
+				.check()
+				.map_err(|e| CopyError::DbError(e.to_string()))?;
+			tracing::info!("kept existing destination, skipping copy: {}", job.dest_path);
+		}
+		None => {
+			db.db
+				.query("UPDATE $id SET status = 'needs_review', last_error = $error, error_kind = 'conflict'")
+				.bind(("id", job_id.clone()))
+				.bind(("error", err.to_string()))
+				.await
+				.map_err(|e| CopyError::DbError(e.to_string()))?
+				.check()
+				.map_err(|e| CopyError::DbError(e.to_string()))?;
+			let item = NewReviewItem {
+				job: job_id,
+				intent: job.intent.clone(),
+				error_kind: "conflict",
+				message: err.to_string(),
+				source_path: &job.source_path,
+				dest_path: &job.dest_path,
+				source,
+				dest,
+			};
+			create_review_item(db, item).await?;
+		}
+	}
+	Err(err)
 }
 
-#[cfg(test)]
-mod tests {
-	use super::*;
-
-	#[test]
-	fn copy_and_verify_small_file() {
-		let tmp = tempfile::tempdir().unwrap();
-		let src = tmp.path().join("source.txt");
-		let dst = tmp.path().join("dest.txt");
-		fs::write(&src, "hello world").unwrap();
-
-		// Copy manually using the same pipeline as copy_and_hash
-		let mut source = fs::File::open(&src).unwrap();
-		let mut dest = fs::File::create(&dst).unwrap();
-		let mut hasher = blake3::Hasher::new();
-		let mut buf = vec![0u8; CHUNK_SIZE];
-		loop {
-			let n = source.read(&mut buf).unwrap();
-			if n == 0 {
-				break;
-			}
-			hasher.update(&buf[..n]);
-			dest.write_all(&buf[..n]).unwrap();
+/// Both sides of a copy whose destination is already a file that differs
+/// from the source, or `None` if there's nothing there or it's identical.
+/// Files of the same size are told apart by hash; `hash_always` hashes the
+/// others too, for the review card.
+fn differing_dest(source: &Path, dest: &Path, hash_always: bool) -> Option<(FileSnapshot, FileSnapshot)> {
+	if !std::fs::metadata(dest).is_ok_and(|m| m.is_file()) {
+		return None;
+	}
+	let (mut source_side, mut dest_side) = (FileSnapshot::stat(source, None), FileSnapshot::stat(dest, None));
+	let same_size = source_side.size == dest_side.size;
+	if same_size || hash_always {
+		source_side.hash = copier::hash_file(source, HashAlgorithm::Blake3).ok();
+		dest_side.hash = copier::hash_file(dest, HashAlgorithm::Blake3).ok();
+		if same_size && source_side.hash.is_some() && source_side.hash == dest_side.hash {
+			return None;
 		}
-		drop(dest);
-
-		let source_hash = hasher.finalize().to_hex().to_string();
-		let dest_hash = hash_file(dst.to_str().unwrap()).unwrap();
-
-		assert_eq!(source_hash, dest_hash);
-		assert_eq!(fs::read_to_string(&dst).unwrap(), "hello world");
 	}
+	Some((source_side, dest_side))
+}
 
-	#[test]
-	fn hash_empty_file() {
-		let tmp = tempfile::tempdir().unwrap();
-		let f = tmp.path().join("empty.txt");
-		fs::write(&f, "").unwrap();
+/// Which side the intent's `on_conflict` policy keeps when a copy's
+/// destination doesn't hash like its source: `Some(true)` copies again,
+/// `Some(false)` keeps the destination, `None` leaves it to review.
+fn settle_mismatch(job: &JobData) -> Option<bool> {
+	let modified = |path: &str| std::fs::metadata(decode_path(path)).and_then(|m| m.modified()).ok();
+	// A remote destination can't be stat'ed from here
+	let dest_modified = match job.dest_remote {
+		Some(_) => None,
+		None => modified(&job.dest_path),
+	};
+	job.on_conflict.source_wins(modified(&job.source_path), dest_modified)
+}
 
-		let hash = hash_file(f.to_str().unwrap()).unwrap();
-		assert_eq!(hash.len(), 64); // blake3 hex
+fn resolution_options(error_kind: &str) -> Vec<String> {
+	match error_kind {
+		"source_missing" => vec!["skip".into(), "rescan".into()],
+		"permission_denied" => vec!["retry".into(), "skip".into()],
+		"disk_full" => vec!["retry".into(), "skip".into()],
+		"hash_mismatch" | "verify_failed" => vec!["retry".into(), "skip".into(), "accept".into()],
+		// Overwrite the destination, or leave it as it is
+		"conflict" => vec!["keep_source".into(), "skip".into()],
+		"io_error" => vec!["retry".into(), "skip".into()],
+		// Retry once the machine's settings are fixed
+		"remote_misconfigured" => vec!["retry".into(), "skip".into()],
+		"file_too_large" | "unsupported_file_type" => vec!["skip".into()],
+		_ => vec!["skip".into()],
 	}
+}
 
-	#[test]
-	fn hash_multichunk_file() {
-		let tmp = tempfile::tempdir().unwrap();
-		let f = tmp.path().join("big.bin");
-		let data = vec![42u8; CHUNK_SIZE * 3 + 1000];
-		fs::write(&f, &data).unwrap();
-
-		let hash = hash_file(f.to_str().unwrap()).unwrap();
+async fn load_job(db: &DbHandle, job_id: &RecordId) -> Result<JobData, CopyError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT intent, destination, source_path, dest_path, size, attempts, max_attempts, bytes_transferred,
+                    source_mtime_ns, intent.retryable_errors AS retryable_errors,
+                    intent.verify_mode AS verify_mode,
+                    intent.speed_mode AS speed_mode,
+                    intent.on_conflict AS on_conflict,
+                    intent.overwrite_policy AS overwrite_policy, overwrite_approved,
+                    intent.copy_strategy AS copy_strategy,
+                    destination.machine.kind AS dest_kind,
+                    destination.machine.hostname AS dest_hostname,
+                    destination.machine.ssh_user AS dest_ssh_user,
+                    destination.machine.ssh_key_path AS dest_ssh_key_path,
+                    destination.machine.ssh_proxy AS dest_ssh_proxy
+             FROM $id",
+		)
+		.bind(("id", job_id.clone()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
 
-		// Must match blake3 computed in one shot
-		let expected = blake3::hash(&data).to_hex().to_string();
-		assert_eq!(hash, expected);
-	}
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
 
-	#[test]
-	fn hash_file_not_found() {
-		let err = hash_file("/tmp/kip_definitely_not_real.txt").unwrap_err();
-		assert!(matches!(err, CopyError::SourceNotFound(_)));
-	}
+	let row = row.ok_or_else(|| CopyError::JobNotFound(format!("{:?}", job_id)))?;
+	let (dest_remote, dest_misconfigured) = match RemoteMachine::from_row(&row, "dest") {
+		Ok(machine) => (machine, None),
+		Err(why) => (None, Some(why)),
+	};
 
-	#[test]
-	fn error_classification() {
-		assert!(CopyError::IoError("tmp".into()).is_retryable());
-		assert!(!CopyError::SourceNotFound("x".into()).is_retryable());
-		assert!(!CopyError::PermissionDenied("x".into()).is_retryable());
-		assert!(!CopyError::DiskFull("x".into()).is_retryable());
-		assert!(!CopyError::HashMismatch { source_hash: "a".into(), dest_hash: "b".into() }.is_retryable());
-	}
+	Ok(JobData {
+		intent: row["intent"].clone(),
+		destination: serde_json::from_value(row["destination"].clone()).ok(),
+		source_path: row["source_path"].as_str().unwrap_or_default().to_string(),
+		dest_path: row["dest_path"].as_str().unwrap_or_default().to_string(),
+		size: row["size"].as_u64().unwrap_or(0),
+		attempts: row["attempts"].as_i64().unwrap_or(0),
+		max_attempts: row["max_attempts"].as_i64().unwrap_or(3),
+		bytes_transferred: row["bytes_transferred"].as_u64().unwrap_or(0),
+		retryable_errors: serde_json::from_value(row["retryable_errors"].clone())
+			.unwrap_or_else(|_| DEFAULT_RETRYABLE_ERRORS.iter().map(|s| s.to_string()).collect()),
+		source_modified: row["source_mtime_ns"]
+			.as_u64()
+			.map(|ns| SystemTime::UNIX_EPOCH + Duration::from_nanos(ns)),
+		verify_mode: serde_json::from_value(row["verify_mode"].clone()).unwrap_or_default(),
+		speed_mode: serde_json::from_value(row["speed_mode"].clone()).unwrap_or_default(),
+		on_conflict: serde_json::from_value(row["on_conflict"].clone()).unwrap_or_default(),
+		overwrite_policy: serde_json::from_value(row["overwrite_policy"].clone()).unwrap_or_default(),
+		overwrite_approved: row["overwrite_approved"].as_bool().unwrap_or(false),
+		copy_strategy: serde_json::from_value(row["copy_strategy"].clone()).unwrap_or_default(),
+		dest_remote,
+		dest_misconfigured,
+	})
 }
@@ -0,0 +1,490 @@
+//! Where a copy reads from and writes to.
+//!
+//! The copier's read → hash → write → verify pipeline is written against
+//! `FileSystem`, so the same code copies between local paths (`LocalFs`) and
+//! onto a remote machine over SFTP (`SftpFs`). Remote connections use the
+//! machine record's `hostname`, `ssh_user`, `ssh_key_path` and `ssh_proxy`,
+//! and only trust hosts already in `~/.ssh/known_hosts`. One session per
+//! machine is kept open and shared by its copies, so each file doesn't pay
+//! for its own handshake.
+//!
+//! Only destinations can be remote: Kip copies *to* other machines, not
+//! from them. A source is walked by the scanner and stat'ed by the dedup,
+//! overlap and overwrite checks, all of which read this machine's disk;
+//! copying from a remote would need those done over SFTP first. An intent
+//! with a remote source is refused when it's created, and a scan of one
+//! fails with `ScanError::RemoteUnsupported`.
+
+use std::{
+	collections::HashMap,
+	fs,
+	io::{self, Read, Seek, Write},
+	net::TcpStream,
+	path::{Path, PathBuf},
+	sync::{Arc, LazyLock, Mutex},
+	time::{Duration, SystemTime},
+};
+
+use filetime::FileTime;
+use ssh2::{CheckResult, KnownHostFileKind, OpenFlags, OpenType, Session, Sftp};
+
+/// How long a remote operation may block before it fails as an I/O error.
+const SSH_TIMEOUT: Duration = Duration::from_secs(30);
+
+const DEFAULT_SSH_PORT: u16 = 22;
+
+/// Open sessions, one per machine, shared by every copy to it.
+static SESSIONS: LazyLock<Mutex<HashMap<RemoteMachine, Arc<SftpFs>>>> = LazyLock::new(Default::default);
+
+/// What the copier needs to know about an existing file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileStat {
+	pub len: u64,
+	/// A regular file, as opposed to a directory, FIFO, socket or device.
+	pub is_file: bool,
+	/// Permission bits, where the filesystem has them.
+	pub mode: Option<u32>,
+	pub modified: Option<SystemTime>,
+}
+
+/// File operations the copy pipeline runs on either end of a transfer.
+pub trait FileSystem {
+	type File: Read + Write + Seek;
+
+	/// Open for reading.
+	fn open(&self, path: &Path) -> io::Result<Self::File>;
+	/// Create or truncate for writing.
+	fn create(&self, path: &Path) -> io::Result<Self::File>;
+	/// Open an existing file for reading and writing, to resume into it.
+	fn open_rw(&self, path: &Path) -> io::Result<Self::File>;
+	fn set_len(&self, file: &mut Self::File, len: u64) -> io::Result<()>;
+	fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+	fn stat(&self, path: &Path) -> io::Result<FileStat>;
+	/// Apply permission bits (if given) and the modification time.
+	fn set_metadata(&self, path: &Path, mode: Option<u32>, modified: Option<SystemTime>) -> io::Result<()>;
+	/// Push a written file through to stable storage.
+	fn sync(&self, file: &mut Self::File) -> io::Result<()>;
+	/// Whether a write that synced cleanly can be taken to hold exactly the
+	/// bytes written, so verifying needn't read it back.
+	fn trusts_writes(&self) -> bool;
+}
+
+// ─── Local ────────────────────────────────────────────────────
+
+/// This machine's filesystem.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFs;
+
+impl FileSystem for LocalFs {
+	type File = fs::File;
+
+	fn open(&self, path: &Path) -> io::Result<fs::File> {
+		fs::File::open(path)
+	}
+
+	fn create(&self, path: &Path) -> io::Result<fs::File> {
+		fs::File::create(path)
+	}
+
+	fn open_rw(&self, path: &Path) -> io::Result<fs::File> {
+		fs::OpenOptions::new().read(true).write(true).open(path)
+	}
+
+	fn set_len(&self, file: &mut fs::File, len: u64) -> io::Result<()> {
+		file.set_len(len)
+	}
+
+	fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+		fs::create_dir_all(path)
+	}
+
+	fn stat(&self, path: &Path) -> io::Result<FileStat> {
+		let meta = fs::metadata(path)?;
+		#[cfg(unix)]
+		let mode = {
+			use std::os::unix::fs::PermissionsExt;
+			Some(meta.permissions().mode())
+		};
+		#[cfg(not(unix))]
+		let mode = Some(if meta.permissions().readonly() { 0o444 } else { 0o644 });
+		Ok(FileStat { len: meta.len(), is_file: meta.is_file(), mode, modified: meta.modified().ok() })
+	}
+
+	fn set_metadata(&self, path: &Path, mode: Option<u32>, modified: Option<SystemTime>) -> io::Result<()> {
+		if let Some(mode) = mode {
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::PermissionsExt;
+				fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+			}
+			#[cfg(not(unix))]
+			{
+				let mut perms = fs::metadata(path)?.permissions();
+				perms.set_readonly(mode & 0o200 == 0);
+				fs::set_permissions(path, perms)?;
+			}
+		}
+		if let Some(time) = modified {
+			filetime::set_file_mtime(path, FileTime::from_system_time(time))?;
+		}
+		Ok(())
+	}
+
+	fn sync(&self, file: &mut fs::File) -> io::Result<()> {
+		file.sync_all()
+	}
+
+	fn trusts_writes(&self) -> bool {
+		true
+	}
+}
+
+// ─── SFTP ─────────────────────────────────────────────────────
+
+/// Connection settings of a `kind: 'remote'` machine.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RemoteMachine {
+	/// `host` or `host:port`.
+	pub hostname: String,
+	pub user: String,
+	pub key_path: Option<String>,
+	/// Jump host passed to `ssh -W`, e.g. `me@bastion`.
+	pub proxy: Option<String>,
+}
+
+impl RemoteMachine {
+	/// Read the machine fields a query selected as `<prefix>_kind`,
+	/// `<prefix>_hostname`, ... Returns `None` unless the machine is remote.
+	/// A remote machine with no hostname is an error: there's nowhere to
+	/// connect to, and taking it for this machine would write to local disk.
+	/// Without an `ssh_user`, the local user name is used, as `ssh` does.
+	pub(crate) fn from_row(row: &serde_json::Value, prefix: &str) -> Result<Option<Self>, String> {
+		Self::from_row_or_user(row, prefix, local_user())
+	}
+
+	/// `from_row`, falling back to `default_user` rather than the local user.
+	fn from_row_or_user(
+		row: &serde_json::Value,
+		prefix: &str,
+		default_user: Option<String>,
+	) -> Result<Option<Self>, String> {
+		let field = |name: &str| {
+			row[format!("{prefix}_{name}")]
+				.as_str()
+				.map(str::trim)
+				.filter(|s| !s.is_empty())
+				.map(String::from)
+		};
+		if field("kind").as_deref() != Some("remote") {
+			return Ok(None);
+		}
+		let hostname = field("hostname").ok_or("remote machine has no hostname")?;
+		let user = field("ssh_user")
+			.or(default_user)
+			.ok_or_else(|| format!("no ssh user set for {hostname}, and no local user name to default to"))?;
+		Ok(Some(RemoteMachine { hostname, user, key_path: field("ssh_key_path"), proxy: field("ssh_proxy") }))
+	}
+
+	fn host_and_port(&self) -> (&str, u16) {
+		host_and_port(&self.hostname)
+	}
+
+	/// The address to check when probing whether the machine is up: its own,
+	/// or for one behind a jump host the jump host's, the only one we dial.
+	pub(crate) fn probe_address(&self) -> (&str, u16) {
+		match &self.proxy {
+			Some(proxy) => host_and_port(proxy.rsplit_once('@').map_or(proxy.as_str(), |(_, host)| host)),
+			None => self.host_and_port(),
+		}
+	}
+}
+
+/// The name this process runs as, which `ssh` logs in with by default.
+fn local_user() -> Option<String> {
+	["USER", "USERNAME", "LOGNAME"]
+		.iter()
+		.find_map(|var| std::env::var(var).ok().filter(|name| !name.is_empty()))
+}
+
+/// Split `host:port`, defaulting to the SSH port.
+fn host_and_port(address: &str) -> (&str, u16) {
+	match address.rsplit_once(':') {
+		Some((host, port)) => match port.parse() {
+			Ok(port) => (host, port),
+			Err(_) => (address, DEFAULT_SSH_PORT),
+		},
+		None => (address, DEFAULT_SSH_PORT),
+	}
+}
+
+/// A remote machine's filesystem over one SFTP session.
+pub struct SftpFs {
+	// The channel lives inside the session; keep it open as long as `sftp`
+	_session: Session,
+	sftp: Sftp,
+}
+
+impl SftpFs {
+	/// Connect, check the host key against `~/.ssh/known_hosts`, and log in
+	/// with the machine's key file, or the SSH agent if it has none.
+	pub fn connect(machine: &RemoteMachine) -> io::Result<Self> {
+		let (host, port) = machine.host_and_port();
+
+		let mut session = Session::new()?;
+		match &machine.proxy {
+			Some(proxy) => session.set_tcp_stream(proxy_stream(proxy, host, port)?),
+			None => session.set_tcp_stream(TcpStream::connect((host, port))?),
+		}
+		session.set_timeout(SSH_TIMEOUT.as_millis() as u32);
+		session.handshake()?;
+		verify_host_key(&session, host, port)?;
+
+		match &machine.key_path {
+			Some(key) => session.userauth_pubkey_file(&machine.user, None, &expand_home(key), None)?,
+			None => session.userauth_agent(&machine.user)?,
+		}
+		if !session.authenticated() {
+			return Err(io::Error::new(
+				io::ErrorKind::PermissionDenied,
+				format!("ssh login as {} on {} failed", machine.user, machine.hostname),
+			));
+		}
+
+		let sftp = session.sftp()?;
+		Ok(SftpFs { _session: session, sftp })
+	}
+
+	/// The open session to `machine`, connecting if there isn't one yet.
+	pub fn shared(machine: &RemoteMachine) -> io::Result<Arc<SftpFs>> {
+		if let Some(remote) = SESSIONS.lock().unwrap().get(machine) {
+			return Ok(remote.clone());
+		}
+		// Connected outside the lock so a slow host doesn't hold up the others
+		let remote = Arc::new(SftpFs::connect(machine)?);
+		Ok(SESSIONS.lock().unwrap().entry(machine.clone()).or_insert(remote).clone())
+	}
+
+	/// Drop the shared session to `machine`, so the next copy reconnects. For
+	/// after an I/O error that may have come from a dead connection.
+	pub fn forget(machine: &RemoteMachine) {
+		SESSIONS.lock().unwrap().remove(machine);
+	}
+}
+
+impl FileSystem for SftpFs {
+	type File = ssh2::File;
+
+	fn open(&self, path: &Path) -> io::Result<ssh2::File> {
+		Ok(self.sftp.open(path)?)
+	}
+
+	fn create(&self, path: &Path) -> io::Result<ssh2::File> {
+		Ok(self.sftp.create(path)?)
+	}
+
+	fn open_rw(&self, path: &Path) -> io::Result<ssh2::File> {
+		Ok(self.sftp.open_mode(path, OpenFlags::READ | OpenFlags::WRITE, 0o644, OpenType::File)?)
+	}
+
+	fn set_len(&self, file: &mut ssh2::File, len: u64) -> io::Result<()> {
+		Ok(file.setstat(sftp_stat(Some(len), None, None))?)
+	}
+
+	fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+		// Create missing ancestors from the top down
+		let missing: Vec<&Path> = path
+			.ancestors()
+			.take_while(|p| !p.as_os_str().is_empty() && self.sftp.stat(p).is_err())
+			.collect();
+		for dir in missing.into_iter().rev() {
+			self.sftp.mkdir(dir, 0o755)?;
+		}
+		Ok(())
+	}
+
+	fn stat(&self, path: &Path) -> io::Result<FileStat> {
+		let stat = self.sftp.stat(path)?;
+		Ok(FileStat {
+			len: stat.size.unwrap_or(0),
+			is_file: stat.is_file(),
+			mode: stat.perm.map(|p| p & 0o7777),
+			modified: stat.mtime.map(|s| SystemTime::UNIX_EPOCH + Duration::from_secs(s)),
+		})
+	}
+
+	fn set_metadata(&self, path: &Path, mode: Option<u32>, modified: Option<SystemTime>) -> io::Result<()> {
+		let secs = modified
+			.and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+			.map(|d| d.as_secs());
+		Ok(self.sftp.setstat(path, sftp_stat(None, mode, secs))?)
+	}
+
+	fn sync(&self, file: &mut ssh2::File) -> io::Result<()> {
+		Ok(file.fsync()?)
+	}
+
+	/// The bytes crossed the network and a server we can't see wrote them.
+	fn trusts_writes(&self) -> bool {
+		false
+	}
+}
+
+fn sftp_stat(size: Option<u64>, perm: Option<u32>, mtime: Option<u64>) -> ssh2::FileStat {
+	// SFTP sets access and modification time together
+	ssh2::FileStat { size, uid: None, gid: None, perm, atime: mtime, mtime }
+}
+
+/// Refuse hosts that aren't in `~/.ssh/known_hosts` or whose key changed.
+/// Connecting once with plain `ssh` is how a machine gets trusted.
+fn verify_host_key(session: &Session, host: &str, port: u16) -> io::Result<()> {
+	let untrusted = |why: &str| io::Error::new(io::ErrorKind::PermissionDenied, format!("{host}: {why}"));
+
+	let mut known = session.known_hosts()?;
+	let file = expand_home("~/.ssh/known_hosts");
+	known
+		.read_file(&file, KnownHostFileKind::OpenSSH)
+		.map_err(|_| untrusted("no ~/.ssh/known_hosts; connect once with ssh to trust this host"))?;
+	let (key, _) = session.host_key().ok_or_else(|| untrusted("server sent no host key"))?;
+
+	match known.check_port(host, port, key) {
+		CheckResult::Match => Ok(()),
+		CheckResult::NotFound => Err(untrusted("host key not in known_hosts; connect once with ssh to trust it")),
+		CheckResult::Mismatch => Err(untrusted("host key changed since it was trusted")),
+		CheckResult::Failure => Err(untrusted("couldn't check the host key")),
+	}
+}
+
+/// Reach `host:port` through a jump host by running `ssh -W`, bridged to a
+/// socket the SSH session can own.
+#[cfg(unix)]
+fn proxy_stream(proxy: &str, host: &str, port: u16) -> io::Result<std::os::unix::net::UnixStream> {
+	use std::process::{Command, Stdio};
+
+	let (ours, theirs) = std::os::unix::net::UnixStream::pair()?;
+	let mut child = Command::new("ssh")
+		.args(["-o", "BatchMode=yes", "-W", &format!("{host}:{port}"), proxy])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()?;
+	let (Some(mut stdin), Some(mut stdout)) = (child.stdin.take(), child.stdout.take()) else {
+		return Err(io::Error::other("ssh -W started without pipes"));
+	};
+
+	// Both pumps end once the session drops its socket and ssh exits
+	let (mut to_proxy, mut from_proxy) = (theirs.try_clone()?, theirs);
+	std::thread::spawn(move || {
+		let _ = io::copy(&mut to_proxy, &mut stdin);
+	});
+	std::thread::spawn(move || {
+		let _ = io::copy(&mut stdout, &mut from_proxy);
+		let _ = child.wait();
+	});
+	Ok(ours)
+}
+
+#[cfg(not(unix))]
+fn proxy_stream(_proxy: &str, _host: &str, _port: u16) -> io::Result<TcpStream> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "jump hosts are not supported on this platform"))
+}
+
+fn expand_home(path: &str) -> PathBuf {
+	match (path.strip_prefix("~/"), dirs::home_dir()) {
+		(Some(rest), Some(home)) => home.join(rest),
+		_ => PathBuf::from(path),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn remote_machine_from_row() {
+		let row = serde_json::json!({
+			"dest_kind": "remote",
+			"dest_hostname": "nas.local:2222",
+			"dest_ssh_user": "",
+			"dest_ssh_proxy": "me@bastion",
+		});
+		let machine = RemoteMachine::from_row_or_user(&row, "dest", Some("alice".into())).unwrap().unwrap();
+		assert_eq!(machine.user, "alice");
+		assert_eq!(machine.key_path, None);
+		assert_eq!(machine.proxy.as_deref(), Some("me@bastion"));
+		assert_eq!(machine.host_and_port(), ("nas.local", 2222));
+
+		let local = serde_json::json!({ "dest_kind": "local", "dest_hostname": "laptop" });
+		assert_eq!(RemoteMachine::from_row(&local, "dest"), Ok(None));
+	}
+
+	#[test]
+	fn remote_machine_user_beats_the_default() {
+		let row = serde_json::json!({ "dest_kind": "remote", "dest_hostname": "nas", "dest_ssh_user": "backup" });
+		let machine = RemoteMachine::from_row_or_user(&row, "dest", Some("alice".into())).unwrap().unwrap();
+		assert_eq!(machine.user, "backup");
+
+		let no_user = serde_json::json!({ "dest_kind": "remote", "dest_hostname": "nas" });
+		assert!(RemoteMachine::from_row_or_user(&no_user, "dest", None).is_err());
+	}
+
+	#[test]
+	fn remote_machine_without_hostname_is_an_error() {
+		let row = serde_json::json!({ "dest_kind": "remote", "dest_hostname": " ", "dest_ssh_user": "me" });
+		assert!(RemoteMachine::from_row_or_user(&row, "dest", None).is_err());
+	}
+
+	/// The remote named by `KIP_TEST_SFTP` (`user@host[:port]`), whose host
+	/// key is in known_hosts and which accepts the SSH agent's key.
+	fn test_remote() -> RemoteMachine {
+		let target = std::env::var("KIP_TEST_SFTP").expect("set KIP_TEST_SFTP=user@host[:port]");
+		let (user, hostname) = target.split_once('@').expect("KIP_TEST_SFTP needs a user");
+		RemoteMachine { hostname: hostname.to_string(), user: user.to_string(), key_path: None, proxy: None }
+	}
+
+	#[test]
+	#[ignore = "needs an SSH server: KIP_TEST_SFTP=user@host cargo test -- --ignored"]
+	fn sftp_round_trips_a_file() {
+		let remote = SftpFs::shared(&test_remote()).unwrap();
+		let dir = PathBuf::from(format!("/tmp/kip-sftp-test-{}", std::process::id()));
+		let path = dir.join("nested/a.txt");
+		let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+		remote.create_dir_all(path.parent().unwrap()).unwrap();
+		let mut file = remote.create(&path).unwrap();
+		file.write_all(b"hello over sftp").unwrap();
+		remote.sync(&mut file).unwrap();
+		drop(file);
+		remote.set_metadata(&path, Some(0o600), Some(mtime)).unwrap();
+
+		let stat = remote.stat(&path).unwrap();
+		assert_eq!(stat.len, 15);
+		assert!(stat.is_file);
+		assert_eq!(stat.mode.map(|m| m & 0o777), Some(0o600));
+		assert_eq!(stat.modified, Some(mtime));
+
+		let mut file = remote.open_rw(&path).unwrap();
+		remote.set_len(&mut file, 5).unwrap();
+		let mut read = String::new();
+		remote.open(&path).unwrap().read_to_string(&mut read).unwrap();
+		assert_eq!(read, "hello");
+
+		// A second lookup reuses the session
+		assert!(Arc::ptr_eq(&remote, &SftpFs::shared(&test_remote()).unwrap()));
+	}
+
+	#[test]
+	fn local_stat_round_trips_metadata() {
+		let tmp = tempfile::tempdir().unwrap();
+		let path = tmp.path().join("a.txt");
+		fs::write(&path, "hello").unwrap();
+		let mtime = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+
+		LocalFs.set_metadata(&path, Some(0o600), Some(mtime)).unwrap();
+		let stat = LocalFs.stat(&path).unwrap();
+
+		assert_eq!(stat.len, 5);
+		assert_eq!(stat.modified, Some(mtime));
+		#[cfg(unix)]
+		assert_eq!(stat.mode.map(|m| m & 0o777), Some(0o600));
+	}
+}
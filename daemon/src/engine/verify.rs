@@ -0,0 +1,228 @@
+//! Re-checking a completed intent's destinations without copying.
+//!
+//! Every completed job recorded the hash its destination file had when it
+//! was written. Verifying re-hashes those files with whichever algorithm
+//! produced that hash, and files a `hash_mismatch`
+//! review item for each one that drifted or went missing, so bit rot and
+//! stray edits show up in review like any other failed copy.
+
+use surrealdb::types::RecordId;
+use tracing::info;
+
+use kip_core::util::decode_path;
+
+use crate::{
+	db::DbHandle,
+	engine::{
+		copier::{self, CopyError, HashAlgorithm},
+		transfer::{create_review_item, FileSnapshot, NewReviewItem},
+	},
+};
+
+/// Outcome of verifying one intent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VerifyResult {
+	pub checked: u64,
+	pub mismatched: u64,
+}
+
+/// Re-hash every completed job's destination and compare it to the stored
+/// `dest_hash`. Nothing is written to the destination. Returns an empty
+/// result if the intent isn't `complete`.
+pub async fn verify_intent(db: &DbHandle, intent_id: &RecordId) -> Result<VerifyResult, CopyError> {
+	let mut response = db
+		.db
+		.query(
+			"UPDATE $id SET status = 'verifying', updated_at = time::now()
+             WHERE status = 'complete' RETURN id",
+		)
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	let claimed: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	if claimed.is_empty() {
+		return Ok(VerifyResult::default());
+	}
+
+	let result = verify_jobs(db, intent_id).await;
+	let status = match &result {
+		Ok(r) if r.mismatched > 0 => "needs_review",
+		Ok(_) => "complete",
+		// Verification never touched the files, so the copy still stands
+		Err(_) => "complete",
+	};
+	db.db
+		.query("UPDATE $id SET status = $status, updated_at = time::now()")
+		.bind(("id", intent_id.clone()))
+		.bind(("status", status.to_string()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	if let Ok(r) = &result {
+		info!("verified {:?}: {} file(s) checked, {} mismatched", intent_id, r.checked, r.mismatched);
+	}
+	result
+}
+
+async fn verify_jobs(db: &DbHandle, intent_id: &RecordId) -> Result<VerifyResult, CopyError> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT id, intent, source_path, dest_path, source_hash, dest_hash FROM transfer_job
+             WHERE intent = $intent_id AND status = 'complete' AND dest_hash != NONE",
+		)
+		.bind(("intent_id", intent_id.clone()))
+		.await
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+	let jobs: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| CopyError::DbError(e.to_string()))?;
+
+	let mut result = VerifyResult::default();
+	for job in jobs {
+		let Ok(job_id) = serde_json::from_value::<RecordId>(job["id"].clone()) else {
+			continue;
+		};
+		let source_path = job["source_path"].as_str().unwrap_or_default().to_string();
+		let dest_path = job["dest_path"].as_str().unwrap_or_default().to_string();
+		let expected = job["dest_hash"].as_str().unwrap_or_default().to_string();
+
+		let dest = decode_path(&dest_path);
+		let actual = tokio::task::spawn_blocking({
+			let (dest, algorithm) = (dest.clone(), HashAlgorithm::of_digest(&expected));
+			move || copier::hash_file(&dest, algorithm)
+		})
+		.await
+		.map_err(|e| CopyError::IoError(e.to_string()))?;
+		result.checked += 1;
+
+		// A destination that vanished or can't be read has drifted too
+		let (message, dest_hash) = match actual {
+			Ok(hash) if hash == expected => continue,
+			Ok(hash) => (
+				CopyError::HashMismatch { source_hash: expected.clone(), dest_hash: hash.clone() }.to_string(),
+				Some(hash),
+			),
+			Err(e) => (e.to_string(), None),
+		};
+		result.mismatched += 1;
+
+		db.db
+			.query(
+				"UPDATE $id SET status = 'needs_review', error_kind = 'hash_mismatch',
+                 last_error = $error",
+			)
+			.bind(("id", job_id.clone()))
+			.bind(("error", message.clone()))
+			.await
+			.map_err(|e| CopyError::DbError(e.to_string()))?;
+		let source_hash = job["source_hash"].as_str().map(String::from);
+		let item = NewReviewItem {
+			job: &job_id,
+			intent: job["intent"].clone(),
+			error_kind: "hash_mismatch",
+			message,
+			source_path: &source_path,
+			dest_path: &dest_path,
+			source: FileSnapshot::stat(&decode_path(&source_path), source_hash),
+			dest: FileSnapshot::stat(&dest, dest_hash),
+		};
+		create_review_item(db, item).await?;
+	}
+
+	Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+	use crate::db::init_memory;
+
+	async fn setup(dest_root: &std::path::Path, files: &[(&str, &str)]) -> DbHandle {
+		let db = init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE intent:backup CONTENT {
+                    source: 'location:src', destinations: ['location:dst'], status: 'complete',
+                    kind: 'one_shot', speed_mode: 'normal',
+                };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		for (name, contents) in files {
+			let dest = dest_root.join(name);
+			fs::write(&dest, contents).unwrap();
+			let hash = copier::hash_file(&dest, HashAlgorithm::Blake3).unwrap();
+			db.db
+				.query(
+					"CREATE transfer_job CONTENT {
+                        intent: intent:backup, destination: location:dst,
+                        source_path: '/src/' + $name, dest_path: $dest_path,
+                        size: 1, status: 'complete', source_hash: $hash, dest_hash: $hash,
+                    };",
+				)
+				.bind(("name", name.to_string()))
+				.bind(("dest_path", dest.to_str().unwrap().to_string()))
+				.bind(("hash", hash))
+				.await
+				.unwrap()
+				.check()
+				.unwrap();
+		}
+		db
+	}
+
+	async fn intent_status(db: &DbHandle) -> String {
+		let mut resp = db.db.query("SELECT VALUE status FROM intent:backup").await.unwrap();
+		let statuses: Vec<String> = resp.take(0).unwrap();
+		statuses.into_iter().next().unwrap()
+	}
+
+	#[tokio::test]
+	async fn untouched_destinations_pass() {
+		let dst = tempfile::tempdir().unwrap();
+		let db = setup(dst.path(), &[("a.txt", "alpha"), ("b.txt", "beta")]).await;
+
+		let result = verify_intent(&db, &RecordId::new("intent", "backup")).await.unwrap();
+
+		assert_eq!(result, VerifyResult { checked: 2, mismatched: 0 });
+		assert_eq!(intent_status(&db).await, "complete");
+	}
+
+	#[tokio::test]
+	async fn drifted_and_missing_files_go_to_review() {
+		let dst = tempfile::tempdir().unwrap();
+		let db = setup(dst.path(), &[("a.txt", "alpha"), ("b.txt", "beta"), ("c.txt", "gamma")]).await;
+		fs::write(dst.path().join("a.txt"), "bit rot").unwrap();
+		fs::remove_file(dst.path().join("c.txt")).unwrap();
+
+		let result = verify_intent(&db, &RecordId::new("intent", "backup")).await.unwrap();
+
+		assert_eq!(result, VerifyResult { checked: 3, mismatched: 2 });
+		assert_eq!(intent_status(&db).await, "needs_review");
+		assert_eq!(fs::read_to_string(dst.path().join("a.txt")).unwrap(), "bit rot", "nothing is recopied");
+
+		let mut resp = db.db.query("SELECT VALUE error_kind FROM review_item").await.unwrap();
+		let kinds: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(kinds, vec!["hash_mismatch".to_string(), "hash_mismatch".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn only_complete_intents_are_verified() {
+		let dst = tempfile::tempdir().unwrap();
+		let db = setup(dst.path(), &[("a.txt", "alpha")]).await;
+		db.db.query("UPDATE intent:backup SET status = 'transferring'").await.unwrap();
+
+		let result = verify_intent(&db, &RecordId::new("intent", "backup")).await.unwrap();
+
+		assert_eq!(result, VerifyResult::default());
+		assert_eq!(intent_status(&db).await, "transferring");
+	}
+}
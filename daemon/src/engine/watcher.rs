@@ -0,0 +1,314 @@
+//! Continuous watching for `sync` intents.
+//!
+//! Once a sync intent's initial transfer completes, the scheduler hands it to
+//! `spawn_sync_watcher`. The watcher debounces filesystem events from the
+//! source, enqueues jobs for files that settled, and runs them. Now and then
+//! it also re-copies files that were deleted from a destination. It exits
+//! when the intent is paused, cancelled, deleted, or stops being a sync
+//! intent. At startup `resume_sync_watchers` restarts the ones that were
+//! running.
+
+use std::{
+	collections::{HashMap, HashSet},
+	path::PathBuf,
+	sync::{LazyLock, Mutex},
+	time::{Duration, Instant},
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use surrealdb::types::RecordId;
+use tracing::{info, warn};
+
+use crate::{
+	db::DbHandle,
+	engine::{scanner, scheduler},
+};
+
+/// How long a path must go without new events before it's enqueued.
+/// Editors often write a file several times per save.
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+/// How often pending paths and the intent's status are checked.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How often destinations are checked for copies that went missing.
+/// Destinations aren't watched, and each check stats every completed copy.
+const RECONCILE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Intents with a live watcher, so re-running an intent doesn't stack them.
+static WATCHED: LazyLock<Mutex<HashSet<RecordId>>> = LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// What the poll loop should do, given the intent's current state.
+#[derive(Debug, PartialEq)]
+enum WatchState {
+	Active,
+	/// Keep collecting events but don't enqueue (initial sync not done).
+	Hold,
+	Stop,
+}
+
+/// Start watching a sync intent's source in the background. Returns `false`
+/// if the intent is already being watched.
+pub fn spawn_sync_watcher(db: DbHandle, intent_id: RecordId) -> bool {
+	if !WATCHED.lock().unwrap().insert(intent_id.clone()) {
+		return false;
+	}
+
+	tokio::spawn(async move {
+		if let Err(e) = watch_intent(&db, &intent_id).await {
+			warn!("sync watcher for {:?} stopped: {}", intent_id, e);
+		}
+		WATCHED.lock().unwrap().remove(&intent_id);
+	});
+	true
+}
+
+/// Start watchers for the sync intents that were following their source
+/// when Kip last stopped. Watchers only live in memory, so without this
+/// they'd stay stopped until the intent ran again. Returns how many started.
+pub async fn resume_sync_watchers(db: &DbHandle) -> Result<usize, String> {
+	let mut response = db
+		.db
+		.query(
+			"SELECT VALUE id FROM intent
+             WHERE kind = 'sync' AND initial_sync_complete = true
+                AND status NOT IN ['paused', 'cancelled', 'error']",
+		)
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let intents: Vec<RecordId> = response.take(0).map_err(|e| e.to_string())?;
+	let started = intents
+		.into_iter()
+		.filter(|id| spawn_sync_watcher(db.clone(), id.clone()))
+		.count();
+	if started > 0 {
+		info!("resumed watching {} sync intent(s)", started);
+	}
+	Ok(started)
+}
+
+async fn watch_intent(db: &DbHandle, intent_id: &RecordId) -> Result<(), String> {
+	let root = scanner::intent_source_root(db, intent_id)
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+	let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+		let _ = tx.send(res);
+	})
+	.map_err(|e| e.to_string())?;
+	watcher
+		.watch(&root, RecursiveMode::Recursive)
+		.map_err(|e| e.to_string())?;
+	info!("watching {} for {:?}", root.display(), intent_id);
+
+	let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+	let mut ticker = tokio::time::interval(POLL_INTERVAL);
+	let mut last_reconcile: Option<Instant> = None;
+
+	loop {
+		tokio::select! {
+			Some(res) = rx.recv() => match res {
+				Ok(event) => record_event(&mut pending, event, Instant::now()),
+				Err(e) => warn!("watch error under {}: {}", root.display(), e),
+			},
+			_ = ticker.tick() => {
+				match watch_state(db, intent_id).await? {
+					WatchState::Stop => break,
+					WatchState::Hold => continue,
+					WatchState::Active => {}
+				}
+
+				if last_reconcile.is_none_or(|t| t.elapsed() >= RECONCILE_INTERVAL) {
+					last_reconcile = Some(Instant::now());
+					let requeued = scanner::requeue_missing_copies(db, intent_id)
+						.await
+						.map_err(|e| e.to_string())?;
+					if requeued > 0 {
+						info!("{} copy(ies) missing from the destination requeued for {:?}", requeued, intent_id);
+						run_queued(db, intent_id).await?;
+					}
+				}
+
+				let due = take_due(&mut pending, Instant::now());
+				if due.is_empty() {
+					continue;
+				}
+
+				let (changed, removed): (Vec<_>, Vec<_>) = due.into_iter().partition(|p| p.exists());
+				for path in &removed {
+					// Deletes aren't propagated: the destination keeps its copy
+					info!("ignoring deletion of {}", path.display());
+				}
+
+				let created = scanner::enqueue_changed_files(db, intent_id, &changed)
+					.await
+					.map_err(|e| e.to_string())?;
+				if created > 0 {
+					info!("{} changed file(s) queued for {:?}", created, intent_id);
+					run_queued(db, intent_id).await?;
+				}
+			}
+		}
+	}
+
+	info!("stopped watching {}", root.display());
+	Ok(())
+}
+
+/// Note the paths an event touched. Pure access events don't change content.
+fn record_event(pending: &mut HashMap<PathBuf, Instant>, event: Event, now: Instant) {
+	if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)) {
+		return;
+	}
+	for path in event.paths {
+		pending.insert(path, now);
+	}
+}
+
+/// Remove and return paths that have been quiet for at least `DEBOUNCE`.
+fn take_due(pending: &mut HashMap<PathBuf, Instant>, now: Instant) -> Vec<PathBuf> {
+	let due: Vec<PathBuf> = pending
+		.iter()
+		.filter(|(_, &last)| now.duration_since(last) >= DEBOUNCE)
+		.map(|(path, _)| path.clone())
+		.collect();
+	for path in &due {
+		pending.remove(path);
+	}
+	due
+}
+
+async fn watch_state(db: &DbHandle, intent_id: &RecordId) -> Result<WatchState, String> {
+	let mut response = db
+		.db
+		.query("SELECT status, kind, initial_sync_complete FROM $id")
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let row: Option<serde_json::Value> = response.take(0).map_err(|e| e.to_string())?;
+	let Some(row) = row else {
+		// Intent was deleted
+		return Ok(WatchState::Stop);
+	};
+
+	Ok(state_for(
+		row["kind"].as_str().unwrap_or_default(),
+		row["status"].as_str().unwrap_or_default(),
+		row["initial_sync_complete"].as_bool().unwrap_or(false),
+	))
+}
+
+fn state_for(kind: &str, status: &str, initial_sync_complete: bool) -> WatchState {
+	if kind != "sync" || matches!(status, "paused" | "cancelled" | "error") {
+		WatchState::Stop
+	} else if !initial_sync_complete {
+		WatchState::Hold
+	} else {
+		WatchState::Active
+	}
+}
+
+/// Run the newly queued jobs, unless a run is already underway — in which
+/// case that run's dispatch loop picks them up.
+async fn run_queued(db: &DbHandle, intent_id: &RecordId) -> Result<(), String> {
+	if !scheduler::claim_intent(db, intent_id).await.map_err(|e| e.to_string())? {
+		return Ok(());
+	}
+
+	scheduler::run_intent(db, intent_id)
+		.await
+		.map(|_| ())
+		.map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+	use super::*;
+
+	fn is_watching(intent_id: &RecordId) -> bool {
+		WATCHED.lock().unwrap().contains(intent_id)
+	}
+
+	fn event(kind: EventKind, path: &str) -> Event {
+		Event::new(kind).add_path(PathBuf::from(path))
+	}
+
+	#[test]
+	fn rapid_saves_collapse_to_one_path() {
+		let start = Instant::now();
+		let mut pending = HashMap::new();
+		record_event(&mut pending, event(EventKind::Create(CreateKind::File), "/src/a.txt"), start);
+		record_event(&mut pending, event(EventKind::Modify(ModifyKind::Any), "/src/a.txt"), start + Duration::from_secs(1));
+
+		// Last event was 1.5s ago: still settling
+		assert!(take_due(&mut pending, start + Duration::from_millis(2500)).is_empty());
+		assert_eq!(take_due(&mut pending, start + Duration::from_secs(3)), vec![PathBuf::from("/src/a.txt")]);
+		assert!(pending.is_empty());
+	}
+
+	#[test]
+	fn access_events_are_ignored() {
+		let mut pending = HashMap::new();
+		record_event(&mut pending, event(EventKind::Access(AccessKind::Any), "/src/a.txt"), Instant::now());
+		assert!(pending.is_empty());
+	}
+
+	#[tokio::test]
+	async fn startup_resumes_only_settled_sync_intents() {
+		let src = tempfile::tempdir().unwrap();
+		let db = crate::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE intent:resumed CONTENT {
+                     source: location:src, destinations: [], status: 'complete',
+                     kind: 'sync', speed_mode: 'normal', initial_sync_complete: true,
+                 };
+                 CREATE intent:unsynced CONTENT {
+                     source: location:src, destinations: [], status: 'idle',
+                     kind: 'sync', speed_mode: 'normal',
+                 };
+                 CREATE intent:paused_sync CONTENT {
+                     source: location:src, destinations: [], status: 'paused',
+                     kind: 'sync', speed_mode: 'normal', initial_sync_complete: true,
+                 };
+                 CREATE intent:once CONTENT {
+                     source: location:src, destinations: [], status: 'complete',
+                     kind: 'one_shot', speed_mode: 'normal', initial_sync_complete: true,
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		assert_eq!(resume_sync_watchers(&db).await.unwrap(), 1);
+		// Give the watcher a chance to fail on startup if it's going to
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		assert!(is_watching(&RecordId::new("intent", "resumed")));
+		for id in ["unsynced", "paused_sync", "once"] {
+			assert!(!is_watching(&RecordId::new("intent", id)));
+		}
+		// Already running: not started twice
+		assert_eq!(resume_sync_watchers(&db).await.unwrap(), 0);
+
+		db.db.query("UPDATE intent:resumed SET status = 'paused'").await.unwrap().check().unwrap();
+		tokio::time::sleep(POLL_INTERVAL * 3).await;
+		assert!(!is_watching(&RecordId::new("intent", "resumed")));
+	}
+
+	#[test]
+	fn watch_state_follows_intent() {
+		assert_eq!(state_for("sync", "complete", true), WatchState::Active);
+		assert_eq!(state_for("sync", "transferring", false), WatchState::Hold);
+		assert_eq!(state_for("sync", "paused", true), WatchState::Stop);
+		assert_eq!(state_for("sync", "error", true), WatchState::Stop);
+		assert_eq!(state_for("one_shot", "complete", true), WatchState::Stop);
+	}
+}
@@ -0,0 +1,178 @@
+//! Engine → UI events.
+//!
+//! The engine runs on background tasks with no handle to the UI, so anything
+//! the user should hear about right away is broadcast here. The frontend
+//! subscribes once and turns events into notifications. Events emitted with
+//! no subscriber are dropped.
+
+use std::{fmt, sync::LazyLock};
+
+use surrealdb::types::RecordId;
+use tokio::sync::broadcast;
+
+use crate::graph_store::rid_string;
+
+/// Buffered events per subscriber before the oldest are dropped.
+const CHANNEL_CAPACITY: usize = 64;
+
+static CHANNEL: LazyLock<broadcast::Sender<EngineEvent>> = LazyLock::new(|| broadcast::channel(CHANNEL_CAPACITY).0);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineEvent {
+	/// Pending jobs for a destination were parked because they won't fit.
+	InsufficientSpace {
+		destination: String,
+		needed: u64,
+		available: u64,
+		jobs: u64,
+	},
+	/// A drive vanished while intents were transferring to or from it; they
+	/// are now `waiting_for_device`.
+	DriveDisconnected { drive: String, intents: Vec<String> },
+	/// A volume this session hadn't seen before was mounted. `drive` is its
+	/// record id as a string.
+	DriveMounted { drive: String, name: String, mount_point: String },
+	/// An intent's source walk began. `intent` is its record id as a string,
+	/// `name` what to call it in messages.
+	ScanStarted { intent: String, name: String },
+	/// The walk finished and `jobs` transfer jobs were queued.
+	ScanFinished { intent: String, name: String, files: u64, jobs: u64 },
+	/// The scan couldn't run to completion.
+	ScanFailed { intent: String, name: String, error: String },
+	/// Periodic progress while an intent's jobs run.
+	TransferProgress { intent: String, name: String, completed: u64, total: u64 },
+	/// Every job finished cleanly.
+	TransferComplete { intent: String, name: String, files: u64 },
+	/// The run finished but `count` jobs are waiting in the review queue.
+	/// `failures` counts every job that didn't go through by `error_kind`.
+	NeedsReview {
+		intent: String,
+		name: String,
+		count: u64,
+		copied: u64,
+		failures: Vec<(String, u64)>,
+	},
+}
+
+/// How loudly the UI should surface an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+impl EngineEvent {
+	pub fn severity(&self) -> Severity {
+		match self {
+			EngineEvent::InsufficientSpace { .. } => Severity::Warning,
+			EngineEvent::DriveDisconnected { .. } => Severity::Error,
+			EngineEvent::DriveMounted { .. }
+			| EngineEvent::ScanStarted { .. }
+			| EngineEvent::ScanFinished { .. }
+			| EngineEvent::TransferProgress { .. }
+			| EngineEvent::TransferComplete { .. } => Severity::Info,
+			EngineEvent::ScanFailed { .. } | EngineEvent::NeedsReview { .. } => Severity::Error,
+		}
+	}
+
+	/// The intent a lifecycle event is about, so the UI can replace one
+	/// intent's spinner with its progress bar and then its result.
+	pub fn intent(&self) -> Option<&str> {
+		match self {
+			EngineEvent::ScanStarted { intent, .. }
+			| EngineEvent::ScanFinished { intent, .. }
+			| EngineEvent::ScanFailed { intent, .. }
+			| EngineEvent::TransferProgress { intent, .. }
+			| EngineEvent::TransferComplete { intent, .. }
+			| EngineEvent::NeedsReview { intent, .. } => Some(intent),
+			EngineEvent::InsufficientSpace { .. }
+			| EngineEvent::DriveDisconnected { .. }
+			| EngineEvent::DriveMounted { .. } => None,
+		}
+	}
+}
+
+/// What to call an intent in messages: its name, else its record id.
+pub(crate) fn intent_label(id: &RecordId, name: Option<&str>) -> String {
+	name.map(String::from).unwrap_or_else(|| rid_string(id))
+}
+
+impl fmt::Display for EngineEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			EngineEvent::InsufficientSpace { destination, needed, available, jobs } => write!(
+				f,
+				"Not enough space on {destination}: {jobs} file(s) need {} MB, {} MB free",
+				needed / 1_048_576,
+				available / 1_048_576
+			),
+			EngineEvent::DriveDisconnected { drive, intents } => write!(
+				f,
+				"{drive} disconnected; paused {} until it's back",
+				intents.join(", ")
+			),
+			EngineEvent::DriveMounted { name, .. } => write!(f, "Mounted: {name}"),
+			EngineEvent::ScanStarted { name, .. } => write!(f, "Scanning {name}…"),
+			EngineEvent::ScanFinished { name, files, jobs, .. } => {
+				write!(f, "Scanned {name}: {files} file(s), {jobs} job(s) queued")
+			}
+			EngineEvent::ScanFailed { name, error, .. } => write!(f, "Scan of {name} failed: {error}"),
+			EngineEvent::TransferProgress { name, completed, total, .. } => {
+				write!(f, "Copying {name}: {completed}/{total}")
+			}
+			EngineEvent::TransferComplete { name, files, .. } => write!(f, "{name} complete: {files} file(s) copied"),
+			EngineEvent::NeedsReview { name, copied, failures, .. } => {
+				write!(f, "{name}: {}", outcome_summary(*copied, failures))
+			}
+		}
+	}
+}
+
+/// A finished run in one line, e.g. "9998 copied, 2 failed verification",
+/// from how many jobs completed and the rest by `error_kind`. Kinds that
+/// read the same are counted together.
+pub fn outcome_summary(copied: u64, failures: &[(String, u64)]) -> String {
+	let mut phrases: Vec<(&str, u64)> = Vec::new();
+	for (kind, count) in failures {
+		let phrase = failure_phrase(kind);
+		match phrases.iter_mut().find(|(p, _)| *p == phrase) {
+			Some((_, total)) => *total += count,
+			None => phrases.push((phrase, *count)),
+		}
+	}
+	let mut parts = vec![format!("{copied} copied")];
+	parts.extend(phrases.into_iter().map(|(phrase, count)| format!("{count} {phrase}")));
+	parts.join(", ")
+}
+
+fn failure_phrase(error_kind: &str) -> &'static str {
+	match error_kind {
+		"hash_mismatch" | "verify_failed" => "failed verification",
+		"source_missing" => "missing at the source",
+		"permission_denied" => "permission denied",
+		"disk_full" => "out of space",
+		"file_too_large" => "too large for the destination",
+		"conflict" => "conflicting with the destination",
+		"unsupported_file_type" => "of an unsupported type",
+		"io_error" => "failed to read or write",
+		"remote_misconfigured" => "bound for a machine that isn't set up",
+		_ => "failed",
+	}
+}
+
+/// Broadcast an event to every current subscriber.
+pub fn emit(event: EngineEvent) {
+	// Progress would flood the log at one line per second per intent
+	if matches!(event, EngineEvent::TransferProgress { .. }) {
+		tracing::debug!("{}", event);
+	} else {
+		tracing::info!("{}", event);
+	}
+	let _ = CHANNEL.send(event);
+}
+
+/// Receive events emitted from now on.
+pub fn subscribe() -> broadcast::Receiver<EngineEvent> {
+	CHANNEL.subscribe()
+}
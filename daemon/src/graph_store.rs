@@ -1,10 +1,17 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use surrealdb::types::{RecordId, RecordIdKey, SurrealValue};
 use tracing::{info, warn};
 use kip_core::*;
 
-use crate::db::DbHandle;
+use crate::{
+	db::DbHandle,
+	engine::{
+		copier::{self, Disposal},
+		scanner,
+		scheduler::{destination_results, DestinationResult},
+	},
+};
 
 // ─── Force simulation constants ───────────────────────────────
 
@@ -17,11 +24,29 @@ const ALPHA_DECAY: f64 = 0.97; // Alpha decay rate (slower decay = more movement
 const ALPHA_MIN: f64 = 0.001; // Stop threshold
 const ALPHA_START: f64 = 0.5; // Initial alpha on wake
 const WARM_RESTART: f64 = 0.8; // Alpha boost on changes
+const SETTLE_ENERGY: f64 = 0.0025; // Kinetic energy per visible node (0.05 px/tick) that counts as still
+const SETTLE_TICKS: u32 = 10; // Stop after this many still ticks in a row
+
+// Above this many visible nodes, repulsion and collision are exact only
+// between nodes in neighbouring grid cells; farther cells act as one mass
+const GRID_THRESHOLD: usize = 100;
+const GRID_CELL: f64 = 250.0;
+const SIBLING_REPULSION: f64 = 800.0; // Extra push between children of one parent
 
 // Collision settings
 const COLLISION_ITERATIONS: usize = 3;
 const COLLISION_K: f64 = 0.7;
 
+// Viewport zoom limits
+const MIN_ZOOM: f64 = 0.2;
+const MAX_ZOOM: f64 = 3.0;
+
+/// Where the workspace starts below the header until it's been measured.
+const DEFAULT_WORKSPACE_TOP: f64 = 61.0;
+
+/// How far (screen pixels) a press has to move before it's a drag, not a click.
+const DRAG_THRESHOLD: f64 = 5.0;
+
 // ─── Helper functions for edge lengths and collision radii ────
 
 /// Get target edge length based on edge type
@@ -125,6 +150,9 @@ pub struct Graph {
 	pub alpha: f64,
 	pub sim_running: bool,
 	pub selected: HashSet<String>,
+	/// Intent whose edges were clicked on the canvas, offered for deletion
+	/// or as the target of "add destination".
+	pub selected_edge: Option<String>,
 	pub drag_state: DragState,
 	pub context_menu: ContextMenuState,
 	pub containers: Vec<ContainerView>,
@@ -136,6 +164,10 @@ pub struct Graph {
 	pub viewport_scale: f64, // Zoom level (1.0 = 100%)
 	pub viewport_x: f64,     // Pan X offset
 	pub viewport_y: f64,     // Pan Y offset
+	/// The workspace's top-left corner in window (client) coordinates.
+	pub workspace_origin: Vec2,
+	// Ticks in a row the layout has been still
+	calm_ticks: u32,
 }
 
 impl Graph {
@@ -146,6 +178,7 @@ impl Graph {
 			alpha: 0.0,
 			sim_running: false,
 			selected: HashSet::new(),
+			selected_edge: None,
 			drag_state: DragState::None,
 			context_menu: ContextMenuState::new(),
 			containers: Vec::new(),
@@ -155,6 +188,8 @@ impl Graph {
 			viewport_scale: 1.0,
 			viewport_x: 0.0,
 			viewport_y: 0.0,
+			workspace_origin: Vec2::new(0.0, DEFAULT_WORKSPACE_TOP),
+			calm_ticks: 0,
 		}
 	}
 
@@ -202,6 +237,44 @@ impl Graph {
 		}
 	}
 
+	/// Walk `parent_id` links up to the machine/drive node that owns `id`.
+	pub fn container_of(&self, id: &str) -> Option<String> {
+		let mut current = self.find_node(id)?;
+		while let Some(parent) = current.parent_id.as_deref().and_then(|p| self.find_node(p)) {
+			current = parent;
+		}
+		Some(current.id.clone())
+	}
+
+	/// Show a location under its new label, or its path if `label` is empty.
+	pub fn set_label(&mut self, id: &str, label: Option<&str>) {
+		if let Some(node) = self.find_node_mut(id) {
+			node.label = location_label(label, &node.path);
+		}
+	}
+
+	/// Show an intent's edges under its new name, or none if `name` is empty.
+	pub fn set_intent_name(&mut self, intent_id: &str, name: Option<&str>) {
+		for edge in self.edges.iter_mut().filter(|e| e.intent_id.as_deref() == Some(intent_id)) {
+			edge.name = name.map(String::from);
+		}
+	}
+
+	/// Recolor a container and every node it owns.
+	pub fn set_container_color(&mut self, container_id: &str, color: &str) {
+		for c in self.containers.iter_mut() {
+			if rid_string(&c.id) == container_id {
+				c.color = color.to_string();
+			}
+		}
+		let owned: Vec<usize> = (0..self.nodes.len())
+			.filter(|&i| self.container_of(&self.nodes[i].id).as_deref() == Some(container_id))
+			.collect();
+		for i in owned {
+			self.nodes[i].color = color.to_string();
+		}
+	}
+
 	pub fn toggle_expand(&mut self, id: &str) {
 		// Find the node's path and current expansion state
 		let (path, was_expanded, node_label, node_id, node_kind) = match self.find_node(id) {
@@ -350,6 +423,8 @@ impl Graph {
 			// Create edge from parent to child
 			self.edges.push(GraphEdge {
 				id: format!("edge_{}_{}", node_id, child_id),
+				intent_id: None,
+				name: None,
 				source_id: node_id.to_string(),
 				dest_id: child_id,
 				status: "idle".to_string(),
@@ -371,6 +446,43 @@ impl Graph {
 		);
 	}
 
+	/// Whether `id` is an expanded directory whose children are still only a
+	/// count, so they need loading from the DB.
+	pub fn needs_children(&self, id: &str) -> bool {
+		let Some(node) = self.find_node(id) else {
+			return false;
+		};
+		matches!(node.kind, NodeKind::Directory { expanded: true })
+			&& node.child_count > 0
+			&& !self.nodes.iter().any(|n| n.parent_id.as_deref() == Some(id))
+	}
+
+	/// Add lazily loaded children under `parent_id`, linked by hierarchy
+	/// edges. If the directory was collapsed while they loaded they stay
+	/// hidden until it's expanded again.
+	pub fn insert_children(&mut self, parent_id: &str, children: Vec<GraphNode>) {
+		let expanded = self.find_node(parent_id).is_some_and(|n| n.kind.is_expanded());
+		let (parent_x, parent_y) = self
+			.find_node(parent_id)
+			.map(|n| (n.position.x, n.position.y))
+			.unwrap_or_default();
+		let total = children.len().max(1) as f64;
+		for (i, mut child) in children.into_iter().enumerate() {
+			if self.find_node(&child.id).is_some() {
+				continue;
+			}
+			child.visible = expanded;
+			if !child.pinned {
+				// Start in a ring around the directory rather than at a random spot
+				let angle = (i as f64 / total) * 2.0 * std::f64::consts::PI;
+				child.position = Vec2::new(parent_x + 120.0 * angle.cos(), parent_y + 120.0 * angle.sin());
+			}
+			self.edges.extend(create_hierarchy_edges(std::slice::from_ref(&child)));
+			self.nodes.push(child);
+		}
+		self.wake(WARM_RESTART);
+	}
+
 	/// Clear scan status
 	pub fn clear_scan_status(&mut self) {
 		self.scanning = None;
@@ -380,7 +492,7 @@ impl Graph {
 	/// Zoom the viewport
 	pub fn zoom(&mut self, delta: f64, center_x: f64, center_y: f64) {
 		let old_scale = self.viewport_scale;
-		let new_scale = (self.viewport_scale * delta).clamp(0.1, 5.0);
+		let new_scale = (self.viewport_scale * delta).clamp(MIN_ZOOM, MAX_ZOOM);
 
 		// Zoom toward mouse position
 		self.viewport_x = center_x - (center_x - self.viewport_x) * (new_scale / old_scale);
@@ -388,6 +500,25 @@ impl Graph {
 		self.viewport_scale = new_scale;
 	}
 
+	/// Convert workspace (screen) coordinates to graph coordinates by
+	/// undoing the viewport's translate + scale.
+	pub fn screen_to_graph(&self, x: f64, y: f64) -> (f64, f64) {
+		((x - self.viewport_x) / self.viewport_scale, (y - self.viewport_y) / self.viewport_scale)
+	}
+
+	/// Convert a pointer's window (client) coordinates to workspace ones,
+	/// relative to where the workspace sits on screen.
+	pub fn client_to_workspace(&self, x: f64, y: f64) -> (f64, f64) {
+		(x - self.workspace_origin.x, y - self.workspace_origin.y)
+	}
+
+	/// Convert a pointer's window (client) coordinates to graph coordinates,
+	/// the space node positions and drag state live in.
+	pub fn client_to_graph(&self, x: f64, y: f64) -> (f64, f64) {
+		let (x, y) = self.client_to_workspace(x, y);
+		self.screen_to_graph(x, y)
+	}
+
 	/// Pan the viewport to a specific position
 	pub fn set_viewport(&mut self, x: f64, y: f64) {
 		self.viewport_x = x;
@@ -418,24 +549,34 @@ impl Graph {
 		}
 	}
 
-	pub fn fix_selected_nodes(&mut self) {
-		let ids: Vec<String> = self.selected.iter().cloned().collect();
-		for id in ids {
-			if let Some(node) = self.find_node_mut(&id) {
-				node.fx = Some(node.position.x);
-				node.fy = Some(node.position.y);
-			}
-		}
+	/// Whether a press that started at one graph point and is now at another
+	/// has moved far enough, on screen, to count as a drag.
+	pub fn is_drag(&self, start_x: f64, start_y: f64, x: f64, y: f64) -> bool {
+		(x - start_x).hypot(y - start_y) * self.viewport_scale > DRAG_THRESHOLD
 	}
 
-	pub fn release_selected_nodes(&mut self) {
-		let ids: Vec<String> = self.selected.iter().cloned().collect();
-		for id in ids {
-			if let Some(node) = self.find_node_mut(&id) {
-				node.fx = None;
-				node.fy = None;
-			}
-		}
+	/// Leave a dragged node where it was dropped, pinned the way a node loaded
+	/// with a saved position is, and end the drag. Returns where it landed.
+	pub fn drop_node(&mut self, id: &str) -> Option<(f64, f64)> {
+		self.drag_state = DragState::None;
+		let node = self.find_node_mut(id)?;
+		node.fx = None;
+		node.fy = None;
+		node.pinned = true;
+		node.velocity = Vec2::default();
+		let landed = (node.position.x, node.position.y);
+		// Let the neighbours settle around its new spot
+		self.start_simulation();
+		Some(landed)
+	}
+
+	/// Current positions of location nodes, the only ones persisted.
+	pub fn location_positions(&self) -> Vec<(String, f64, f64)> {
+		self.nodes
+			.iter()
+			.filter(|n| n.id.starts_with("location:"))
+			.map(|n| (n.id.clone(), n.position.x, n.position.y))
+			.collect()
 	}
 
 	// ── Edge mutations ──
@@ -459,8 +600,46 @@ impl Graph {
 		}
 	}
 
+	pub fn select_all_visible(&mut self) {
+		self.selected = self.nodes.iter().filter(|n| n.visible).map(|n| n.id.clone()).collect();
+	}
+
 	pub fn clear_selection(&mut self) {
 		self.selected.clear();
+		self.selected_edge = None;
+	}
+
+	/// Select an intent's edges, or deselect them if already selected.
+	pub fn toggle_edge_selection(&mut self, intent_id: &str) {
+		if self.selected_edge.as_deref() == Some(intent_id) {
+			self.selected_edge = None;
+		} else {
+			self.selected_edge = Some(intent_id.to_string());
+		}
+	}
+
+	/// Drop every edge drawn for an intent.
+	pub fn remove_intent_edges(&mut self, intent_id: &str) {
+		self.edges.retain(|e| e.intent_id.as_deref() != Some(intent_id));
+		if self.selected_edge.as_deref() == Some(intent_id) {
+			self.selected_edge = None;
+		}
+	}
+
+	/// Name of an intent drawn on the canvas, if it has one.
+	pub fn intent_name(&self, intent_id: &str) -> Option<&str> {
+		self.edges
+			.iter()
+			.find(|e| e.intent_id.as_deref() == Some(intent_id))
+			.and_then(|e| e.name.as_deref())
+	}
+
+	/// Source node of an intent drawn on the canvas.
+	pub fn intent_source(&self, intent_id: &str) -> Option<&str> {
+		self.edges
+			.iter()
+			.find(|e| e.intent_id.as_deref() == Some(intent_id))
+			.map(|e| e.source_id.as_str())
 	}
 
 	pub fn select_in_rect(&mut self, min_x: f64, min_y: f64, max_x: f64, max_y: f64) {
@@ -481,9 +660,11 @@ impl Graph {
 	pub fn start_simulation(&mut self) {
 		self.alpha = ALPHA_START;
 		self.sim_running = true;
+		self.calm_ticks = 0;
 	}
 
 	fn wake(&mut self, alpha: f64) {
+		self.calm_ticks = 0;
 		// Only wake if not already running, to prevent constant restarts
 		if !self.sim_running {
 			self.alpha = alpha;
@@ -501,14 +682,23 @@ impl Graph {
 		}
 
 		// tracing::info!("tick: alpha={:.4}, nodes={}, edges={}", self.alpha, self.nodes.len(), self.edges.len());
-		apply_forces(&mut self.nodes, &self.edges, self.alpha);
+		let energy = apply_forces(&mut self.nodes, &self.edges, self.alpha);
 		self.alpha *= ALPHA_DECAY;
 
+		// Stop once nodes have stopped moving, without waiting out the decay
+		let visible = self.nodes.iter().filter(|n| n.visible).count().max(1);
+		if energy < SETTLE_ENERGY * visible as f64 {
+			self.calm_ticks += 1;
+		} else {
+			self.calm_ticks = 0;
+		}
+
 		// Check if alpha has dropped below threshold - if so, stop simulation
-		if self.alpha < ALPHA_MIN {
+		if self.alpha < ALPHA_MIN || self.calm_ticks >= SETTLE_TICKS {
 			self.sim_running = false;
 			self.alpha = 0.0; // Ensure alpha is properly set to 0
-			tracing::info!("tick: simulation stopped (alpha too low)");
+			self.calm_ticks = 0;
+			tracing::info!("tick: simulation stopped (layout settled)");
 			return false;
 		}
 
@@ -535,46 +725,45 @@ impl Graph {
 
 // ─── Force-directed algorithm ─────────────────────────────────
 
-fn apply_forces(nodes: &mut [GraphNode], edges: &[GraphEdge], alpha: f64) {
+/// One simulation step. Returns the total kinetic energy (sum of squared
+/// speeds) of the nodes that moved, for settle detection.
+fn apply_forces(nodes: &mut [GraphNode], edges: &[GraphEdge], alpha: f64) -> f64 {
 	let n = nodes.len();
 
 	// Collect visible indices for O(1) lookup
 	let visible: Vec<usize> = (0..n).filter(|&i| nodes[i].visible).collect();
+	let index: HashMap<String, usize> = nodes.iter().enumerate().map(|(i, n)| (n.id.clone(), i)).collect();
 
 	// Workspace center
 	let center = Vec2::new(600.0, 400.0);
 
-	// 1. Repulsion between all visible pairs (forceManyBody equivalent)
-	for i in 0..visible.len() {
-		for j in (i + 1)..visible.len() {
-			let ai = visible[i];
-			let bi = visible[j];
-
-			// Skip nodes with fixed positions
-			let a_fixed = nodes[ai].fx.is_some() && nodes[ai].fy.is_some();
-			let b_fixed = nodes[bi].fx.is_some() && nodes[bi].fy.is_some();
-			if a_fixed && b_fixed {
-				continue;
-			}
-
-			let delta = nodes[bi].center() - nodes[ai].center();
-			let dist = delta.length().max(1.0);
-			let force_mag = REPULSION / (dist * dist);
-			let force = delta.normalized() * force_mag * alpha;
+	// Pairs close enough to push on each other individually: all of them in
+	// small graphs, neighbouring grid cells in large ones
+	let grid = (visible.len() > GRID_THRESHOLD).then(|| Grid::build(nodes, &visible));
+	let pairs = match &grid {
+		Some(grid) => grid.near_pairs(),
+		None => (0..visible.len())
+			.flat_map(|i| ((i + 1)..visible.len()).map(move |j| (i, j)))
+			.map(|(i, j)| (visible[i], visible[j]))
+			.collect(),
+	};
 
-			if !a_fixed && !nodes[ai].pinned {
-				nodes[ai].velocity -= force;
-			}
-			if !b_fixed && !nodes[bi].pinned {
-				nodes[bi].velocity += force;
-			}
-		}
+	// 1. Repulsion between visible pairs (forceManyBody equivalent). Siblings
+	// repel each other more strongly, which keeps children from clustering
+	// too tightly around their parent
+	for &(ai, bi) in &pairs {
+		let siblings = nodes[ai].parent_id.is_some() && nodes[ai].parent_id == nodes[bi].parent_id;
+		let strength = if siblings { REPULSION + SIBLING_REPULSION } else { REPULSION };
+		repel(nodes, ai, bi, strength * alpha);
+	}
+	if let Some(grid) = &grid {
+		grid.repel_far(nodes, &visible, REPULSION * alpha);
 	}
 
 	// 2. Edge springs (forceLink equivalent)
 	for edge in edges {
-		let src_idx = nodes.iter().position(|n| n.id == edge.source_id);
-		let dst_idx = nodes.iter().position(|n| n.id == edge.dest_id);
+		let src_idx = index.get(&edge.source_id).copied();
+		let dst_idx = index.get(&edge.dest_id).copied();
 		if let (Some(si), Some(di)) = (src_idx, dst_idx) {
 			if !nodes[si].visible || !nodes[di].visible {
 				continue;
@@ -614,9 +803,10 @@ fn apply_forces(nodes: &mut [GraphNode], edges: &[GraphEdge], alpha: f64) {
 		.iter()
 		.filter_map(|&i| {
 			nodes[i].parent_id.as_ref().and_then(|pid| {
-				nodes
-					.iter()
-					.position(|n| n.id == *pid && n.visible)
+				index
+					.get(pid)
+					.copied()
+					.filter(|&pi| nodes[pi].visible)
 					.map(|pi| (i, pi))
 			})
 		})
@@ -656,50 +846,6 @@ fn apply_forces(nodes: &mut [GraphNode], edges: &[GraphEdge], alpha: f64) {
 		}
 	}
 
-	// 3b. Sibling repulsion - nodes with same parent repel each other more strongly
-	// This prevents children from clustering too tightly around parent
-	let mut siblings_by_parent: std::collections::HashMap<String, Vec<usize>> = std::collections::HashMap::new();
-	for &i in &visible {
-		if let Some(ref pid) = nodes[i].parent_id {
-			siblings_by_parent
-				.entry(pid.clone())
-				.or_insert_with(Vec::new)
-				.push(i);
-		}
-	}
-
-	for (_parent_id, siblings) in siblings_by_parent.iter() {
-		if siblings.len() < 2 {
-			continue;
-		}
-		// Moderate repulsion between siblings - enough to spread but not too far
-		let sibling_repulsion_strength = 800.0; // Reduced from 3000
-		for i in 0..siblings.len() {
-			for j in (i + 1)..siblings.len() {
-				let ai = siblings[i];
-				let bi = siblings[j];
-
-				let a_fixed = nodes[ai].fx.is_some() && nodes[ai].fy.is_some();
-				let b_fixed = nodes[bi].fx.is_some() && nodes[bi].fy.is_some();
-				if a_fixed && b_fixed {
-					continue;
-				}
-
-				let delta = nodes[bi].center() - nodes[ai].center();
-				let dist = delta.length().max(1.0);
-				let force_mag = sibling_repulsion_strength / (dist * dist);
-				let force = delta.normalized() * force_mag * alpha;
-
-				if !a_fixed && !nodes[ai].pinned {
-					nodes[ai].velocity -= force;
-				}
-				if !b_fixed && !nodes[bi].pinned {
-					nodes[bi].velocity += force;
-				}
-			}
-		}
-	}
-
 	// 4. Center gravity (forceX/forceY equivalent)
 	for &i in &visible {
 		if nodes[i].pinned {
@@ -713,36 +859,33 @@ fn apply_forces(nodes: &mut [GraphNode], edges: &[GraphEdge], alpha: f64) {
 		nodes[i].velocity += to_center * CENTER_GRAVITY * alpha;
 	}
 
-	// 5. Collision resolution (forceCollide equivalent)
+	// 5. Collision resolution (forceCollide equivalent). Colliding nodes are
+	// always within a grid cell of each other
 	for _iteration in 0..COLLISION_ITERATIONS {
-		for i in 0..visible.len() {
-			for j in (i + 1)..visible.len() {
-				let ai = visible[i];
-				let bi = visible[j];
-
-				let a_fixed = nodes[ai].fx.is_some() && nodes[ai].fy.is_some();
-				let b_fixed = nodes[bi].fx.is_some() && nodes[bi].fy.is_some();
+		for &(ai, bi) in &pairs {
+			let a_fixed = nodes[ai].fx.is_some() && nodes[ai].fy.is_some();
+			let b_fixed = nodes[bi].fx.is_some() && nodes[bi].fy.is_some();
 
-				let delta = nodes[bi].center() - nodes[ai].center();
-				let dist = delta.length();
-				let min_dist = get_collision_radius(&nodes[ai]) + get_collision_radius(&nodes[bi]);
+			let delta = nodes[bi].center() - nodes[ai].center();
+			let dist = delta.length();
+			let min_dist = get_collision_radius(&nodes[ai]) + get_collision_radius(&nodes[bi]);
 
-				if dist > 0.0 && dist < min_dist {
-					let overlap = min_dist - dist;
-					let push = delta.normalized() * overlap * COLLISION_K * alpha;
+			if dist > 0.0 && dist < min_dist {
+				let overlap = min_dist - dist;
+				let push = delta.normalized() * overlap * COLLISION_K * alpha;
 
-					if !a_fixed && !nodes[ai].pinned {
-						nodes[ai].velocity -= push * 0.5;
-					}
-					if !b_fixed && !nodes[bi].pinned {
-						nodes[bi].velocity += push * 0.5;
-					}
+				if !a_fixed && !nodes[ai].pinned {
+					nodes[ai].velocity -= push * 0.5;
+				}
+				if !b_fixed && !nodes[bi].pinned {
+					nodes[bi].velocity += push * 0.5;
 				}
 			}
 		}
 	}
 
 	// 6. Apply velocities with damping and respect fixed positions
+	let mut energy = 0.0;
 	for &i in &visible {
 		let fixed = nodes[i].fx.is_some() && nodes[i].fy.is_some();
 
@@ -758,20 +901,120 @@ fn apply_forces(nodes: &mut [GraphNode], edges: &[GraphEdge], alpha: f64) {
 
 		// Apply damping and integrate
 		nodes[i].velocity = nodes[i].velocity * DAMPING;
+		let before = nodes[i].position;
 		nodes[i].position += nodes[i].velocity;
 
 		// Clamp to workspace bounds
 		nodes[i].position.x = nodes[i].position.x.clamp(20.0, 1160.0);
 		nodes[i].position.y = nodes[i].position.y.clamp(20.0, 760.0);
+
+		// A node pressed against the bounds isn't moving
+		let step = (nodes[i].position - before).length();
+		energy += step * step;
+	}
+
+	energy
+}
+
+/// Push two nodes apart with inverse-square `strength`, leaving fixed and
+/// pinned ones in place.
+fn repel(nodes: &mut [GraphNode], ai: usize, bi: usize, strength: f64) {
+	let a_fixed = nodes[ai].fx.is_some() && nodes[ai].fy.is_some();
+	let b_fixed = nodes[bi].fx.is_some() && nodes[bi].fy.is_some();
+	if a_fixed && b_fixed {
+		return;
+	}
+
+	let delta = nodes[bi].center() - nodes[ai].center();
+	let dist = delta.length().max(1.0);
+	let force = delta.normalized() * (strength / (dist * dist));
+
+	if !a_fixed && !nodes[ai].pinned {
+		nodes[ai].velocity -= force;
+	}
+	if !b_fixed && !nodes[bi].pinned {
+		nodes[bi].velocity += force;
+	}
+}
+
+/// Visible nodes bucketed into `GRID_CELL`-sized squares, for approximating
+/// repulsion in large graphs.
+struct Grid {
+	cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl Grid {
+	fn build(nodes: &[GraphNode], visible: &[usize]) -> Self {
+		let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+		for &i in visible {
+			cells.entry(grid_cell(nodes[i].center())).or_default().push(i);
+		}
+		Self { cells }
+	}
+
+	/// Every pair of nodes in the same or adjacent cells, once each.
+	fn near_pairs(&self) -> Vec<(usize, usize)> {
+		let mut pairs = Vec::new();
+		for (&(cx, cy), members) in &self.cells {
+			for (k, &a) in members.iter().enumerate() {
+				pairs.extend(members[k + 1..].iter().map(|&b| (a, b)));
+			}
+			// Half the neighbours, so each adjacent pair of cells is visited once
+			for (dx, dy) in [(1, -1), (1, 0), (1, 1), (0, 1)] {
+				if let Some(others) = self.cells.get(&(cx + dx, cy + dy)) {
+					for &a in members {
+						pairs.extend(others.iter().map(|&b| (a, b)));
+					}
+				}
+			}
+		}
+		pairs
+	}
+
+	/// Repulsion from cells beyond a node's neighbours, each treated as its
+	/// node count at its centroid.
+	fn repel_far(&self, nodes: &mut [GraphNode], visible: &[usize], strength: f64) {
+		let masses: Vec<((i64, i64), Vec2, f64)> = self
+			.cells
+			.iter()
+			.map(|(&cell, members)| {
+				let sum = members.iter().fold(Vec2::default(), |acc, &i| acc + nodes[i].center());
+				let count = members.len() as f64;
+				(cell, sum * (1.0 / count), count)
+			})
+			.collect();
+
+		for &i in visible {
+			let fixed = nodes[i].fx.is_some() && nodes[i].fy.is_some();
+			if fixed || nodes[i].pinned {
+				continue;
+			}
+			let (cx, cy) = grid_cell(nodes[i].center());
+			for &((x, y), centroid, count) in &masses {
+				if (x - cx).abs() <= 1 && (y - cy).abs() <= 1 {
+					continue;
+				}
+				let delta = centroid - nodes[i].center();
+				let dist = delta.length().max(1.0);
+				nodes[i].velocity -= delta.normalized() * (strength * count / (dist * dist));
+			}
+		}
 	}
 }
 
+fn grid_cell(p: Vec2) -> (i64, i64) {
+	((p.x / GRID_CELL).floor() as i64, (p.y / GRID_CELL).floor() as i64)
+}
+
 // ─── DB loading ───────────────────────────────────────────────
 
 #[derive(Debug, Clone, SurrealValue)]
 struct MachineRow {
 	id: RecordId,
 	name: String,
+	color: Option<String>,
+	/// Last probe's answer for remotes; the local machine is always up.
+	online: Option<bool>,
 }
 
 #[derive(Debug, Clone, SurrealValue)]
@@ -780,6 +1023,9 @@ struct DriveRow {
 	name: String,
 	connected: bool,
 	mount_point: Option<String>,
+	color: Option<String>,
+	capacity_bytes: Option<i64>,
+	free_bytes: Option<i64>,
 }
 
 #[derive(Debug, Clone, SurrealValue)]
@@ -788,6 +1034,9 @@ struct LocationRow {
 	machine: Option<RecordId>,
 	drive: Option<RecordId>,
 	path: String,
+	label: Option<String>,
+	available: bool,
+	is_dir: Option<bool>,
 	graph_x: Option<f64>,
 	graph_y: Option<f64>,
 }
@@ -795,8 +1044,9 @@ struct LocationRow {
 #[derive(Debug, Clone, SurrealValue)]
 struct IntentRow {
 	id: String,
+	name: Option<String>,
 	source: String,
-	dest_id: String,
+	dest_ids: Vec<String>,
 	status: String,
 	total_files: i64,
 	completed_files: i64,
@@ -858,7 +1108,7 @@ async fn load_containers(db: &DbHandle) -> Result<Vec<ContainerView>, String> {
 
 	let mut resp = db
 		.db
-		.query("SELECT id, name FROM machine")
+		.query("SELECT id, name, color, online FROM machine")
 		.await
 		.map_err(|e| e.to_string())?;
 	let machines: Vec<MachineRow> = resp.take(0).map_err(|e| e.to_string())?;
@@ -875,15 +1125,17 @@ async fn load_containers(db: &DbHandle) -> Result<Vec<ContainerView>, String> {
 			} else {
 				"remote".into()
 			},
-			color: palette_color(i).to_string(),
-			connected: true,
+			color: m.color.clone().unwrap_or_else(|| palette_color(i).to_string()),
+			connected: is_local || m.online == Some(true),
 			mount_point: if is_local { dirs_home() } else { None },
+			capacity_bytes: None,
+			free_bytes: None,
 		});
 	}
 
 	let mut resp = db
 		.db
-		.query("SELECT id, name, connected, mount_point FROM drive")
+		.query("SELECT id, name, connected, mount_point, color, capacity_bytes, free_bytes FROM drive")
 		.await
 		.map_err(|e| e.to_string())?;
 	let drives: Vec<DriveRow> = resp.take(0).map_err(|e| e.to_string())?;
@@ -901,9 +1153,11 @@ async fn load_containers(db: &DbHandle) -> Result<Vec<ContainerView>, String> {
 			id: d.id.clone(),
 			name: d.name.clone(),
 			kind: "drive".into(),
-			color: palette_color(offset + i).to_string(),
+			color: d.color.clone().unwrap_or_else(|| palette_color(offset + i).to_string()),
 			connected: d.connected,
 			mount_point: d.mount_point.clone(),
+			capacity_bytes: d.capacity_bytes,
+			free_bytes: d.free_bytes,
 		});
 	}
 
@@ -931,10 +1185,12 @@ async fn load_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<G
 			},
 			parent_id: None,
 			color: container.color.clone(),
-			position: random_start_position(),
+			position: seeded_position(&cid),
 			velocity: Vec2::default(),
 			pinned: false,
 			visible: true,
+			child_count: 0,
+			missing: false,
 			width: w,
 			height: h,
 			fx: None,
@@ -942,11 +1198,42 @@ async fn load_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<G
 		});
 	}
 
+	// Only top-level locations get nodes up front; deeper ones are loaded
+	// when their directory is expanded
+	let locations = load_location_nodes(db, containers).await?;
+	nodes.extend(locations.into_iter().filter(|n| n.visible));
+
+	Ok(nodes)
+}
+
+/// Load the location nodes directly under `parent_id`, for a directory that
+/// is being expanded for the first time. They come back visible.
+pub async fn load_child_nodes(
+	db: &DbHandle,
+	containers: &[ContainerView],
+	parent_id: &str,
+) -> Result<Vec<GraphNode>, String> {
+	let mut children: Vec<GraphNode> = load_location_nodes(db, containers)
+		.await?
+		.into_iter()
+		.filter(|n| n.parent_id.as_deref() == Some(parent_id))
+		.collect();
+	for child in &mut children {
+		child.visible = true;
+	}
+	Ok(children)
+}
+
+/// Build a node for every location, with parent links and child counts.
+/// Only top-level locations (directly under their machine/drive) are visible.
+async fn load_location_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<GraphNode>, String> {
+	let mut nodes = Vec::new();
+
 	// Load locations from DB
 	tracing::info!("Loading locations from database...");
 	let mut resp = db
 		.db
-		.query("SELECT id, machine, drive, path, graph_x, graph_y FROM location ORDER BY path ASC")
+		.query("SELECT id, machine, drive, path, label, available, is_dir, graph_x, graph_y FROM location ORDER BY path ASC")
 		.await
 		.map_err(|e| e.to_string())?;
 	let rows: Vec<LocationRow> = resp.take(0).map_err(|e| e.to_string())?;
@@ -975,12 +1262,14 @@ async fn load_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<G
 
 		let parent_rid = rid_string(owner_id);
 
-		// Determine if directory
-		// Check if any other location is a child of this path, OR if it ends with /
-		let is_dir = row.path.ends_with('/')
-			|| all_paths
-				.iter()
-				.any(|&other| path_contains(&row.path, other));
+		// Determine if directory: as recorded when the location was added, else
+		// if any other location is a child of this path, OR if it ends with /
+		let is_dir = row.is_dir.unwrap_or_else(|| {
+			row.path.ends_with('/')
+				|| all_paths
+					.iter()
+					.any(|&other| path_contains(&row.path, other))
+		});
 
 		if is_dir {
 			tracing::info!("Location {} is a directory (path: {})", rid_string(&row.id), row.path);
@@ -1000,10 +1289,11 @@ async fn load_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<G
 
 		let (w, h) = node_dimensions(&kind, child_count);
 
-		// Use saved position if available
+		// Use saved position if available, else start near the owner
+		let id = rid_string(&row.id);
 		let (position, pinned) = match (row.graph_x, row.graph_y) {
 			(Some(x), Some(y)) => (Vec2::new(x, y), true),
-			_ => (random_start_position(), false),
+			_ => (position_near(&id, seeded_position(&parent_rid)), false),
 		};
 
 		// Determine parent_id: find closest ancestor among existing locations,
@@ -1022,8 +1312,8 @@ async fn load_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<G
 			.any(|other| other.id != row.id && path_contains(&other.path, &row.path));
 
 		nodes.push(GraphNode {
-			id: rid_string(&row.id),
-			label: short_path(&row.path),
+			id,
+			label: location_label(row.label.as_deref(), &row.path),
 			path: row.path.clone(),
 			kind,
 			parent_id: Some(parent_id),
@@ -1032,6 +1322,8 @@ async fn load_nodes(db: &DbHandle, containers: &[ContainerView]) -> Result<Vec<G
 			velocity: Vec2::default(),
 			pinned,
 			visible: is_top_level,
+			child_count,
+			missing: !row.available,
 			width: w,
 			height: h,
 			fx: None,
@@ -1049,6 +1341,8 @@ fn create_hierarchy_edges(nodes: &[GraphNode]) -> Vec<GraphEdge> {
 		if let Some(ref parent_id) = node.parent_id {
 			edges.push(GraphEdge {
 				id: format!("hier_{}_{}", parent_id, node.id),
+				intent_id: None,
+				name: None,
 				source_id: parent_id.clone(),
 				dest_id: node.id.clone(),
 				status: "idle".to_string(),
@@ -1065,7 +1359,7 @@ async fn load_edges(db: &DbHandle) -> Result<Vec<GraphEdge>, String> {
 	let mut resp = db
 		.db
 		.query(
-			"SELECT string::slice(id, 0, 100) AS id, string::slice(source, 0, 100) AS source, destinations[0] AS dest_id, status, total_files, completed_files, created_at
+			"SELECT string::slice(id, 0, 100) AS id, name, string::slice(source, 0, 100) AS source, (destinations ?? []).map(|$d| string::slice(<string> $d, 0, 100)) AS dest_ids, status, total_files, completed_files, created_at
              FROM intent ORDER BY created_at DESC",
 		)
 		.await
@@ -1079,25 +1373,62 @@ async fn load_edges(db: &DbHandle) -> Result<Vec<GraphEdge>, String> {
 		}
 	};
 
+	// Each edge shows its own destination's jobs, not the whole intent's
+	let by_destination: HashMap<(String, String), DestinationResult> = match destination_results(db).await {
+		Ok(results) => results
+			.into_iter()
+			.map(|((intent, dest), result)| ((rid_string(&intent), rid_string(&dest)), result))
+			.collect(),
+		Err(e) => {
+			tracing::warn!("load_edges: failed to count jobs per destination: {}", e);
+			HashMap::new()
+		}
+	};
+
 	let mut edges = Vec::new();
 	for row in &rows {
-		if row.dest_id.is_empty() {
-			continue;
+		// One edge per destination, grouped by intent_id
+		for dest_id in row.dest_ids.iter().filter(|d| !d.is_empty()) {
+			let result = by_destination.get(&(row.id.clone(), dest_id.clone()));
+			let (total_files, completed_files) = match result {
+				Some(r) => (r.total as i64, r.completed as i64),
+				None => (row.total_files, row.completed_files),
+			};
+			edges.push(GraphEdge {
+				id: format!("{}->{}", row.id, dest_id),
+				intent_id: Some(row.id.clone()),
+				name: row.name.clone(),
+				source_id: row.source.clone(),
+				dest_id: dest_id.clone(),
+				status: destination_status(&row.status, result),
+				total_files,
+				completed_files,
+				created_at: row.created_at.clone(),
+			});
 		}
-		edges.push(GraphEdge {
-			id: row.id.clone(),
-			source_id: row.source.clone(),
-			dest_id: row.dest_id.clone(),
-			status: row.status.clone(),
-			total_files: row.total_files,
-			completed_files: row.completed_files,
-			created_at: row.created_at.clone(),
-		});
 	}
 	tracing::info!("Loaded {} edges", edges.len());
 	Ok(edges)
 }
 
+/// An edge's status: the intent's, except that a destination whose jobs
+/// have all finished shows how they finished even while the others run.
+/// Mid-scan the jobs are still being created, so counts don't mean much yet.
+fn destination_status(intent_status: &str, result: Option<&DestinationResult>) -> String {
+	match (intent_status, result) {
+		("transferring" | "complete" | "needs_review", Some(r)) if r.remaining == 0 => {
+			if r.needs_review > 0 {
+				"needs_review".to_string()
+			} else if r.failed > 0 {
+				"failed".to_string()
+			} else {
+				"complete".to_string()
+			}
+		}
+		_ => intent_status.to_string(),
+	}
+}
+
 async fn load_review_count(db: &DbHandle) -> Result<i64, String> {
 	let mut resp = db
 		.db
@@ -1110,17 +1441,40 @@ async fn load_review_count(db: &DbHandle) -> Result<i64, String> {
 
 // ─── DB actions ───────────────────────────────────────────────
 
+/// Create an intent from `source_id` to `dest_id` and return its id. A
+/// source that's a scanned folder (`fs:` node) rather than a location
+/// transfers just that folder: the intent's source is the location holding
+/// it, with the rest of the way as its `source_subpath`. A destination that
+/// is the source folder, or nests with it, is refused, as is a source
+/// location whose folder is gone or that is on a remote machine.
 pub async fn create_edge_in_db(db: &DbHandle, source_id: &str, dest_id: &str) -> Result<String, String> {
-	let (_, src_key) = source_id.split_once(':').ok_or("Invalid source ID")?;
+	let (source, subpath, source_root) = match source_id.strip_prefix("fs:") {
+		Some(path) => {
+			let (location, subpath) = location_containing(db, path).await?;
+			(location, Some(subpath), path.to_string())
+		}
+		None => {
+			let (_, src_key) = source_id.split_once(':').ok_or("Invalid source ID")?;
+			let location = RecordId::new("location", src_key);
+			let (path, remote) = location_path(db, &location).await?;
+			// Remote machines only receive copies; see `engine::transport`
+			if remote {
+				return Err(format!("can't copy from a remote machine, only to one: {path}"));
+			}
+			check_source_dir(&path)?;
+			(location, None, path)
+		}
+	};
 	let (_, dst_key) = dest_id.split_once(':').ok_or("Invalid dest ID")?;
+	check_endpoints(db, &source_root, &RecordId::new("location", dst_key)).await?;
 
 	let mut resp = db
 		.db
 		.query(
-			"LET $src = type::record('location', $src_key);
-             LET $dst = type::record('location', $dst_key);
+			"LET $dst = type::record('location', $dst_key);
              CREATE intent CONTENT {
                  source: $src,
+                 source_subpath: $subpath,
                  destinations: [$dst],
                  status: 'idle',
                  kind: 'one_shot',
@@ -1134,20 +1488,154 @@ pub async fn create_edge_in_db(db: &DbHandle, source_id: &str, dest_id: &str) ->
                  initial_sync_complete: false,
                  created_at: time::now(),
                  updated_at: time::now(),
-             }",
+             } RETURN VALUE id",
 		)
-		.bind(("src_key", src_key.to_string()))
+		.bind(("src", source))
+		.bind(("subpath", subpath))
 		.bind(("dst_key", dst_key.to_string()))
 		.await
 		.map_err(|e| e.to_string())?;
 
-	resp.check().map_err(|e| e.to_string())?;
-	Ok("created".into())
+	let mut resp = resp.check().map_err(|e| e.to_string())?;
+	let ids: Vec<RecordId> = resp.take(1).map_err(|e| e.to_string())?;
+	ids.first().map(rid_string).ok_or_else(|| "intent wasn't created".to_string())
 }
 
-pub async fn add_remote_machine(db: &DbHandle, name: &str, hostname: &str, ssh_user: &str) -> Result<(), String> {
-	let display_name = if name.is_empty() { hostname } else { name };
+/// A location's path, and whether it's on a remote machine.
+async fn location_path(db: &DbHandle, location: &RecordId) -> Result<(String, bool), String> {
+	let mut resp = db
+		.db
+		.query("SELECT path, machine.kind = 'remote' AS remote FROM ONLY $id")
+		.bind(("id", location.clone()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let row: Option<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+	let row = row.ok_or_else(|| format!("location not found: {}", rid_string(location)))?;
+	let path = row["path"].as_str().ok_or("location has no path")?.to_string();
+	Ok((path, row["remote"].as_bool() == Some(true)))
+}
 
+/// Whether `path` exists on this machine, and if so whether it's a
+/// directory.
+pub fn probe_path(path: &str) -> Option<bool> {
+	std::fs::metadata(kip_core::util::decode_path(path)).ok().map(|m| m.is_dir())
+}
+
+/// Refuse a local source that isn't there or isn't a folder, rather than
+/// creating an intent whose first scan fails.
+fn check_source_dir(path: &str) -> Result<(), String> {
+	match probe_path(path) {
+		Some(true) => Ok(()),
+		Some(false) => Err(format!("source is not a folder: {path}")),
+		None => Err(format!("source does not exist: {path}")),
+	}
+}
+
+/// Re-check every location's `available` flag. A local location is
+/// available while its path exists, and a drive's only while the drive is
+/// connected too. A remote machine's paths can't be looked at from here, so
+/// its locations follow the machine's `online` flag. Returns the locations
+/// whose flag changed.
+pub async fn refresh_location_availability(db: &DbHandle) -> Result<Vec<RecordId>, String> {
+	let mut resp = db
+		.db
+		.query(
+			"SELECT id, path, available, machine.kind = 'remote' AS remote, machine.online AS online,
+                    drive != NONE AS on_drive, drive.connected AS connected
+             FROM location",
+		)
+		.await
+		.map_err(|e| e.to_string())?;
+	let rows: Vec<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+
+	// Off the runtime: a stat on a slow network share can take a while
+	let changed: Vec<(RecordId, bool)> = tokio::task::spawn_blocking(move || {
+		rows.iter()
+			.filter_map(|row| {
+				let id = serde_json::from_value::<RecordId>(row["id"].clone()).ok()?;
+				let available = if row["remote"].as_bool() == Some(true) {
+					row["online"].as_bool() == Some(true)
+				} else if row["on_drive"].as_bool() == Some(true) && row["connected"].as_bool() != Some(true) {
+					false
+				} else {
+					probe_path(row["path"].as_str()?).is_some()
+				};
+				(row["available"].as_bool() != Some(available)).then_some((id, available))
+			})
+			.collect()
+	})
+	.await
+	.map_err(|e| e.to_string())?;
+
+	for (id, available) in &changed {
+		info!("{} is now {}", rid_string(id), if *available { "available" } else { "missing" });
+		db.db
+			.query("UPDATE $id SET available = $available")
+			.bind(("id", id.clone()))
+			.bind(("available", *available))
+			.await
+			.map_err(|e| e.to_string())?
+			.check()
+			.map_err(|e| e.to_string())?;
+	}
+	Ok(changed.into_iter().map(|(id, _)| id).collect())
+}
+
+/// Refuse a local destination that is the folder an intent copies from, or
+/// holds it or sits inside it.
+async fn check_endpoints(db: &DbHandle, source_root: &str, dest: &RecordId) -> Result<(), String> {
+	let (dest_path, remote) = location_path(db, dest).await?;
+	if remote {
+		return Ok(());
+	}
+	scanner::check_overlap(source_root, &dest_path).map_err(|e| e.to_string())
+}
+
+/// The innermost location holding `path`, and `path` relative to it.
+async fn location_containing(db: &DbHandle, path: &str) -> Result<(RecordId, String), String> {
+	let mut resp = db
+		.db
+		.query("SELECT id, path FROM location")
+		.await
+		.map_err(|e| e.to_string())?;
+	let rows: Vec<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+
+	let (id, base) = rows
+		.iter()
+		.filter_map(|row| {
+			let base = row["path"].as_str()?;
+			let id = serde_json::from_value::<RecordId>(row["id"].clone()).ok()?;
+			path_contains(base, path).then(|| (id, base.to_string()))
+		})
+		.max_by_key(|(_, base)| base.len())
+		.ok_or_else(|| format!("{path} isn't inside a location; add it as a location first"))?;
+	let subpath = path[base.trim_end_matches('/').len()..].trim_start_matches('/').to_string();
+	Ok((id, subpath))
+}
+
+/// A remote machine's connection settings as edited in the machine form.
+/// Blank optional fields mean "not set".
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MachineSettings {
+	pub name: String,
+	pub hostname: String,
+	pub ssh_user: String,
+	pub ssh_key_path: String,
+	pub ssh_proxy: String,
+}
+
+impl MachineSettings {
+	/// The name shown in the graph, defaulting to the hostname.
+	fn display_name(&self) -> String {
+		if self.name.is_empty() { self.hostname.clone() } else { self.name.clone() }
+	}
+}
+
+fn non_empty(s: &str) -> Option<String> {
+	Some(s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+pub async fn add_remote_machine(db: &DbHandle, settings: &MachineSettings) -> Result<(), String> {
 	db.db
 		.query(
 			"CREATE machine CONTENT {
@@ -1156,20 +1644,303 @@ pub async fn add_remote_machine(db: &DbHandle, name: &str, hostname: &str, ssh_u
                 hostname: $hostname,
                 is_current: false,
                 ssh_user: $ssh_user,
+                ssh_key_path: $ssh_key_path,
+                ssh_proxy: $ssh_proxy,
                 last_seen: time::now(),
                 online: false,
             }",
 		)
-		.bind(("name", display_name.to_string()))
-		.bind(("hostname", hostname.to_string()))
-		.bind((
-			"ssh_user",
-			if ssh_user.is_empty() {
-				"root".to_string()
-			} else {
-				ssh_user.to_string()
-			},
-		))
+		.bind(("name", settings.display_name()))
+		.bind(("hostname", settings.hostname.clone()))
+		.bind(("ssh_user", non_empty(&settings.ssh_user)))
+		.bind(("ssh_key_path", non_empty(&settings.ssh_key_path)))
+		.bind(("ssh_proxy", non_empty(&settings.ssh_proxy)))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Read a remote machine's settings back into the form.
+pub async fn load_machine(db: &DbHandle, machine_id: &str) -> Result<MachineSettings, String> {
+	let (_, key) = machine_id.split_once(':').ok_or("Invalid machine ID")?;
+
+	let mut resp = db
+		.db
+		.query("SELECT name, hostname, ssh_user, ssh_key_path, ssh_proxy FROM type::record('machine', $key)")
+		.bind(("key", key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let row: Option<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+	let row = row.ok_or_else(|| format!("machine not found: {machine_id}"))?;
+
+	let field = |name: &str| row[name].as_str().unwrap_or_default().to_string();
+	Ok(MachineSettings {
+		name: field("name"),
+		hostname: field("hostname"),
+		ssh_user: field("ssh_user"),
+		ssh_key_path: field("ssh_key_path"),
+		ssh_proxy: field("ssh_proxy"),
+	})
+}
+
+/// Change a remote machine's connection settings in place, keeping its
+/// locations and intents.
+pub async fn update_machine(db: &DbHandle, machine_id: &str, settings: &MachineSettings) -> Result<(), String> {
+	let (_, key) = machine_id.split_once(':').ok_or("Invalid machine ID")?;
+
+	db.db
+		.query(
+			"UPDATE type::record('machine', $key) SET
+                name = $name,
+                hostname = $hostname,
+                ssh_user = $ssh_user,
+                ssh_key_path = $ssh_key_path,
+                ssh_proxy = $ssh_proxy
+             WHERE kind = 'remote'",
+		)
+		.bind(("key", key.to_string()))
+		.bind(("name", settings.display_name()))
+		.bind(("hostname", settings.hostname.clone()))
+		.bind(("ssh_user", non_empty(&settings.ssh_user)))
+		.bind(("ssh_key_path", non_empty(&settings.ssh_key_path)))
+		.bind(("ssh_proxy", non_empty(&settings.ssh_proxy)))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Add another destination to an existing intent, so one source fans out
+/// to several targets. Already-listed destinations are left as they are.
+/// The new destination gets its jobs on the intent's next run. One that
+/// overlaps the intent's source is refused.
+pub async fn add_destination(db: &DbHandle, intent_id: &str, dest_location_id: &str) -> Result<(), String> {
+	let (_, intent_key) = intent_id.split_once(':').ok_or("Invalid intent ID")?;
+	let (_, dst_key) = dest_location_id.split_once(':').ok_or("Invalid dest ID")?;
+
+	let mut resp = db
+		.db
+		.query("SELECT (<record> source).path AS path, source_subpath FROM ONLY type::record('intent', $key)")
+		.bind(("key", intent_key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let row: Option<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+	let row = row.ok_or_else(|| format!("intent not found: {intent_id}"))?;
+	let source_path = row["path"].as_str().ok_or("intent's source has no path")?;
+	let source_root = match row["source_subpath"].as_str().filter(|s| !s.is_empty()) {
+		Some(subpath) => format!("{}/{}", source_path.trim_end_matches('/'), subpath),
+		None => source_path.to_string(),
+	};
+	check_endpoints(db, &source_root, &RecordId::new("location", dst_key)).await?;
+
+	db.db
+		.query(
+			"LET $dst = type::record('location', $dst_key);
+             UPDATE type::record('intent', $intent_key) SET
+                 destinations = array::union(destinations, [$dst]),
+                 updated_at = time::now()",
+		)
+		.bind(("intent_key", intent_key.to_string()))
+		.bind(("dst_key", dst_key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Persist a container's color override. `None` clears it, falling back to
+/// the palette color on next load.
+pub async fn save_container_color(db: &DbHandle, container_id: &str, color: Option<String>) -> Result<(), String> {
+	let (table, key) = container_id.split_once(':').ok_or("Invalid container ID")?;
+
+	let query = match table {
+		"machine" => "UPDATE type::record('machine', $key) SET color = $color",
+		"drive" => "UPDATE type::record('drive', $key) SET color = $color",
+		_ => return Err(format!("not a container: {container_id}")),
+	};
+
+	db.db
+		.query(query)
+		.bind(("key", key.to_string()))
+		.bind(("color", color))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Delete an intent (a graph edge), its jobs and review items. Running jobs
+/// are cancelled first, the same way cancelling the intent would, so the
+/// scheduler stops dispatching before the records disappear.
+pub async fn delete_intent(db: &DbHandle, intent_id: &str) -> Result<(), String> {
+	let (table, key) = intent_id.split_once(':').ok_or("Invalid intent ID")?;
+	if table != "intent" {
+		return Err(format!("not an intent: {intent_id}"));
+	}
+
+	db.db
+		.query(
+			"LET $intent = type::record('intent', $key);
+             UPDATE $intent SET status = 'cancelled', updated_at = time::now();
+             UPDATE transfer_job SET status = 'cancelled'
+                 WHERE <record> intent = $intent AND status IN ['pending', 'transferring'];
+             DELETE review_item WHERE <record> intent = $intent;
+             DELETE transfer_job WHERE <record> intent = $intent;
+             DELETE scan_entry WHERE <record> intent = $intent;
+             DELETE $intent;",
+		)
+		.bind(("key", key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Intents that read from or write to a location, for confirming a delete.
+pub async fn intents_using_location(db: &DbHandle, location_id: &str) -> Result<u64, String> {
+	let (_, key) = location_id.split_once(':').ok_or("Invalid location ID")?;
+
+	let mut resp = db
+		.db
+		.query(
+			"LET $loc = type::record('location', $key);
+             SELECT VALUE id FROM intent
+             WHERE <record> source = $loc OR $loc IN destinations.map(|$d| <record> $d);",
+		)
+		.bind(("key", key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let ids: Vec<RecordId> = resp.take(1).map_err(|e| e.to_string())?;
+	Ok(ids.len() as u64)
+}
+
+/// Delete a location along with every intent that references it (and those
+/// intents' jobs and review items). Each intent goes through
+/// `delete_intent`, so its running jobs are cancelled first. Files on disk
+/// are left alone; `trash_location` removes the folder too.
+pub async fn delete_location(db: &DbHandle, location_id: &str) -> Result<(), String> {
+	let (table, key) = location_id.split_once(':').ok_or("Invalid location ID")?;
+	if table != "location" {
+		return Err(format!("not a location: {location_id}"));
+	}
+
+	let mut resp = db
+		.db
+		.query(
+			"LET $loc = type::record('location', $key);
+             SELECT VALUE id FROM intent
+             WHERE <record> source = $loc OR $loc IN destinations.map(|$d| <record> $d);",
+		)
+		.bind(("key", key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let intents: Vec<RecordId> = resp.take(1).map_err(|e| e.to_string())?;
+	for intent in &intents {
+		delete_intent(db, &rid_string(intent)).await?;
+	}
+
+	db.db
+		.query(
+			"LET $loc = type::record('location', $key);
+             DELETE exists_at WHERE out = $loc;
+             DELETE $loc;",
+		)
+		.bind(("key", key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// `delete_location`, then move the location's folder to the OS trash (or
+/// delete it permanently where the volume has no trash). Returns the path
+/// and how it went, or `None` when the folder was already gone. Remote
+/// locations are refused up front: their folders aren't ours to remove.
+pub async fn trash_location(db: &DbHandle, location_id: &str) -> Result<Option<(String, Disposal)>, String> {
+	let (table, key) = location_id.split_once(':').ok_or("Invalid location ID")?;
+	if table != "location" {
+		return Err(format!("not a location: {location_id}"));
+	}
+	let (path, remote) = location_path(db, &RecordId::new("location", key)).await?;
+	if remote {
+		return Err(format!("can't trash a folder on a remote machine: {path}"));
+	}
+
+	delete_location(db, location_id).await?;
+
+	let folder = kip_core::util::decode_path(&path);
+	if !folder.exists() {
+		return Ok(None);
+	}
+	let disposal = copier::move_to_trash(&folder).map_err(|e| e.to_string())?;
+	info!("location {} removed from disk: {} ({:?})", location_id, path, disposal);
+	Ok(Some((path, disposal)))
+}
+
+/// Set or (with `None`) clear a location's display label.
+pub async fn save_location_label(db: &DbHandle, location_id: &str, label: Option<String>) -> Result<(), String> {
+	let (table, key) = location_id.split_once(':').ok_or("Invalid location ID")?;
+	if table != "location" {
+		return Err(format!("not a location: {location_id}"));
+	}
+
+	db.db
+		.query("UPDATE type::record('location', $key) SET label = $label")
+		.bind(("key", key.to_string()))
+		.bind(("label", label))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Name an intent, or clear its name with `None` so it shows its paths again.
+pub async fn save_intent_name(db: &DbHandle, intent_id: &str, name: Option<String>) -> Result<(), String> {
+	let (table, key) = intent_id.split_once(':').ok_or("Invalid intent ID")?;
+	if table != "intent" {
+		return Err(format!("not an intent: {intent_id}"));
+	}
+
+	db.db
+		.query("UPDATE type::record('intent', $key) SET name = $name, updated_at = time::now()")
+		.bind(("key", key.to_string()))
+		.bind(("name", name))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Set an intent's dispatch priority; its jobs run before those of intents
+/// with a lower one.
+pub async fn update_intent_priority(db: &DbHandle, intent_id: &str, priority: i64) -> Result<(), String> {
+	let (table, key) = intent_id.split_once(':').ok_or("Invalid intent ID")?;
+	if table != "intent" {
+		return Err(format!("not an intent: {intent_id}"));
+	}
+
+	db.db
+		.query("UPDATE type::record('intent', $key) SET priority = $priority, updated_at = time::now()")
+		.bind(("key", key.to_string()))
+		.bind(("priority", priority))
 		.await
 		.map_err(|e| e.to_string())?
 		.check()
@@ -1290,6 +2061,8 @@ pub async fn scan_directory(
 			velocity: Vec2::default(),
 			pinned: false,
 			visible: true,
+			child_count: 0,
+			missing: false,
 			width: if is_dir { 60.0 } else { 70.0 },  // Reduced width for new layout
 			height: if is_dir { 60.0 } else { 56.0 }, // Increased height for icon+label
 			fx: None,
@@ -1305,13 +2078,198 @@ pub async fn scan_directory(
 
 // ─── Helpers ──────────────────────────────────────────────────
 
-fn random_start_position() -> Vec2 {
-	// Spread initial positions around center to avoid all-at-origin
-	use std::hash::{Hash, Hasher};
-	let mut hasher = std::collections::hash_map::DefaultHasher::new();
-	std::time::Instant::now().hash(&mut hasher);
-	let h = hasher.finish();
+/// Starting spot for a node that was never placed, derived from its id so
+/// the layout comes out the same on every launch. Spread around the center
+/// to avoid all-at-origin.
+fn seeded_position(id: &str) -> Vec2 {
+	let h = xxhash_rust::xxh3::xxh3_64(id.as_bytes());
 	let x = 300.0 + ((h % 600) as f64);
 	let y = 200.0 + (((h >> 16) % 400) as f64);
 	Vec2::new(x, y)
 }
+
+/// A stable spot 100–180 units from `center`, so a container's locations
+/// start clustered around it.
+fn position_near(id: &str, center: Vec2) -> Vec2 {
+	let h = xxhash_rust::xxh3::xxh3_64(id.as_bytes());
+	let angle = (h % 360) as f64 * std::f64::consts::PI / 180.0;
+	let radius = 100.0 + ((h >> 16) % 80) as f64;
+	center + Vec2::new(radius * angle.cos(), radius * angle.sin())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// `home` syncs to `usb`, and `home` also backs up to `nas`, each with a
+	/// running job and a review item. Endpoints are stored as strings.
+	async fn db_with_intents() -> (DbHandle, tempfile::TempDir) {
+		let db = crate::db::init_memory().await.unwrap();
+		let root = tempfile::tempdir().unwrap();
+		for dir in ["home", "usb", "nas", "other"] {
+			std::fs::create_dir(root.path().join(dir)).unwrap();
+		}
+		db.db
+			.query(
+				"CREATE location:home CONTENT { path: $home, available: true };
+                 CREATE location:usb CONTENT { path: $usb, available: true };
+                 CREATE location:nas CONTENT { path: $nas, available: true };
+                 CREATE location:other CONTENT { path: $other, available: true };
+                 CREATE intent:to_usb CONTENT {
+                     source: 'location:home', destinations: ['location:usb'], status: 'transferring',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };
+                 CREATE intent:to_nas CONTENT {
+                     source: 'location:home', destinations: ['location:nas'], status: 'transferring',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };
+                 FOR $intent IN [intent:to_usb, intent:to_nas] {
+                     CREATE transfer_job CONTENT {
+                         intent: $intent, destination: location:usb,
+                         source_path: '/a', dest_path: '/b', size: 1, status: 'transferring',
+                     };
+                     CREATE review_item CONTENT {
+                         intent: $intent, job: 'transfer_job:none', error_kind: 'io_error', error_message: 'x',
+                         source_path: '/a', dest_path: '/b', options: ['retry'], created_at: time::now(),
+                     };
+                 };",
+			)
+			.bind(("home", root.path().join("home").to_str().unwrap().to_string()))
+			.bind(("usb", root.path().join("usb").to_str().unwrap().to_string()))
+			.bind(("nas", root.path().join("nas").to_str().unwrap().to_string()))
+			.bind(("other", root.path().join("other").to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		(db, root)
+	}
+
+	async fn remaining(db: &DbHandle) -> (Vec<RecordId>, usize, usize) {
+		let mut resp = db
+			.db
+			.query(
+				"SELECT VALUE id FROM intent ORDER BY id;
+                 SELECT VALUE id FROM transfer_job;
+                 SELECT VALUE id FROM review_item;",
+			)
+			.await
+			.unwrap();
+		let intents: Vec<RecordId> = resp.take(0).unwrap();
+		let jobs: Vec<RecordId> = resp.take(1).unwrap();
+		let items: Vec<RecordId> = resp.take(2).unwrap();
+		(intents, jobs.len(), items.len())
+	}
+
+	#[tokio::test]
+	async fn intents_using_location_counts_both_ends() {
+		let (db, _root) = db_with_intents().await;
+
+		assert_eq!(intents_using_location(&db, "location:home").await.unwrap(), 2);
+		assert_eq!(intents_using_location(&db, "location:usb").await.unwrap(), 1);
+		assert_eq!(intents_using_location(&db, "location:other").await.unwrap(), 0);
+	}
+
+	#[tokio::test]
+	async fn deleting_an_intent_takes_its_jobs_and_review_items() {
+		let (db, _root) = db_with_intents().await;
+
+		delete_intent(&db, "intent:to_usb").await.unwrap();
+
+		assert_eq!(remaining(&db).await, (vec![RecordId::new("intent", "to_nas")], 1, 1));
+		assert!(delete_intent(&db, "location:usb").await.is_err());
+	}
+
+	#[tokio::test]
+	async fn deleting_a_location_takes_only_the_intents_using_it() {
+		let (db, _root) = db_with_intents().await;
+
+		delete_location(&db, "location:usb").await.unwrap();
+
+		assert_eq!(remaining(&db).await, (vec![RecordId::new("intent", "to_nas")], 1, 1));
+		let mut resp = db.db.query("SELECT VALUE id FROM location:usb").await.unwrap();
+		let gone: Vec<RecordId> = resp.take(0).unwrap();
+		assert!(gone.is_empty());
+
+		delete_location(&db, "location:home").await.unwrap();
+		assert_eq!(remaining(&db).await, (Vec::new(), 0, 0));
+	}
+
+	#[tokio::test]
+	async fn trashing_a_location_refuses_remote_folders() {
+		let (db, root) = db_with_intents().await;
+		db.db
+			.query(
+				"CREATE machine:nas CONTENT {
+                     name: 'NAS', kind: 'remote', hostname: 'nas', is_current: false, online: true,
+                     last_seen: time::now(),
+                 };
+                 UPDATE location:nas SET machine = machine:nas;",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let err = trash_location(&db, "location:nas").await.unwrap_err();
+		assert!(err.contains("remote"), "{err}");
+		assert_eq!(intents_using_location(&db, "location:nas").await.unwrap(), 1);
+		assert!(root.path().join("nas").exists());
+
+		// A folder already gone from disk still takes the location with it
+		std::fs::remove_dir(root.path().join("usb")).unwrap();
+		assert_eq!(trash_location(&db, "location:usb").await.unwrap(), None);
+		assert_eq!(remaining(&db).await, (vec![RecordId::new("intent", "to_nas")], 1, 1));
+	}
+
+	#[tokio::test]
+	async fn adding_a_destination_fans_out_once() {
+		let (db, _root) = db_with_intents().await;
+
+		add_destination(&db, "intent:to_usb", "location:other").await.unwrap();
+		add_destination(&db, "intent:to_usb", "location:other").await.unwrap();
+
+		let mut resp = db
+			.db
+			.query("SELECT VALUE destinations.map(|$d| <string> $d) FROM ONLY intent:to_usb")
+			.await
+			.unwrap();
+		let destinations: Option<Vec<String>> = resp.take(0).unwrap();
+		assert_eq!(destinations.unwrap(), vec!["location:usb".to_string(), "location:other".to_string()]);
+	}
+
+	#[tokio::test]
+	async fn a_remote_source_is_refused() {
+		let (db, _root) = db_with_intents().await;
+		db.db
+			.query(
+				"CREATE machine:nas CONTENT {
+                     name: 'NAS', kind: 'remote', hostname: 'nas', is_current: false, online: true,
+                     last_seen: time::now(),
+                 };
+                 CREATE location:nas_photos CONTENT { path: '/srv/photos', machine: machine:nas, available: true };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let err = create_edge_in_db(&db, "location:nas_photos", "location:usb").await.unwrap_err();
+		assert!(err.contains("remote"), "{err}");
+	}
+
+	#[tokio::test]
+	async fn a_destination_overlapping_the_source_is_refused() {
+		let (db, root) = db_with_intents().await;
+		std::fs::create_dir(root.path().join("home/inner")).unwrap();
+		db.db
+			.query("CREATE location:inner CONTENT { path: $path, available: true }")
+			.bind(("path", root.path().join("home/inner").to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		assert!(add_destination(&db, "intent:to_usb", "location:inner").await.is_err());
+	}
+}
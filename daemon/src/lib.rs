@@ -10,9 +10,13 @@
 
 pub mod db;
 pub mod engine;
+pub mod events;
 pub mod graph_store;
 
 pub use graph_store::{
-	add_remote_machine, load_graph_data, rid_string, save_node_position, scan_directory, DragState, Graph,
+	add_destination, add_remote_machine, create_edge_in_db, delete_intent, delete_location, intents_using_location,
+	load_child_nodes, load_graph_data, load_machine, probe_path, refresh_location_availability, rid_string,
+	save_container_color, save_intent_name, save_location_label, save_node_position, scan_directory,
+	trash_location, update_intent_priority, update_machine, DragState, Graph, MachineSettings,
 };
 pub use db::DbHandle;
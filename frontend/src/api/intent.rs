@@ -2,6 +2,7 @@
 
 use chrono::Utc;
 use daemon::DbHandle;
+use surrealdb_types::RecordId;
 
 use crate::api::{KipError, LocationId, RunResult};
 
@@ -12,14 +13,32 @@ pub async fn create_intent(
 	destinations: Vec<LocationId>,
 	config: crate::api::IntentConfig,
 ) -> Result<LocationId, KipError> {
+	check_locations(db, &source, &destinations).await?;
 	let intent_id = format!("intent:{}", ulid::Ulid::new());
 
+	let retryable_errors = config.retryable_errors.unwrap_or_else(|| {
+		kip_core::models::intent::DEFAULT_RETRYABLE_ERRORS
+			.iter()
+			.map(|s| s.to_string())
+			.collect()
+	});
+
 	db.db
-        .query("CREATE intent CONTENT { source: $source, destinations: $destinations, status: 'idle', kind: 'backup', speed_mode: 'fast', priority: $priority, name: $name }")
+        .query("CREATE intent CONTENT { source: $source, destinations: $destinations, status: 'idle', kind: 'backup', speed_mode: 'fast', priority: $priority, name: $name, retryable_errors: $retryable_errors, job_order: $job_order, concurrency: $concurrency, follow_symlinks: $follow_symlinks, source_subpath: $source_subpath, verify_mode: $verify_mode, on_conflict: $on_conflict, overwrite_policy: $overwrite_policy, copy_strategy: $copy_strategy, write_manifest: $write_manifest }")
         .bind(("source", source))
         .bind(("destinations", destinations))
         .bind(("priority", config.priority as i64))
         .bind(("name", config.name))
+        .bind(("retryable_errors", retryable_errors))
+        .bind(("job_order", config.job_order.as_str().to_string()))
+        .bind(("concurrency", config.concurrency.map(|n| n as i64)))
+        .bind(("follow_symlinks", config.follow_symlinks))
+        .bind(("source_subpath", config.source_subpath))
+        .bind(("verify_mode", config.verify_mode.as_str().to_string()))
+        .bind(("on_conflict", config.on_conflict.as_str().to_string()))
+        .bind(("overwrite_policy", config.overwrite_policy.as_str().to_string()))
+        .bind(("copy_strategy", config.copy_strategy.as_str().to_string()))
+        .bind(("write_manifest", config.write_manifest))
         .await
         .map_err(|e| KipError::Database(e.to_string()))?
         .check()
@@ -28,12 +47,47 @@ pub async fn create_intent(
 	Ok(intent_id)
 }
 
+/// Refuse an intent whose source or a destination isn't a known location,
+/// or whose local source folder is gone, rather than failing at scan time.
+async fn check_locations(db: &DbHandle, source: &str, destinations: &[LocationId]) -> Result<(), KipError> {
+	let row = location_row(db, source).await?;
+	// A remote machine's folders can't be looked at from here
+	if row["remote"].as_bool() != Some(true) {
+		let path = row["path"].as_str().unwrap_or_default();
+		match daemon::probe_path(path) {
+			Some(true) => {}
+			Some(false) => return Err(KipError::SourcePathNotDir(path.into())),
+			None => return Err(KipError::SourcePathNotExists(path.into())),
+		}
+	}
+	for dest in destinations {
+		location_row(db, dest).await?;
+	}
+	Ok(())
+}
+
+/// A location's path and whether it's on a remote machine, by its id with
+/// or without the `location:` prefix.
+async fn location_row(db: &DbHandle, id: &str) -> Result<serde_json::Value, KipError> {
+	let key = id.strip_prefix("location:").unwrap_or(id);
+	let mut response = db
+		.db
+		.query("SELECT path, machine.kind = 'remote' AS remote FROM ONLY $id")
+		.bind(("id", RecordId::new("location", key)))
+		.await
+		.map_err(|e| KipError::Database(e.to_string()))?;
+	let row: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| KipError::Database(e.to_string()))?;
+	row.ok_or_else(|| KipError::LocationNotFound(id.to_string()))
+}
+
 /// Delete an intent
 pub async fn delete_intent(db: &DbHandle, intent_id: &str) -> Result<(), KipError> {
 	let intent_id = intent_id.to_string();
 
 	db.db
-		.query("DELETE FROM transfer_job WHERE intent = $intent")
+		.query("DELETE FROM transfer_job WHERE intent = $intent; DELETE FROM scan_entry WHERE intent = $intent;")
 		.bind(("intent", intent_id.clone()))
 		.await
 		.map_err(|e| KipError::Database(e.to_string()))?
@@ -81,6 +135,7 @@ pub async fn list_intents(db: &DbHandle) -> Result<Vec<crate::api::IntentSummary
 			"transferring" => crate::api::IntentStatus::Transferring,
 			"complete" => crate::api::IntentStatus::Complete,
 			"needs_review" => crate::api::IntentStatus::NeedsReview,
+			"waiting_for_device" => crate::api::IntentStatus::WaitingForDevice,
 			"error" => crate::api::IntentStatus::Error,
 			_ => crate::api::IntentStatus::Idle,
 		};
@@ -170,14 +225,28 @@ pub async fn run_intent(
 	intent_id: &str,
 	_progress: Option<crate::api::ProgressCallback>,
 ) -> Result<RunResult, KipError> {
-	use daemon::engine::scheduler;
+	use daemon::engine::{scanner, scheduler};
 
 	let record_id = surrealdb::types::RecordId::new("intent", intent_id);
 
 	let status = get_intent_status(db, &record_id).await?;
 
-	if status == crate::api::IntentStatus::Idle {
-		scan_intent(db, intent_id).await?;
+	// Only the call that claims the intent (by scanning it, or by moving it to
+	// `transferring`) goes on to dispatch; a second start is a no-op
+	let claimed = if status == crate::api::IntentStatus::Idle {
+		match scanner::scan_intent(db, &record_id).await {
+			Ok(_) => true,
+			// Another start won the race, or the scan was cancelled
+			Err(scanner::ScanError::AlreadyRunning(_) | scanner::ScanError::Cancelled) => false,
+			Err(e) => return Err(KipError::Database(e.to_string())),
+		}
+	} else {
+		scheduler::claim_intent(db, &record_id)
+			.await
+			.map_err(|e| KipError::Database(e.to_string()))?
+	};
+	if !claimed {
+		return Ok(RunResult::default());
 	}
 
 	let result = scheduler::run_intent(db, &record_id)
@@ -193,8 +262,29 @@ pub async fn run_intent(
 	})
 }
 
-/// Cancel an intent
+/// Run pending jobs across all intents, highest priority first
+pub async fn run_all_pending(db: &DbHandle) -> Result<RunResult, KipError> {
+	let result = daemon::engine::scheduler::run_all_pending(db)
+		.await
+		.map_err(|e| KipError::Database(e.to_string()))?;
+
+	Ok(RunResult {
+		completed: result.completed,
+		failed: result.failed,
+		needs_review: result.needs_review,
+		bytes_transferred: 0,
+		duration: std::time::Duration::from_secs(0),
+	})
+}
+
+/// Cancel an intent. One still scanning just has its scan stopped, which
+/// writes no jobs and leaves it `idle`.
 pub async fn cancel_intent(db: &DbHandle, intent_id: &str) -> Result<(), KipError> {
+	let key = intent_id.strip_prefix("intent:").unwrap_or(intent_id);
+	if daemon::engine::scanner::cancel_scan(&surrealdb::types::RecordId::new("intent", key)) {
+		return Ok(());
+	}
+
 	db.db
 		.query("UPDATE intent SET status = 'error', updated_at = time::now() WHERE id = $id")
 		.bind(("id", intent_id.to_string()))
@@ -237,7 +327,7 @@ pub async fn scan_intent(db: &DbHandle, intent_id: &str) -> Result<crate::api::S
 /// Retry failed transfers
 pub async fn retry_failed(db: &DbHandle, intent_id: &str) -> Result<RunResult, KipError> {
 	db.db
-        .query("UPDATE transfer_job SET status = 'pending', error_kind = NONE WHERE intent = $id AND status IN ['failed', 'needs_review']")
+        .query("UPDATE transfer_job SET status = 'pending', error_kind = NONE, next_retry_at = NONE WHERE intent = $id AND status IN ['failed', 'needs_review']")
         .bind(("id", intent_id.to_string()))
         .await
         .map_err(|e| KipError::Database(e.to_string()))?
@@ -253,8 +343,8 @@ async fn get_intent_status(
 ) -> Result<crate::api::IntentStatus, KipError> {
 	let mut response = db
 		.db
-		.query("SELECT status FROM intent WHERE id = $id")
-		.bind(("id", format!("{:?}", record_id)))
+		.query("SELECT status FROM $id")
+		.bind(("id", record_id.clone()))
 		.await
 		.map_err(|e| KipError::Database(e.to_string()))?
 		.check()
@@ -272,6 +362,7 @@ async fn get_intent_status(
 		"transferring" => crate::api::IntentStatus::Transferring,
 		"complete" => crate::api::IntentStatus::Complete,
 		"needs_review" => crate::api::IntentStatus::NeedsReview,
+		"waiting_for_device" => crate::api::IntentStatus::WaitingForDevice,
 		"error" => crate::api::IntentStatus::Error,
 		_ => crate::api::IntentStatus::Idle,
 	})
@@ -292,6 +383,7 @@ fn parse_intent_summary(row: &serde_json::Value) -> Result<crate::api::IntentSum
 		"transferring" => crate::api::IntentStatus::Transferring,
 		"complete" => crate::api::IntentStatus::Complete,
 		"needs_review" => crate::api::IntentStatus::NeedsReview,
+		"waiting_for_device" => crate::api::IntentStatus::WaitingForDevice,
 		"error" => crate::api::IntentStatus::Error,
 		_ => crate::api::IntentStatus::Idle,
 	};
@@ -346,3 +438,47 @@ fn parse_intent_summary(row: &serde_json::Value) -> Result<crate::api::IntentSum
 		progress,
 	})
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn two_starts_copy_each_file_once() {
+		let db = daemon::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		for name in ["a.txt", "b.txt", "c.txt"] {
+			std::fs::write(src.path().join(name), name).unwrap();
+		}
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src, available: true };
+                 CREATE location:dst CONTENT { path: $dst, available: true };
+                 CREATE intent:twice CONTENT {
+                     source: location:src, destinations: [location:dst], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal',
+                 };",
+			)
+			.bind(("src", src.path().to_str().unwrap().to_string()))
+			.bind(("dst", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		let (first, second) = tokio::join!(run_intent(&db, "twice", None), run_intent(&db, "twice", None));
+
+		first.unwrap();
+		second.unwrap();
+		let mut resp = db
+			.db
+			.query("SELECT VALUE source_path FROM transfer_job ORDER BY source_path")
+			.await
+			.unwrap();
+		let jobs: Vec<String> = resp.take(0).unwrap();
+		assert_eq!(jobs.len(), 3, "each file should have one job: {jobs:?}");
+		for name in ["a.txt", "b.txt", "c.txt"] {
+			assert_eq!(std::fs::read_to_string(dst.path().join(name)).unwrap(), name);
+		}
+	}
+}
@@ -15,20 +15,27 @@ pub async fn add_location(
 ) -> Result<LocationId, KipError> {
 	let path = expand_tilde(path)?;
 
-	if !path.exists() {
+	let Ok(metadata) = std::fs::metadata(&path) else {
 		return Err(KipError::SourcePathNotExists(path.clone()));
-	}
+	};
 
 	if let Some(existing) = find_location_by_path(db, &path).await? {
 		return Ok(existing);
 	}
 
-	let location_id = format!("location:{}", ulid::Ulid::new());
+	let key = ulid::Ulid::new().to_string();
+	let location_id = format!("location:{key}");
 
 	db.db
-		.query("CREATE location CONTENT { path: $path, label: $label, available: true }")
+		.query(
+			"CREATE type::record('location', $key) CONTENT {
+                path: $path, label: $label, available: true, is_dir: $is_dir,
+            }",
+		)
+		.bind(("key", key))
 		.bind(("path", path.to_string_lossy().to_string()))
 		.bind(("label", label))
+		.bind(("is_dir", metadata.is_dir()))
 		.await
 		.map_err(|e| KipError::Database(e.to_string()))?
 		.check()
@@ -2,7 +2,9 @@
 
 use daemon::DbHandle;
 
-use crate::api::{DriveStatus, IntentCounts, KipError, ReviewCounts, StatusSummary, TransferCounts};
+use crate::api::{
+	DriveStatus, IntentCounts, KipError, ReportFormat, ReportRow, ReviewCounts, StatusSummary, Summary, TransferCounts,
+};
 
 /// Get overall system status
 pub async fn status(db: &DbHandle) -> Result<StatusSummary, KipError> {
@@ -16,6 +18,122 @@ pub async fn status(db: &DbHandle) -> Result<StatusSummary, KipError> {
 	Ok(StatusSummary { intents, transfers, review_queue: review, drives })
 }
 
+/// Totals across all intents in one round-trip: what's copying, what's
+/// queued, what finished today and what needs review.
+pub async fn fetch_summary(db: &DbHandle) -> Result<Summary, KipError> {
+	let midnight = chrono::Local::now()
+		.date_naive()
+		.and_hms_opt(0, 0, 0)
+		.and_then(|t| t.and_local_timezone(chrono::Local).earliest())
+		.map(|t| t.to_rfc3339());
+
+	let mut response = db
+		.db
+		.query(
+			"SELECT
+                math::sum(IF status = 'transferring' THEN 1 ELSE 0 END) AS active,
+                math::sum(IF status = 'transferring' THEN size ?? 0 ELSE 0 END) AS in_flight,
+                math::sum(IF status = 'pending' THEN 1 ELSE 0 END) AS queued,
+                math::sum(IF status = 'complete' AND completed_at >= <datetime>$midnight THEN 1 ELSE 0 END)
+                    AS completed_today
+             FROM transfer_job GROUP ALL;
+             SELECT count() AS count FROM review_item WHERE resolution IS NONE GROUP ALL;",
+		)
+		.bind(("midnight", midnight.unwrap_or_else(|| chrono::Utc::now().to_rfc3339())))
+		.await
+		.map_err(|e| KipError::Database(e.to_string()))?
+		.check()
+		.map_err(|e| KipError::Database(e.to_string()))?;
+
+	let jobs: Option<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| KipError::Database(e.to_string()))?;
+	let review: Option<serde_json::Value> = response
+		.take(1)
+		.map_err(|e| KipError::Database(e.to_string()))?;
+
+	let jobs = jobs.unwrap_or_default();
+	let field = |name: &str| jobs[name].as_u64().unwrap_or(0);
+	let dispatch = daemon::engine::scheduler::dispatch_status();
+	Ok(Summary {
+		active_transfers: field("active"),
+		bytes_in_flight: field("in_flight"),
+		queued_files: field("queued"),
+		copying: dispatch.copying as u64,
+		copy_slots: dispatch.slots as u64,
+		completed_today: field("completed_today"),
+		needs_review: review.and_then(|r| r["count"].as_u64()).unwrap_or(0),
+	})
+}
+
+/// Every transfer job of an intent — what was copied, its hash and size,
+/// and what failed and why — as CSV or pretty-printed JSON, for keeping a
+/// record of a finished transfer. The failed and needs-review rows are the
+/// files behind the run's summary, e.g. "2 failed verification".
+pub async fn export_intent_report(db: &DbHandle, intent_id: &str, format: ReportFormat) -> Result<String, KipError> {
+	let key = intent_id.strip_prefix("intent:").unwrap_or(intent_id);
+	let mut response = db
+		.db
+		.query(
+			"SELECT source_path, dest_path, size, status, source_hash, error_kind, last_error FROM transfer_job
+             WHERE intent = $intent ORDER BY source_path, dest_path",
+		)
+		.bind(("intent", surrealdb::types::RecordId::new("intent", key)))
+		.await
+		.map_err(|e| KipError::Database(e.to_string()))?
+		.check()
+		.map_err(|e| KipError::Database(e.to_string()))?;
+
+	let rows: Vec<serde_json::Value> = response
+		.take(0)
+		.map_err(|e| KipError::Database(e.to_string()))?;
+	let text = |row: &serde_json::Value, field: &str| row[field].as_str().map(String::from);
+	let rows: Vec<ReportRow> = rows
+		.iter()
+		.map(|row| ReportRow {
+			source_path: text(row, "source_path").unwrap_or_default(),
+			dest_path: text(row, "dest_path").unwrap_or_default(),
+			size: row["size"].as_i64().unwrap_or(0),
+			status: text(row, "status").unwrap_or_default(),
+			source_hash: text(row, "source_hash"),
+			error_kind: text(row, "error_kind"),
+			error: text(row, "last_error"),
+		})
+		.collect();
+
+	match format {
+		ReportFormat::Json => serde_json::to_string_pretty(&rows).map_err(|e| KipError::Io(e.into())),
+		ReportFormat::Csv => Ok(report_csv(&rows)),
+	}
+}
+
+fn report_csv(rows: &[ReportRow]) -> String {
+	let mut out = String::from("source_path,dest_path,size,status,source_hash,error_kind,error\n");
+	for row in rows {
+		let fields = [
+			csv_field(&row.source_path),
+			csv_field(&row.dest_path),
+			row.size.to_string(),
+			csv_field(&row.status),
+			csv_field(row.source_hash.as_deref().unwrap_or_default()),
+			csv_field(row.error_kind.as_deref().unwrap_or_default()),
+			csv_field(row.error.as_deref().unwrap_or_default()),
+		];
+		out.push_str(&fields.join(","));
+		out.push('\n');
+	}
+	out
+}
+
+/// Quote a field if it holds a comma, quote or line break (RFC 4180).
+fn csv_field(value: &str) -> String {
+	if value.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
+
 /// Get transfer history
 pub async fn transfer_history(
 	db: &DbHandle,
@@ -57,7 +57,7 @@ pub async fn resolve_review(db: &DbHandle, review_id: &str, resolution: Resoluti
 			// Reset job to pending
 			let job_record = RecordId::new("transfer_job", job_id);
 			db.db
-				.query("UPDATE $job SET status = 'pending', error_kind = NONE")
+				.query("UPDATE $job SET status = 'pending', error_kind = NONE, next_retry_at = NONE")
 				.bind(("job", job_record))
 				.await
 				.map_err(|e| KipError::Database(e.to_string()))?
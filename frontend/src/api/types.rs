@@ -146,6 +146,7 @@ pub enum IntentStatus {
 	Transferring,
 	Complete,
 	NeedsReview,
+	WaitingForDevice,
 	Error,
 }
 
@@ -157,6 +158,7 @@ impl std::fmt::Display for IntentStatus {
 			IntentStatus::Transferring => write!(f, "transferring"),
 			IntentStatus::Complete => write!(f, "complete"),
 			IntentStatus::NeedsReview => write!(f, "needs_review"),
+			IntentStatus::WaitingForDevice => write!(f, "waiting_for_device"),
 			IntentStatus::Error => write!(f, "error"),
 		}
 	}
@@ -188,6 +190,27 @@ pub struct IntentConfig {
 	pub include_patterns: Vec<String>,
 	pub exclude_patterns: Vec<String>,
 	pub bidirectional: bool,
+	/// Error kinds to auto-retry before escalating to review.
+	/// `None` keeps the default policy (transient I/O only).
+	pub retryable_errors: Option<Vec<String>>,
+	/// Dispatch order for pending jobs.
+	pub job_order: kip_core::models::intent::JobOrder,
+	/// Max concurrent copies. `None` uses the global default.
+	pub concurrency: Option<u32>,
+	/// Scan through symlinks instead of skipping them.
+	pub follow_symlinks: bool,
+	/// Transfer only this folder under the source location (relative to it).
+	pub source_subpath: Option<String>,
+	/// How each copy is checked after it's written.
+	pub verify_mode: kip_core::models::intent::VerifyMode,
+	/// Settles conflicts and hash mismatches without review.
+	pub on_conflict: kip_core::models::intent::ConflictPolicy,
+	/// Whether copies replace destination files that already exist and differ.
+	pub overwrite_policy: kip_core::models::intent::OverwritePolicy,
+	/// Whether same-filesystem copies may be reflinked or hardlinked.
+	pub copy_strategy: kip_core::models::intent::CopyStrategy,
+	/// Write a checksum manifest at each destination when a run finishes.
+	pub write_manifest: bool,
 }
 
 /// Summary of an intent
@@ -275,7 +298,7 @@ pub struct ScanResult {
 }
 
 /// Run result for an intent
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct RunResult {
 	pub completed: u64,
 	pub failed: u64,
@@ -326,6 +349,25 @@ pub struct StatusSummary {
 	pub drives: DriveStatus,
 }
 
+/// At-a-glance totals across every intent, for the header's health line.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+	/// Jobs copying right now.
+	pub active_transfers: u64,
+	/// Total size of the files being copied right now.
+	pub bytes_in_flight: u64,
+	/// Jobs waiting their turn, including ones backing off before a retry.
+	pub queued_files: u64,
+	/// Copies holding one of the scheduler's permits right now.
+	pub copying: u64,
+	/// Permits across every running dispatch loop; 0 when nothing is running.
+	pub copy_slots: u64,
+	/// Jobs that finished since local midnight.
+	pub completed_today: u64,
+	/// Unresolved review items.
+	pub needs_review: u64,
+}
+
 #[derive(Debug, Clone)]
 pub struct IntentCounts {
 	pub total: u64,
@@ -385,3 +427,24 @@ pub enum ConfigFormat {
 	Toml,
 	Json,
 }
+
+/// Intent report export format
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+	Csv,
+	Json,
+}
+
+/// One transfer job in an exported intent report.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReportRow {
+	pub source_path: String,
+	pub dest_path: String,
+	pub size: i64,
+	pub status: String,
+	pub source_hash: Option<String>,
+	/// Why the job failed or went to review; `None` for clean copies.
+	pub error_kind: Option<String>,
+	/// The failure's message, naming what went wrong with this file.
+	pub error: Option<String>,
+}
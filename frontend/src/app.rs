@@ -1,25 +1,90 @@
 use dioxus::prelude::*;
 use daemon::DbHandle;
+use daemon::engine::{machines, shutdown};
+use daemon::events::{EngineEvent, Severity};
+use dioxus::desktop::tao::event::{Event, WindowEvent};
 
 use crate::ui::{
+	dashboard::DashboardHeader,
 	file_picker::{FilePickerLayer, PickerManager},
 	graph::MappingGraph,
-	notification::{NotificationLayer, NotificationService},
+	intent_row::IntentList,
+	notification::{track_intent, NotificationAction, NotificationLayer, NotificationLevel, NotificationService},
 	review_queue::ReviewQueue,
 };
 
 const MAIN_CSS: Asset = asset!("/assets/main.css");
 
+/// Turn one engine event into a notification. A scan starts a tracker that
+/// owns that intent's spinner, progress bar and final toast, so the events
+/// it would duplicate are dropped here. A newly mounted drive offers to
+/// open a picker on it.
+fn show_engine_event(db: &DbHandle, notifs: &mut Store<NotificationService>, event: EngineEvent) {
+	match &event {
+		EngineEvent::ScanStarted { intent, .. } => track_intent(db.clone(), *notifs, intent),
+		EngineEvent::DriveMounted { drive, name, mount_point } => notifs.add_with_actions(
+			event.to_string(),
+			NotificationLevel::Info,
+			vec![NotificationAction::OpenPicker {
+				container_id: drive.clone(),
+				name: name.clone(),
+				root: mount_point.clone(),
+			}],
+		),
+		EngineEvent::ScanFinished { .. }
+		| EngineEvent::TransferProgress { .. }
+		| EngineEvent::TransferComplete { .. }
+		| EngineEvent::NeedsReview { .. } => {}
+		_ => match event.severity() {
+			Severity::Error => notifs.error(event.to_string()),
+			Severity::Warning => notifs.warn(event.to_string()),
+			Severity::Info => notifs.info(event.to_string()),
+		},
+	}
+}
+
 #[derive(Clone)]
-pub struct DbError(pub String);
+pub struct DbError {
+	pub message: String,
+	/// Another instance held the lock; retrying may succeed once it exits.
+	pub locked: bool,
+}
 
 #[component]
 pub fn DbErrorApp() -> Element {
-	let err = use_context::<DbError>();
-	let msg = if err.0.contains("already locked") {
+	let initial = use_context::<DbError>();
+	let mut error = use_signal(|| initial);
+	let mut retrying = use_signal(|| false);
+	let mut db = use_signal(|| None::<DbHandle>);
+
+	if db.read().is_some() {
+		return rsx! { RecoveredApp { db } };
+	}
+
+	let err = error();
+	let msg = if err.locked {
 		"Database is being accessed by another Kip instance."
 	} else {
-		&err.0
+		err.message.as_str()
+	};
+
+	let on_retry = move |_| {
+		retrying.set(true);
+		spawn(async move {
+			match daemon::db::init().await {
+				Ok(handle) => {
+					if let Err(e) = daemon::engine::watcher::resume_sync_watchers(&handle).await {
+						tracing::warn!("couldn't resume sync watchers: {}", e);
+					}
+					db.set(Some(handle));
+				}
+				Err(e) => {
+					let locked = matches!(e, daemon::db::InitError::Locked(_));
+					error.set(DbError { message: e.to_string(), locked });
+				}
+			}
+			retrying.set(false);
+		});
 	};
 
 	rsx! {
@@ -28,16 +93,33 @@ pub fn DbErrorApp() -> Element {
 			div { class: "header",
 				h1 { "Kip" }
 			}
-			div { class: "db-locked-banner", "{msg}" }
+			div { class: "db-locked-banner",
+				"{msg}"
+				if err.locked {
+					button {
+						class: "db-retry-btn",
+						disabled: retrying(),
+						onclick: on_retry,
+						if retrying() { "Retrying..." } else { "Retry" }
+					}
+				}
+			}
 		}
 	}
 }
 
+/// The normal app, once a retry from `DbErrorApp` opened the database.
+#[component]
+fn RecoveredApp(db: Signal<Option<DbHandle>>) -> Element {
+	use_context_provider(move || db.peek().clone().expect("opened before RecoveredApp renders"));
+	rsx! { App {} }
+}
+
 #[component]
 pub fn App() -> Element {
 	let db = use_context::<DbHandle>();
 	// let db = use_signal(|| DbHandle::new());
-	let picker = use_store(|| PickerManager::new());
+	let mut picker = use_store(|| PickerManager::new());
 	let notifs = use_store(|| NotificationService::new());
 	let mut hostname = use_signal(|| String::from("..."));
 	let mut refresh_tick = use_signal(|| 0u32);
@@ -59,16 +141,62 @@ pub fn App() -> Element {
 		});
 	});
 
-	// Start drive watcher (polls /Volumes/ every 5s)
+	// Pick up intents that were still transferring when Kip last quit
+	let db_for_resume = db.clone();
+	use_effect(move || {
+		let db = db_for_resume.clone();
+		spawn(async move {
+			if let Err(e) = shutdown::resume_interrupted_intents(&db).await {
+				warn!("couldn't resume interrupted intents: {}", e);
+			}
+		});
+	});
+
+	// Closing the window stops running copies and checkpoints them first
+	let db_for_close = db.clone();
+	dioxus::desktop::use_wry_event_handler(move |event, _| {
+		if let Event::WindowEvent { event: WindowEvent::CloseRequested, .. } = event {
+			shutdown::shutdown_blocking(&db_for_close, shutdown::SHUTDOWN_GRACE);
+		}
+	});
+
+	// Start drive watcher (polls mounted volumes every couple of seconds)
 	let db_for_watcher = db.clone();
 	use_effect(move || {
 		let db_clone = db_for_watcher.clone();
 		spawn(async move {
-			let _watcher = crate::devices::DriveWatcher::start(db_clone);
+			let _watcher = crate::devices::DriveWatcher::start(db_clone, crate::devices::DEFAULT_POLL_INTERVAL);
 			std::future::pending::<()>().await;
 		});
 	});
 
+	// Probe remote machines so offline ones grey out like unplugged drives
+	let db_for_machines = db.clone();
+	use_effect(move || {
+		let db_clone = db_for_machines.clone();
+		spawn(async move {
+			let _watcher = machines::MachineWatcher::start(db_clone, machines::MACHINE_POLL_INTERVAL);
+			std::future::pending::<()>().await;
+		});
+	});
+
+	// Surface engine events (out of space, drive unplugged, scans, ...) as notifications
+	let db_for_events = db.clone();
+	use_effect(move || {
+		let mut notifs = notifs;
+		let db = db_for_events.clone();
+		spawn(async move {
+			let mut events = daemon::events::subscribe();
+			loop {
+				match events.recv().await {
+					Ok(event) => show_engine_event(&db, &mut notifs, event),
+					Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+					Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+				}
+			}
+		});
+	});
+
 	// Poll for updates every 2 seconds - use use_effect so it only runs once
 	use_effect(move || {
 		spawn(async move {
@@ -83,9 +211,23 @@ pub fn App() -> Element {
 		*refresh_tick.write() += 1;
 	};
 
+	let on_notification_action = move |action: NotificationAction| match action {
+		NotificationAction::OpenReview => {
+			// Refresh first so items filed since the last poll are already listed
+			*refresh_tick.write() += 1;
+			document::eval(
+				"document.getElementById('review-queue')?.scrollIntoView({ behavior: 'smooth', block: 'start' })",
+			);
+		}
+		NotificationAction::OpenPicker { container_id, name, root } => {
+			picker.open(container_id, name, std::path::PathBuf::from(root));
+		}
+	};
+
 	rsx! {
 		document::Stylesheet { href: MAIN_CSS }
 		div { class: "app",
+			DashboardHeader { hostname: hostname(), refresh_tick: refresh_tick() }
 			MappingGraph {
 				picker,
 				refresh_tick: refresh_tick(),
@@ -93,8 +235,9 @@ pub fn App() -> Element {
 				notifs,
 			}
 			FilePickerLayer { picker, on_location_added: on_refresh }
+			IntentList { refresh_tick: refresh_tick(), on_changed: on_refresh }
 			ReviewQueue { refresh_tick: refresh_tick(), on_resolved: on_refresh }
-			NotificationLayer { notifs }
+			NotificationLayer { notifs, on_action: on_notification_action }
 		}
 	}
 }
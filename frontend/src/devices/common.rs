@@ -0,0 +1,410 @@
+//! Platform-independent drive tracking: DB sync, disconnects, benchmarks.
+//!
+//! Each platform module provides `discover_mounted_volumes()`; everything
+//! after discovery is shared.
+
+use std::{
+	collections::HashSet,
+	io::Write,
+	path::Path,
+	sync::{Arc, LazyLock, Mutex},
+	time::{Duration, Instant},
+};
+
+use tokio::task::JoinHandle;
+use daemon::{
+	engine::{
+		copier::{filesystem_usage, FsUsage},
+		drives,
+	},
+	events::{self, EngineEvent},
+	DbHandle,
+};
+use surrealdb::types::RecordId;
+
+use super::platform;
+
+/// How often mounted volumes are re-listed. Known volumes only get a cheap
+/// free-space refresh, so this can be short enough that plugging a drive in
+/// feels immediate.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Size of the write-speed benchmark file (small enough to be quick on USB2).
+const BENCHMARK_BYTES: usize = 8 * 1024 * 1024;
+/// Re-benchmark a drive at most this often.
+const BENCHMARK_MAX_AGE: &str = "7d";
+/// A network share that can't answer `statvfs` this quickly counts as gone.
+const NETWORK_PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Filesystems that are network shares rather than attached disks.
+pub(super) const NETWORK_FILESYSTEMS: &[&str] = &["smbfs", "afpfs", "webdav", "nfs", "nfs4", "cifs", "smb3"];
+
+/// Network mounts whose last `statvfs` hasn't returned yet. A share behind a
+/// dropped VPN can hang it indefinitely; while one is stuck the share isn't
+/// probed again.
+static PROBING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+pub struct DriveWatcher {
+	handle: JoinHandle<()>,
+}
+
+impl DriveWatcher {
+	pub fn start(db: DbHandle, interval: Duration) -> Self {
+		let benchmarking = Arc::new(Mutex::new(HashSet::new()));
+		let handle = tokio::spawn(async move {
+			let mut known = None;
+			loop {
+				if let Err(e) = poll_volumes(&db, &benchmarking, &mut known).await {
+					eprintln!("drive poll error: {e}");
+				}
+				tokio::time::sleep(interval).await;
+			}
+		});
+		DriveWatcher { handle }
+	}
+
+	#[allow(dead_code)]
+	pub fn stop(self) {
+		self.handle.abort();
+	}
+}
+
+/// A mounted external volume, as reported by the platform's discovery.
+#[derive(Debug, Clone)]
+pub(super) struct VolumeInfo {
+	pub(super) uuid: String,
+	pub(super) name: String,
+	pub(super) mount_point: String,
+	pub(super) filesystem: String,
+	pub(super) kind: VolumeKind,
+	pub(super) capacity_bytes: i64,
+	/// Space currently available for writes; `None` if it couldn't be read.
+	pub(super) free_bytes: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum VolumeKind {
+	/// A disk attached to this machine, identified by its filesystem UUID.
+	Local,
+	/// An SMB/NFS/AFP share, identified by server and share.
+	Network,
+}
+
+impl VolumeKind {
+	pub(super) fn as_str(&self) -> &'static str {
+		match self {
+			VolumeKind::Local => "local",
+			VolumeKind::Network => "network",
+		}
+	}
+}
+
+/// A stable drive key for a network share, from its mount source:
+/// `//user@nas.local/Media` (SMB/AFP) becomes `net:nas.local/media`,
+/// `nas.local:/export/media` (NFS) becomes `net:nas.local:/export/media`.
+/// Credentials and letter case don't change the key.
+pub(super) fn network_volume_key(source: &str) -> Option<String> {
+	let source = source.trim().trim_end_matches('/');
+	let share = match source.strip_prefix("//") {
+		Some(rest) => rest.rsplit_once('@').map_or(rest, |(_, host)| host).to_string(),
+		None if source.contains(":/") => source.to_string(),
+		None => return None,
+	};
+	(!share.is_empty()).then(|| format!("net:{}", share.to_lowercase()))
+}
+
+/// Describe a mounted network share, or `None` if it doesn't answer within
+/// `NETWORK_PROBE_TIMEOUT`. An unreachable share is left out of the poll, so
+/// it's marked disconnected and its intents wait for it like an unplugged drive.
+pub(super) async fn network_volume(source: &str, mount_point: &str, filesystem: &str) -> Option<VolumeInfo> {
+	let uuid = network_volume_key(source)?;
+	let usage = probe_usage(mount_point).await?;
+	let name = Path::new(mount_point)
+		.file_name()
+		.map(|n| n.to_string_lossy().to_string())
+		.unwrap_or_else(|| "Network share".into());
+
+	Some(VolumeInfo {
+		uuid,
+		name,
+		mount_point: mount_point.to_string(),
+		filesystem: filesystem.to_string(),
+		kind: VolumeKind::Network,
+		capacity_bytes: usage.total as i64,
+		free_bytes: Some(usage.available as i64),
+	})
+}
+
+async fn probe_usage(mount_point: &str) -> Option<FsUsage> {
+	if !PROBING.lock().unwrap().insert(mount_point.to_string()) {
+		return None;
+	}
+	let mount_point = mount_point.to_string();
+	let probe = tokio::task::spawn_blocking(move || {
+		let usage = filesystem_usage(&mount_point).ok();
+		PROBING.lock().unwrap().remove(&mount_point);
+		usage
+	});
+	tokio::time::timeout(NETWORK_PROBE_TIMEOUT, probe).await.ok()?.ok()?
+}
+
+/// Volumes in `volumes` whose UUID isn't in `known`, adding them to it.
+/// `known` starts as `None`, and the first poll only fills it in: drives
+/// already mounted at launch weren't just plugged in.
+fn first_sightings<'a>(known: &mut Option<HashSet<String>>, volumes: &'a [VolumeInfo]) -> Vec<&'a VolumeInfo> {
+	let Some(known) = known else {
+		*known = Some(volumes.iter().map(|v| v.uuid.clone()).collect());
+		return Vec::new();
+	};
+	volumes.iter().filter(|v| known.insert(v.uuid.clone())).collect()
+}
+
+async fn poll_volumes(
+	db: &DbHandle,
+	benchmarking: &Arc<Mutex<HashSet<String>>>,
+	known: &mut Option<HashSet<String>>,
+) -> Result<(), String> {
+	let volumes = platform::discover_mounted_volumes().await;
+	let seen_uuids: Vec<String> = volumes.iter().map(|v| v.uuid.clone()).collect();
+
+	for vol in &volumes {
+		sync_drive_to_db(db, vol).await?;
+		if needs_benchmark(db, &vol.uuid).await? {
+			spawn_benchmark(db, vol, benchmarking);
+		}
+	}
+
+	// Announced only once synced, so the picker it offers finds the drive
+	for vol in first_sightings(known, &volumes) {
+		events::emit(EngineEvent::DriveMounted {
+			drive: daemon::rid_string(&RecordId::new("drive", vol.uuid.as_str())),
+			name: vol.name.clone(),
+			mount_point: vol.mount_point.clone(),
+		});
+	}
+
+	mark_disconnected_drives(db, &seen_uuids).await?;
+	daemon::refresh_location_availability(db).await?;
+
+	Ok(())
+}
+
+/// Upsert the drive record. If the drive was known but disconnected, intents
+/// waiting on it are resumed.
+async fn sync_drive_to_db(db: &DbHandle, vol: &VolumeInfo) -> Result<(), String> {
+	// Share limits depend on the server's disk, not the protocol
+	let limitations = match vol.kind {
+		VolumeKind::Local => detect_limitations(&vol.filesystem),
+		VolumeKind::Network => None,
+	};
+
+	let mut response = db
+		.db
+		.query(
+			"UPSERT type::record('drive', $uuid) MERGE {
+                name: $name,
+                uuid: $uuid,
+                filesystem: $filesystem,
+                kind: $kind,
+                capacity_bytes: $capacity,
+                free_bytes: $free,
+                mount_point: $mount_point,
+                connected: true,
+                last_seen: time::now(),
+                limitations: $limitations,
+            } RETURN BEFORE",
+		)
+		.bind(("uuid", vol.uuid.clone()))
+		.bind(("name", vol.name.clone()))
+		.bind(("filesystem", vol.filesystem.clone()))
+		.bind(("kind", vol.kind.as_str().to_string()))
+		.bind(("capacity", vol.capacity_bytes))
+		.bind(("free", vol.free_bytes))
+		.bind(("mount_point", vol.mount_point.clone()))
+		.bind(("limitations", limitations))
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let before: Option<serde_json::Value> = response.take(0).map_err(|e| e.to_string())?;
+	if before.is_some_and(|b| b["connected"] == false) {
+		let drive = RecordId::new("drive", vol.uuid.as_str());
+		drives::resume_intents_for_drive(db, &drive).await.map_err(|e| e.to_string())?;
+	}
+
+	Ok(())
+}
+
+/// Flag drives that are no longer mounted, pausing any intent that was
+/// transferring to or from them.
+async fn mark_disconnected_drives(db: &DbHandle, seen_uuids: &[String]) -> Result<(), String> {
+	let mut response = db
+		.db
+		.query(
+			"UPDATE drive SET connected = false, mount_point = NONE
+             WHERE connected = true AND uuid NOT IN $seen_uuids
+             RETURN id, name",
+		)
+		.bind(("seen_uuids", seen_uuids.to_vec()))
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let gone: Vec<serde_json::Value> = response.take(0).map_err(|e| e.to_string())?;
+	for row in gone {
+		let Ok(drive) = serde_json::from_value::<RecordId>(row["id"].clone()) else {
+			continue;
+		};
+		let name = row["name"].as_str().unwrap_or("Drive");
+		drives::pause_intents_for_drive(db, &drive, name).await.map_err(|e| e.to_string())?;
+	}
+
+	Ok(())
+}
+
+/// True if the drive has no write-speed measurement newer than `BENCHMARK_MAX_AGE`.
+async fn needs_benchmark(db: &DbHandle, uuid: &str) -> Result<bool, String> {
+	let query = format!(
+		"SELECT count() AS count FROM drive
+         WHERE uuid = $uuid AND benchmarked_at > time::now() - {BENCHMARK_MAX_AGE}
+         GROUP ALL"
+	);
+	let mut resp = db
+		.db
+		.query(query)
+		.bind(("uuid", uuid.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let rows: Vec<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+	let fresh = rows.first().and_then(|r| r["count"].as_i64()).unwrap_or(0);
+	Ok(fresh == 0)
+}
+
+/// Run the write benchmark in the background and store the result on the
+/// drive record. At most one benchmark per drive is in flight.
+fn spawn_benchmark(db: &DbHandle, vol: &VolumeInfo, benchmarking: &Arc<Mutex<HashSet<String>>>) {
+	if !benchmarking.lock().unwrap().insert(vol.uuid.clone()) {
+		return;
+	}
+
+	let db = db.clone();
+	let uuid = vol.uuid.clone();
+	let mount_point = vol.mount_point.clone();
+	let benchmarking = benchmarking.clone();
+
+	tokio::spawn(async move {
+		let result = tokio::task::spawn_blocking(move || benchmark_write(Path::new(&mount_point)))
+			.await
+			.map_err(|e| e.to_string())
+			.and_then(|r| r.map_err(|e| e.to_string()));
+
+		match result {
+			Ok(mbps) => {
+				let _ = db
+					.db
+					.query(
+						"UPDATE type::record('drive', $uuid) SET
+                            measured_write_mbps = $mbps,
+                            benchmarked_at = time::now()",
+					)
+					.bind(("uuid", uuid.clone()))
+					.bind(("mbps", mbps))
+					.await;
+			}
+			// Read-only or full volumes can't be benchmarked; try again next time
+			Err(e) => eprintln!("drive benchmark failed for {uuid}: {e}"),
+		}
+
+		benchmarking.lock().unwrap().remove(&uuid);
+	});
+}
+
+/// Write `BENCHMARK_BYTES` to a temp file on the volume, fsync, and return
+/// the observed throughput in MB/s. The temp file is always removed.
+fn benchmark_write(mount_point: &Path) -> std::io::Result<f64> {
+	let path = mount_point.join(format!(".kip-benchmark-{}.tmp", std::process::id()));
+	let chunk = vec![0xA5u8; 1024 * 1024];
+
+	let result: std::io::Result<f64> = (|| {
+		let start = Instant::now();
+		let mut file = std::fs::File::create(&path)?;
+		for _ in 0..BENCHMARK_BYTES / chunk.len() {
+			file.write_all(&chunk)?;
+		}
+		file.sync_all()?;
+		let secs = start.elapsed().as_secs_f64().max(0.001);
+		Ok((BENCHMARK_BYTES as f64 / 1_048_576.0) / secs)
+	})();
+
+	let _ = std::fs::remove_file(&path);
+	result
+}
+
+fn detect_limitations(filesystem: &str) -> Option<serde_json::Value> {
+	match filesystem.to_lowercase().as_str() {
+		"msdos" | "vfat" | "fat32" | "fat16" => Some(serde_json::json!({
+			"max_file_size": 4_294_967_295_i64
+		})),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_detect_limitations_fat32() {
+		let lim = detect_limitations("msdos").unwrap();
+		assert_eq!(lim["max_file_size"], 4_294_967_295_i64);
+	}
+
+	#[test]
+	fn test_detect_limitations_apfs() {
+		assert!(detect_limitations("apfs").is_none());
+	}
+
+	#[test]
+	fn test_detect_limitations_exfat() {
+		assert!(detect_limitations("exfat").is_none());
+	}
+
+	#[test]
+	fn test_network_volume_key() {
+		assert_eq!(network_volume_key("//anders@NAS.local/Media").as_deref(), Some("net:nas.local/media"));
+		assert_eq!(network_volume_key("//GUEST:@nas.local/Media/").as_deref(), Some("net:nas.local/media"));
+		assert_eq!(network_volume_key("nas.local:/export/media").as_deref(), Some("net:nas.local:/export/media"));
+		assert_eq!(network_volume_key("/dev/sdb1"), None);
+	}
+
+	fn volume(uuid: &str) -> VolumeInfo {
+		VolumeInfo {
+			uuid: uuid.to_string(),
+			name: uuid.to_string(),
+			mount_point: format!("/Volumes/{uuid}"),
+			filesystem: "apfs".to_string(),
+			kind: VolumeKind::Local,
+			capacity_bytes: 0,
+			free_bytes: None,
+		}
+	}
+
+	#[test]
+	fn test_first_sightings() {
+		let mut known = None;
+		let uuids = |seen: Vec<&VolumeInfo>| seen.iter().map(|v| v.uuid.clone()).collect::<Vec<_>>();
+
+		// Mounted at launch: learned, not announced
+		assert!(first_sightings(&mut known, &[volume("a")]).is_empty());
+		assert_eq!(uuids(first_sightings(&mut known, &[volume("a"), volume("b")])), vec!["b"]);
+		assert!(first_sightings(&mut known, &[volume("a"), volume("b")]).is_empty());
+		// Unplugged and plugged back in: already seen
+		assert!(first_sightings(&mut known, &[]).is_empty());
+		assert!(first_sightings(&mut known, &[volume("b")]).is_empty());
+	}
+
+	#[test]
+	fn test_benchmark_write_cleans_up() {
+		let tmp = tempfile::tempdir().unwrap();
+		let mbps = benchmark_write(tmp.path()).unwrap();
+		assert!(mbps > 0.0);
+		assert_eq!(std::fs::read_dir(tmp.path()).unwrap().count(), 0);
+	}
+}
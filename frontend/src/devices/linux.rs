@@ -0,0 +1,217 @@
+//! Linux volume discovery via `/proc/self/mountinfo` and `/dev/disk/by-uuid`.
+//!
+//! Mirrors the macOS `/Volumes` scan: only user-facing mounts (removable
+//! media under `/media`, `/run/media`, `/mnt`) count as drives, so the root
+//! filesystem and system partitions never show up. Network shares (CIFS,
+//! NFS) count wherever they're mounted.
+
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+};
+
+use daemon::engine::copier::filesystem_usage;
+
+use super::common::{network_volume, VolumeInfo, VolumeKind, NETWORK_FILESYSTEMS};
+
+/// Mount roots that hold external/removable volumes.
+const VOLUME_ROOTS: &[&str] = &["/media/", "/run/media/", "/mnt/"];
+
+/// Kernel and container filesystems that are never drives.
+const PSEUDO_FILESYSTEMS: &[&str] = &[
+	"proc", "sysfs", "tmpfs", "devtmpfs", "devpts", "overlay", "cgroup", "cgroup2", "securityfs", "debugfs",
+	"tracefs", "pstore", "bpf", "mqueue", "hugetlbfs", "configfs", "fusectl", "autofs", "squashfs", "nsfs", "ramfs",
+	"efivarfs", "binfmt_misc", "rpc_pipefs",
+];
+
+/// One line of mountinfo, reduced to what drive detection needs.
+#[derive(Debug, PartialEq)]
+struct MountEntry {
+	mount_point: String,
+	filesystem: String,
+	source: String,
+}
+
+pub(super) async fn discover_mounted_volumes() -> Vec<VolumeInfo> {
+	let (mut volumes, shares) = tokio::task::spawn_blocking(discover_blocking).await.unwrap_or_default();
+	// Probed off the blocking scan: a share behind a dropped VPN can hang
+	for share in shares {
+		if let Some(volume) = network_volume(&share.source, &share.mount_point, &share.filesystem).await {
+			volumes.push(volume);
+		}
+	}
+	volumes
+}
+
+/// Local drives, fully described, and the network mounts still to probe.
+fn discover_blocking() -> (Vec<VolumeInfo>, Vec<MountEntry>) {
+	let mountinfo = match fs::read_to_string("/proc/self/mountinfo") {
+		Ok(s) => s,
+		Err(e) => {
+			eprintln!("failed to read /proc/self/mountinfo: {e}");
+			return Default::default();
+		}
+	};
+	let uuids = uuids_by_device();
+
+	let (shares, mounts): (Vec<MountEntry>, Vec<MountEntry>) =
+		parse_mountinfo(&mountinfo).into_iter().partition(is_network_volume);
+
+	let volumes = mounts
+		.into_iter()
+		.filter(is_external_volume)
+		.filter_map(|mount| {
+			// Skip volumes without a UUID, same as on macOS
+			let device = fs::canonicalize(&mount.source).ok()?;
+			let uuid = uuids.get(&device)?.clone();
+			let usage = filesystem_usage(&mount.mount_point).ok();
+			let name = Path::new(&mount.mount_point)
+				.file_name()
+				.map(|n| n.to_string_lossy().to_string())
+				.unwrap_or_else(|| "Untitled".into());
+
+			Some(VolumeInfo {
+				uuid,
+				name,
+				mount_point: mount.mount_point,
+				filesystem: mount.filesystem,
+				kind: VolumeKind::Local,
+				capacity_bytes: usage.map_or(0, |u| u.total as i64),
+				free_bytes: usage.map(|u| u.available as i64),
+			})
+		})
+		.collect();
+	(volumes, shares)
+}
+
+/// Map each block device (canonical path) to its filesystem UUID.
+fn uuids_by_device() -> HashMap<PathBuf, String> {
+	let Ok(entries) = fs::read_dir("/dev/disk/by-uuid") else {
+		return HashMap::new();
+	};
+	entries
+		.flatten()
+		.filter_map(|entry| {
+			let device = fs::canonicalize(entry.path()).ok()?;
+			Some((device, entry.file_name().to_string_lossy().to_string()))
+		})
+		.collect()
+}
+
+fn is_external_volume(mount: &MountEntry) -> bool {
+	mount.source.starts_with("/dev/")
+		&& !PSEUDO_FILESYSTEMS.contains(&mount.filesystem.as_str())
+		&& VOLUME_ROOTS.iter().any(|root| mount.mount_point.starts_with(root))
+}
+
+fn is_network_volume(mount: &MountEntry) -> bool {
+	NETWORK_FILESYSTEMS.contains(&mount.filesystem.as_str())
+}
+
+/// Parse `/proc/self/mountinfo`:
+///
+/// `36 35 98:0 / /media/usb rw,noatime shared:1 - vfat /dev/sdb1 rw,...`
+///
+/// Optional fields before the `-` separator vary in count, so the filesystem
+/// and source are taken from after it.
+fn parse_mountinfo(contents: &str) -> Vec<MountEntry> {
+	contents
+		.lines()
+		.filter_map(|line| {
+			let (before, after) = line.split_once(" - ")?;
+			let mount_point = before.split(' ').nth(4)?;
+			let mut after = after.split(' ');
+			let filesystem = after.next()?;
+			let source = after.next()?;
+			Some(MountEntry {
+				mount_point: unescape_mount_field(mount_point),
+				filesystem: filesystem.to_string(),
+				source: unescape_mount_field(source),
+			})
+		})
+		.collect()
+}
+
+/// Undo the kernel's octal escaping of space, tab, newline and backslash.
+fn unescape_mount_field(field: &str) -> String {
+	let bytes = field.as_bytes();
+	let mut out = Vec::with_capacity(bytes.len());
+	let mut i = 0;
+	while i < bytes.len() {
+		match (bytes[i], bytes.get(i + 1..i + 4)) {
+			(b'\\', Some(digits)) if digits.iter().all(|b| (b'0'..=b'7').contains(b)) => {
+				let code = digits.iter().fold(0u16, |acc, b| acc * 8 + u16::from(b - b'0'));
+				out.push(code as u8);
+				i += 4;
+			}
+			(b, _) => {
+				out.push(b);
+				i += 1;
+			}
+		}
+	}
+	String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MOUNTINFO: &str = "\
+22 28 0:21 / /proc rw,nosuid,nodev,noexec,relatime shared:13 - proc proc rw
+28 1 259:2 / / rw,relatime shared:1 - ext4 /dev/nvme0n1p2 rw
+31 28 259:1 / /boot/efi rw,relatime shared:2 - vfat /dev/nvme0n1p1 rw,fmask=0077
+45 28 0:40 / /run/user/1000 rw,nosuid,nodev,relatime shared:300 - tmpfs tmpfs rw,size=1612404k
+61 28 8:17 / /media/anders/My\\040Backup rw,nosuid,nodev,relatime shared:320 - exfat /dev/sdb1 rw,uid=1000
+62 28 8:33 / /run/media/anders/SOMETHING rw,nosuid,nodev,relatime shared:321 master:5 - vfat /dev/sdc1 rw
+63 28 0:50 / /mnt/overlay rw,relatime - overlay overlay rw,lowerdir=/a
+";
+
+	#[test]
+	fn test_parse_mountinfo_fields() {
+		let mounts = parse_mountinfo(MOUNTINFO);
+		assert_eq!(mounts.len(), 7);
+		assert_eq!(
+			mounts[4],
+			MountEntry {
+				mount_point: "/media/anders/My Backup".into(),
+				filesystem: "exfat".into(),
+				source: "/dev/sdb1".into(),
+			}
+		);
+		// Extra optional field (master:5) before the separator
+		assert_eq!(mounts[5].filesystem, "vfat");
+		assert_eq!(mounts[5].source, "/dev/sdc1");
+	}
+
+	#[test]
+	fn test_filters_system_and_pseudo_mounts() {
+		let external: Vec<String> = parse_mountinfo(MOUNTINFO)
+			.into_iter()
+			.filter(is_external_volume)
+			.map(|m| m.mount_point)
+			.collect();
+		assert_eq!(external, vec!["/media/anders/My Backup", "/run/media/anders/SOMETHING"]);
+	}
+
+	#[test]
+	fn test_network_shares_are_found_anywhere() {
+		let mountinfo = "\
+70 28 0:60 / /home/anders/nas rw,relatime shared:400 - cifs //nas.local/media rw,vers=3.1.1
+71 28 0:61 / /srv/backup rw,relatime shared:401 - nfs4 nas.local:/export/backup rw,vers=4.2
+72 28 0:62 / /media/anders/fuse rw,relatime - fuse.sshfs me@host:/ rw
+";
+		let (shares, others): (Vec<MountEntry>, Vec<MountEntry>) =
+			parse_mountinfo(mountinfo).into_iter().partition(is_network_volume);
+		let mount_points: Vec<&str> = shares.iter().map(|m| m.mount_point.as_str()).collect();
+		assert_eq!(mount_points, vec!["/home/anders/nas", "/srv/backup"]);
+		assert!(!others.iter().any(is_external_volume), "sshfs isn't a block device");
+	}
+
+	#[test]
+	fn test_unescape_mount_field() {
+		assert_eq!(unescape_mount_field("a\\040b\\134c"), "a b\\c");
+		assert_eq!(unescape_mount_field("trailing\\04"), "trailing\\04");
+	}
+}
@@ -1,41 +1,41 @@
-use std::path::Path;
-
+//! macOS volume discovery via `/Volumes` and `diskutil info -plist`.
+//!
+//! `diskutil` is slow, so it only runs for volumes that weren't mounted on
+//! the previous poll. Known volumes keep their cached info and just get their
+//! free space re-read. Network shares have no volume UUID, so they're found
+//! in `mount`'s output instead.
+
+use std::{
+	collections::{HashMap, HashSet},
+	os::unix::fs::MetadataExt,
+	path::Path,
+	sync::{LazyLock, Mutex},
+};
+
+use daemon::engine::copier::filesystem_usage;
 use serde::Deserialize;
-use tokio::task::JoinHandle;
-use daemon::DbHandle;
-
-const POLL_INTERVAL_SECS: u64 = 5;
 
-pub struct DriveWatcher {
-	handle: JoinHandle<()>,
-}
+use super::common::{network_volume, VolumeInfo, VolumeKind, NETWORK_FILESYSTEMS};
 
-impl DriveWatcher {
-	pub fn start(db: DbHandle) -> Self {
-		let handle = tokio::spawn(async move {
-			loop {
-				if let Err(e) = poll_volumes(&db).await {
-					eprintln!("drive poll error: {e}");
-				}
-				tokio::time::sleep(std::time::Duration::from_secs(POLL_INTERVAL_SECS)).await;
-			}
-		});
-		DriveWatcher { handle }
-	}
+/// What the last `diskutil` call said about each `/Volumes` entry, keyed by
+/// path. `None` marks volumes that don't count as drives (internal, no UUID),
+/// so they aren't asked about again either.
+static KNOWN_VOLUMES: LazyLock<Mutex<HashMap<String, CachedVolume>>> = LazyLock::new(Default::default);
 
-	#[allow(dead_code)]
-	pub fn stop(self) {
-		self.handle.abort();
-	}
+/// A network share, from a line of `mount` output.
+#[derive(Debug, PartialEq)]
+struct NetworkMount {
+	source: String,
+	mount_point: String,
+	filesystem: String,
 }
 
 #[derive(Debug, Clone)]
-struct VolumeInfo {
-	uuid: String,
-	name: String,
-	mount_point: String,
-	filesystem: String,
-	capacity_bytes: i64,
+struct CachedVolume {
+	/// Device the path was mounted from; a different drive mounted at the
+	/// same path has a different one.
+	device: u64,
+	info: Option<VolumeInfo>,
 }
 
 #[derive(Deserialize)]
@@ -52,23 +52,16 @@ struct DiskutilInfo {
 	#[serde(default)]
 	total_size: Option<i64>,
 	#[serde(default)]
+	free_space: Option<i64>,
+	/// Free space of the whole APFS container, which is what's actually
+	/// writable; `FreeSpace` undercounts on APFS.
+	#[serde(default, rename = "APFSContainerFree")]
+	apfs_container_free: Option<i64>,
+	#[serde(default)]
 	internal: Option<bool>,
 }
 
-async fn poll_volumes(db: &DbHandle) -> Result<(), String> {
-	let volumes = discover_mounted_volumes().await;
-	let seen_uuids: Vec<String> = volumes.iter().map(|v| v.uuid.clone()).collect();
-
-	for vol in &volumes {
-		sync_drive_to_db(db, vol).await?;
-	}
-
-	mark_disconnected_drives(db, &seen_uuids).await?;
-
-	Ok(())
-}
-
-async fn discover_mounted_volumes() -> Vec<VolumeInfo> {
+pub(super) async fn discover_mounted_volumes() -> Vec<VolumeInfo> {
 	let volumes_dir = Path::new("/Volumes");
 	let entries = match std::fs::read_dir(volumes_dir) {
 		Ok(e) => e,
@@ -78,52 +71,118 @@ async fn discover_mounted_volumes() -> Vec<VolumeInfo> {
 		}
 	};
 
+	let shares = network_mounts().await;
+	let share_points: HashSet<&str> = shares.iter().map(|m| m.mount_point.as_str()).collect();
+	let known = KNOWN_VOLUMES.lock().unwrap().clone();
+	let mut current = HashMap::new();
 	let mut volumes = Vec::new();
 
 	for entry in entries.flatten() {
-		// Skip symlinks (boot volume "Macintosh HD" is a symlink to /)
-		if let Ok(meta) = entry.metadata() {
-			if meta.file_type().is_symlink() {
-				continue;
-			}
+		// Shares are probed below; even stat can hang on an unreachable one
+		if share_points.contains(entry.path().to_string_lossy().as_ref()) {
+			continue;
 		}
-		// Also check via symlink_metadata for reliability
-		if let Ok(meta) = std::fs::symlink_metadata(entry.path()) {
-			if meta.file_type().is_symlink() {
-				continue;
-			}
+		// Skip symlinks (boot volume "Macintosh HD" is a symlink to /)
+		let Ok(meta) = std::fs::symlink_metadata(entry.path()) else {
+			continue;
+		};
+		if meta.file_type().is_symlink() {
+			continue;
 		}
 
 		let path = entry.path();
 		let path_str = path.to_string_lossy().to_string();
 
-		match get_diskutil_info(&path_str).await {
-			Some(info) => {
-				// Skip internal drives
-				if info.internal.unwrap_or(false) {
-					continue;
-				}
-				// Skip volumes without UUID
-				let uuid = match info.volume_uuid {
-					Some(ref u) if !u.is_empty() => u.clone(),
-					_ => continue,
-				};
-
-				volumes.push(VolumeInfo {
-					uuid,
-					name: info.volume_name.unwrap_or_else(|| "Untitled".into()),
-					mount_point: info.mount_point.unwrap_or(path_str.clone()),
-					filesystem: info.filesystem_type.unwrap_or_default(),
-					capacity_bytes: info.total_size.unwrap_or(0),
-				});
-			}
-			None => continue,
+		let cached = match cached_volume(&known, &path_str, meta.dev()) {
+			Some(cached) => refresh_free_space(cached, &path),
+			None => CachedVolume { device: meta.dev(), info: query_volume(&path, &path_str).await },
+		};
+		if let Some(info) = &cached.info {
+			volumes.push(info.clone());
 		}
+		current.insert(path_str, cached);
 	}
 
+	// Unmounted volumes drop out, so a remount is looked up afresh
+	*KNOWN_VOLUMES.lock().unwrap() = current;
+
+	for share in &shares {
+		if let Some(volume) = network_volume(&share.source, &share.mount_point, &share.filesystem).await {
+			volumes.push(volume);
+		}
+	}
 	volumes
 }
 
+async fn network_mounts() -> Vec<NetworkMount> {
+	let output = tokio::time::timeout(std::time::Duration::from_secs(5), tokio::process::Command::new("mount").output())
+		.await
+		.ok()
+		.and_then(Result::ok);
+	match output {
+		Some(output) if output.status.success() => parse_mount_output(&String::from_utf8_lossy(&output.stdout)),
+		_ => Vec::new(),
+	}
+}
+
+/// Pick the network shares out of `mount`'s output:
+///
+/// `//anders@nas.local/Media on /Volumes/Media (smbfs, nodev, nosuid, mounted by anders)`
+fn parse_mount_output(output: &str) -> Vec<NetworkMount> {
+	output
+		.lines()
+		.filter_map(|line| {
+			let (source, rest) = line.split_once(" on ")?;
+			let (mount_point, options) = rest.rsplit_once(" (")?;
+			let filesystem = options.split([',', ')']).next()?.trim();
+			NETWORK_FILESYSTEMS.contains(&filesystem).then(|| NetworkMount {
+				source: source.to_string(),
+				mount_point: mount_point.to_string(),
+				filesystem: filesystem.to_string(),
+			})
+		})
+		.collect()
+}
+
+/// The cached entry for `path`, unless a different device is mounted there now.
+fn cached_volume(known: &HashMap<String, CachedVolume>, path: &str, device: u64) -> Option<CachedVolume> {
+	known.get(path).filter(|c| c.device == device).cloned()
+}
+
+fn refresh_free_space(mut cached: CachedVolume, path: &Path) -> CachedVolume {
+	if let Some(info) = cached.info.as_mut() {
+		if let Ok(usage) = filesystem_usage(path) {
+			info.free_bytes = Some(usage.available as i64);
+		}
+	}
+	cached
+}
+
+/// Ask `diskutil` about a newly seen volume. `None` if it isn't a drive Kip
+/// tracks.
+async fn query_volume(path: &Path, path_str: &str) -> Option<VolumeInfo> {
+	let info = get_diskutil_info(path_str).await?;
+	// Skip internal drives
+	if info.internal.unwrap_or(false) {
+		return None;
+	}
+	// Skip volumes without UUID
+	let uuid = info.volume_uuid.filter(|u| !u.is_empty())?;
+
+	Some(VolumeInfo {
+		uuid,
+		name: info.volume_name.unwrap_or_else(|| "Untitled".into()),
+		mount_point: info.mount_point.unwrap_or_else(|| path_str.to_string()),
+		filesystem: info.filesystem_type.unwrap_or_default(),
+		kind: VolumeKind::Local,
+		capacity_bytes: info.total_size.unwrap_or(0),
+		free_bytes: info
+			.apfs_container_free
+			.or(info.free_space)
+			.or_else(|| filesystem_usage(path).ok().map(|u| u.available as i64)),
+	})
+}
+
 async fn get_diskutil_info(volume_path: &str) -> Option<DiskutilInfo> {
 	let output = tokio::time::timeout(
 		std::time::Duration::from_secs(5),
@@ -142,80 +201,10 @@ async fn get_diskutil_info(volume_path: &str) -> Option<DiskutilInfo> {
 	plist::from_bytes(&output.stdout).ok()
 }
 
-async fn sync_drive_to_db(db: &DbHandle, vol: &VolumeInfo) -> Result<(), String> {
-	let limitations = detect_limitations(&vol.filesystem);
-
-	db.db
-		.query(
-			"UPSERT type::record('drive', $uuid) CONTENT {
-                name: $name,
-                uuid: $uuid,
-                filesystem: $filesystem,
-                capacity_bytes: $capacity,
-                mount_point: $mount_point,
-                connected: true,
-                last_seen: time::now(),
-                limitations: $limitations,
-            }",
-		)
-		.bind(("uuid", vol.uuid.clone()))
-		.bind(("name", vol.name.clone()))
-		.bind(("filesystem", vol.filesystem.clone()))
-		.bind(("capacity", vol.capacity_bytes))
-		.bind(("mount_point", vol.mount_point.clone()))
-		.bind(("limitations", limitations))
-		.await
-		.map_err(|e| e.to_string())?
-		.check()
-		.map_err(|e| e.to_string())?;
-
-	Ok(())
-}
-
-async fn mark_disconnected_drives(db: &DbHandle, seen_uuids: &[String]) -> Result<(), String> {
-	db.db
-		.query(
-			"UPDATE drive SET connected = false, mount_point = NONE
-             WHERE connected = true AND uuid NOT IN $seen_uuids",
-		)
-		.bind(("seen_uuids", seen_uuids.to_vec()))
-		.await
-		.map_err(|e| e.to_string())?
-		.check()
-		.map_err(|e| e.to_string())?;
-
-	Ok(())
-}
-
-fn detect_limitations(filesystem: &str) -> Option<serde_json::Value> {
-	match filesystem.to_lowercase().as_str() {
-		"msdos" | "fat32" | "fat16" => Some(serde_json::json!({
-			"max_file_size": 4_294_967_295_i64
-		})),
-		_ => None,
-	}
-}
-
 #[cfg(test)]
 mod tests {
 	use super::*;
 
-	#[test]
-	fn test_detect_limitations_fat32() {
-		let lim = detect_limitations("msdos").unwrap();
-		assert_eq!(lim["max_file_size"], 4_294_967_295_i64);
-	}
-
-	#[test]
-	fn test_detect_limitations_apfs() {
-		assert!(detect_limitations("apfs").is_none());
-	}
-
-	#[test]
-	fn test_detect_limitations_exfat() {
-		assert!(detect_limitations("exfat").is_none());
-	}
-
 	#[test]
 	fn test_parse_diskutil_plist() {
 		let xml = br#"<?xml version="1.0" encoding="UTF-8"?>
@@ -232,6 +221,10 @@ mod tests {
     <string>msdos</string>
     <key>TotalSize</key>
     <integer>122768752640</integer>
+    <key>FreeSpace</key>
+    <integer>40960000000</integer>
+    <key>APFSContainerFree</key>
+    <integer>51200000000</integer>
     <key>Internal</key>
     <false/>
 </dict>
@@ -243,6 +236,52 @@ mod tests {
 		assert_eq!(info.mount_point.as_deref(), Some("/Volumes/SOMETHING"));
 		assert_eq!(info.filesystem_type.as_deref(), Some("msdos"));
 		assert_eq!(info.total_size, Some(122768752640));
+		assert_eq!(info.free_space, Some(40960000000));
+		assert_eq!(info.apfs_container_free, Some(51200000000));
 		assert_eq!(info.internal, Some(false));
 	}
+
+	#[test]
+	fn test_parse_mount_output_finds_shares() {
+		let output = "\
+/dev/disk3s1s1 on / (apfs, sealed, local, read-only, journaled)
+//anders@nas.local/Media on /Volumes/Media (smbfs, nodev, nosuid, mounted by anders)
+nas.local:/export/backup on /private/nfs/backup (nfs, asynchronous)
+/dev/disk4s1 on /Volumes/USB (msdos, local, nodev, nosuid, noowners)
+";
+		assert_eq!(
+			parse_mount_output(output),
+			vec![
+				NetworkMount {
+					source: "//anders@nas.local/Media".into(),
+					mount_point: "/Volumes/Media".into(),
+					filesystem: "smbfs".into(),
+				},
+				NetworkMount {
+					source: "nas.local:/export/backup".into(),
+					mount_point: "/private/nfs/backup".into(),
+					filesystem: "nfs".into(),
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn test_cache_is_dropped_when_device_changes() {
+		let volume = VolumeInfo {
+			uuid: "u-1".into(),
+			name: "USB".into(),
+			mount_point: "/Volumes/USB".into(),
+			filesystem: "exfat".into(),
+			kind: VolumeKind::Local,
+			capacity_bytes: 100,
+			free_bytes: Some(50),
+		};
+		let known = HashMap::from([("/Volumes/USB".to_string(), CachedVolume { device: 7, info: Some(volume) })]);
+
+		let hit = cached_volume(&known, "/Volumes/USB", 7).expect("same device is cached");
+		assert_eq!(hit.info.unwrap().uuid, "u-1");
+		assert!(cached_volume(&known, "/Volumes/USB", 8).is_none(), "another drive at the same path");
+		assert!(cached_volume(&known, "/Volumes/Other", 7).is_none());
+	}
 }
@@ -1,3 +1,13 @@
+mod common;
+#[cfg(target_os = "linux")]
+mod linux;
+// Compiled everywhere so its plist parsing stays tested; only used off Linux
+#[cfg_attr(target_os = "linux", allow(dead_code))]
 mod macos;
 
-pub use macos::DriveWatcher;
+#[cfg(target_os = "linux")]
+use linux as platform;
+#[cfg(not(target_os = "linux"))]
+use macos as platform;
+
+pub use common::{DriveWatcher, DEFAULT_POLL_INTERVAL};
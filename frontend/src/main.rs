@@ -21,7 +21,7 @@ fn main() {
 		.with_max_level(tracing::Level::INFO)
 		.init();
 
-	info!("Starting Kip");
+	info!("Starting Kip (data in {})", daemon::db::data_dir().display());
 
 	// Initialize database before Dioxus launch.
 	// We keep the runtime alive — SurrealDB uses it for background tasks.
@@ -30,16 +30,30 @@ fn main() {
 
 	// Leak the runtime so SurrealDB's internal channels stay open.
 	// Dioxus creates its own runtime for UI async work.
-	Box::leak(Box::new(rt));
+	let rt: &'static tokio::runtime::Runtime = Box::leak(Box::new(rt));
 
 	match db_result {
 		Ok(db) => {
+			// Ctrl-C / SIGTERM checkpoint running copies before exiting, like closing the window
+			let db_for_signal = db.clone();
+			rt.spawn(async move {
+				daemon::engine::shutdown::wait_for_signal().await;
+				daemon::engine::shutdown::shutdown(&db_for_signal, daemon::engine::shutdown::SHUTDOWN_GRACE).await;
+				std::process::exit(0);
+			});
+			// Sync intents go back to following their sources
+			let db_for_watchers = db.clone();
+			rt.spawn(async move {
+				if let Err(e) = daemon::engine::watcher::resume_sync_watchers(&db_for_watchers).await {
+					tracing::warn!("couldn't resume sync watchers: {}", e);
+				}
+			});
 			LaunchBuilder::new().with_context(db).launch(app::App);
 		}
 		Err(e) => {
-			let err = e.to_string();
+			let locked = matches!(e, daemon::db::InitError::Locked(_));
 			LaunchBuilder::new()
-				.with_context(app::DbError(err))
+				.with_context(app::DbError { message: e.to_string(), locked })
 				.launch(app::DbErrorApp);
 		}
 	}
@@ -16,6 +16,12 @@ pub fn MachineChip(container: ContainerView, on_click: EventHandler<ContainerVie
 		"offline"
 	};
 	let opacity = if connected { "1" } else { "0.5" };
+	let usage = container.fullness().map(|f| {
+		let free_gb = container.free_bytes.unwrap_or(0) as f64 / 1e9;
+		let total_gb = container.capacity_bytes.unwrap_or(0) as f64 / 1e9;
+		let level = if f >= 0.9 { "full" } else { "ok" };
+		(f * 100.0, level, format!("{free_gb:.1} GB free of {total_gb:.1} GB"))
+	});
 
 	rsx! {
 		button {
@@ -26,6 +32,11 @@ pub fn MachineChip(container: ContainerView, on_click: EventHandler<ContainerVie
 			div { class: "chip-dot", style: "background: {color};" }
 			span { class: "chip-name", "{name}" }
 			span { class: "chip-kind", "{kind_label}" }
+			if let Some((pct, level, title)) = usage {
+				div { class: "chip-usage", title: "{title}",
+					div { class: "chip-usage-fill {level}", style: "width: {pct:.0}%;" }
+				}
+			}
 		}
 	}
 }
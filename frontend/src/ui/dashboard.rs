@@ -0,0 +1,176 @@
+use std::time::Instant;
+
+use dioxus::prelude::*;
+use surrealdb_types::SurrealValue;
+use tracing::error;
+use daemon::{engine::dedup::DedupStats, DbHandle};
+
+use crate::{
+	api::{self, Summary},
+	util::format_bytes,
+};
+
+#[derive(Debug, Clone, SurrealValue)]
+struct SumRow {
+	total: Option<i64>,
+}
+
+#[derive(Debug, Clone, SurrealValue)]
+struct CountRow {
+	count: i64,
+}
+
+/// Workspace-wide aggregates shown in the header, alongside `Summary`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct WorkspaceStats {
+	managed_bytes: i64,
+	transferred_bytes: i64,
+	connected_drives: i64,
+	dedup: DedupStats,
+}
+
+#[component]
+pub fn DashboardHeader(hostname: String, refresh_tick: u32) -> Element {
+	let db = use_context::<DbHandle>();
+	// Previous (time, transferred_bytes) sample, used to derive throughput
+	let mut last_sample = use_signal(|| None::<(Instant, i64)>);
+	let mut throughput = use_signal(|| 0.0f64);
+
+	let stats = use_resource(move || {
+		let db = db.clone();
+		let _tick = refresh_tick;
+		async move {
+			let stats = fetch_workspace_stats(&db).await?;
+			let summary = api::fetch_summary(&db).await.map_err(|e| e.to_string())?;
+			let now = Instant::now();
+			if let Some((then, bytes)) = *last_sample.peek() {
+				let secs = now.duration_since(then).as_secs_f64();
+				if secs > 0.0 {
+					let delta = (stats.transferred_bytes - bytes).max(0);
+					throughput.set(delta as f64 / secs);
+				}
+			}
+			last_sample.set(Some((now, stats.transferred_bytes)));
+			Ok::<_, String>((stats, summary))
+		}
+	});
+
+	let (current, summary) = match &*stats.read() {
+		Some(Ok(s)) => s.clone(),
+		Some(Err(e)) => {
+			error!("dashboard stats load failed: {}", e);
+			Default::default()
+		}
+		None => Default::default(),
+	};
+
+	let managed = format_bytes(current.managed_bytes as u64);
+	let duplicated = format_bytes(current.dedup.duplicate_bytes);
+	let saved = format_bytes(current.dedup.saved_bytes);
+	let health = health_line(&summary);
+	let health_class = if summary.needs_review > 0 { "header-health warn" } else { "header-health" };
+	let rate = if summary.active_transfers > 0 {
+		format!("{}/s", format_bytes(throughput() as u64))
+	} else {
+		"—".to_string()
+	};
+
+	rsx! {
+		div { class: "header",
+			div { class: "header-left",
+				h1 { "Kip" }
+				span { class: "host", "{hostname}" }
+			}
+			div { class: "{health_class}", "{health}" }
+			div { class: "header-stats",
+				HeaderStat { label: "Managed", value: managed }
+				HeaderStat { label: "Active", value: "{summary.active_transfers}" }
+				HeaderStat { label: "Throughput", value: rate }
+				HeaderStat {
+					label: "Review",
+					value: "{summary.needs_review}",
+					warn: summary.needs_review > 0,
+				}
+				HeaderStat { label: "Drives", value: "{current.connected_drives}" }
+				HeaderStat { label: "Duplicated", value: duplicated }
+				HeaderStat { label: "Dedup saved", value: saved }
+			}
+		}
+	}
+}
+
+#[component]
+fn HeaderStat(label: String, value: String, #[props(default)] warn: bool) -> Element {
+	let class = if warn { "header-stat warn" } else { "header-stat" };
+	rsx! {
+		div { class: "{class}",
+			span { class: "stat-value", "{value}" }
+			span { class: "stat-label", "{label}" }
+		}
+	}
+}
+
+/// One line of overall status, e.g. "2 of 4 copying (1.4 GB) · 340 queued ·
+/// 12 done today · 1 needs review". Quiet parts are left out. While the
+/// scheduler is dispatching, the copy count is its permits in use out of
+/// those available, so a stalled-looking transfer shows whether it's
+/// waiting on the concurrency limit.
+fn health_line(summary: &Summary) -> String {
+	let mut parts = Vec::new();
+	if summary.copy_slots > 0 {
+		parts.push(format!(
+			"{} of {} copying ({})",
+			summary.copying,
+			summary.copy_slots,
+			format_bytes(summary.bytes_in_flight)
+		));
+	} else if summary.active_transfers > 0 {
+		parts.push(format!(
+			"{} copying ({})",
+			summary.active_transfers,
+			format_bytes(summary.bytes_in_flight)
+		));
+	}
+	if summary.queued_files > 0 {
+		parts.push(format!("{} queued", summary.queued_files));
+	}
+	if summary.completed_today > 0 {
+		parts.push(format!("{} done today", summary.completed_today));
+	}
+	match summary.needs_review {
+		0 => {}
+		1 => parts.push("1 needs review".to_string()),
+		n => parts.push(format!("{n} need review")),
+	}
+	if parts.is_empty() {
+		return "All quiet".to_string();
+	}
+	parts.join(" · ")
+}
+
+async fn fetch_workspace_stats(db: &DbHandle) -> Result<WorkspaceStats, String> {
+	let mut resp = db
+		.db
+		.query(
+			"SELECT math::sum(total_bytes ?? 0) AS total FROM intent GROUP ALL;
+             SELECT math::sum(bytes_transferred ?? 0) AS total FROM transfer_job GROUP ALL;
+             SELECT count() AS count FROM drive WHERE connected = true GROUP ALL;",
+		)
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let managed: Vec<SumRow> = resp.take(0).map_err(|e| e.to_string())?;
+	let transferred: Vec<SumRow> = resp.take(1).map_err(|e| e.to_string())?;
+	let drives: Vec<CountRow> = resp.take(2).map_err(|e| e.to_string())?;
+	let dedup = daemon::engine::dedup::dedup_report(db).await.map_err(|e| e.to_string())?;
+
+	let sum = |rows: &[SumRow]| rows.first().and_then(|r| r.total).unwrap_or(0);
+	let count = |rows: &[CountRow]| rows.first().map(|r| r.count).unwrap_or(0);
+
+	Ok(WorkspaceStats {
+		managed_bytes: sum(&managed),
+		transferred_bytes: sum(&transferred),
+		connected_drives: count(&drives),
+		dedup,
+	})
+}
@@ -1,8 +1,14 @@
-use std::path::{Path, PathBuf};
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Path, PathBuf},
+	sync::{LazyLock, Mutex},
+	time::{Duration, Instant},
+};
 
 use dioxus::prelude::*;
 use tracing::{error, info, warn};
 use daemon::DbHandle;
+use walkdir::WalkDir;
 
 // ─── Pane ID generator ──────────────────────────────────────
 
@@ -26,6 +32,8 @@ pub struct PickerColumn {
 	pub dir_path: PathBuf,
 	pub entries: Vec<FsEntry>,
 	pub selected: Option<usize>,
+	/// Extra entries picked with shift/cmd-click, added alongside `selected`.
+	pub marked: HashSet<usize>,
 }
 
 #[derive(Store, Debug, Clone, PartialEq)]
@@ -37,6 +45,12 @@ pub struct PickerPaneData {
 	pub columns: Vec<PickerColumn>,
 	pub minimized: bool,
 	pub show_hidden: bool,
+	/// Walk directories to show their total size (opt-in, it's slow).
+	pub show_dir_sizes: bool,
+	/// Narrows the deepest column's entries; cleared on navigation.
+	pub filter: String,
+	/// Column the arrow keys move within.
+	pub active_column: usize,
 }
 
 // ─── Shared state (provided as context) ─────────────────────
@@ -62,6 +76,7 @@ impl Store<PickerManager> {
 				return;
 			}
 		}
+		// The pane itself reopens at the container's last-browsed directory
 		info!("opening picker for {} at {:?}", container_name, root);
 		self.panes().push(PickerPaneData {
 			id: next_pane_id(),
@@ -71,6 +86,9 @@ impl Store<PickerManager> {
 			columns: vec![],
 			minimized: false,
 			show_hidden: false,
+			show_dir_sizes: false,
+			filter: String::new(),
+			active_column: 0,
 		});
 	}
 
@@ -144,6 +162,77 @@ async fn read_dir_sorted(path: &Path, show_hidden: bool) -> Vec<FsEntry> {
 	.unwrap_or_default()
 }
 
+// ─── Directory sizes ────────────────────────────────────────
+
+/// Deepest level a directory-size walk descends to.
+const DIR_SIZE_MAX_DEPTH: usize = 16;
+
+/// Time one directory-size walk may take before it reports what it has.
+const DIR_SIZE_BUDGET: Duration = Duration::from_secs(2);
+
+/// Directory totals computed this session, so re-navigating is free.
+static DIR_SIZES: LazyLock<Mutex<HashMap<PathBuf, DirSize>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DirSize {
+	pub bytes: u64,
+	pub files: u64,
+	/// False if the walk hit the depth or time cap, so the totals are a floor.
+	pub complete: bool,
+}
+
+/// Total the regular files under `path`. Symlinks are neither followed nor
+/// counted.
+fn measure_dir(path: &Path) -> DirSize {
+	let deadline = Instant::now() + DIR_SIZE_BUDGET;
+	let mut size = DirSize { complete: true, ..Default::default() };
+	for entry in WalkDir::new(path).follow_links(false).max_depth(DIR_SIZE_MAX_DEPTH) {
+		if Instant::now() > deadline {
+			size.complete = false;
+			break;
+		}
+		let Ok(entry) = entry else { continue };
+		if entry.depth() == DIR_SIZE_MAX_DEPTH && entry.file_type().is_dir() {
+			size.complete = false;
+		}
+		if entry.file_type().is_file() {
+			if let Ok(meta) = entry.metadata() {
+				size.bytes += meta.len();
+				size.files += 1;
+			}
+		}
+	}
+	size
+}
+
+/// Sizes for `dirs`, from the session cache or a background walk.
+async fn dir_sizes(dirs: Vec<PathBuf>) -> Vec<(PathBuf, DirSize)> {
+	let mut sizes = Vec::with_capacity(dirs.len());
+	for dir in dirs {
+		let cached = DIR_SIZES.lock().unwrap().get(&dir).copied();
+		let size = match cached {
+			Some(size) => size,
+			None => {
+				let size = tokio::task::spawn_blocking({
+					let dir = dir.clone();
+					move || measure_dir(&dir)
+				})
+				.await
+				.unwrap_or_default();
+				DIR_SIZES.lock().unwrap().insert(dir.clone(), size);
+				size
+			}
+		};
+		sizes.push((dir, size));
+	}
+	sizes
+}
+
+fn format_dir_size(size: &DirSize) -> String {
+	let plus = if size.complete { "" } else { "+" };
+	format!("{}{plus} · {}{plus} files", format_size(size.bytes), size.files)
+}
+
 fn format_size(bytes: u64) -> String {
 	if bytes < 1024 {
 		format!("{bytes} B")
@@ -156,16 +245,24 @@ fn format_size(bytes: u64) -> String {
 	}
 }
 
-/// Get the "selected path" from the deepest column that has a selection.
-fn selected_path(columns: &[PickerColumn]) -> Option<PathBuf> {
-	for col in columns.iter().rev() {
-		if let Some(idx) = col.selected {
-			if let Some(entry) = col.entries.get(idx) {
-				return Some(entry.path.clone());
-			}
-		}
-	}
-	None
+/// Case-insensitive substring match for the column filter. An empty filter
+/// matches everything.
+fn matches_filter(name: &str, filter: &str) -> bool {
+	filter.is_empty() || name.to_lowercase().contains(&filter.to_lowercase())
+}
+
+/// Paths picked in the deepest column that has a selection: the selected
+/// entry plus any shift/cmd-clicked ones, in listing order.
+fn selected_paths(columns: &[PickerColumn]) -> Vec<PathBuf> {
+	let Some(col) = columns.iter().rev().find(|c| c.selected.is_some() || !c.marked.is_empty()) else {
+		return Vec::new();
+	};
+	col.entries
+		.iter()
+		.enumerate()
+		.filter(|(i, _)| col.selected == Some(*i) || col.marked.contains(i))
+		.map(|(_, e)| e.path.clone())
+		.collect()
 }
 
 /// Short label for a path (last 1-2 components).
@@ -181,6 +278,204 @@ fn short_label(path: &Path) -> String {
 	}
 }
 
+fn pane_index(picker: Store<PickerManager>, pane_id: u64) -> Option<usize> {
+	let panes = picker.panes();
+	let panes_read = panes.read();
+	panes_read.iter().position(|p| p.id == pane_id)
+}
+
+/// Select an entry and make its column the active one, dropping any columns
+/// to its right. Returns the pane's `show_hidden` for a follow-up read.
+fn select_entry(picker: Store<PickerManager>, pane_id: u64, col_idx: usize, entry_idx: usize, opening_dir: bool) -> Option<bool> {
+	let idx = pane_index(picker, pane_id)?;
+	let panes = picker.panes();
+	let pane_store = panes.index(idx);
+	let mut cols = pane_store.columns();
+	let mut cols_write = cols.write();
+	// The deepest column is about to change, so its filter no longer applies
+	let navigated = opening_dir || cols_write.len() > col_idx + 1;
+	cols_write.truncate(col_idx + 1);
+	if let Some(col) = cols_write.get_mut(col_idx) {
+		col.selected = Some(entry_idx);
+		col.marked.clear();
+	}
+	drop(cols_write);
+	if navigated {
+		pane_store.filter().set(String::new());
+	}
+	pane_store.active_column().set(col_idx);
+	Some(pane_store.show_hidden().cloned())
+}
+
+/// Shift/cmd-click: add or remove an entry from the multi-selection without
+/// navigating. Columns to its right go away, so it becomes the deepest.
+fn toggle_mark(picker: Store<PickerManager>, pane_id: u64, col_idx: usize, entry_idx: usize) {
+	let Some(idx) = pane_index(picker, pane_id) else { return };
+	let panes = picker.panes();
+	let pane_store = panes.index(idx);
+	let mut cols = pane_store.columns();
+	let mut cols_write = cols.write();
+	if cols_write.len() > col_idx + 1 {
+		cols_write.truncate(col_idx + 1);
+		pane_store.filter().set(String::new());
+	}
+	if let Some(col) = cols_write.get_mut(col_idx) {
+		if col.selected == Some(entry_idx) {
+			col.selected = None;
+		} else if !col.marked.remove(&entry_idx) {
+			col.marked.insert(entry_idx);
+		}
+	}
+	drop(cols_write);
+	pane_store.active_column().set(col_idx);
+}
+
+/// Breadcrumb click: drop every column right of `dir`'s, clear its selection
+/// and re-read it.
+async fn jump_to_column(picker: Store<PickerManager>, pane_id: u64, dir: PathBuf) {
+	let show_hidden = {
+		let Some(idx) = pane_index(picker, pane_id) else { return };
+		let panes = picker.panes();
+		let pane_store = panes.index(idx);
+		let mut cols = pane_store.columns();
+		let mut cols_write = cols.write();
+		let Some(col_idx) = cols_write.iter().position(|c| c.dir_path == dir) else { return };
+		cols_write.truncate(col_idx + 1);
+		if let Some(col) = cols_write.get_mut(col_idx) {
+			col.selected = None;
+			col.marked.clear();
+		}
+		drop(cols_write);
+		pane_store.filter().set(String::new());
+		pane_store.active_column().set(col_idx);
+		pane_store.show_hidden().cloned()
+	};
+
+	let entries = read_dir_sorted(&dir, show_hidden).await;
+	if let Some(idx) = pane_index(picker, pane_id) {
+		let panes = picker.panes();
+		let mut cols = panes.index(idx).columns();
+		let mut cols_write = cols.write();
+		if let Some(col) = cols_write.last_mut().filter(|c| c.dir_path == dir) {
+			col.entries = entries;
+		}
+	}
+}
+
+/// Each ancestor of `path` down to `path` itself, labelled by its last
+/// component (`/` for the filesystem root).
+fn breadcrumb_segments(path: &Path) -> Vec<(String, PathBuf)> {
+	let mut segments: Vec<(String, PathBuf)> = path
+		.ancestors()
+		.map(|p| {
+			let label = p
+				.file_name()
+				.map(|n| n.to_string_lossy().to_string())
+				.unwrap_or_else(|| p.to_string_lossy().to_string());
+			(label, p.to_path_buf())
+		})
+		.filter(|(label, _)| !label.is_empty())
+		.collect();
+	segments.reverse();
+	segments
+}
+
+/// Columns from `root` down to `target`, each with the next directory on the
+/// way selected. Just the root column when there's no target.
+async fn columns_down_to(root: &Path, target: Option<&Path>, show_hidden: bool) -> Vec<PickerColumn> {
+	let mut dirs: Vec<PathBuf> = target
+		.map(|t| t.ancestors().take_while(|a| a.starts_with(root)).map(Path::to_path_buf).collect())
+		.unwrap_or_default();
+	dirs.reverse();
+	if dirs.is_empty() {
+		dirs.push(root.to_path_buf());
+	}
+
+	let mut columns: Vec<PickerColumn> = Vec::with_capacity(dirs.len());
+	for dir in dirs {
+		let entries = read_dir_sorted(&dir, show_hidden).await;
+		if let Some(parent) = columns.last_mut() {
+			parent.selected = parent.entries.iter().position(|e| e.path == dir);
+		}
+		columns.push(PickerColumn { dir_path: dir, entries, selected: None, marked: HashSet::new() });
+	}
+	columns
+}
+
+/// Read `dir` and push it as a new column.
+async fn open_dir(picker: Store<PickerManager>, pane_id: u64, dir: PathBuf, show_hidden: bool) {
+	let entries = read_dir_sorted(&dir, show_hidden).await;
+	if let Some(idx) = pane_index(picker, pane_id) {
+		picker.panes().index(idx).columns().push(PickerColumn {
+			dir_path: dir,
+			entries,
+			selected: None,
+			marked: HashSet::new(),
+		});
+	}
+}
+
+/// Arrow keys: Up/Down move the selection within the active column, Right
+/// opens the selected directory and focuses its first entry, Left goes back
+/// a column. Right on a file does nothing.
+async fn navigate(picker: Store<PickerManager>, pane_id: u64, key: Key) {
+	let Some(idx) = pane_index(picker, pane_id) else { return };
+	let pane = picker.panes().index(idx).cloned();
+	let active = pane.active_column.min(pane.columns.len().saturating_sub(1));
+	let Some(col) = pane.columns.get(active) else { return };
+
+	match key {
+		Key::ArrowUp | Key::ArrowDown => {
+			let is_last = active + 1 == pane.columns.len();
+			let visible: Vec<usize> = col
+				.entries
+				.iter()
+				.enumerate()
+				.filter(|(_, e)| !is_last || matches_filter(&e.name, &pane.filter))
+				.map(|(i, _)| i)
+				.collect();
+			let pos = col.selected.and_then(|s| visible.iter().position(|&i| i == s));
+			let next = match (key == Key::ArrowDown, pos) {
+				(true, Some(p)) => visible.get(p + 1).or(visible.last()),
+				(true, None) => visible.first(),
+				(false, Some(p)) => visible.get(p.saturating_sub(1)),
+				(false, None) => visible.last(),
+			};
+			if let Some(&next) = next {
+				select_entry(picker, pane_id, active, next, false);
+			}
+		}
+		Key::ArrowRight => {
+			let Some(entry) = col.selected.and_then(|s| col.entries.get(s)) else { return };
+			if !entry.is_dir {
+				return;
+			}
+			if pane.columns.len() <= active + 1 {
+				open_dir(picker, pane_id, entry.path.clone(), pane.show_hidden).await;
+			}
+			let Some(idx) = pane_index(picker, pane_id) else { return };
+			let panes = picker.panes();
+			let pane_store = panes.index(idx);
+			let mut cols = pane_store.columns();
+			let mut cols_write = cols.write();
+			let Some(child) = cols_write.get_mut(active + 1) else { return };
+			if child.selected.is_none() && !child.entries.is_empty() {
+				child.selected = Some(0);
+			}
+			drop(cols_write);
+			pane_store.active_column().set(active + 1);
+		}
+		Key::ArrowLeft if active > 0 => {
+			let panes = picker.panes();
+			let pane_store = panes.index(idx);
+			pane_store.columns().write().truncate(active);
+			pane_store.filter().set(String::new());
+			pane_store.active_column().set(active - 1);
+		}
+		_ => {}
+	}
+}
+
 // ─── Top-level layer ────────────────────────────────────────
 
 #[component]
@@ -255,31 +550,98 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 	let db = use_context::<DbHandle>();
 
 	// Find pane index helper
-	let find_pane_idx = move || -> Option<usize> {
-		let panes = picker.panes();
-		let panes_read = panes.read();
-		panes_read.iter().position(|p| p.id == pane_id)
-	};
+	let find_pane_idx = move || pane_index(picker, pane_id);
+
+	// Load root dir on mount, reopening wherever this container was last browsed
+	let mut restored = use_signal(|| false);
+	use_effect({
+		let db = db.clone();
+		move || {
+			let Some(idx) = find_pane_idx() else { return };
+			let panes = picker.panes();
+			let pane_store = panes.index(idx);
+			let needs_load = pane_store.columns().is_empty();
+			if needs_load {
+				let root = pane_store.root_path().cloned();
+				let show_hidden = pane_store.show_hidden().cloned();
+				let container_id = pane_store.container_id().cloned();
+				// Only the first load restores; a hidden-files toggle goes back to the root
+				let restore = !*restored.peek();
+				restored.set(true);
+				let db = db.clone();
+				spawn(async move {
+					let target = if restore {
+						match load_last_dir(&db, &container_id).await {
+							Ok(dir) => dir.filter(|d| d.starts_with(&root) && d.is_dir()),
+							Err(e) => {
+								warn!("loading last picker dir failed: {}", e);
+								None
+							}
+						}
+					} else {
+						None
+					};
+					let columns = columns_down_to(&root, target.as_deref(), show_hidden).await;
+					if let Some(idx) = find_pane_idx() {
+						let panes = picker.panes();
+						let pane_store = panes.index(idx);
+						pane_store.active_column().set(columns.len().saturating_sub(1));
+						pane_store.columns().set(columns);
+					}
+				});
+			}
+		}
+	});
 
-	// Load root dir on mount
+	// Directory sizes, filled in as background walks finish
+	let mut known_sizes = use_signal(HashMap::<PathBuf, DirSize>::new);
+	let mut measuring = use_signal(HashSet::<PathBuf>::new);
 	use_effect(move || {
 		let Some(idx) = find_pane_idx() else { return };
 		let panes = picker.panes();
 		let pane_store = panes.index(idx);
-		let needs_load = pane_store.columns().is_empty();
-		if needs_load {
-			let root = pane_store.root_path().cloned();
-			let show_hidden = pane_store.show_hidden().cloned();
+		if !pane_store.show_dir_sizes().cloned() {
+			return;
+		}
+		let dirs: Vec<PathBuf> = pane_store
+			.columns()
+			.read()
+			.iter()
+			.flat_map(|c| c.entries.iter().filter(|e| e.is_dir).map(|e| e.path.clone()))
+			.filter(|p| !known_sizes.peek().contains_key(p) && !measuring.peek().contains(p))
+			.collect();
+		if dirs.is_empty() {
+			return;
+		}
+		measuring.write().extend(dirs.iter().cloned());
+		spawn(async move {
+			for (dir, size) in dir_sizes(dirs).await {
+				measuring.write().remove(&dir);
+				known_sizes.write().insert(dir, size);
+			}
+		});
+	});
+
+	// Remember the deepest open directory for next time
+	let mut last_saved = use_signal(|| None::<PathBuf>);
+	use_effect({
+		let db = db.clone();
+		move || {
+			let Some(idx) = find_pane_idx() else { return };
+			let panes = picker.panes();
+			let pane_store = panes.index(idx);
+			let Some(dir) = pane_store.columns().read().last().map(|c| c.dir_path.clone()) else {
+				return;
+			};
+			if last_saved.peek().as_ref() == Some(&dir) {
+				return;
+			}
+			last_saved.set(Some(dir.clone()));
+			let container_id = pane_store.container_id().cloned();
+			let db = db.clone();
 			spawn(async move {
-				let entries = read_dir_sorted(&root, show_hidden).await;
-				if let Some(idx) = find_pane_idx() {
-					let panes = picker.panes();
-					let mut cols = panes.index(idx).columns();
-					cols.set(vec![PickerColumn {
-						dir_path: root.to_path_buf(),
-						entries,
-						selected: None,
-					}]);
+				if let Err(e) = save_last_dir(&db, &container_id, &dir).await {
+					warn!("saving last picker dir failed: {}", e);
 				}
 			});
 		}
@@ -300,25 +662,74 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 	let container_id = pane.container_id.clone();
 	let columns = pane.columns.clone();
 	let show_hidden = pane.show_hidden;
+	let show_dir_sizes = pane.show_dir_sizes;
+	let filter = pane.filter.clone();
+	let last_col = columns.len().saturating_sub(1);
+
+	// Compute selected paths for the bottom bar
+	let sel_paths = selected_paths(&columns);
+	let sel_display = match sel_paths.as_slice() {
+		[] => String::new(),
+		[path] => path.to_string_lossy().to_string(),
+		paths => format!("{} items selected", paths.len()),
+	};
+	let has_selection = !sel_paths.is_empty();
 
-	// Compute selected path for the bottom bar
-	let sel_path = selected_path(&columns);
-	let sel_display = sel_path
-		.as_ref()
-		.map(|p| p.to_string_lossy().to_string())
-		.unwrap_or_default();
-	let has_selection = sel_path.is_some();
+	// Breadcrumb: the last column's path. Segments with a column of their own
+	// (the root and below) jump back to it; ones above the root are inert.
+	let segments = columns.last().map(|c| breadcrumb_segments(&c.dir_path)).unwrap_or_default();
+	let breadcrumb: Vec<(String, PathBuf, bool, bool)> = segments
+		.iter()
+		.enumerate()
+		.map(|(i, (label, path))| {
+			let has_column = columns.iter().any(|c| c.dir_path == *path);
+			// The root segment is already a "/"
+			let separator = i > 0 && segments[i - 1].0 != "/";
+			(label.clone(), path.clone(), has_column, separator)
+		})
+		.collect();
 
-	// Breadcrumb: show the path of the last column
-	let breadcrumb = columns
-		.last()
-		.map(|c| c.dir_path.to_string_lossy().to_string())
-		.unwrap_or_default();
+	let add_selected = move || {
+		let sel = sel_paths.clone();
+		let cid = container_id.clone();
+		let db = db.clone();
+		spawn(async move {
+			let mut added = 0;
+			for path in sel {
+				let path_str = kip_core::util::encode_path(&path);
+				match add_location_from_picker(&db, &cid, &path_str).await {
+					Ok(()) => {
+						info!("location added from picker: {}", path_str);
+						added += 1;
+					}
+					Err(e) => error!("add location {} failed: {}", path_str, e),
+				}
+			}
+			if added > 0 {
+				on_location_added.call(());
+			}
+		});
+	};
 
 	rsx! {
 		div {
 			class: "picker-pane",
+			tabindex: "0",
 			onclick: move |e: MouseEvent| e.stop_propagation(),
+			onkeydown: {
+			    let add_selected = add_selected.clone();
+			    move |e: KeyboardEvent| {
+			        let key = e.key();
+			        match key {
+			            Key::ArrowUp | Key::ArrowDown | Key::ArrowLeft | Key::ArrowRight => {
+			                e.prevent_default();
+			                spawn(navigate(picker, pane_id, key));
+			            }
+			            Key::Enter if has_selection => add_selected(),
+			            _ => {}
+			        }
+			    }
+			},
 
 			// Title bar
 			div { class: "picker-title-bar",
@@ -336,6 +747,8 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 						        let new_show_hidden = !pane_store.show_hidden().cloned();
 						        pane_store.show_hidden().set(new_show_hidden);
 						        pane_store.columns().clear();
+						        pane_store.filter().set(String::new());
+						        pane_store.active_column().set(0);
 
 						        let root = pane_store.root_path().cloned();
 						        spawn(async move {
@@ -349,6 +762,7 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 						                            dir_path: root.to_path_buf(),
 						                            entries,
 						                            selected: None,
+						                            marked: HashSet::new(),
 						                        },
 						                    ],
 						                );
@@ -358,6 +772,20 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 						},
 						".*"
 					}
+					// Toggle directory sizes
+					button {
+						class: if show_dir_sizes { "picker-btn-toggle active" } else { "picker-btn-toggle" },
+						title: "Show folder sizes",
+						onclick: move |_| {
+						    if let Some(idx) = find_pane_idx() {
+						        let panes = picker.panes();
+						        let pane_store = panes.index(idx);
+						        let next = !pane_store.show_dir_sizes().cloned();
+						        pane_store.show_dir_sizes().set(next);
+						    }
+						},
+						"\u{03A3}" // Σ
+					}
 					button {
 						class: "picker-btn-minimize",
 						onclick: move |_| picker.minimize(pane_id),
@@ -372,15 +800,57 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 			}
 
 			// Breadcrumb
-			div { class: "picker-breadcrumb", "{breadcrumb}" }
+			div { class: "picker-breadcrumb",
+				for (i , (label , path , has_column , separator)) in breadcrumb.into_iter().enumerate() {
+					if separator {
+						span { key: "sep-{i}", class: "picker-crumb-sep", "/" }
+					}
+					if has_column {
+						span {
+							key: "{i}",
+							class: "picker-crumb",
+							onclick: move |_| {
+							    spawn(jump_to_column(picker, pane_id, path.clone()));
+							},
+							"{label}"
+						}
+					} else {
+						span { key: "{i}", class: "picker-crumb inert", "{label}" }
+					}
+				}
+			}
+
+			// Filter for the deepest column
+			input {
+				class: "picker-filter",
+				r#type: "search",
+				placeholder: "Filter",
+				value: "{filter}",
+				oninput: move |e: FormEvent| {
+				    if let Some(idx) = find_pane_idx() {
+				        picker.panes().index(idx).filter().set(e.value());
+				    }
+				},
+				// Left/Right move the caret here rather than between columns
+				onkeydown: move |e: KeyboardEvent| {
+				    if matches!(e.key(), Key::ArrowLeft | Key::ArrowRight) {
+				        e.stop_propagation();
+				    }
+				},
+			}
 
 			// Column view
 			div { class: "picker-columns",
 				for (col_idx , col) in columns.iter().enumerate() {
 					div { key: "{col_idx}", class: "picker-column",
-						for (entry_idx , entry) in col.entries.iter().enumerate() {
+						for (entry_idx , entry) in col
+							.entries
+							.iter()
+							.enumerate()
+							.filter(|(_, e)| col_idx != last_col || matches_filter(&e.name, &filter))
+						{
 							{
-							    let is_selected = col.selected == Some(entry_idx);
+							    let is_selected = col.selected == Some(entry_idx) || col.marked.contains(&entry_idx);
 							    let is_dir = entry.is_dir;
 							    let entry_path = entry.path.clone();
 							    let name = entry.name.clone();
@@ -390,40 +860,29 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 							        "picker-entry"
 							    };
 
-							    let size_str = if is_dir { String::new() } else { format_size(entry.size) };
+							    let size_str = match (is_dir, known_sizes.read().get(&entry.path)) {
+							        (false, _) => format_size(entry.size),
+							        (true, Some(size)) => format_dir_size(size),
+							        (true, None) => "…".to_string(),
+							    };
 							    rsx! {
 								div {
 									key: "{name}",
 									class: "{entry_class}",
-									onclick: move |_| {
+									onclick: move |e: MouseEvent| {
 									    let entry_path = entry_path.clone();
+									    let mods = e.modifiers();
+									    let multi = mods.shift() || mods.meta() || mods.ctrl();
 									    async move {
-									        let show_hidden = {
-									            let Some(idx) = find_pane_idx() else { return };
-									            let panes = picker.panes();
-									            let pane_store = panes.index(idx);
-									            let mut cols = pane_store.columns();
-									            let mut cols_write = cols.write();
-									            cols_write.truncate(col_idx + 1);
-									            if let Some(col) = cols_write.get_mut(col_idx) {
-									                col.selected = Some(entry_idx);
-									            }
-									            drop(cols_write);
-									            pane_store.show_hidden().cloned()
+									        if multi {
+									            toggle_mark(picker, pane_id, col_idx, entry_idx);
+									            return;
+									        }
+									        let Some(show_hidden) = select_entry(picker, pane_id, col_idx, entry_idx, is_dir) else {
+									            return;
 									        };
 									        if is_dir {
-									            let entries = read_dir_sorted(&entry_path, show_hidden).await;
-									            if let Some(idx) = find_pane_idx() {
-									                let panes = picker.panes();
-									                panes
-									                    .index(idx)
-									                    .columns()
-									                    .push(PickerColumn {
-									                        dir_path: entry_path.to_path_buf(),
-									                        entries,
-									                        selected: None,
-									                    });
-									            }
+									            open_dir(picker, pane_id, entry_path, show_hidden).await;
 									        }
 									    }
 									},
@@ -433,7 +892,7 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 										span { class: "entry-icon file", "\u{25AB}" } // ▫
 									}
 									span { class: "entry-name", "{name}" }
-									if !is_dir {
+									if !is_dir || show_dir_sizes {
 										span { class: "entry-size", "{size_str}" }
 									}
 								}
@@ -454,26 +913,7 @@ fn PickerPaneView(picker: Store<PickerManager>, pane_id: u64, on_location_added:
 				button {
 					class: "btn-primary picker-add-btn",
 					disabled: !has_selection,
-					onclick: {
-					    let db = db.clone();
-					    move |_| {
-					        let sel = sel_path.clone();
-					        let cid = container_id.clone();
-					        let db = db.clone();
-					        spawn(async move {
-					            if let Some(path) = sel {
-					                let path_str = path.to_string_lossy().to_string();
-					                match add_location_from_picker(&db, &cid, &path_str).await {
-					                    Ok(()) => {
-					                        info!("location added from picker: {}", path_str);
-					                        on_location_added.call(());
-					                    }
-					                    Err(e) => error!("add location failed: {}", e),
-					                }
-					            }
-					        });
-					    }
-					},
+					onclick: move |_| add_selected(),
 					"Add to workspace"
 				}
 			}
@@ -487,15 +927,68 @@ fn parse_rid(s: &str) -> Option<(&str, &str)> {
 	s.split_once(':')
 }
 
+/// The directory last browsed for a container, if one was saved.
+async fn load_last_dir(db: &DbHandle, container_id: &str) -> Result<Option<PathBuf>, String> {
+	let mut resp = db
+		.db
+		.query("SELECT VALUE last_dir FROM type::record('picker_state', $container)")
+		.bind(("container", container_id.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let dirs: Vec<String> = resp.take(0).map_err(|e| e.to_string())?;
+	Ok(dirs.first().map(|d| kip_core::util::decode_path(d)))
+}
+
+async fn save_last_dir(db: &DbHandle, container_id: &str, dir: &Path) -> Result<(), String> {
+	db.db
+		.query("UPSERT type::record('picker_state', $container) SET last_dir = $dir, updated_at = time::now()")
+		.bind(("container", container_id.to_string()))
+		.bind(("dir", kip_core::util::encode_path(dir)))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+	Ok(())
+}
+
+/// Whether the container is a remote machine, and if so whether it's online.
+async fn remote_machine_online(db: &DbHandle, table: &str, key: &str) -> Result<Option<bool>, String> {
+	if table != "machine" {
+		return Ok(None);
+	}
+	let mut resp = db
+		.db
+		.query("SELECT kind, online FROM ONLY type::record('machine', $key)")
+		.bind(("key", key.to_string()))
+		.await
+		.map_err(|e| e.to_string())?;
+	let row: Option<serde_json::Value> = resp.take(0).map_err(|e| e.to_string())?;
+	Ok(row
+		.filter(|r| r["kind"] == "remote")
+		.map(|r| r["online"].as_bool() == Some(true)))
+}
+
+/// Record `path` as a location on the container. A path on this machine or
+/// a drive must exist; a remote machine's can't be checked from here, so its
+/// location is available while the machine is online.
 async fn add_location_from_picker(db: &DbHandle, container_id: &str, path: &str) -> Result<(), String> {
 	let (table, key) = parse_rid(container_id).ok_or("Invalid container ID")?;
+	if !kip_core::util::decode_path(path).is_absolute() {
+		return Err(format!("not an absolute path: {path}"));
+	}
+
+	let (available, is_dir) = match remote_machine_online(db, table, key).await? {
+		Some(online) => (online, None),
+		None => (true, Some(daemon::probe_path(path).ok_or_else(|| format!("{path} does not exist"))?)),
+	};
 
 	let query = format!(
 		"LET $container = type::record('{table}', $key);
          CREATE location CONTENT {{
              {table}: $container,
              path: $path,
-             available: true,
+             available: $available,
+             is_dir: $is_dir,
              created_at: time::now(),
          }}"
 	);
@@ -504,6 +997,8 @@ async fn add_location_from_picker(db: &DbHandle, container_id: &str, path: &str)
 		.query(&query)
 		.bind(("key", key.to_string()))
 		.bind(("path", path.to_string()))
+		.bind(("available", available))
+		.bind(("is_dir", is_dir))
 		.await
 		.map_err(|e| e.to_string())?
 		.check()
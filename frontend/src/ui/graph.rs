@@ -1,6 +1,8 @@
+use std::collections::HashMap;
+
 use dioxus::prelude::*;
 use tracing::{error, info};
-use daemon::{load_graph_data, DbHandle, Graph};
+use daemon::{load_graph_data, DbHandle, Graph, MachineSettings};
 use kip_core::{ContainerView, Vec2};
 
 use crate::ui::{
@@ -8,13 +10,17 @@ use crate::ui::{
 	notification::NotificationService,
 };
 
+/// Wheel delta (in pixels) to zoom exponent; ~100px per notch ≈ 10%.
+const WHEEL_ZOOM_SPEED: f64 = 0.001;
+
+/// How long the simulation must stay stopped before its layout is saved.
+const SETTLE_SAVE_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
 // ─── Helper: Get workspace-relative mouse coordinates ─────────────────
 
-fn get_workspace_coords(e: &MouseEvent) -> (f64, f64) {
-	// Use client coordinates and subtract header offset
-	// Header height: ~61px (padding 16+16 + font 17 + border 1 + spacing)
+fn get_workspace_coords(graph: Signal<Graph>, e: &MouseEvent) -> (f64, f64) {
 	let client_coords = e.client_coordinates();
-	(client_coords.x, client_coords.y - 61.0)
+	graph.with(|g| g.client_to_workspace(client_coords.x, client_coords.y))
 }
 
 // ─── Graph Toolbar Component ──────────────────────────────────
@@ -87,6 +93,91 @@ pub fn GraphToolbar(
 pub enum AddPanelState {
 	Closed,
 	AddMachine,
+	/// Editing the remote machine with this id.
+	EditMachine(String),
+}
+
+// ─── Keyboard delete ──────────────────────────────────────────
+
+/// Delete the selected nodes. Locations are deleted with their intents, so
+/// if any intent would go too, the first press only warns and a second press
+/// with the same selection confirms. Scanned files and folders are only
+/// removed from the canvas; machines and drives are kept.
+async fn delete_selected(
+	db: DbHandle,
+	mut graph: Signal<Graph>,
+	selected: Vec<String>,
+	mut pending_delete: Signal<Option<(Vec<String>, u64)>>,
+	mut notifs: Store<NotificationService>,
+	on_changed: EventHandler,
+) {
+	let mut selected = selected;
+	selected.sort();
+	let locations: Vec<String> = selected.iter().filter(|id| id.starts_with("location:")).cloned().collect();
+
+	let mut intents = 0;
+	for id in &locations {
+		match daemon::intents_using_location(&db, id).await {
+			Ok(n) => intents += n,
+			Err(e) => {
+				error!("intents_using_location failed: {}", e);
+				return;
+			}
+		}
+	}
+
+	let confirmed = pending_delete().is_some_and(|(ids, _)| ids == selected);
+	if intents > 0 && !confirmed {
+		notifs.warn(format!(
+			"Deleting {} location(s) also deletes {} intent(s). Press Delete again to confirm.",
+			locations.len(),
+			intents
+		));
+		pending_delete.set(Some((selected, intents)));
+		return;
+	}
+	pending_delete.set(None);
+
+	for id in &selected {
+		let is_container = id.starts_with("machine:") || id.starts_with("drive:");
+		if is_container {
+			continue;
+		}
+		if id.starts_with("location:") {
+			if let Err(e) = daemon::delete_location(&db, id).await {
+				error!("delete_location failed: {}", e);
+				continue;
+			}
+		}
+		graph.with_mut(|g| {
+			g.selected.remove(id);
+			g.remove_node(id);
+		});
+	}
+	if !locations.is_empty() {
+		on_changed.call(());
+	}
+}
+
+// ─── Lazy directory children ─────────────────────────────────
+
+/// Toggle a node, and when that expands a directory whose children haven't
+/// been loaded yet, fetch them from the DB and add them under it.
+pub(crate) fn toggle_and_load(db: DbHandle, mut graph: Signal<Graph>, node_id: String) {
+	let needs_children = graph.with_mut(|g| {
+		g.toggle_expand(&node_id);
+		g.needs_children(&node_id)
+	});
+	if !needs_children {
+		return;
+	}
+	spawn(async move {
+		let containers = graph.with(|g| g.containers.clone());
+		match daemon::load_child_nodes(&db, &containers, &node_id).await {
+			Ok(children) => graph.with_mut(|g| g.insert_children(&node_id, children)),
+			Err(e) => error!("loading children of {} failed: {}", node_id, e),
+		}
+	});
 }
 
 // ─── Main Mapping Graph Component ──────────────────────────────
@@ -104,10 +195,8 @@ pub fn MappingGraph(
 	// // Create the main graph state as a signal
 	// let mut graph = use_signal(|| Graph::new());
 
-	// Add-machine form fields
-	let mut machine_name = use_signal(|| String::new());
-	let mut machine_host = use_signal(|| String::new());
-	let mut machine_user = use_signal(|| String::new());
+	// Add/edit-machine form fields
+	let mut machine_form = use_signal(MachineSettings::default);
 	let mut add_panel = use_signal(|| AddPanelState::Closed);
 
 	// Create the main graph state as a signal
@@ -230,10 +319,14 @@ pub fn MappingGraph(
 	});
 
 	// Start the simulation loop if running
+	let db_for_sim = db.clone();
 	use_effect(move || {
 		tracing::info!("Simulation loop started");
+		let db = db_for_sim.clone();
 		spawn(async move {
 			let mut tick_count = 0;
+			// Positions last written to the DB, so a settle only saves what moved
+			let mut saved: HashMap<String, (f64, f64)> = HashMap::new();
 			loop {
 				// Check if simulation should run before sleeping
 				let sim_state = graph.with(|g| g.sim_running);
@@ -265,41 +358,98 @@ pub fn MappingGraph(
 					// tracing::info!("Simulation loop: tick {} stopped, will restart if needed", tick_count);
 					// Don't break - just reset tick count and wait for sim_running to become true again
 					tick_count = 0;
+
+					// Persist the settled layout, unless a drag restarts the sim right away
+					tokio::time::sleep(SETTLE_SAVE_DELAY).await;
+					if graph.with(|g| g.sim_running) {
+						continue;
+					}
+					let moved: Vec<(String, f64, f64)> = graph
+						.with(|g| g.location_positions())
+						.into_iter()
+						.filter(|(id, x, y)| {
+							saved.get(id).is_none_or(|(sx, sy)| (sx - x).abs() > 0.5 || (sy - y).abs() > 0.5)
+						})
+						.collect();
+					for (id, x, y) in moved {
+						match daemon::save_node_position(&db, &id, x, y).await {
+							Ok(()) => {
+								saved.insert(id, (x, y));
+							}
+							Err(e) => error!("Failed to save node position: {}", e),
+						}
+					}
 				}
 			}
 		});
 	});
 
-	// Keyboard shortcuts
-	use_effect(move || {
-		let graph_signal = graph;
-		spawn(async move {
-			// Note: Dioxus 0.7 doesn't have global keyboard listeners yet
-			// This is a placeholder for future keyboard shortcut implementation
-			// Common shortcuts to implement:
-			// - Delete/Backspace: Delete selected nodes
-			// - Ctrl+A: Select all
-			// - Escape: Deselect all, close menus
-			// - Space: Pan mode
-			// - +/-: Zoom in/out
-			// - E: Expand selected directory
-			// - C: Collapse selected directory
-			// - S: Start sync (edge creation mode)
-			tracing::info!("Keyboard shortcuts ready (implementation pending)");
-		});
-	});
+	// Space held down turns a plain drag into a pan
+	let mut space_held = use_signal(|| false);
+
+	// Selected locations waiting for a second Delete press, because deleting
+	// them also deletes this many intents
+	let mut pending_delete = use_signal(|| None::<(Vec<String>, u64)>);
+
+	// Keyboard shortcuts (the graph area is focusable; clicking it focuses it)
+	let on_keydown = {
+		let db = db.clone();
+		move |e: KeyboardEvent| {
+			let key = e.key();
+			if key == Key::Escape {
+				*add_panel.write() = AddPanelState::Closed;
+				pending_delete.set(None);
+				graph.with_mut(|g| {
+					if matches!(g.drag_state, daemon::DragState::CreatingEdge { .. } | daemon::DragState::Lasso { .. }) {
+						g.drag_state = daemon::DragState::None;
+					}
+					g.context_menu.hide();
+				});
+				return;
+			}
+			// Typing in the add-machine form must not edit the graph
+			if *add_panel.read() != AddPanelState::Closed {
+				return;
+			}
+
+			let mods = e.modifiers();
+			if key == Key::Character(" ".into()) {
+				e.prevent_default();
+				space_held.set(true);
+			} else if (mods.meta() || mods.ctrl()) && key == Key::Character("a".into()) {
+				e.prevent_default();
+				graph.with_mut(|g| g.select_all_visible());
+			} else if key == Key::Delete || key == Key::Backspace {
+				e.prevent_default();
+				let selected: Vec<String> = graph.with(|g| g.selected.iter().cloned().collect());
+				if selected.is_empty() {
+					return;
+				}
+				let db = db.clone();
+				spawn(async move {
+					delete_selected(db, graph, selected, pending_delete, notifs, on_changed).await;
+				});
+			}
+		}
+	};
 
 	rsx! {
-		div { class: "graph-area",
+		div {
+			class: "graph-area",
+			tabindex: "0",
+			onkeydown: on_keydown,
+			onkeyup: move |e: KeyboardEvent| {
+			    if e.key() == Key::Character(" ".into()) {
+			        space_held.set(false);
+			    }
+			},
 			// Toolbar with status and machine chips
 			GraphToolbar {
 				graph,
 				containers: graph().containers.clone(),
 				review_count: graph().review_count,
 				on_add_machine_click: move |_| {
-				    *machine_name.write() = String::new();
-				    *machine_host.write() = String::new();
-				    *machine_user.write() = String::new();
+				    machine_form.set(MachineSettings::default());
 				    *add_panel.write() = AddPanelState::AddMachine;
 				},
 				on_container_click: move |c: ContainerView| {
@@ -319,11 +469,27 @@ pub fn MappingGraph(
 				id: "workspace",
 				class: "workspace",
 				style: "width: 100%; height: 100%; overflow: hidden;",
-				// Zoom/pan handled via Alt+drag for now
+				// Pointer positions are measured from wherever the workspace ends up
+				onmounted: move |e: MountedEvent| async move {
+				    if let Ok(rect) = e.get_client_rect().await {
+				        graph.with_mut(|g| g.workspace_origin = Vec2::new(rect.origin.x, rect.origin.y));
+				    }
+				},
+				// Wheel zooms toward the cursor; Alt-, Space- or middle-drag pans
+				onwheel: move |e: WheelEvent| {
+				    e.prevent_default();
+				    let coords = e.client_coordinates();
+				    let dy = e.delta().strip_units().y;
+				    let factor = (-dy * WHEEL_ZOOM_SPEED).exp();
+				    graph.with_mut(|g| {
+				        let (x, y) = g.client_to_workspace(coords.x, coords.y);
+				        g.zoom(factor, x, y)
+				    });
+				},
 				onmousedown: move |e: MouseEvent| {
-				    let (x, y) = get_workspace_coords(&e);
-				    // Start panning on workspace click (Alt+click or middle click)
-				    if e.data().modifiers().alt() {
+				    let (x, y) = get_workspace_coords(graph, &e);
+				    let middle = e.trigger_button() == Some(dioxus::html::input_data::MouseButton::Auxiliary);
+				    if e.data().modifiers().alt() || middle || space_held() {
 				        let (vp_x, vp_y) = graph.with(|g| (g.viewport_x, g.viewport_y));
 				        graph
 				            .with_mut(|g| {
@@ -335,6 +501,7 @@ pub fn MappingGraph(
 				                };
 				            });
 				    } else if e.data().modifiers().shift() {
+				        let (x, y) = graph.with(|g| g.screen_to_graph(x, y));
 				        graph
 				            .with_mut(|g| {
 				                g.drag_state = daemon::DragState::Lasso {
@@ -352,7 +519,7 @@ pub fn MappingGraph(
 				    }
 				},
 				onmousemove: move |e: MouseEvent| {
-				    let (x, y) = get_workspace_coords(&e);
+				    let (x, y) = get_workspace_coords(graph, &e);
 				    let drag_state_snapshot = graph().drag_state.clone();
 
 				    // Handle panning - 1:1 with mouse movement
@@ -360,9 +527,6 @@ pub fn MappingGraph(
 				        start_x,
 				        start_y,
 				        start_viewport_x,
-
-				        // Fix the node position when drag starts
-				        // Update fixed position during drag
 				        start_viewport_y,
 				    } = &drag_state_snapshot {
 				        let dx = x - start_x;
@@ -373,6 +537,8 @@ pub fn MappingGraph(
 				            });
 				        return;
 				    }
+				    // Everything else lives in graph space
+				    let (x, y) = graph.with(|g| g.screen_to_graph(x, y));
 				    match &drag_state_snapshot {
 				        daemon::DragState::CreatingEdge { source_id, source_x, source_y, .. } => {
 				            graph
@@ -398,16 +564,16 @@ pub fn MappingGraph(
 				                });
 				        }
 				        daemon::DragState::ClickPending { node_id, start_x, start_y, .. } => {
-				            let distance_moved = ((x - start_x).powi(2) + (y - start_y).powi(2))
-				                .sqrt();
-				            if distance_moved > 5.0 {
+				            if graph.with(|g| g.is_drag(*start_x, *start_y, x, y)) {
 				                graph
 				                    .with_mut(|g| {
 				                        g.fix_node_position(&node_id);
+				                        // Keep the node under the point it was grabbed by
+				                        let origin = g.find_node(&node_id).map(|n| n.position).unwrap_or_default();
 				                        g.drag_state = daemon::DragState::Dragging {
 				                            node_id: node_id.clone(),
-				                            offset_x: x - start_x,
-				                            offset_y: y - start_y,
+				                            offset_x: start_x - origin.x,
+				                            offset_y: start_y - origin.y,
 				                        };
 				                    });
 				            } else {
@@ -448,28 +614,11 @@ pub fn MappingGraph(
 				    move |_| {
 				        let current_drag = graph().drag_state.clone();
 				        match current_drag {
-				            // Edge creation will be handled by individual node components
-				            // when mouseup occurs over another node
-
-				            // Check if it was actually a click (not a drag)
-				            // It was a click - handle expansion for directory nodes
-
-				            // Toggle expansion for expandable nodes
-
-				            // Save the final position to the database
-
-				            daemon::DragState::CreatingEdge { source_id: _, .. } => {}
-
-				            // Start filesystem scan for Machine/Drive nodes
-
-				            // Get mount point from containers
-
-				            // Scan filesystem asynchronously
-				            // Get parent node position for orbit placement
-
-				            // Release the node and restart simulation
-
-				            // Save position to DB
+				            // Released over a node, an edge is finished by that node;
+				            // dropped on empty canvas, it's abandoned
+				            daemon::DragState::CreatingEdge { .. } => {
+				                graph.with_mut(|g| g.drag_state = daemon::DragState::None);
+				            }
 				            daemon::DragState::Lasso { start_x, start_y, current_x, current_y } => {
 				                let min_x = start_x.min(current_x);
 				                let max_x = start_x.max(current_x);
@@ -488,14 +637,7 @@ pub fn MappingGraph(
 				                mouse_x,
 				                mouse_y,
 				            } => {
-				                let distance_moved = ((mouse_x - start_x).powi(2)
-				                    + (mouse_y - start_y).powi(2))
-				                    .sqrt();
-				                tracing::info!(
-				                    "ClickPending: node={}, distance_moved={:.2}", node_id,
-				                    distance_moved
-				                );
-				                if distance_moved < 5.0 {
+				                if !graph.with(|g| g.is_drag(start_x, start_y, mouse_x, mouse_y)) {
 				                    let node_info = graph()
 				                        .find_node(&node_id)
 				                        .map(|n| (n.kind.clone(), n.path.clone(), n.label.clone()));
@@ -576,10 +718,7 @@ pub fn MappingGraph(
 				                                    }
 				                                });
 				                            } else {
-				                                graph_signal
-				                                    .with_mut(|g| {
-				                                        g.toggle_expand(&node_id);
-				                                    });
+				                                toggle_and_load(db.clone(), graph_signal, node_id.clone());
 				                            }
 				                        }
 				                    }
@@ -589,34 +728,9 @@ pub fn MappingGraph(
 				                        g.drag_state = daemon::DragState::None;
 				                    });
 				            }
+				            // Released off any node: same drop as on one
 				            daemon::DragState::Dragging { node_id, .. } => {
-				                graph
-				                    .with_mut(|g| {
-				                        g.release_node_position(&node_id);
-				                        g.start_simulation();
-				                    });
-				                if let Some(node) = graph().find_node(&node_id) {
-				                    let db_clone = db.clone();
-				                    let node_id_clone = node_id.clone();
-				                    let x = node.position.x;
-				                    let y = node.position.y;
-				                    spawn(async move {
-				                        if let Err(e) = daemon::save_node_position(
-				                                &db_clone,
-				                                &node_id_clone,
-				                                x,
-				                                y,
-				                            )
-				                            .await
-				                        {
-				                            error!("Failed to save node position: {}", e);
-				                        }
-				                    });
-				                }
-				                graph
-				                    .with_mut(|g| {
-				                        g.drag_state = daemon::DragState::None;
-				                    });
+				                drop_dragged_node(graph, db.clone(), &node_id);
 				            }
 				            _ => {
 				                graph
@@ -636,14 +750,13 @@ pub fn MappingGraph(
 				        .with(|g| (g.viewport_scale, g.viewport_x, g.viewport_y));
 				    let visible_count = graph().visible_nodes().len();
 				    rsx! {
-					div { style: "transform: translate({x}px, {y}px) scale({scale}); transform-origin: 0 0; width: 100%; height: 100%; position: relative;",
+					div {
+						class: "graph-html-layer",
+						style: "transform: translate({x}px, {y}px) scale({scale}); transform-origin: 0 0; width: 100%; height: 100%; position: relative;",
 						GraphSvgOverlay {
 							graph,
 							canvas_width: 2000.0,
 							canvas_height: 2000.0,
-							viewport_scale: scale,
-							viewport_x: x,
-							viewport_y: y,
 						}
 						for node in graph().visible_nodes().iter() {
 							GraphNodeComponent { graph, node: (*node).clone() }
@@ -652,41 +765,113 @@ pub fn MappingGraph(
 				}
 				}
 				// Context menu (rendered outside viewport transform so it stays fixed on screen)
-				GraphNodeContextMenu { graph }
+				GraphNodeContextMenu {
+					graph,
+					on_changed,
+					notifs,
+					on_edit_machine: {
+					    let db = db.clone();
+					    move |machine_id: String| {
+					        let db = db.clone();
+					        spawn(async move {
+					            match daemon::load_machine(&db, &machine_id).await {
+					                Ok(settings) => {
+					                    machine_form.set(settings);
+					                    *add_panel.write() = AddPanelState::EditMachine(machine_id);
+					                }
+					                Err(e) => error!("loading machine failed: {}", e),
+					            }
+					        });
+					    }
+					},
+				}
+				if let Some(edge_id) = graph().selected_edge.clone() {
+					div { class: "edge-actions",
+						IntentNameField { key: "{edge_id}", graph, intent_id: edge_id.clone(), on_changed }
+						span { class: "edge-actions-label", "Ctrl-drag from its source to add a destination" }
+						button {
+							class: "btn-ghost",
+							onclick: move |_| graph.with_mut(|g| g.selected_edge = None),
+							"Cancel"
+						}
+						button {
+							class: "btn-danger",
+							onclick: {
+							    let db = db.clone();
+							    move |_| {
+							        let db = db.clone();
+							        let id = edge_id.clone();
+							        spawn(async move {
+							            match daemon::delete_intent(&db, &id).await {
+							                Ok(()) => {
+							                    graph.with_mut(|g| g.remove_intent_edges(&id));
+							                    on_changed.call(());
+							                }
+							                Err(e) => error!("delete intent failed: {}", e),
+							            }
+							        });
+							    }
+							},
+							"Delete Intent"
+						}
+					}
+				}
 			}
 
-			// Add machine panel
-			if *add_panel.read() == AddPanelState::AddMachine {
+			// Add/edit machine panel
+			if *add_panel.read() != AddPanelState::Closed {
 				div {
 					class: "add-panel-overlay",
 					onclick: move |_| *add_panel.write() = AddPanelState::Closed,
 					div {
 						class: "add-panel",
 						onclick: move |e: MouseEvent| e.stop_propagation(),
-						div { class: "add-panel-title", "Add remote machine" }
+						div { class: "add-panel-title",
+							if matches!(*add_panel.read(), AddPanelState::EditMachine(_)) {
+								"Edit remote machine"
+							} else {
+								"Add remote machine"
+							}
+						}
 						div { class: "add-machine-form",
 							div { class: "form-field",
 								label { "Name" }
 								input {
-									value: "{machine_name}",
+									value: "{machine_form().name}",
 									placeholder: "My Server",
-									oninput: move |e| *machine_name.write() = e.value(),
+									oninput: move |e| machine_form.write().name = e.value(),
 								}
 							}
 							div { class: "form-field",
 								label { "Hostname" }
 								input {
-									value: "{machine_host}",
+									value: "{machine_form().hostname}",
 									placeholder: "192.168.1.100 or server.local",
-									oninput: move |e| *machine_host.write() = e.value(),
+									oninput: move |e| machine_form.write().hostname = e.value(),
 								}
 							}
 							div { class: "form-field",
 								label { "SSH User" }
 								input {
-									value: "{machine_user}",
-									placeholder: "root",
-									oninput: move |e| *machine_user.write() = e.value(),
+									value: "{machine_form().ssh_user}",
+									placeholder: "your user name here",
+									oninput: move |e| machine_form.write().ssh_user = e.value(),
+								}
+							}
+							div { class: "form-field",
+								label { "SSH Key" }
+								input {
+									value: "{machine_form().ssh_key_path}",
+									placeholder: "~/.ssh/id_ed25519 (optional)",
+									oninput: move |e| machine_form.write().ssh_key_path = e.value(),
+								}
+							}
+							div { class: "form-field",
+								label { "Jump Host" }
+								input {
+									value: "{machine_form().ssh_proxy}",
+									placeholder: "user@bastion (optional)",
+									oninput: move |e| machine_form.write().ssh_proxy = e.value(),
 								}
 							}
 							div { class: "form-actions-row",
@@ -697,29 +882,48 @@ pub fn MappingGraph(
 								}
 								button {
 									class: "btn-primary",
-									disabled: machine_host().trim().is_empty(),
+									disabled: machine_form().hostname.trim().is_empty(),
 									onclick: {
 									    let db = db.clone();
 									    move |_| {
-									        let name = machine_name().trim().to_string();
-									        let host = machine_host().trim().to_string();
-									        let user = machine_user().trim().to_string();
+									        let mut settings = machine_form();
+									        for field in [
+									            &mut settings.name,
+									            &mut settings.hostname,
+									            &mut settings.ssh_user,
+									            &mut settings.ssh_key_path,
+									            &mut settings.ssh_proxy,
+									        ] {
+									            *field = field.trim().to_string();
+									        }
+									        let editing = match &*add_panel.read() {
+									            AddPanelState::EditMachine(id) => Some(id.clone()),
+									            _ => None,
+									        };
 									        let db = db.clone();
 									        let on_changed = on_changed;
 									        let mut add_panel = add_panel;
 									        spawn(async move {
-									            match daemon::add_remote_machine(&db, &name, &host, &user).await {
+									            let result = match &editing {
+									                Some(id) => daemon::update_machine(&db, id, &settings).await,
+									                None => daemon::add_remote_machine(&db, &settings).await,
+									            };
+									            match result {
 									                Ok(()) => {
-									                    info!("remote machine added: {}", host);
+									                    info!("remote machine saved: {}", settings.hostname);
 									                    on_changed.call(());
 									                }
-									                Err(e) => error!("add machine failed: {}", e),
+									                Err(e) => error!("saving machine failed: {}", e),
 									            }
 									            *add_panel.write() = AddPanelState::Closed;
 									        });
 									    }
 									},
-									"Add"
+									if matches!(*add_panel.read(), AddPanelState::EditMachine(_)) {
+										"Save"
+									} else {
+										"Add"
+									}
 								}
 							}
 						}
@@ -1,16 +1,29 @@
 use dioxus::prelude::*;
-use daemon::Graph;
+use daemon::{engine::copier::Disposal, DbHandle, Graph};
+use kip_core::{palette_color, NodeKind, PALETTE};
+
+use crate::ui::{graph::toggle_and_load, notification::NotificationService};
 
 #[derive(Props, Clone, PartialEq)]
 pub struct GraphNodeContextMenuProps {
 	pub graph: Signal<Graph>,
+	pub on_changed: EventHandler,
+	/// Open the machine form for this remote machine id.
+	pub on_edit_machine: EventHandler<String>,
+	pub notifs: Store<NotificationService>,
 }
 
 // ─── Context Menu Component ───────────────────────────────────
 
 #[component]
 pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
+	let db = use_context::<DbHandle>();
 	let mut graph = props.graph;
+	let on_changed = props.on_changed;
+	let on_edit_machine = props.on_edit_machine;
+	let mut notifs = props.notifs;
+	// Location awaiting delete confirmation, with the number of intents that go with it
+	let mut confirm_delete = use_signal(|| None::<(String, u64)>);
 	let menu_state = graph().context_menu.clone();
 
 	if !menu_state.visible {
@@ -21,16 +34,17 @@ pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
 	let node_info = menu_state.node_id.as_ref().and_then(|id| {
 		graph()
 			.find_node(id)
-			.map(|n| (n.label.clone(), n.kind.clone()))
+			.map(|n| (n.label.clone(), n.kind.clone(), n.color.clone()))
 	});
 
-	let Some((node_label, node_kind)) = node_info else {
+	let Some((node_label, node_kind, node_color)) = node_info else {
 		return rsx! {};
 	};
 
 	// Context menu actions
 	let is_expandable = node_kind.is_expandable();
 	let is_expanded = node_kind.is_expanded();
+	let is_container = matches!(node_kind, NodeKind::Machine { .. } | NodeKind::Drive { .. });
 
 	// Clone values needed for closures
 	let menu_x = menu_state.x;
@@ -45,6 +59,42 @@ pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
 	let expand_node_id = menu_node_id.clone();
 	let select_node_id = menu_node_id.clone();
 	let sync_node_id = menu_node_id.clone();
+	let color_node_id = menu_node_id.clone().unwrap_or_default();
+	let delete_node_id = menu_node_id.clone().unwrap_or_default();
+	let is_location = delete_node_id.starts_with("location:");
+	let is_remote_machine = graph()
+		.containers
+		.iter()
+		.any(|c| c.kind == "remote" && daemon::rid_string(&c.id) == color_node_id);
+	let pending_delete = confirm_delete()
+		.filter(|(id, _)| *id == delete_node_id)
+		.map(|(_, intents)| intents);
+
+	// Apply a color override locally and persist it. `None` resets to the palette.
+	let apply_color = {
+		let color_node_id = color_node_id.clone();
+		move |color: Option<String>| {
+			let id = color_node_id.clone();
+			let shown = color.clone().unwrap_or_else(|| {
+				let index = graph()
+					.containers
+					.iter()
+					.position(|c| daemon::rid_string(&c.id) == id)
+					.unwrap_or(0);
+				palette_color(index).to_string()
+			});
+			graph.with_mut(|g| {
+				g.set_container_color(&id, &shown);
+				g.context_menu.hide();
+			});
+			let db = db.clone();
+			spawn(async move {
+				if let Err(e) = daemon::save_container_color(&db, &id, color).await {
+					tracing::error!("save_container_color failed: {}", e);
+				}
+			});
+		}
+	};
 
 	rsx! {
 		div {
@@ -67,14 +117,13 @@ pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
 					if is_expandable {
 						button {
 							class: "context-menu-item",
-							onclick: move |_| {
-							    if let Some(ref id) = expand_node_id {
-							        let id_clone = id.clone();
-							        graph
-							            .with_mut(|g| {
-							                g.toggle_expand(&id_clone);
-							                g.context_menu.hide();
-							            });
+							onclick: {
+							    let db = db.clone();
+							    move |_| {
+							        if let Some(ref id) = expand_node_id {
+							            graph.with_mut(|g| g.context_menu.hide());
+							            toggle_and_load(db.clone(), graph, id.clone());
+							        }
 							    }
 							},
 							span {
@@ -133,8 +182,8 @@ pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
 						                        .find_node(&id_clone)
 						                        .map(|n| n.center_y())
 						                        .unwrap_or(0.0),
-						                    mouse_x: menu_x,
-						                    mouse_y: menu_y,
+						                    mouse_x: g.screen_to_graph(menu_x, menu_y).0,
+						                    mouse_y: g.screen_to_graph(menu_x, menu_y).1,
 						                };
 						                g.context_menu.hide();
 						            });
@@ -144,11 +193,151 @@ pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
 						span { "Create Sync..." }
 					}
 
+					if is_container {
+						div { class: "context-menu-divider" }
+						div { class: "context-menu-colors",
+							for color in PALETTE.iter() {
+								ColorSwatch {
+									color: color.to_string(),
+									selected: node_color == *color,
+									onclick: {
+									    let mut apply_color = apply_color.clone();
+									    move |color: String| apply_color(Some(color))
+									},
+								}
+							}
+							input {
+								class: "color-custom",
+								r#type: "color",
+								value: "{node_color}",
+								title: "Custom color",
+								onchange: {
+								    let mut apply_color = apply_color.clone();
+								    move |e: FormEvent| apply_color(Some(e.value()))
+								},
+							}
+						}
+						button {
+							class: "context-menu-item",
+							onclick: {
+							    let mut apply_color = apply_color.clone();
+							    move |_| apply_color(None)
+							},
+							span { "◐" }
+							span { "Reset Color" }
+						}
+					}
+
+					if is_remote_machine {
+						button {
+							class: "context-menu-item",
+							onclick: {
+							    let id = color_node_id.clone();
+							    move |_| {
+							        graph.with_mut(|g| g.context_menu.hide());
+							        on_edit_machine.call(id.clone());
+							    }
+							},
+							span { "⚙" }
+							span { "Edit SSH Settings..." }
+						}
+					}
+
+					if is_location {
+						div { class: "context-menu-divider" }
+						if let Some(intents) = pending_delete {
+							button {
+								class: "context-menu-item danger",
+								onclick: {
+								    let db = db.clone();
+								    let id = delete_node_id.clone();
+								    move |_| {
+								        let db = db.clone();
+								        let id = id.clone();
+								        confirm_delete.set(None);
+								        graph.with_mut(|g| g.context_menu.hide());
+								        spawn(async move {
+								            match daemon::delete_location(&db, &id).await {
+								                Ok(()) => {
+								                    graph.with_mut(|g| g.remove_node(&id));
+								                    on_changed.call(());
+								                }
+								                Err(e) => tracing::error!("delete_location failed: {}", e),
+								            }
+								        });
+								    }
+								},
+								span { "⚠" }
+								span {
+									if intents == 0 {
+										"Confirm delete"
+									} else {
+										"Also delete {intents} intent(s)?"
+									}
+								}
+							}
+							button {
+								class: "context-menu-item danger",
+								onclick: {
+								    let db = db.clone();
+								    let id = delete_node_id.clone();
+								    move |_| {
+								        let db = db.clone();
+								        let id = id.clone();
+								        confirm_delete.set(None);
+								        graph.with_mut(|g| g.context_menu.hide());
+								        spawn(async move {
+								            match daemon::trash_location(&db, &id).await {
+								                Ok(disposal) => {
+								                    graph.with_mut(|g| g.remove_node(&id));
+								                    on_changed.call(());
+								                    match disposal {
+								                        Some((path, Disposal::Trashed)) => {
+								                            notifs.info(format!("Moved {path} to the Trash"))
+								                        }
+								                        Some((path, Disposal::Removed)) => notifs.warn(format!(
+								                            "{path} is on a volume without a Trash and was deleted permanently"
+								                        )),
+								                        None => {}
+								                    }
+								                }
+								                Err(e) => notifs.error(format!("Couldn't delete the folder: {e}")),
+								            }
+								        });
+								    }
+								},
+								span { "🗑" }
+								span { "Delete and move folder to Trash" }
+							}
+						} else {
+							button {
+								class: "context-menu-item danger",
+								onclick: {
+								    let db = db.clone();
+								    let id = delete_node_id.clone();
+								    move |_| {
+								        let db = db.clone();
+								        let id = id.clone();
+								        spawn(async move {
+								            match daemon::intents_using_location(&db, &id).await {
+								                Ok(n) => confirm_delete.set(Some((id, n))),
+								                Err(e) => tracing::error!("intents_using_location failed: {}", e),
+								            }
+								        });
+								    }
+								},
+								span { "🗑" }
+								span { "Delete Location" }
+							}
+						}
+					}
+
 					div { class: "context-menu-divider" }
 
 					button {
 						class: "context-menu-item",
 						onclick: move |_| {
+						    confirm_delete.set(None);
 						    graph.with_mut(|g| g.context_menu.hide());
 						},
 						span { "✕" }
@@ -159,3 +348,17 @@ pub fn GraphNodeContextMenu(props: GraphNodeContextMenuProps) -> Element {
 		}
 	}
 }
+
+/// One palette color in a container's color picker.
+#[component]
+fn ColorSwatch(color: String, selected: bool, onclick: EventHandler<String>) -> Element {
+	let class = if selected { "color-swatch active" } else { "color-swatch" };
+	rsx! {
+		button {
+			class: "{class}",
+			style: "background: {color};",
+			title: "{color}",
+			onclick: move |_| onclick.call(color.clone()),
+		}
+	}
+}
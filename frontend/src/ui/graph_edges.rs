@@ -1,5 +1,5 @@
 use dioxus::prelude::*;
-use daemon::{DragState, Graph};
+use daemon::{DbHandle, DragState, Graph};
 use kip_core::graph_types::*;
 
 // ─── GraphSvgOverlay ───────────────────────────────────────────
@@ -10,9 +10,6 @@ pub fn GraphSvgOverlay(
 	graph: Signal<Graph>,
 	canvas_width: f64,
 	canvas_height: f64,
-	viewport_scale: f64,
-	viewport_x: f64,
-	viewport_y: f64,
 ) -> Element {
 	let graph_snapshot = graph();
 	let visible_edges = graph_snapshot.visible_edges();
@@ -35,12 +32,10 @@ pub fn GraphSvgOverlay(
 	// Capture drag state for the rubber band
 	let drag_state_snapshot = &graph_snapshot.drag_state;
 
-	// Pre-compute rubber-band line coordinates (transform mouse coords to graph space)
+	// Rubber-band line coordinates (the mouse position is already in graph space)
 	let rubber_band_line =
 		if let DragState::CreatingEdge { source_x, source_y, mouse_x, mouse_y, .. } = &drag_state_snapshot {
-			let graph_mouse_x = (mouse_x - viewport_x) / viewport_scale;
-			let graph_mouse_y = (mouse_y - viewport_y) / viewport_scale;
-			Some((source_x, source_y, graph_mouse_x, graph_mouse_y))
+			Some((source_x, source_y, *mouse_x, *mouse_y))
 		} else {
 			None
 		};
@@ -50,7 +45,7 @@ pub fn GraphSvgOverlay(
 			class: "workspace-svg",
 			width: "{canvas_width}",
 			height: "{canvas_height}",
-			style: "width: {canvas_width}px; height: {canvas_height}px;",
+			style: "width: {canvas_width}px; height: {canvas_height}px; overflow: visible;",
 
 			// Render all visible edges - connect node centers with straight lines
 			for edge in visible_edges.iter() {
@@ -65,11 +60,17 @@ pub fn GraphSvgOverlay(
 
 				        let path_d = bezier_path(sx, sy, dx, dy);
 				        let color = edge_color(&edge.status);
-				        let width = if edge.status == "transferring" || edge.status == "scanning" {
+				        let selected = edge.intent_id.is_some() && graph_snapshot.selected_edge == edge.intent_id;
+				        let width = if selected {
+				            "4"
+				        } else if edge.status == "transferring" || edge.status == "scanning" {
 				            "3"
 				        } else {
 				            "2"
 				        };
+				        let opacity = if selected { "0.9" } else { "0.5" };
+				        // Only intent edges can be selected; hierarchy edges aren't records
+				        let intent_id = edge.intent_id.clone();
 
 				        rsx! {
 					path {
@@ -79,7 +80,22 @@ pub fn GraphSvgOverlay(
 						stroke_width: "{width}",
 						fill: "none",
 						stroke_linecap: "round",
-						opacity: "0.5",
+						opacity: "{opacity}",
+					}
+					if let Some(intent_id) = intent_id {
+						// Wider invisible stroke so the edge is easy to click
+						path {
+							key: "{edge.id}-hit",
+							class: "edge-hit",
+							d: "{path_d}",
+							stroke: "transparent",
+							stroke_width: "14",
+							fill: "none",
+							onclick: move |e: MouseEvent| {
+							    e.stop_propagation();
+							    graph.with_mut(|g| g.toggle_edge_selection(&intent_id));
+							},
+						}
 					}
 				}
 				    } else {
@@ -120,3 +136,47 @@ pub fn GraphSvgOverlay(
 		}
 	}
 }
+
+// ─── IntentNameField ───────────────────────────────────────────
+// Names the selected intent; a blank name shows its paths again
+
+#[component]
+pub fn IntentNameField(graph: Signal<Graph>, intent_id: String, on_changed: EventHandler) -> Element {
+	let db = use_context::<DbHandle>();
+	let saved = graph.with(|g| g.intent_name(&intent_id).unwrap_or_default().to_string());
+	let mut draft = use_signal(|| saved.clone());
+
+	let commit = move || {
+		let name = Some(draft().trim().to_string()).filter(|n| !n.is_empty());
+		if name.as_deref().unwrap_or_default() == saved {
+			return;
+		}
+		graph.with_mut(|g| g.set_intent_name(&intent_id, name.as_deref()));
+		let db = db.clone();
+		let intent_id = intent_id.clone();
+		spawn(async move {
+			match daemon::save_intent_name(&db, &intent_id, name).await {
+				Ok(()) => on_changed.call(()),
+				Err(e) => tracing::error!("Failed to save intent name: {}", e),
+			}
+		});
+	};
+	let mut commit_on_enter = commit.clone();
+	let mut commit_on_blur = commit;
+
+	rsx! {
+		input {
+			class: "edge-name-input",
+			value: "{draft}",
+			placeholder: "Name this intent",
+			oninput: move |e: FormEvent| draft.set(e.value()),
+			onkeydown: move |e: KeyboardEvent| {
+			    e.stop_propagation();
+			    if e.key() == Key::Enter {
+			        commit_on_enter();
+			    }
+			},
+			onblur: move |_| commit_on_blur(),
+		}
+	}
+}
@@ -1,7 +1,129 @@
 use dioxus::prelude::*;
-use daemon::{DragState, Graph};
+use daemon::{DbHandle, DragState, Graph};
 use kip_core::graph_types::*;
 
+// ─── Edge creation ─────────────────────────────────────────────
+
+/// Complete a ctrl-drag that ended on `dest_id`. If the drag started at the
+/// source of the selected intent, the target becomes another destination of
+/// that intent; otherwise a new intent is created and selected, so it can be
+/// named right away. The graph picks up the change on its next refresh.
+fn finish_edge(mut graph: Signal<Graph>, db: DbHandle, source_id: String, dest_id: String) {
+	let fold_into = graph.with(|g| {
+		g.selected_edge
+			.clone()
+			.filter(|intent| g.intent_source(intent) == Some(source_id.as_str()))
+	});
+	graph.with_mut(|g| g.drag_state = DragState::None);
+
+	if source_id == dest_id {
+		return;
+	}
+	// A scanned folder inside a location can be a source too; the intent
+	// then copies just that folder
+	let source_is_folder = source_id.starts_with("fs:")
+		&& graph.with(|g| g.find_node(&source_id).is_some_and(|n| matches!(n.kind, NodeKind::Directory { .. })));
+	if !(source_id.starts_with("location:") || source_is_folder) || !dest_id.starts_with("location:") {
+		tracing::warn!("intents connect locations; ignoring drop of {} on {}", source_id, dest_id);
+		return;
+	}
+
+	spawn(async move {
+		let result = match fold_into {
+			Some(intent_id) => daemon::add_destination(&db, &intent_id, &dest_id).await,
+			None => daemon::create_edge_in_db(&db, &source_id, &dest_id)
+				.await
+				.map(|intent_id| graph.with_mut(|g| g.selected_edge = Some(intent_id))),
+		};
+		if let Err(e) = result {
+			tracing::error!("creating intent edge failed: {}", e);
+		}
+	});
+}
+
+/// Pin a dragged node where it was dropped and persist the spot. The
+/// simulation loop saves the settled layout again once it stops.
+pub fn drop_dragged_node(mut graph: Signal<Graph>, db: DbHandle, node_id: &str) {
+	let Some((x, y)) = graph.with_mut(|g| g.drop_node(node_id)) else {
+		return;
+	};
+	let node_id = node_id.to_string();
+	spawn(async move {
+		if let Err(e) = daemon::save_node_position(&db, &node_id, x, y).await {
+			tracing::error!("Failed to save node position: {}", e);
+		}
+	});
+}
+
+/// Relabel a location right away and persist it. A blank name clears the
+/// label so the node shows its path again.
+fn save_label(mut graph: Signal<Graph>, db: DbHandle, node_id: String, draft: String) {
+	let label = Some(draft.trim().to_string()).filter(|l| !l.is_empty());
+	graph.with_mut(|g| g.set_label(&node_id, label.as_deref()));
+	spawn(async move {
+		if let Err(e) = daemon::save_location_label(&db, &node_id, label).await {
+			tracing::error!("Failed to save location label: {}", e);
+		}
+	});
+}
+
+// ─── NodeLabel ─────────────────────────────────────────────────
+// Double-click a location's label to rename it
+
+#[component]
+fn NodeLabel(graph: Signal<Graph>, node_id: String, label: String) -> Element {
+	let db = use_context::<DbHandle>();
+	let mut draft = use_signal(|| None::<String>);
+	let renamable = node_id.starts_with("location:");
+
+	let Some(text) = draft() else {
+		return rsx! {
+			span {
+				class: "node-label",
+				title: if renamable { "Double-click to rename" },
+				ondoubleclick: move |e: MouseEvent| {
+				    if renamable {
+				        e.stop_propagation();
+				        draft.set(Some(label.clone()));
+				    }
+				},
+				"{label}"
+			}
+		};
+	};
+
+	let commit = move || {
+		if let Some(text) = draft.write().take() {
+			save_label(graph, db.clone(), node_id.clone(), text);
+		}
+	};
+	let mut commit_on_enter = commit.clone();
+	let mut commit_on_blur = commit;
+
+	rsx! {
+		input {
+			class: "node-label-input",
+			value: "{text}",
+			onmounted: move |e: MountedEvent| async move {
+			    let _ = e.set_focus(true).await;
+			},
+			// Keep typing and clicking in the field away from graph shortcuts and drags
+			onmousedown: move |e: MouseEvent| e.stop_propagation(),
+			onmouseup: move |e: MouseEvent| e.stop_propagation(),
+			oninput: move |e: FormEvent| draft.set(Some(e.value())),
+			onkeydown: move |e: KeyboardEvent| {
+			    e.stop_propagation();
+			    match e.key() {
+			        Key::Enter => commit_on_enter(),
+			        Key::Escape => draft.set(None),
+			        _ => {}
+			    }
+			},
+			onblur: move |_| commit_on_blur(),
+		}
+	}
+}
+
 // ─── GraphNodeComponent ────────────────────────────────────────
 
 #[component]
@@ -30,6 +152,7 @@ pub fn GraphNodeComponent(graph: Signal<Graph>, node: GraphNode) -> Element {
 
 #[component]
 pub fn FileNode(graph: Signal<Graph>, node: GraphNode) -> Element {
+	let db = use_context::<DbHandle>();
 	let node_id = node.id.clone();
 	let node_id_mousedown = node_id.clone();
 	let node_id_mouseup = node_id.clone();
@@ -47,25 +170,22 @@ pub fn FileNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 		_ => "📎",
 	};
 
-	let class = if is_selected {
-		"graph-node file-node selected"
-	} else {
-		"graph-node file-node"
-	};
+	let selected = if is_selected { " selected" } else { "" };
+	// The location's path wasn't there at the last check
+	let missing = if node.missing { " missing" } else { "" };
+	let class = format!("graph-node file-node{selected}{missing}");
+	let title = if node.missing { format!("Missing: {}", node.path) } else { node.path.clone() };
 
 	rsx! {
 		div {
 			class: "{class}",
+			title: "{title}",
 			"data-node-id": "{node_id}",
 			style: "left: {x}px; top: {y}px; width: {width}px; height: {height}px; --node-color: {color};",
 			onmousedown: move |e: MouseEvent| {
 			    e.stop_propagation();
 			    let coords = e.client_coordinates();
-			    let (screen_x, screen_y) = (coords.x, coords.y);
-			    let (viewport_x, viewport_y, viewport_scale) = graph
-			        .with(|g| (g.viewport_x, g.viewport_y, g.viewport_scale));
-			    let mx = (screen_x - viewport_x) / viewport_scale;
-			    let my = (screen_y - 61.0 - viewport_y) / viewport_scale;
+			    let (mx, my) = graph.with(|g| g.client_to_graph(coords.x, coords.y));
 			    let node_id_for_drag = node_id_mousedown.clone();
 			    if e.modifiers().shift() {
 			        graph.with_mut(|g| g.toggle_select(&node_id_for_drag));
@@ -100,25 +220,17 @@ pub fn FileNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			    let drag_state = graph().drag_state.clone();
 			    match &drag_state {
 			        DragState::CreatingEdge { source_id, .. } => {
-			            // Edge creation handled in graph.rs workspace onmouseup
-			            // Release the node - handled by workspace, but we need to reset drag state
-			            if source_id != &node_id_mouseup {}
+			            finish_edge(graph, db.clone(), source_id.clone(), node_id_mouseup.clone());
 			        }
 			        DragState::Dragging { .. } => {
-			            graph
-			                .with_mut(|g| {
-			                    g.release_node_position(&node_id_mouseup);
-			                    g.release_selected_nodes();
-			                    g.start_simulation();
-			                    g.drag_state = DragState::None;
-			                });
+			            drop_dragged_node(graph, db.clone(), &node_id_mouseup);
 			        }
 			        _ => {}
 			    }
 			},
 			div { class: "file-node-content",
 				span { class: "file-node-icon", "{file_icon}" }
-				span { class: "node-label", "{label}" }
+				NodeLabel { graph, node_id: node_id.clone(), label }
 			}
 		}
 	}
@@ -128,6 +240,7 @@ pub fn FileNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 
 #[component]
 pub fn DirNode(graph: Signal<Graph>, node: GraphNode) -> Element {
+	let db = use_context::<DbHandle>();
 	let node_id = node.id.clone();
 	let node_id_mousedown = node_id.clone();
 	let node_id_mouseup = node_id.clone();
@@ -139,26 +252,29 @@ pub fn DirNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 	let height = node.height;
 	let is_selected = graph().selected.contains(&node_id);
 	let is_expanded = node.kind.is_expanded();
-
-	let class = if is_selected {
-		"graph-node dir-node selected"
-	} else {
-		"graph-node dir-node"
+	// Collapsed directories stand in for their children with a count
+	let badge = match node.child_count {
+		0 => None,
+		1 => Some("1 file".to_string()),
+		n => Some(format!("{n} files")),
 	};
 
+	let selected = if is_selected { " selected" } else { "" };
+	// The location's path wasn't there at the last check
+	let missing = if node.missing { " missing" } else { "" };
+	let class = format!("graph-node dir-node{selected}{missing}");
+	let title = if node.missing { format!("Missing: {}", node.path) } else { node.path.clone() };
+
 	rsx! {
 		div {
 			class: "{class}",
+			title: "{title}",
 			"data-node-id": "{node_id}",
 			style: "left: {x}px; top: {y}px; width: {width}px; height: {height}px; --node-color: {color};",
 			onmousedown: move |e: MouseEvent| {
 			    e.stop_propagation();
 			    let coords = e.client_coordinates();
-			    let (screen_x, screen_y) = (coords.x, coords.y);
-			    let (viewport_x, viewport_y, viewport_scale) = graph
-			        .with(|g| (g.viewport_x, g.viewport_y, g.viewport_scale));
-			    let mx = (screen_x - viewport_x) / viewport_scale;
-			    let my = (screen_y - 61.0 - viewport_y) / viewport_scale;
+			    let (mx, my) = graph.with(|g| g.client_to_graph(coords.x, coords.y));
 			    let node_id_for_drag = node_id_mousedown.clone();
 			    if e.modifiers().shift() {
 			        graph.with_mut(|g| g.toggle_select(&node_id_for_drag));
@@ -193,27 +309,23 @@ pub fn DirNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			    let drag_state = graph().drag_state.clone();
 			    match &drag_state {
 			        DragState::CreatingEdge { source_id, .. } => {
-			            // Edge creation handled in graph.rs workspace onmouseup
-			            if source_id != &node_id_mouseup {}
+			            finish_edge(graph, db.clone(), source_id.clone(), node_id_mouseup.clone());
 			        }
 			        DragState::Dragging { .. } => {
-			            graph
-			                .with_mut(|g| {
-			                    g.release_node_position(&node_id_mouseup);
-			                    g.release_selected_nodes();
-			                    g.start_simulation();
-			                    g.drag_state = DragState::None;
-			                });
+			            drop_dragged_node(graph, db.clone(), &node_id_mouseup);
 			        }
 			        _ => {}
 			    }
 			},
 			div { class: "node-content",
-				span { class: "node-label", "{label}" }
+				NodeLabel { graph, node_id: node_id.clone(), label }
 				if is_expanded {
 					span { class: "expansion-indicator", "▼" }
 				} else {
 					span { class: "expansion-indicator", "▶" }
+					if let Some(badge) = badge {
+						span { class: "node-badge", "{badge}" }
+					}
 				}
 			}
 		}
@@ -224,6 +336,7 @@ pub fn DirNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 
 #[component]
 pub fn GroupNode(graph: Signal<Graph>, node: GraphNode) -> Element {
+	let db = use_context::<DbHandle>();
 	let node_id = node.id.clone();
 	let node_id_mousedown = node_id.clone();
 	let node_id_mouseup = node_id.clone();
@@ -249,11 +362,7 @@ pub fn GroupNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			onmousedown: move |e: MouseEvent| {
 			    e.stop_propagation();
 			    let coords = e.client_coordinates();
-			    let (screen_x, screen_y) = (coords.x, coords.y);
-			    let (viewport_x, viewport_y, viewport_scale) = graph
-			        .with(|g| (g.viewport_x, g.viewport_y, g.viewport_scale));
-			    let mx = (screen_x - viewport_x) / viewport_scale;
-			    let my = (screen_y - 61.0 - viewport_y) / viewport_scale;
+			    let (mx, my) = graph.with(|g| g.client_to_graph(coords.x, coords.y));
 			    let node_id_for_drag = node_id_mousedown.clone();
 			    if e.modifiers().shift() {
 			        graph.with_mut(|g| g.toggle_select(&node_id_for_drag));
@@ -288,17 +397,10 @@ pub fn GroupNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			    let drag_state = graph().drag_state.clone();
 			    match &drag_state {
 			        DragState::CreatingEdge { source_id, .. } => {
-			            // Edge creation handled in graph.rs workspace onmouseup
-			            if source_id != &node_id_mouseup {}
+			            finish_edge(graph, db.clone(), source_id.clone(), node_id_mouseup.clone());
 			        }
 			        DragState::Dragging { .. } => {
-			            graph
-			                .with_mut(|g| {
-			                    g.release_node_position(&node_id_mouseup);
-			                    g.release_selected_nodes();
-			                    g.start_simulation();
-			                    g.drag_state = DragState::None;
-			                });
+			            drop_dragged_node(graph, db.clone(), &node_id_mouseup);
 			        }
 			        _ => {}
 			    }
@@ -314,6 +416,7 @@ pub fn GroupNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 
 #[component]
 pub fn MachineNode(graph: Signal<Graph>, node: GraphNode) -> Element {
+	let db = use_context::<DbHandle>();
 	let node_id = node.id.clone();
 	let node_id_mousedown = node_id.clone();
 	let node_id_mouseup = node_id.clone();
@@ -339,11 +442,7 @@ pub fn MachineNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			onmousedown: move |e: MouseEvent| {
 			    e.stop_propagation();
 			    let coords = e.client_coordinates();
-			    let (screen_x, screen_y) = (coords.x, coords.y);
-			    let (viewport_x, viewport_y, viewport_scale) = graph
-			        .with(|g| (g.viewport_x, g.viewport_y, g.viewport_scale));
-			    let mx = (screen_x - viewport_x) / viewport_scale;
-			    let my = (screen_y - 61.0 - viewport_y) / viewport_scale;
+			    let (mx, my) = graph.with(|g| g.client_to_graph(coords.x, coords.y));
 			    let node_id_for_drag = node_id_mousedown.clone();
 			    if e.modifiers().shift() {
 			        graph.with_mut(|g| g.toggle_select(&node_id_for_drag));
@@ -378,17 +477,10 @@ pub fn MachineNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			    let drag_state = graph().drag_state.clone();
 			    match &drag_state {
 			        DragState::CreatingEdge { source_id, .. } => {
-			            // Edge creation handled in graph.rs workspace onmouseup
-			            if source_id != &node_id_mouseup {}
+			            finish_edge(graph, db.clone(), source_id.clone(), node_id_mouseup.clone());
 			        }
 			        DragState::Dragging { .. } => {
-			            graph
-			                .with_mut(|g| {
-			                    g.release_node_position(&node_id_mouseup);
-			                    g.release_selected_nodes();
-			                    g.start_simulation();
-			                    g.drag_state = DragState::None;
-			                });
+			            drop_dragged_node(graph, db.clone(), &node_id_mouseup);
 			        }
 			        _ => {}
 			    }
@@ -404,6 +496,7 @@ pub fn MachineNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 
 #[component]
 pub fn DriveNode(graph: Signal<Graph>, node: GraphNode) -> Element {
+	let db = use_context::<DbHandle>();
 	let node_id = node.id.clone();
 	let node_id_mousedown = node_id.clone();
 	let node_id_mouseup = node_id.clone();
@@ -441,11 +534,7 @@ pub fn DriveNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			onmousedown: move |e: MouseEvent| {
 			    e.stop_propagation();
 			    let coords = e.client_coordinates();
-			    let (screen_x, screen_y) = (coords.x, coords.y);
-			    let (viewport_x, viewport_y, viewport_scale) = graph
-			        .with(|g| (g.viewport_x, g.viewport_y, g.viewport_scale));
-			    let mx = (screen_x - viewport_x) / viewport_scale;
-			    let my = (screen_y - 61.0 - viewport_y) / viewport_scale;
+			    let (mx, my) = graph.with(|g| g.client_to_graph(coords.x, coords.y));
 			    let node_id_for_drag = node_id_mousedown.clone();
 			    if e.modifiers().shift() {
 			        graph.with_mut(|g| g.toggle_select(&node_id_for_drag));
@@ -480,17 +569,10 @@ pub fn DriveNode(graph: Signal<Graph>, node: GraphNode) -> Element {
 			    let drag_state = graph().drag_state.clone();
 			    match &drag_state {
 			        DragState::CreatingEdge { source_id, .. } => {
-			            // Edge creation handled in graph.rs workspace onmouseup
-			            if source_id != &node_id_mouseup {}
+			            finish_edge(graph, db.clone(), source_id.clone(), node_id_mouseup.clone());
 			        }
 			        DragState::Dragging { .. } => {
-			            graph
-			                .with_mut(|g| {
-			                    g.release_node_position(&node_id_mouseup);
-			                    g.release_selected_nodes();
-			                    g.start_simulation();
-			                    g.drag_state = DragState::None;
-			                });
+			            drop_dragged_node(graph, db.clone(), &node_id_mouseup);
 			        }
 			        _ => {}
 			    }
@@ -0,0 +1,954 @@
+use dioxus::prelude::*;
+use surrealdb_types::{RecordId, SurrealValue};
+use tracing::{error, info};
+use daemon::{
+	engine::{
+		scanner::{self, ScanResult},
+		scheduler::JobHealth,
+	},
+	rid_string, DbHandle,
+};
+use kip_core::util::display_path;
+
+use crate::{
+	api::{self, ReportFormat},
+	util::format_bytes,
+};
+
+/// Intent fields copied by "Duplicate" and stored in templates. Progress,
+/// status and endpoints are deliberately left out.
+const SETTINGS_FIELDS: &str =
+	"kind, speed_mode, priority, include_patterns, exclude_patterns, bidirectional, retryable_errors, job_order, concurrency, max_attempts, follow_symlinks, verify_mode, on_conflict, overwrite_policy, copy_strategy, write_manifest";
+
+#[derive(Debug, Clone, SurrealValue)]
+struct IntentListRow {
+	id: RecordId,
+	name: Option<String>,
+	status: String,
+	priority: i64,
+	source: Option<RecordId>,
+	destinations: Vec<RecordId>,
+	source_path: Option<String>,
+	dest_paths: Vec<Option<String>>,
+	total_files: i64,
+	completed_files: i64,
+	total_bytes: i64,
+	completed_bytes: i64,
+	bytes_per_sec: Option<i64>,
+	skipped_entries: Option<i64>,
+	skip_log: Option<Vec<SkippedEntry>>,
+	created_at: String,
+}
+
+/// An entry the last scan left out, from the intent's `skip_log`.
+#[derive(Debug, Clone, PartialEq, SurrealValue)]
+struct SkippedEntry {
+	path: String,
+	reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct IntentView {
+	id: RecordId,
+	name: Option<String>,
+	status: String,
+	/// Dispatch order across intents: higher runs first.
+	priority: i64,
+	source: Option<RecordId>,
+	destination: Option<RecordId>,
+	source_path: String,
+	dest_path: String,
+	total_files: i64,
+	completed_files: i64,
+	total_bytes: i64,
+	completed_bytes: i64,
+	/// Recent throughput while the scheduler is running it.
+	bytes_per_sec: Option<i64>,
+	health: JobHealth,
+	/// Entries the last scan skipped; `skip_log` names the first of them.
+	skipped_entries: i64,
+	skip_log: Vec<SkippedEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, SurrealValue)]
+struct LocationOption {
+	id: RecordId,
+	path: String,
+	label: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, SurrealValue)]
+struct TemplateOption {
+	id: RecordId,
+	name: String,
+}
+
+/// Which inline form (if any) an intent row has open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RowPanel {
+	Closed,
+	Rename,
+	Duplicate,
+	SaveTemplate,
+	/// "Would copy N files" confirmation before starting.
+	Preview,
+	/// What the last scan skipped, and why.
+	Skipped,
+}
+
+#[component]
+pub fn IntentList(refresh_tick: u32, on_changed: EventHandler) -> Element {
+	let db = use_context::<DbHandle>();
+
+	let data = use_resource(move || {
+		let db = db.clone();
+		let _tick = refresh_tick;
+		async move {
+			let intents = fetch_intents(&db).await?;
+			let locations = fetch_locations(&db).await?;
+			let templates = fetch_templates(&db).await?;
+			Ok::<_, String>((intents, locations, templates))
+		}
+	});
+
+	rsx! {
+		match &*data.read() {
+		    Some(Ok((intents, _, _))) if intents.is_empty() => {
+		        rsx! {}
+		    }
+		    Some(Ok((intents, locations, templates))) => {
+		        rsx! {
+			div { class: "intent-list",
+				div { class: "section-title", "Intents ({intents.len()})" }
+				for intent in intents.iter() {
+					IntentRow {
+						key: "{intent.id:?}",
+						intent: intent.clone(),
+						locations: locations.clone(),
+						templates: templates.clone(),
+						on_changed,
+					}
+				}
+			}
+		}
+		    }
+		    Some(Err(e)) => {
+		        error!("intent list load failed: {}", e);
+		        rsx! {}
+		    }
+		    None => {
+		        rsx! {}
+		    }
+		}
+	}
+}
+
+#[component]
+fn IntentRow(
+	intent: IntentView,
+	locations: Vec<LocationOption>,
+	templates: Vec<TemplateOption>,
+	on_changed: EventHandler,
+) -> Element {
+	let db = use_context::<DbHandle>();
+	let mut running = use_signal(|| false);
+	let mut panel = use_signal(|| RowPanel::Closed);
+	let mut template_name = use_signal(String::new);
+	let mut name_draft = use_signal(|| intent.name.clone().unwrap_or_default());
+	let mut preview = use_signal(|| None::<ScanResult>);
+	let mut dup_source = use_signal(|| intent.source.as_ref().map(rid_string).unwrap_or_default());
+	let mut dup_dest = use_signal(|| intent.destination.as_ref().map(rid_string).unwrap_or_default());
+
+	let title = intent
+		.name
+		.clone()
+		.filter(|n| !n.trim().is_empty())
+		.unwrap_or_else(|| format!("{} → {}", short_name(&intent.source_path), short_name(&intent.dest_path)));
+	let badge_class = format!("badge badge-{}", intent.status.replace('_', "-"));
+	let status_label = intent.status.replace('_', " ");
+	let unlisted_skips = intent.skipped_entries - intent.skip_log.len() as i64;
+	let pct = if intent.total_files > 0 {
+		(intent.completed_files as f64 / intent.total_files as f64 * 100.0).round() as i64
+	} else {
+		0
+	};
+	let is_active = matches!(intent.status.as_str(), "scanning" | "transferring" | "verifying");
+	let health = health_summary(&intent.health);
+	let throughput = intent.bytes_per_sec.filter(|&rate| is_active && rate > 0).map(|rate| {
+		let remaining = (intent.total_bytes - intent.completed_bytes).max(0);
+		format!("{}/s · {} left", format_bytes(rate as u64), format_eta(remaining / rate))
+	});
+
+	let open_preview = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		move |_| {
+			*running.write() = true;
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				match scanner::scan_preview(&db, &id).await {
+					Ok(result) => {
+						preview.set(Some(result));
+						panel.set(RowPanel::Preview);
+						*running.write() = false;
+					}
+					// Let the real scan report it (and park the intent if a drive is missing)
+					Err(e) => {
+						info!("scan preview failed, starting anyway: {}", e);
+						if let Err(e) = start_intent(&db, &id).await {
+							error!("start intent failed: {}", e);
+						}
+						*running.write() = false;
+						on_changed.call(());
+					}
+				}
+			});
+		}
+	};
+
+	let start = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		move |_| {
+			panel.set(RowPanel::Closed);
+			*running.write() = true;
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				if let Err(e) = start_intent(&db, &id).await {
+					error!("start intent failed: {}", e);
+				}
+				*running.write() = false;
+				on_changed.call(());
+			});
+		}
+	};
+
+	let verify = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		move |_| {
+			*running.write() = true;
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				if let Err(e) = verify_intent(&db, &id).await {
+					error!("verify intent failed: {}", e);
+				}
+				*running.write() = false;
+				on_changed.call(());
+			});
+		}
+	};
+
+	let cancel_scan = {
+		let db = db.clone();
+		let id = rid_string(&intent.id);
+		move |_| {
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				if let Err(e) = api::cancel_intent(&db, &id).await {
+					error!("cancel scan failed: {}", e);
+				}
+				on_changed.call(());
+			});
+		}
+	};
+
+	// Listed and dispatched by priority, so a step either way can reorder the list
+	let set_priority = {
+		let db = db.clone();
+		let id = rid_string(&intent.id);
+		move |priority: i64| {
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				if let Err(e) = daemon::update_intent_priority(&db, &id, priority).await {
+					error!("set intent priority failed: {}", e);
+				}
+				on_changed.call(());
+			});
+		}
+	};
+	let priority = intent.priority;
+	let raise = {
+		let set_priority = set_priority.clone();
+		move |_| set_priority(priority + 1)
+	};
+	let lower = move |_| set_priority(priority - 1);
+
+	let export = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		let title = title.clone();
+		move |_| {
+			let db = db.clone();
+			let id = id.clone();
+			let title = title.clone();
+			spawn(async move {
+				if let Err(e) = export_report(&db, &id, &title).await {
+					error!("export report failed: {}", e);
+				}
+			});
+		}
+	};
+
+	let rename = {
+		let db = db.clone();
+		let id = rid_string(&intent.id);
+		move |_| {
+			let name = Some(name_draft().trim().to_string()).filter(|n| !n.is_empty());
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				match daemon::save_intent_name(&db, &id, name).await {
+					Ok(()) => *panel.write() = RowPanel::Closed,
+					Err(e) => error!("rename intent failed: {}", e),
+				}
+				on_changed.call(());
+			});
+		}
+	};
+
+	let duplicate = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		move |_| {
+			let db = db.clone();
+			let id = id.clone();
+			let (source, dest) = (dup_source(), dup_dest());
+			spawn(async move {
+				match duplicate_intent(&db, &id, &source, &dest).await {
+					Ok(()) => *panel.write() = RowPanel::Closed,
+					Err(e) => error!("duplicate intent failed: {}", e),
+				}
+				on_changed.call(());
+			});
+		}
+	};
+
+	let save_template = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		move |_| {
+			let name = template_name().trim().to_string();
+			if name.is_empty() {
+				return;
+			}
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				match save_template(&db, &id, &name).await {
+					Ok(()) => {
+						template_name.set(String::new());
+						*panel.write() = RowPanel::Closed;
+					}
+					Err(e) => error!("save template failed: {}", e),
+				}
+				on_changed.call(());
+			});
+		}
+	};
+
+	let apply = {
+		let db = db.clone();
+		let id = intent.id.clone();
+		move |e: FormEvent| {
+			let template = e.value();
+			if template.is_empty() {
+				return;
+			}
+			let db = db.clone();
+			let id = id.clone();
+			spawn(async move {
+				if let Err(e) = apply_template(&db, &template, &id).await {
+					error!("apply template failed: {}", e);
+				}
+				on_changed.call(());
+			});
+		}
+	};
+
+	rsx! {
+		div { class: "intent-row",
+			div { class: "intent-main",
+				div { class: "intent-title", "{title}" }
+				div { class: "intent-paths", "{intent.source_path} → {intent.dest_path}" }
+			}
+			div { class: "intent-priority", title: "Priority: higher runs first",
+				button { class: "priority-step", onclick: raise, "▲" }
+				span { class: "priority-value", "{intent.priority}" }
+				button { class: "priority-step", onclick: lower, "▼" }
+			}
+			span { class: "{badge_class}", "{status_label}" }
+			if let Some(health) = health {
+				span { class: "intent-health", "{health}" }
+			}
+			if intent.skipped_entries > 0 {
+				button {
+					class: "intent-skipped",
+					title: "Show what the last scan skipped",
+					onclick: move |_| {
+					    let next = if panel() == RowPanel::Skipped { RowPanel::Closed } else { RowPanel::Skipped };
+					    panel.set(next);
+					},
+					"{intent.skipped_entries} skipped"
+				}
+			}
+			div { class: "progress-container intent-progress",
+				div { class: "progress-bar",
+					div { class: "progress-fill", style: "width: {pct}%;" }
+				}
+				span { class: "progress-text", "{intent.completed_files}/{intent.total_files}" }
+				if let Some(throughput) = throughput {
+					span { class: "progress-rate", "{throughput}" }
+				}
+			}
+			div { class: "intent-actions",
+				button {
+					class: "btn-resolve btn-resolve-retry",
+					disabled: running() || is_active,
+					onclick: open_preview,
+					if running() || is_active {
+						"Running…"
+					} else {
+						"Start"
+					}
+				}
+				if intent.status == "scanning" {
+					button {
+						class: "btn-resolve btn-resolve-skip",
+						title: "Stop scanning; no jobs are queued",
+						onclick: cancel_scan,
+						"Cancel"
+					}
+				}
+				if intent.status == "complete" {
+					button {
+						class: "btn-resolve btn-resolve-skip",
+						disabled: running(),
+						onclick: verify,
+						"Verify"
+					}
+					button {
+						class: "btn-resolve btn-resolve-skip",
+						onclick: export,
+						"Export report"
+					}
+				}
+				button {
+					class: "btn-resolve btn-resolve-skip",
+					onclick: move |_| {
+					    let next = if panel() == RowPanel::Rename { RowPanel::Closed } else { RowPanel::Rename };
+					    panel.set(next);
+					},
+					"Rename"
+				}
+				button {
+					class: "btn-resolve btn-resolve-skip",
+					onclick: move |_| {
+					    let next = if panel() == RowPanel::Duplicate { RowPanel::Closed } else { RowPanel::Duplicate };
+					    panel.set(next);
+					},
+					"Duplicate"
+				}
+				button {
+					class: "btn-resolve btn-resolve-skip",
+					onclick: move |_| {
+					    let next = if panel() == RowPanel::SaveTemplate { RowPanel::Closed } else { RowPanel::SaveTemplate };
+					    panel.set(next);
+					},
+					"Save as template"
+				}
+				if !templates.is_empty() {
+					select { class: "intent-select", value: "", onchange: apply,
+						option { value: "", "Apply template…" }
+						for t in templates.iter() {
+							option { value: "{rid_string(&t.id)}", "{t.name}" }
+						}
+					}
+				}
+			}
+			if panel() == RowPanel::Preview {
+				if let Some(p) = preview() {
+					div { class: "intent-panel",
+						span { class: "intent-preview",
+							"Would copy {p.jobs_created} file(s) ({format_bytes(p.total_bytes)})"
+						}
+						button {
+							class: "btn-resolve btn-resolve-retry",
+							disabled: running(),
+							onclick: start,
+							"Confirm"
+						}
+						button {
+							class: "btn-resolve btn-resolve-skip",
+							onclick: move |_| panel.set(RowPanel::Closed),
+							"Cancel"
+						}
+					}
+				}
+			}
+			if panel() == RowPanel::Skipped {
+				div { class: "intent-panel intent-skip-log",
+					for entry in intent.skip_log.iter() {
+						div { class: "skip-entry",
+							span { class: "skip-reason", "{skip_reason_label(&entry.reason)}" }
+							span { class: "skip-path", "{display_path(&entry.path)}" }
+						}
+					}
+					if unlisted_skips > 0 {
+						div { class: "skip-entry", "…and {unlisted_skips} more" }
+					}
+				}
+			}
+			if panel() == RowPanel::Rename {
+				div { class: "intent-panel",
+					input {
+						value: "{name_draft}",
+						placeholder: "Name (blank shows the paths)",
+						oninput: move |e| name_draft.set(e.value()),
+					}
+					button {
+						class: "btn-resolve btn-resolve-retry",
+						onclick: rename,
+						"Save"
+					}
+				}
+			}
+			if panel() == RowPanel::Duplicate {
+				div { class: "intent-panel",
+					label { "From" }
+					LocationSelect {
+						locations: locations.clone(),
+						value: dup_source(),
+						on_change: move |v| dup_source.set(v),
+					}
+					label { "To" }
+					LocationSelect {
+						locations: locations.clone(),
+						value: dup_dest(),
+						on_change: move |v| dup_dest.set(v),
+					}
+					button {
+						class: "btn-resolve btn-resolve-retry",
+						disabled: dup_source().is_empty() || dup_dest().is_empty() || dup_source() == dup_dest(),
+						onclick: duplicate,
+						"Create copy"
+					}
+				}
+			}
+			if panel() == RowPanel::SaveTemplate {
+				div { class: "intent-panel",
+					input {
+						value: "{template_name}",
+						placeholder: "Template name",
+						oninput: move |e| template_name.set(e.value()),
+					}
+					button {
+						class: "btn-resolve btn-resolve-retry",
+						disabled: template_name().trim().is_empty(),
+						onclick: save_template,
+						"Save"
+					}
+				}
+			}
+		}
+	}
+}
+
+#[component]
+fn LocationSelect(locations: Vec<LocationOption>, value: String, on_change: EventHandler<String>) -> Element {
+	rsx! {
+		select {
+			class: "intent-select",
+			value: "{value}",
+			onchange: move |e| on_change.call(e.value()),
+			option { value: "", "Choose location…" }
+			for loc in locations.iter() {
+				{
+				    let id = rid_string(&loc.id);
+				    let label = loc.label.clone().unwrap_or_else(|| display_path(&loc.path));
+				    rsx! {
+					option { value: "{id}", selected: id == value, "{label}" }
+				}
+				}
+			}
+		}
+	}
+}
+
+fn short_name(path: &str) -> String {
+	path.trim_end_matches('/')
+		.rsplit('/')
+		.next()
+		.filter(|s| !s.is_empty())
+		.unwrap_or(path)
+		.to_string()
+}
+
+/// "2 need review, 1 retrying", or `None` when every job went through.
+fn health_summary(health: &JobHealth) -> Option<String> {
+	let parts: Vec<String> = [
+		(health.needs_review, "need review"),
+		(health.failed, "failed"),
+		(health.retrying, "retrying"),
+	]
+	.into_iter()
+	.filter(|&(n, _)| n > 0)
+	.map(|(n, what)| format!("{n} {what}"))
+	.collect();
+	(!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Parse a `table:key` string into a record for `table`, rejecting others.
+fn parse_record(s: &str, table: &str) -> Result<RecordId, String> {
+	match s.split_once(':') {
+		Some((t, key)) if t == table => Ok(RecordId::new(table, key)),
+		_ => Err(format!("expected a {table} id, got {s:?}")),
+	}
+}
+
+/// A skip reason as stored in `skip_log`, for display.
+fn skip_reason_label(reason: &str) -> String {
+	reason.replace('_', " ")
+}
+
+// ─── DB actions ──────────────────────────────────────────────
+
+async fn fetch_intents(db: &DbHandle) -> Result<Vec<IntentView>, String> {
+	let mut resp = db
+		.db
+		.query(
+			"SELECT id, name, status, priority, total_files, completed_files,
+                    total_bytes, completed_bytes, bytes_per_sec, skipped_entries, skip_log, created_at,
+                    <record> source AS source,
+                    destinations.map(|$d| <record> $d) AS destinations,
+                    (<record> source).path AS source_path,
+                    destinations.map(|$d| (<record> $d).path) AS dest_paths
+             FROM intent
+             ORDER BY priority DESC, created_at DESC",
+		)
+		.await
+		.map_err(|e| e.to_string())?;
+
+	let rows: Vec<IntentListRow> = resp.take(0).map_err(|e| e.to_string())?;
+	let health = daemon::engine::scheduler::job_health(db).await.map_err(|e| e.to_string())?;
+
+	Ok(rows
+		.into_iter()
+		.map(|row| IntentView {
+			health: health.get(&row.id).copied().unwrap_or_default(),
+			id: row.id,
+			name: row.name,
+			status: row.status,
+			priority: row.priority,
+			source: row.source,
+			destination: row.destinations.first().cloned(),
+			source_path: row.source_path.as_deref().map(display_path).unwrap_or_default(),
+			dest_path: row
+				.dest_paths
+				.first()
+				.cloned()
+				.flatten()
+				.as_deref()
+				.map(display_path)
+				.unwrap_or_default(),
+			total_files: row.total_files,
+			completed_files: row.completed_files,
+			total_bytes: row.total_bytes,
+			completed_bytes: row.completed_bytes,
+			bytes_per_sec: row.bytes_per_sec,
+			skipped_entries: row.skipped_entries.unwrap_or(0),
+			skip_log: row.skip_log.unwrap_or_default(),
+		})
+		.collect())
+}
+
+async fn fetch_locations(db: &DbHandle) -> Result<Vec<LocationOption>, String> {
+	let mut resp = db
+		.db
+		.query("SELECT id, path, label FROM location ORDER BY path ASC")
+		.await
+		.map_err(|e| e.to_string())?;
+	resp.take(0).map_err(|e| e.to_string())
+}
+
+async fn fetch_templates(db: &DbHandle) -> Result<Vec<TemplateOption>, String> {
+	let mut resp = db
+		.db
+		.query("SELECT id, name FROM intent_template ORDER BY name ASC")
+		.await
+		.map_err(|e| e.to_string())?;
+	resp.take(0).map_err(|e| e.to_string())
+}
+
+/// Scan (if the intent isn't already running) and then run all pending jobs.
+async fn start_intent(db: &DbHandle, intent_id: &RecordId) -> Result<(), String> {
+	use daemon::engine::scheduler;
+
+	match scanner::scan_intent(db, intent_id).await {
+		Ok(_) => {}
+		// Another start won the race, or the scan was cancelled — nothing to do
+		Err(scanner::ScanError::AlreadyRunning(_) | scanner::ScanError::Cancelled) => return Ok(()),
+		Err(e) => return Err(e.to_string()),
+	}
+
+	let result = scheduler::run_intent(db, intent_id)
+		.await
+		.map_err(|e| e.to_string())?;
+	info!(
+		"intent {} finished: {} complete, {} review",
+		rid_string(intent_id),
+		result.completed,
+		result.needs_review
+	);
+	Ok(())
+}
+
+fn format_eta(secs: i64) -> String {
+	if secs >= 3600 {
+		format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+	} else if secs >= 60 {
+		format!("{}m {}s", secs / 60, secs % 60)
+	} else {
+		format!("{secs}s")
+	}
+}
+
+/// Re-hash the intent's completed copies without recopying anything.
+async fn verify_intent(db: &DbHandle, intent_id: &RecordId) -> Result<(), String> {
+	let result = daemon::engine::verify::verify_intent(db, intent_id)
+		.await
+		.map_err(|e| e.to_string())?;
+	info!(
+		"intent {} verified: {} checked, {} mismatched",
+		rid_string(intent_id),
+		result.checked,
+		result.mismatched
+	);
+	Ok(())
+}
+
+/// Ask where to save a report of the intent's jobs and write it there. The
+/// format follows the chosen extension: `.json` for JSON, CSV otherwise.
+async fn export_report(db: &DbHandle, intent_id: &RecordId, title: &str) -> Result<(), String> {
+	let Some(file) = rfd::AsyncFileDialog::new()
+		.set_file_name(format!("{title} report.csv"))
+		.add_filter("CSV", &["csv"])
+		.add_filter("JSON", &["json"])
+		.save_file()
+		.await
+	else {
+		return Ok(());
+	};
+	let path = file.path().to_path_buf();
+	let format = if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("json")) {
+		ReportFormat::Json
+	} else {
+		ReportFormat::Csv
+	};
+	let report = api::export_intent_report(db, &rid_string(intent_id), format)
+		.await
+		.map_err(|e| e.to_string())?;
+	tokio::fs::write(&path, report).await.map_err(|e| e.to_string())?;
+	info!("exported report for {} to {}", rid_string(intent_id), path.display());
+	Ok(())
+}
+
+/// Create a new idle intent with the same settings as `intent_id` but new
+/// endpoints and no progress.
+async fn duplicate_intent(db: &DbHandle, intent_id: &RecordId, source: &str, dest: &str) -> Result<(), String> {
+	let source = parse_record(source, "location")?;
+	let dest = parse_record(dest, "location")?;
+
+	let query = format!(
+		"LET $settings = (SELECT {SETTINGS_FIELDS} FROM ONLY $id);
+         LET $created = CREATE ONLY intent CONTENT {{
+             source: $source,
+             destinations: [$dest],
+             status: 'idle',
+             kind: $settings.kind,
+             speed_mode: $settings.speed_mode,
+             created_at: time::now(),
+             updated_at: time::now(),
+         }};
+         UPDATE $created.id MERGE $settings;"
+	);
+
+	db.db
+		.query(query)
+		.bind(("id", intent_id.clone()))
+		.bind(("source", rid_string(&source)))
+		.bind(("dest", rid_string(&dest)))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Store an intent's settings as a named template.
+async fn save_template(db: &DbHandle, intent_id: &RecordId, name: &str) -> Result<(), String> {
+	let query = format!(
+		"LET $settings = (SELECT {SETTINGS_FIELDS} FROM ONLY $id);
+         CREATE intent_template CONTENT {{
+             name: $name,
+             settings: $settings,
+             created_at: time::now(),
+         }};"
+	);
+
+	db.db
+		.query(query)
+		.bind(("id", intent_id.clone()))
+		.bind(("name", name.to_string()))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+/// Overwrite an intent's settings with a template's. Endpoints and progress
+/// are untouched.
+async fn apply_template(db: &DbHandle, template_id: &str, intent_id: &RecordId) -> Result<(), String> {
+	let template = parse_record(template_id, "intent_template")?;
+
+	db.db
+		.query(
+			"LET $settings = (SELECT VALUE settings FROM ONLY $template);
+             UPDATE $id MERGE $settings;
+             UPDATE $id SET updated_at = time::now();",
+		)
+		.bind(("template", template))
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| e.to_string())?
+		.check()
+		.map_err(|e| e.to_string())?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	async fn db_with_intent() -> DbHandle {
+		let db = daemon::db::init_memory().await.unwrap();
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: '/src', available: true, created_at: time::now() };
+                 CREATE location:dst CONTENT { path: '/dst', available: true, created_at: time::now() };
+                 CREATE location:other CONTENT { path: '/other', available: true, created_at: time::now() };
+                 CREATE intent:x CONTENT {
+                     name: 'Photos', source: 'location:src', destinations: ['location:dst'],
+                     status: 'complete', kind: 'one_shot', speed_mode: 'background', priority: 7,
+                     exclude_patterns: ['*.tmp'], total_files: 3, completed_files: 3,
+                     created_at: time::now(), updated_at: time::now(),
+                 };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		db
+	}
+
+	#[tokio::test]
+	async fn listing_resolves_string_endpoints() {
+		let db = db_with_intent().await;
+
+		let intents = fetch_intents(&db).await.unwrap();
+		assert_eq!(intents.len(), 1);
+		assert_eq!(intents[0].source, Some(RecordId::new("location", "src")));
+		assert_eq!(intents[0].destination, Some(RecordId::new("location", "dst")));
+		assert_eq!(intents[0].source_path, display_path("/src"));
+		assert_eq!(intents[0].dest_path, display_path("/dst"));
+	}
+
+	#[tokio::test]
+	async fn duplicate_copies_settings_but_not_progress() {
+		let db = db_with_intent().await;
+		let original = RecordId::new("intent", "x");
+
+		duplicate_intent(&db, &original, "location:other", "location:dst").await.unwrap();
+
+		let mut resp = db
+			.db
+			.query(
+				"SELECT status, speed_mode, priority, exclude_patterns, total_files,
+                        (<record> source).path AS source_path
+                 FROM intent WHERE id != intent:x",
+			)
+			.await
+			.unwrap();
+		let copies: Vec<serde_json::Value> = resp.take(0).unwrap();
+		assert_eq!(copies.len(), 1);
+		let copy = &copies[0];
+		assert_eq!(copy["status"], "idle");
+		assert_eq!(copy["speed_mode"], "background");
+		assert_eq!(copy["priority"], 7);
+		assert_eq!(copy["exclude_patterns"], serde_json::json!(["*.tmp"]));
+		assert_eq!(copy["total_files"], 0);
+		assert_eq!(copy["source_path"], "/other");
+	}
+
+	#[tokio::test]
+	async fn duplicate_rejects_non_location_endpoints() {
+		let db = db_with_intent().await;
+		let original = RecordId::new("intent", "x");
+
+		assert!(duplicate_intent(&db, &original, "intent:x", "location:dst").await.is_err());
+	}
+
+	#[tokio::test]
+	async fn template_round_trips_settings_onto_another_intent() {
+		let db = db_with_intent().await;
+		db.db
+			.query(
+				"CREATE intent:y CONTENT {
+                     source: 'location:other', destinations: ['location:dst'], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal', priority: 0,
+                     created_at: time::now(), updated_at: time::now(),
+                 };",
+			)
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+
+		save_template(&db, &RecordId::new("intent", "x"), "Weekly").await.unwrap();
+		let mut resp = db.db.query("SELECT VALUE id FROM intent_template WHERE name = 'Weekly'").await.unwrap();
+		let templates: Vec<RecordId> = resp.take(0).unwrap();
+		assert_eq!(templates.len(), 1);
+
+		let target = RecordId::new("intent", "y");
+		apply_template(&db, &rid_string(&templates[0]), &target).await.unwrap();
+
+		let mut resp = db
+			.db
+			.query("SELECT speed_mode, priority, exclude_patterns, status, source FROM ONLY intent:y")
+			.await
+			.unwrap();
+		let row: Option<serde_json::Value> = resp.take(0).unwrap();
+		let row = row.unwrap();
+		assert_eq!(row["speed_mode"], "background");
+		assert_eq!(row["priority"], 7);
+		assert_eq!(row["exclude_patterns"], serde_json::json!(["*.tmp"]));
+		assert_eq!(row["status"], "idle");
+		assert_eq!(row["source"], "location:other");
+	}
+
+	#[tokio::test]
+	async fn applying_an_unknown_template_id_is_rejected() {
+		let db = db_with_intent().await;
+
+		assert!(apply_template(&db, "intent:x", &RecordId::new("intent", "y")).await.is_err());
+	}
+}
@@ -1,8 +1,10 @@
 pub mod container_components;
+pub mod dashboard;
 pub mod file_picker;
 pub mod graph;
 pub mod graph_context_menu;
 pub mod graph_edges;
 pub mod graph_nodes;
+pub mod intent_row;
 pub mod notification;
 pub mod review_queue;
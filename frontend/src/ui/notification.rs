@@ -1,6 +1,15 @@
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use dioxus::prelude::*;
+use surrealdb_types::RecordId;
+use daemon::{engine::scheduler, events::outcome_summary, rid_string, DbHandle};
+
+/// How often `track_intent` re-reads the intent it follows.
+const TRACK_INTERVAL: Duration = Duration::from_secs(2);
+/// How long a plain toast stays up before it goes by itself.
+const TOAST_LIFETIME: Duration = Duration::from_secs(5);
+/// How often `NotificationLayer` clears out expired toasts.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(1);
 
 // ─── NotificationService Store ────────────────────────────────
 
@@ -12,6 +21,25 @@ pub enum NotificationLevel {
 	Progress,
 }
 
+/// Something a toast can offer to do. The layer hands clicked actions to its
+/// `on_action` handler; the app decides what each one means.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotificationAction {
+	/// Bring the review queue into view.
+	OpenReview,
+	/// Open a file picker on a container, the way clicking its chip does.
+	OpenPicker { container_id: String, name: String, root: String },
+}
+
+impl NotificationAction {
+	pub fn label(&self) -> &'static str {
+		match self {
+			NotificationAction::OpenReview => "Review",
+			NotificationAction::OpenPicker { .. } => "Browse",
+		}
+	}
+}
+
 #[derive(Store, Debug, Clone, PartialEq)]
 pub struct Notification {
 	pub id: u32,
@@ -21,16 +49,22 @@ pub struct Notification {
 	pub dismissed: bool,
 	pub progress: Option<f64>, // For progress notifications (0.0 to 1.0)
 	pub spinner: bool,         // For ongoing operations
+	pub actions: Vec<NotificationAction>,
 }
 
 impl Notification {
 	pub fn is_expired(&self) -> bool {
-		if self.spinner || matches!(self.level, NotificationLevel::Progress) {
-			// Progress/spinner notifications don't expire automatically
-			self.dismissed
-		} else {
-			self.dismissed || Instant::now().duration_since(self.created_at).as_secs() >= 5
-		}
+		self.is_expired_at(Instant::now())
+	}
+
+	/// Whether the toast should be gone by `now`. Progress, spinner and
+	/// actionable toasts only go once dismissed, however long they've been up.
+	fn is_expired_at(&self, now: Instant) -> bool {
+		self.dismissed || (!self.is_persistent() && now.saturating_duration_since(self.created_at) >= TOAST_LIFETIME)
+	}
+
+	fn is_persistent(&self) -> bool {
+		self.spinner || self.level == NotificationLevel::Progress || !self.actions.is_empty()
 	}
 }
 
@@ -49,6 +83,11 @@ impl NotificationService {
 #[store(pub)]
 impl Store<NotificationService> {
 	fn add(&mut self, message: String, level: NotificationLevel) {
+		self.add_with_actions(message, level, Vec::new());
+	}
+
+	/// A toast with buttons; it stays up until dismissed or one is clicked.
+	fn add_with_actions(&mut self, message: String, level: NotificationLevel, actions: Vec<NotificationAction>) {
 		let id = self.next_id().cloned();
 		self.next_id().set(id + 1);
 		self.notifications().push(Notification {
@@ -59,6 +98,7 @@ impl Store<NotificationService> {
 			dismissed: false,
 			progress: None,
 			spinner: false,
+			actions,
 		});
 	}
 
@@ -73,6 +113,7 @@ impl Store<NotificationService> {
 			dismissed: false,
 			progress,
 			spinner: progress.is_none(), // Spinner if no progress value provided
+			actions: Vec::new(),
 		};
 		self.notifications().push(notification);
 		id
@@ -129,18 +170,22 @@ impl Store<NotificationService> {
 	}
 
 	fn cleanup(&mut self) {
-		self.notifications().retain(|n| !n.is_expired());
+		let now = Instant::now();
+		// Only write when something goes, so a quiet stack doesn't re-render
+		if self.notifications().read().iter().any(|n| n.is_expired_at(now)) {
+			self.notifications().retain(|n| !n.is_expired_at(now));
+		}
 	}
 }
 
 // ─── NotificationLayer Component ──────────────────────────────
 
 #[component]
-pub fn NotificationLayer(mut notifs: Store<NotificationService>) -> Element {
-	// Auto-cleanup expired notifications every second
-	spawn(async move {
+pub fn NotificationLayer(mut notifs: Store<NotificationService>, on_action: EventHandler<NotificationAction>) -> Element {
+	// One cleanup loop for the layer's lifetime, not one per render
+	use_future(move || async move {
 		loop {
-			tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+			tokio::time::sleep(CLEANUP_INTERVAL).await;
 			notifs.cleanup();
 		}
 	});
@@ -170,6 +215,7 @@ pub fn NotificationLayer(mut notifs: Store<NotificationService>) -> Element {
 				    let msg = notif.message.clone();
 				    let progress = notif.progress;
 				    let spinner = notif.spinner;
+				    let actions = notif.actions.clone();
 
 				    rsx! {
 
@@ -197,6 +243,19 @@ pub fn NotificationLayer(mut notifs: Store<NotificationService>) -> Element {
 							}
 						}
 
+						for action in actions {
+							button {
+								key: "{action:?}",
+								class: "notif-action",
+								onclick: move |e: MouseEvent| {
+								    e.stop_propagation();
+								    notifs.dismiss(id);
+								    on_action.call(action.clone());
+								},
+								"{action.label()}"
+							}
+						}
+
 						button {
 							class: "notif-close",
 							onclick: move |e: MouseEvent| {
@@ -212,3 +271,139 @@ pub fn NotificationLayer(mut notifs: Store<NotificationService>) -> Element {
 		}
 	}
 }
+
+// ─── Intent tracking ──────────────────────────────────────────
+
+/// Follow one intent (`table:key`) until it stops running: a spinner while
+/// it scans or hasn't counted its files yet, a progress bar of
+/// `completed_files / total_files` while it transfers, then a toast summing
+/// up the run, e.g. "9998 copied, 2 failed verification", with a Review
+/// button if jobs need review. An intent
+/// that goes idle, parked or cancelled just loses its toast; the failure or
+/// disconnect has its own.
+pub fn track_intent(db: DbHandle, mut notifs: Store<NotificationService>, intent_id: &str) {
+	let Some((table, key)) = intent_id.split_once(':') else {
+		return;
+	};
+	let intent_id = RecordId::new(table, key);
+
+	spawn(async move {
+		// The live notification and whether it's the progress bar yet
+		let mut live: Option<(u32, bool)> = None;
+		loop {
+			let row = match fetch_tracked(&db, &intent_id).await {
+				Ok(Some(row)) => row,
+				// Deleted, or the DB is gone
+				_ => {
+					if let Some((id, _)) = live {
+						notifs.dismiss(id);
+					}
+					return;
+				}
+			};
+			let name = row["name"].as_str().map(String::from).unwrap_or_else(|| rid_string(&intent_id));
+			let total = row["total_files"].as_u64().unwrap_or(0);
+			let completed = row["completed_files"].as_u64().unwrap_or(0);
+
+			match row["status"].as_str().unwrap_or_default() {
+				status @ ("scanning" | "transferring" | "verifying") => {
+					let counted = status != "scanning" && total > 0;
+					match live {
+						Some((id, true)) => notifs.update_progress(id, completed as f64 / total.max(1) as f64),
+						Some((_, false)) if !counted => {}
+						_ => {
+							if let Some((id, _)) = live.take() {
+								notifs.dismiss(id);
+							}
+							live = Some(if counted {
+								let fraction = completed as f64 / total as f64;
+								(notifs.add_progress(format!("Copying {name}"), Some(fraction)), true)
+							} else {
+								(notifs.start_spinner(format!("Scanning {name}…")), false)
+							});
+						}
+					}
+				}
+				status => {
+					if let Some((id, _)) = live {
+						notifs.dismiss(id);
+					}
+					let failures = match status {
+						"complete" | "needs_review" => {
+							scheduler::failure_counts(&db, &intent_id).await.unwrap_or_default()
+						}
+						_ => Vec::new(),
+					};
+					match status {
+						"complete" if failures.is_empty() => {
+							notifs.info(format!("{name} complete: {completed} file(s) copied"))
+						}
+						"complete" => notifs.warn(format!("{name} complete: {}", outcome_summary(completed, &failures))),
+						"needs_review" => {
+							notifs.add_with_actions(
+								format!("{name}: {}", outcome_summary(completed, &failures)),
+								NotificationLevel::Error,
+								vec![NotificationAction::OpenReview],
+							);
+						}
+						_ => {}
+					}
+					return;
+				}
+			}
+
+			tokio::time::sleep(TRACK_INTERVAL).await;
+		}
+	});
+}
+
+async fn fetch_tracked(db: &DbHandle, intent_id: &RecordId) -> Result<Option<serde_json::Value>, String> {
+	let mut resp = db
+		.db
+		.query(
+			"SELECT name, status, total_files, completed_files FROM $id",
+		)
+		.bind(("id", intent_id.clone()))
+		.await
+		.map_err(|e| e.to_string())?;
+	resp.take(0).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn toast(level: NotificationLevel, spinner: bool) -> Notification {
+		Notification {
+			id: 0,
+			message: String::new(),
+			level,
+			created_at: Instant::now(),
+			dismissed: false,
+			progress: None,
+			spinner,
+			actions: Vec::new(),
+		}
+	}
+
+	#[test]
+	fn progress_and_spinner_toasts_outlive_the_timer() {
+		let later = Instant::now() + TOAST_LIFETIME * 10;
+		let progress = Notification { progress: Some(0.4), ..toast(NotificationLevel::Progress, false) };
+		let spinner = toast(NotificationLevel::Progress, true);
+		let actionable = Notification { actions: vec![NotificationAction::OpenReview], ..toast(NotificationLevel::Error, false) };
+
+		for notification in [&progress, &spinner, &actionable] {
+			assert!(!notification.is_expired_at(later));
+			let dismissed = Notification { dismissed: true, ..notification.clone() };
+			assert!(dismissed.is_expired_at(Instant::now()));
+		}
+	}
+
+	#[test]
+	fn plain_toasts_expire_after_their_lifetime() {
+		let info = toast(NotificationLevel::Info, false);
+		assert!(!info.is_expired_at(info.created_at + TOAST_LIFETIME / 2));
+		assert!(info.is_expired_at(info.created_at + TOAST_LIFETIME));
+	}
+}
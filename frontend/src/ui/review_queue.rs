@@ -2,6 +2,9 @@ use dioxus::prelude::*;
 use surrealdb_types::{RecordId, SurrealValue};
 use tracing::error;
 use daemon::DbHandle;
+use kip_core::util::display_path;
+
+use crate::util::format_bytes;
 
 #[derive(Debug, Clone, SurrealValue)]
 struct ReviewRow {
@@ -13,7 +16,12 @@ struct ReviewRow {
 	dest_path: String,
 	options: Vec<String>,
 	source_size: Option<i64>,
+	source_hash: Option<String>,
+	source_modified: Option<String>,
 	dest_size: Option<i64>,
+	dest_hash: Option<String>,
+	dest_modified: Option<String>,
+	dest_container: Option<String>,
 	created_at: String,
 }
 
@@ -28,28 +36,102 @@ struct ReviewView {
 	options: Vec<String>,
 	source_size: Option<i64>,
 	dest_size: Option<i64>,
+	/// How the two sides differ, e.g. "source newer, 2.0 MB larger".
+	comparison: Option<String>,
+	/// Short source/dest hashes when both are known and differ.
+	hashes: Option<(String, String)>,
+	/// Name of the drive/machine the failing destination lives on.
+	dest_container: Option<String>,
 }
 
 #[component]
 pub fn ReviewQueue(refresh_tick: u32, on_resolved: EventHandler) -> Element {
 	let db = use_context::<DbHandle>();
+	let mut batch_running = use_signal(|| false);
 
-	let items = use_resource(move || {
+	let items = use_resource({
 		let db = db.clone();
-		let _tick = refresh_tick;
-		async move { fetch_review_items(&db).await }
+		move || {
+			let db = db.clone();
+			let _tick = refresh_tick;
+			async move { fetch_review_items(&db).await }
+		}
 	});
 
+	// Resolve every item that offers `resolution`, then refresh once
+	let resolve_batch = move |targets: Vec<ReviewView>, resolution: &'static str| {
+		let db = db.clone();
+		*batch_running.write() = true;
+		spawn(async move {
+			if let Err(e) = resolve_all(&db, &targets, resolution).await {
+				error!("bulk {} failed: {}", resolution, e);
+			}
+			*batch_running.write() = false;
+			on_resolved.call(());
+		});
+	};
+
 	rsx! {
 		match &*items.read() {
 		    Some(Ok(list)) if list.is_empty() => {
 		        rsx! {}
 		    }
 		    Some(Ok(list)) => {
+		        let retryable = offering(list.iter(), "retry");
+		        let skippable = offering(list.iter(), "skip");
 		        rsx! {
-			div { class: "section-title mt-24", "Review Queue ({list.len()})" }
-			for item in list.iter() {
-				ReviewCard { key: "{item.id:?}", item: item.clone(), on_resolved }
+			div { id: "review-queue", class: "section-title mt-24 review-queue-header",
+				span { "Review Queue ({list.len()})" }
+				div { class: "review-bulk-actions",
+					button {
+						class: "btn-resolve btn-resolve-retry",
+						disabled: batch_running() || retryable.is_empty(),
+						onclick: {
+						    let mut resolve_batch = resolve_batch.clone();
+						    move |_| resolve_batch(retryable.clone(), "retry")
+						},
+						"Retry all"
+					}
+					button {
+						class: "btn-resolve btn-resolve-skip",
+						disabled: batch_running() || skippable.is_empty(),
+						onclick: {
+						    let mut resolve_batch = resolve_batch.clone();
+						    move |_| resolve_batch(skippable.clone(), "skip")
+						},
+						"Skip all"
+					}
+				}
+			}
+			for (kind, group) in group_by_kind(list) {
+				{
+				    let kind_retryable = offering(group.iter(), "retry");
+				    let mut resolve_batch = resolve_batch.clone();
+				    rsx! {
+					div { key: "{kind}", class: "review-group",
+						div { class: "review-group-header",
+							span { class: "{kind_class(&kind)}", "{kind_label(&kind)}" }
+							span { class: "review-group-count", "{group.len()}" }
+							if !kind_retryable.is_empty() && group.len() > 1 {
+								button {
+									class: "btn-resolve btn-resolve-retry",
+									disabled: batch_running(),
+									onclick: move |_| resolve_batch(kind_retryable.clone(), "retry"),
+									"Retry all {kind_label(&kind)}"
+								}
+							}
+						}
+						for item in group.iter() {
+							ReviewCard {
+								key: "{item.id:?}",
+								item: item.clone(),
+								disabled: batch_running(),
+								on_resolved,
+							}
+						}
+					}
+				}
+				}
 			}
 		}
 		    }
@@ -65,30 +147,16 @@ pub fn ReviewQueue(refresh_tick: u32, on_resolved: EventHandler) -> Element {
 }
 
 #[component]
-fn ReviewCard(item: ReviewView, on_resolved: EventHandler) -> Element {
+fn ReviewCard(item: ReviewView, disabled: bool, on_resolved: EventHandler) -> Element {
 	let db = use_context::<DbHandle>();
 	let mut resolving = use_signal(|| false);
 
-	let kind_class = match item.error_kind.as_str() {
-		"source_missing" => "review-kind review-kind-missing",
-		"permission_denied" => "review-kind review-kind-permission",
-		"disk_full" => "review-kind review-kind-disk",
-		"hash_mismatch" => "review-kind review-kind-hash",
-		_ => "review-kind review-kind-io",
-	};
-
-	let kind_label = match item.error_kind.as_str() {
-		"source_missing" => "Source Missing",
-		"permission_denied" => "Permission Denied",
-		"disk_full" => "Disk Full",
-		"hash_mismatch" => "Hash Mismatch",
-		"io_error" => "I/O Error",
-		_ => &item.error_kind,
-	};
+	let kind_class = kind_class(&item.error_kind);
+	let kind_label = kind_label(&item.error_kind);
 
 	let size_info = match (item.source_size, item.dest_size) {
-		(Some(s), Some(d)) => format!("{} → {}", format_bytes(s), format_bytes(d)),
-		(Some(s), None) => format!("{}", format_bytes(s)),
+		(Some(s), Some(d)) => format!("{} → {}", format_bytes(s as u64), format_bytes(d as u64)),
+		(Some(s), None) => format_bytes(s as u64),
 		_ => String::new(),
 	};
 
@@ -96,12 +164,21 @@ fn ReviewCard(item: ReviewView, on_resolved: EventHandler) -> Element {
 		div { class: "review-card",
 			div { class: "review-header",
 				span { class: "{kind_class}", "{kind_label}" }
+				if let Some(ref container) = item.dest_container {
+					span { class: "review-dest", "on {container}" }
+				}
 			}
 			div { class: "review-message", "{item.error_message}" }
 			div { class: "review-paths", "{item.source_path} → {item.dest_path}" }
 			if !size_info.is_empty() {
 				div { class: "review-meta", "{size_info}" }
 			}
+			if let Some(ref comparison) = item.comparison {
+				div { class: "review-compare", "{comparison}" }
+			}
+			if let Some((ref source_hash, ref dest_hash)) = item.hashes {
+				div { class: "review-hashes", "{source_hash} ≠ {dest_hash}" }
+			}
 			div { class: "review-actions",
 				for option in item.options.iter() {
 					{
@@ -112,7 +189,7 @@ fn ReviewCard(item: ReviewView, on_resolved: EventHandler) -> Element {
 					    let on_resolved = on_resolved;
 
 					    let btn_class = match opt.as_str() {
-					        "retry" | "rescan" => "btn-resolve btn-resolve-retry",
+					        "retry" | "rescan" | "keep_source" | "keep_dest" => "btn-resolve btn-resolve-retry",
 					        "accept" => "btn-resolve btn-resolve-accept",
 					        _ => "btn-resolve btn-resolve-skip",
 					    };
@@ -120,7 +197,7 @@ fn ReviewCard(item: ReviewView, on_resolved: EventHandler) -> Element {
 					    rsx! {
 						button {
 							class: "{btn_class}",
-							disabled: resolving(),
+							disabled: resolving() || disabled,
 							onclick: move |_| {
 							    *resolving.write() = true;
 							    let db = db.clone();
@@ -143,16 +220,80 @@ fn ReviewCard(item: ReviewView, on_resolved: EventHandler) -> Element {
 	}
 }
 
-fn format_bytes(bytes: i64) -> String {
-	if bytes >= 1_073_741_824 {
-		format!("{:.1} GB", bytes as f64 / 1_073_741_824.0)
-	} else if bytes >= 1_048_576 {
-		format!("{:.1} MB", bytes as f64 / 1_048_576.0)
-	} else if bytes >= 1024 {
-		format!("{:.0} KB", bytes as f64 / 1024.0)
-	} else {
-		format!("{bytes} B")
+fn kind_class(error_kind: &str) -> &'static str {
+	match error_kind {
+		"source_missing" => "review-kind review-kind-missing",
+		"permission_denied" => "review-kind review-kind-permission",
+		"disk_full" => "review-kind review-kind-disk",
+		"hash_mismatch" | "verify_failed" => "review-kind review-kind-hash",
+		"file_too_large" => "review-kind review-kind-disk",
+		"unsupported_file_type" => "review-kind review-kind-missing",
+		"conflict" => "review-kind review-kind-conflict",
+		_ => "review-kind review-kind-io",
+	}
+}
+
+fn kind_label(error_kind: &str) -> &str {
+	match error_kind {
+		"source_missing" => "Source Missing",
+		"permission_denied" => "Permission Denied",
+		"disk_full" => "Disk Full",
+		"hash_mismatch" => "Hash Mismatch",
+		"verify_failed" => "Verify Failed",
+		"io_error" => "I/O Error",
+		"file_too_large" => "File Too Large",
+		"unsupported_file_type" => "Not a Regular File",
+		"conflict" => "Conflict",
+		"remote_misconfigured" => "Remote Not Set Up",
+		_ => error_kind,
+	}
+}
+
+/// Items grouped by `error_kind`, largest group first.
+fn group_by_kind(items: &[ReviewView]) -> Vec<(String, Vec<ReviewView>)> {
+	let mut groups: Vec<(String, Vec<ReviewView>)> = Vec::new();
+	for item in items {
+		match groups.iter_mut().find(|(kind, _)| *kind == item.error_kind) {
+			Some((_, group)) => group.push(item.clone()),
+			None => groups.push((item.error_kind.clone(), vec![item.clone()])),
+		}
+	}
+	// Stable, so equal-sized groups keep newest-first order
+	groups.sort_by(|a, b| b.1.len().cmp(&a.1.len()));
+	groups
+}
+
+/// The items that can be resolved with `resolution`.
+fn offering<'a>(items: impl Iterator<Item = &'a ReviewView>, resolution: &str) -> Vec<ReviewView> {
+	items.filter(|item| item.options.iter().any(|o| o == resolution)).cloned().collect()
+}
+
+/// Summarise how the destination differs from the source, from each side's
+/// (size, modified) pair. `None` if there's nothing on the other side to
+/// compare against.
+fn describe_difference(source: (Option<i64>, Option<&str>), dest: (Option<i64>, Option<&str>)) -> Option<String> {
+	let parse = |t: Option<&str>| t.and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok());
+	let mut parts = Vec::new();
+
+	match (parse(source.1), parse(dest.1)) {
+		(Some(s), Some(d)) if s > d => parts.push("source newer".to_string()),
+		(Some(s), Some(d)) if s < d => parts.push("destination newer".to_string()),
+		(Some(_), Some(_)) => parts.push("same modified time".to_string()),
+		_ => {}
 	}
+	if let (Some(s), Some(d)) = (source.0, dest.0) {
+		parts.push(match s.cmp(&d) {
+			std::cmp::Ordering::Greater => format!("{} larger", format_bytes((s - d) as u64)),
+			std::cmp::Ordering::Less => format!("{} smaller", format_bytes((d - s) as u64)),
+			std::cmp::Ordering::Equal => "same size".to_string(),
+		});
+	}
+
+	(!parts.is_empty()).then(|| parts.join(", "))
+}
+
+fn short_hash(hash: &str) -> String {
+	hash.chars().take(12).collect()
 }
 
 async fn fetch_review_items(db: &DbHandle) -> Result<Vec<ReviewView>, String> {
@@ -160,7 +301,9 @@ async fn fetch_review_items(db: &DbHandle) -> Result<Vec<ReviewView>, String> {
 		.db
 		.query(
 			"SELECT id, job, error_kind, error_message, source_path, dest_path,
-                    options, source_size, dest_size, created_at
+                    options, source_size, source_hash, source_modified,
+                    dest_size, dest_hash, dest_modified, created_at,
+                    (job.destination.drive.name ?? job.destination.machine.name) AS dest_container
              FROM review_item
              WHERE resolution IS NONE
              ORDER BY created_at DESC",
@@ -173,19 +316,44 @@ async fn fetch_review_items(db: &DbHandle) -> Result<Vec<ReviewView>, String> {
 	Ok(rows
 		.into_iter()
 		.map(|row| ReviewView {
+			comparison: describe_difference(
+				(row.source_size, row.source_modified.as_deref()),
+				(row.dest_size, row.dest_modified.as_deref()),
+			),
+			hashes: match (row.source_hash, row.dest_hash) {
+				(Some(s), Some(d)) if s != d => Some((short_hash(&s), short_hash(&d))),
+				_ => None,
+			},
 			id: row.id,
 			job: row.job,
 			error_kind: row.error_kind,
 			error_message: row.error_message,
-			source_path: row.source_path,
-			dest_path: row.dest_path,
+			source_path: display_path(&row.source_path),
+			dest_path: display_path(&row.dest_path),
 			options: row.options,
 			source_size: row.source_size,
 			dest_size: row.dest_size,
+			dest_container: row.dest_container,
 		})
 		.collect())
 }
 
+/// Apply one resolution to several items. Keeps going past failures and
+/// reports how many there were.
+async fn resolve_all(db: &DbHandle, items: &[ReviewView], resolution: &str) -> Result<(), String> {
+	let mut failed = 0;
+	for item in items {
+		if let Err(e) = resolve_item(db, &item.id, &item.job, resolution).await {
+			error!("resolving {:?} failed: {}", item.id, e);
+			failed += 1;
+		}
+	}
+	if failed > 0 {
+		return Err(format!("{failed} of {} item(s) failed", items.len()));
+	}
+	Ok(())
+}
+
 async fn resolve_item(db: &DbHandle, item_id: &RecordId, job_id: &RecordId, resolution: &str) -> Result<(), String> {
 	// Mark the review item as resolved
 	db.db
@@ -199,9 +367,46 @@ async fn resolve_item(db: &DbHandle, item_id: &RecordId, job_id: &RecordId, reso
 
 	// Act on the resolution
 	match resolution {
+		// Conflict jobs already point source → destination
 		"retry" | "rescan" => {
+			// A directory the scan couldn't read is walked again rather than copied
+			if daemon::engine::scanner::retry_unreadable(db, job_id).await.map_err(|e| e.to_string())?.is_some() {
+				return Ok(());
+			}
+			db.db
+				.query("UPDATE $job SET status = 'pending', attempts = 0, next_retry_at = NONE")
+				.bind(("job", job_id.clone()))
+				.await
+				.map_err(|e| e.to_string())?
+				.check()
+				.map_err(|e| e.to_string())?;
+		}
+		"keep_source" => {
+			// The source's version goes over whatever the destination holds
+			db.db
+				.query("UPDATE $job SET status = 'pending', attempts = 0, next_retry_at = NONE, overwrite_approved = true")
+				.bind(("job", job_id.clone()))
+				.await
+				.map_err(|e| e.to_string())?
+				.check()
+				.map_err(|e| e.to_string())?;
+		}
+		"keep_dest" => {
+			// Flip the job so the destination's version overwrites the source
 			db.db
-				.query("UPDATE $job SET status = 'pending', attempts = 0")
+				.query(
+					"LET $j = (SELECT source_path, dest_path, <record> (<record> intent).source AS source_location
+                               FROM ONLY $job);
+                     IF $j.source_location IS NONE { THROW 'the intent has no source location' };
+                     UPDATE $job SET
+                        source_path = $j.dest_path,
+                        dest_path = $j.source_path,
+                        destination = $j.source_location,
+                        status = 'pending',
+                        attempts = 0,
+                        next_retry_at = NONE,
+                        overwrite_approved = true",
+				)
 				.bind(("job", job_id.clone()))
 				.await
 				.map_err(|e| e.to_string())?
@@ -231,3 +436,70 @@ async fn resolve_item(db: &DbHandle, item_id: &RecordId, job_id: &RecordId, reso
 
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::*;
+
+	#[tokio::test]
+	async fn keep_dest_copies_the_destination_back_over_the_source() {
+		let db = daemon::db::init_memory().await.unwrap();
+		let (src, dst) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+		let (source_file, dest_file) = (src.path().join("notes.txt"), dst.path().join("notes.txt"));
+		fs::write(&source_file, "edited at the source").unwrap();
+		fs::write(&dest_file, "edited at the destination").unwrap();
+		// Both sides changed since the last sync
+		db.db
+			.query(
+				"CREATE location:src CONTENT { path: $src_root, available: true };
+                 CREATE location:dst CONTENT { path: $dst_root, available: true };
+                 CREATE intent:notes CONTENT {
+                     source: 'location:src', destinations: ['location:dst'], status: 'idle',
+                     kind: 'one_shot', speed_mode: 'normal', bidirectional: true,
+                     last_synced_at: d'2000-01-01T00:00:00Z',
+                 };",
+			)
+			.bind(("src_root", src.path().to_str().unwrap().to_string()))
+			.bind(("dst_root", dst.path().to_str().unwrap().to_string()))
+			.await
+			.unwrap()
+			.check()
+			.unwrap();
+		let intent_id = RecordId::new("intent", "notes");
+
+		daemon::engine::scanner::scan_intent(&db, &intent_id).await.unwrap();
+
+		let mut resp = db
+			.db
+			.query("SELECT id, <record> job AS job, error_kind, options FROM review_item")
+			.await
+			.unwrap();
+		let items: Vec<serde_json::Value> = resp.take(0).unwrap();
+		assert_eq!(items.len(), 1);
+		assert_eq!(items[0]["error_kind"], "conflict");
+		assert_eq!(items[0]["options"], serde_json::json!(["keep_source", "keep_dest", "skip"]));
+		let item: RecordId = serde_json::from_value(items[0]["id"].clone()).unwrap();
+		let job: RecordId = serde_json::from_value(items[0]["job"].clone()).unwrap();
+
+		resolve_item(&db, &item, &job, "keep_dest").await.unwrap();
+
+		let mut resp = db
+			.db
+			.query("SELECT source_path, dest_path, <string> destination AS destination, status FROM ONLY $job")
+			.bind(("job", job.clone()))
+			.await
+			.unwrap();
+		let flipped: Option<serde_json::Value> = resp.take(0).unwrap();
+		let flipped = flipped.unwrap();
+		assert_eq!(flipped["source_path"], dest_file.to_str().unwrap());
+		assert_eq!(flipped["dest_path"], source_file.to_str().unwrap());
+		assert_eq!(flipped["destination"], "location:src");
+		assert_eq!(flipped["status"], "pending");
+
+		db.db.query("UPDATE intent:notes SET status = 'transferring'").await.unwrap().check().unwrap();
+		daemon::engine::scheduler::run_intent(&db, &intent_id).await.unwrap();
+		assert_eq!(fs::read_to_string(&source_file).unwrap(), "edited at the destination");
+	}
+}
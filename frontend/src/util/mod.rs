@@ -1 +1,27 @@
-// Utilities (hashing, filesystem helpers)
+// Utilities (hashing, filesystem helpers, display formatting)
+
+/// A byte count for display, in binary units: `512 B`, `3 KB`, `1.5 MB`.
+pub fn format_bytes(bytes: u64) -> String {
+	if bytes >= 1_073_741_824 {
+		format!("{:.1} GB", bytes as f64 / 1_073_741_824.0)
+	} else if bytes >= 1_048_576 {
+		format!("{:.1} MB", bytes as f64 / 1_048_576.0)
+	} else if bytes >= 1024 {
+		format!("{:.0} KB", bytes as f64 / 1024.0)
+	} else {
+		format!("{bytes} B")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn bytes_use_the_largest_unit_that_fits() {
+		assert_eq!(format_bytes(512), "512 B");
+		assert_eq!(format_bytes(3 * 1024), "3 KB");
+		assert_eq!(format_bytes(1_572_864), "1.5 MB");
+		assert_eq!(format_bytes(2 * 1_073_741_824), "2.0 GB");
+	}
+}
@@ -51,6 +51,103 @@ async fn test_status_initial_state() {
 	let _ = status.review_queue.total;
 }
 
+#[tokio::test]
+async fn test_summary_totals_jobs_across_intents() {
+	let app = TestApp::new().await;
+	app.db()
+		.db
+		.query(
+			"CREATE transfer_job CONTENT {
+                 intent: 'intent:a', destination: 'location:d', source_path: '/s/1', dest_path: '/d/1',
+                 size: 100, status: 'transferring',
+             };
+             CREATE transfer_job CONTENT {
+                 intent: 'intent:b', destination: 'location:d', source_path: '/s/2', dest_path: '/d/2',
+                 size: 50, status: 'transferring',
+             };
+             CREATE transfer_job CONTENT {
+                 intent: 'intent:b', destination: 'location:d', source_path: '/s/3', dest_path: '/d/3',
+                 size: 5, status: 'pending',
+             };
+             CREATE transfer_job CONTENT {
+                 intent: 'intent:a', destination: 'location:d', source_path: '/s/4', dest_path: '/d/4',
+                 size: 5, status: 'complete', completed_at: time::now(),
+             };
+             CREATE transfer_job CONTENT {
+                 intent: 'intent:a', destination: 'location:d', source_path: '/s/5', dest_path: '/d/5',
+                 size: 5, status: 'complete', completed_at: time::now() - 3d,
+             };
+             CREATE review_item CONTENT {
+                 job: 'transfer_job:x', intent: 'intent:a', error_kind: 'io_error', error_message: 'boom',
+                 source_path: '/s/6', dest_path: '/d/6', options: ['retry', 'skip'],
+             };",
+		)
+		.await
+		.expect("Should seed jobs")
+		.check()
+		.expect("Seed queries should succeed");
+
+	let summary = api::fetch_summary(app.db()).await.expect("Should get summary");
+
+	assert_eq!(
+		summary,
+		api::Summary {
+			active_transfers: 2,
+			bytes_in_flight: 150,
+			queued_files: 1,
+			copying: 0,
+			copy_slots: 0,
+			completed_today: 1,
+			needs_review: 1,
+		}
+	);
+}
+
+#[tokio::test]
+async fn test_export_intent_report_formats() {
+	let app = TestApp::new().await;
+	app.db()
+		.db
+		.query(
+			"CREATE transfer_job CONTENT {
+                 intent: intent:backup, destination: location:d, source_path: '/s/a.txt', dest_path: '/d/a.txt',
+                 size: 5, status: 'complete', source_hash: 'abc',
+             };
+             CREATE transfer_job CONTENT {
+                 intent: intent:backup, destination: location:d, source_path: '/s/b, c.txt', dest_path: '/d/b, c.txt',
+                 size: 7, status: 'needs_review', error_kind: 'permission_denied',
+                 last_error: 'permission denied: /d/b, c.txt',
+             };
+             CREATE transfer_job CONTENT {
+                 intent: intent:other, destination: location:d, source_path: '/s/x', dest_path: '/d/x',
+                 size: 1, status: 'complete',
+             };",
+		)
+		.await
+		.expect("Should seed jobs")
+		.check()
+		.expect("Seed queries should succeed");
+
+	let csv = api::export_intent_report(app.db(), "backup", api::ReportFormat::Csv)
+		.await
+		.expect("Should export CSV");
+	assert_eq!(
+		csv,
+		"source_path,dest_path,size,status,source_hash,error_kind,error\n\
+         /s/a.txt,/d/a.txt,5,complete,abc,,\n\
+         \"/s/b, c.txt\",\"/d/b, c.txt\",7,needs_review,,permission_denied,\"permission denied: /d/b, c.txt\"\n"
+	);
+
+	let json = api::export_intent_report(app.db(), "intent:backup", api::ReportFormat::Json)
+		.await
+		.expect("Should export JSON");
+	let rows: Vec<serde_json::Value> = serde_json::from_str(&json).expect("Report should be valid JSON");
+	assert_eq!(rows.len(), 2);
+	assert_eq!(rows[0]["source_hash"], "abc");
+	assert_eq!(rows[1]["error_kind"], "permission_denied");
+	assert_eq!(rows[1]["error"], "permission denied: /d/b, c.txt");
+}
+
 #[tokio::test]
 async fn test_tilde_expansion() {
 	let app = TestApp::new().await;
@@ -154,6 +154,12 @@ pub struct GraphNode {
 	pub velocity: Vec2,
 	pub pinned: bool,
 	pub visible: bool,
+	/// Direct children recorded in the DB, whether or not their nodes have
+	/// been loaded yet. Collapsed directories show this as a badge.
+	pub child_count: usize,
+	/// A location whose path isn't there right now (`available` false on
+	/// its record), drawn with a "missing" marker.
+	pub missing: bool,
 	pub width: f64,
 	pub height: f64,
 	// Fixed position during drag (D3-style)
@@ -176,6 +182,11 @@ impl GraphNode {
 #[derive(Debug, Clone, PartialEq)]
 pub struct GraphEdge {
 	pub id: String,
+	/// The intent this edge draws; `None` for hierarchy edges. An intent with
+	/// several destinations has one edge per destination sharing this id.
+	pub intent_id: Option<String>,
+	/// The intent's name, if it was given one.
+	pub name: Option<String>,
 	pub source_id: String,
 	pub dest_id: String,
 	pub status: String,
@@ -194,6 +205,19 @@ pub struct ContainerView {
 	pub color: String,
 	pub connected: bool,
 	pub mount_point: Option<String>,
+	/// Drives only; `None` for machines or before the first poll.
+	pub capacity_bytes: Option<i64>,
+	pub free_bytes: Option<i64>,
+}
+
+impl ContainerView {
+	/// Fraction of capacity in use (0.0–1.0), when both sizes are known.
+	pub fn fullness(&self) -> Option<f64> {
+		match (self.capacity_bytes, self.free_bytes) {
+			(Some(cap), Some(free)) if cap > 0 => Some((1.0 - free as f64 / cap as f64).clamp(0.0, 1.0)),
+			_ => None,
+		}
+	}
 }
 
 // ─── Visual helpers ───────────────────────────────────────────
@@ -230,14 +254,23 @@ pub fn bezier_path_curved(x1: f64, y1: f64, x2: f64, y2: f64) -> String {
 pub fn edge_color(status: &str) -> &'static str {
 	match status {
 		"idle" => "#555",
-		"scanning" | "transferring" => "#4a9eff",
+		"scanning" | "transferring" | "verifying" => "#4a9eff",
 		"complete" => "#3fb950",
-		"needs_review" => "#d29922",
+		"needs_review" | "waiting_for_device" => "#d29922",
 		"failed" => "#f85149",
 		_ => "#555",
 	}
 }
 
+/// What a location node is called: its label if it has one, else a
+/// shortened path.
+pub fn location_label(label: Option<&str>, path: &str) -> String {
+	match label.map(str::trim) {
+		Some(label) if !label.is_empty() => label.to_string(),
+		_ => short_path(path),
+	}
+}
+
 pub fn short_path(path: &str) -> String {
 	let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
 	if parts.len() <= 2 {
@@ -311,6 +344,20 @@ mod tests {
 		assert!(!is_direct_child("/a/b", "/a/b"));
 	}
 
+	#[test]
+	fn test_location_label_falls_back_to_path() {
+		assert_eq!(location_label(Some("Photos"), "/home/me/pictures/2024"), "Photos");
+		assert_eq!(location_label(Some("  "), "/home/me/pictures/2024"), ".../pictures/2024");
+		assert_eq!(location_label(None, "/backup"), "/backup");
+	}
+
+	#[test]
+	fn test_edge_color_for_waiting_and_verifying() {
+		assert_eq!(edge_color("waiting_for_device"), edge_color("needs_review"));
+		assert_eq!(edge_color("verifying"), edge_color("transferring"));
+		assert_ne!(edge_color("verifying"), edge_color("idle"));
+	}
+
 	#[test]
 	fn test_vec2_ops() {
 		let a = Vec2::new(1.0, 2.0);
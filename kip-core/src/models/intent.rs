@@ -1,3 +1,5 @@
+use std::time::SystemTime;
+
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use surrealdb::types::RecordId;
@@ -23,6 +25,44 @@ pub struct Intent {
 	pub completed_bytes: i64,
 	pub include_patterns: Option<Vec<String>>,
 	pub exclude_patterns: Option<Vec<String>>,
+	/// Error kinds (as classified by the copier) that are retried
+	/// automatically before a job escalates to the review queue.
+	#[serde(default = "default_retryable_errors")]
+	pub retryable_errors: Vec<String>,
+	#[serde(default)]
+	pub job_order: JobOrder,
+	/// Max concurrent copies. `None` uses the speed mode's (for `Normal`,
+	/// the global default: 4, or `KIP_MAX_CONCURRENCY`); slow destination
+	/// drives cap it further.
+	#[serde(default)]
+	pub concurrency: Option<u32>,
+	/// Scan through symlinks to the files and directories they point at
+	/// instead of skipping them.
+	#[serde(default)]
+	pub follow_symlinks: bool,
+	/// Folder under `source` to transfer instead of the whole location,
+	/// relative to it.
+	#[serde(default)]
+	pub source_subpath: Option<String>,
+	#[serde(default)]
+	pub verify_mode: VerifyMode,
+	#[serde(default)]
+	pub on_conflict: ConflictPolicy,
+	#[serde(default)]
+	pub overwrite_policy: OverwritePolicy,
+	#[serde(default)]
+	pub copy_strategy: CopyStrategy,
+	/// Write a `.kip-manifest.json` of every copied file's size and blake3
+	/// hash at each destination's root when a run finishes.
+	#[serde(default)]
+	pub write_manifest: bool,
+}
+
+/// Error kinds retried by default: only transient I/O failures.
+pub const DEFAULT_RETRYABLE_ERRORS: &[&str] = &["io_error"];
+
+fn default_retryable_errors() -> Vec<String> {
+	DEFAULT_RETRYABLE_ERRORS.iter().map(|s| s.to_string()).collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -58,3 +98,195 @@ impl Default for SpeedMode {
 		SpeedMode::Normal
 	}
 }
+
+/// What a speed mode means for a transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpeedProfile {
+	/// Concurrent copies when the intent doesn't set its own `concurrency`.
+	pub concurrency: usize,
+	/// Bytes per second each copy is held to, or `None` for as fast as the
+	/// disks go.
+	pub rate_limit: Option<u64>,
+	/// Largest read/write buffer a copy uses; smaller files get less.
+	pub chunk_size: usize,
+}
+
+impl SpeedMode {
+	/// The concurrency, bandwidth and buffer size the scheduler and copier
+	/// use for this mode. `Ninja` keeps out of the way of whatever else the
+	/// machine is doing; `Blast` uses all it can get.
+	pub const fn profile(&self) -> SpeedProfile {
+		match self {
+			SpeedMode::Normal => SpeedProfile { concurrency: 4, rate_limit: None, chunk_size: 256 * 1024 },
+			SpeedMode::Ninja => SpeedProfile {
+				concurrency: 1,
+				rate_limit: Some(10 * 1024 * 1024),
+				chunk_size: 256 * 1024,
+			},
+			SpeedMode::Blast => SpeedProfile { concurrency: 12, rate_limit: None, chunk_size: 4 * 1024 * 1024 },
+		}
+	}
+}
+
+/// Order in which pending transfer jobs are dispatched.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobOrder {
+	/// Small files first — progress moves quickly.
+	SmallestFirst,
+	/// Large files first — overlaps big copies with many small ones.
+	LargestFirst,
+	/// Whatever order the scan produced.
+	#[default]
+	AsFound,
+}
+
+impl JobOrder {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			JobOrder::SmallestFirst => "smallest_first",
+			JobOrder::LargestFirst => "largest_first",
+			JobOrder::AsFound => "as_found",
+		}
+	}
+}
+
+/// How a copy is checked once it's written.
+///
+/// The hashing modes hash the bytes as they're written. On a local disk a
+/// write that flushed and fsynced cleanly is trusted to hold exactly those
+/// bytes, so nothing is read back; remote destinations are still re-read.
+/// `Paranoid` always re-reads, at roughly twice the I/O, for drives that
+/// might acknowledge writes they didn't keep.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyMode {
+	/// blake3.
+	#[default]
+	Hash,
+	/// xxh3 — much faster, but not cryptographic.
+	FastHash,
+	/// blake3, and always read the destination back to compare.
+	Paranoid,
+	/// Only check the destination's size and mtime; nothing is re-read.
+	SizeMtime,
+	/// Trust the write.
+	None,
+}
+
+impl VerifyMode {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			VerifyMode::Hash => "hash",
+			VerifyMode::FastHash => "fast_hash",
+			VerifyMode::Paranoid => "paranoid",
+			VerifyMode::SizeMtime => "size_mtime",
+			VerifyMode::None => "none",
+		}
+	}
+}
+
+/// What happens when both sides of a copy disagree and neither is clearly
+/// the one to keep: a file edited on both ends of a two-way sync, or a
+/// destination that doesn't hash like its source after a copy.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+	/// Keep whichever side was modified last.
+	NewerWins,
+	SourceWins,
+	DestWins,
+	/// Leave it in the review queue.
+	#[default]
+	Review,
+}
+
+impl ConflictPolicy {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			ConflictPolicy::NewerWins => "newer_wins",
+			ConflictPolicy::SourceWins => "source_wins",
+			ConflictPolicy::DestWins => "dest_wins",
+			ConflictPolicy::Review => "review",
+		}
+	}
+
+	/// `Some(true)` if the source's version should be kept, `Some(false)` for
+	/// the destination's, `None` if it goes to review. `NewerWins` can't
+	/// pick between unknown or identical mtimes.
+	pub fn source_wins(&self, source_modified: Option<SystemTime>, dest_modified: Option<SystemTime>) -> Option<bool> {
+		match self {
+			ConflictPolicy::SourceWins => Some(true),
+			ConflictPolicy::DestWins => Some(false),
+			ConflictPolicy::NewerWins => match (source_modified?, dest_modified?) {
+				(s, d) if s == d => None,
+				(s, d) => Some(s > d),
+			},
+			ConflictPolicy::Review => None,
+		}
+	}
+}
+
+/// How a file reaches a destination on the same filesystem as its source.
+/// Anything that can't be linked or cloned is copied byte for byte.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CopyStrategy {
+	/// Always copy the bytes.
+	#[default]
+	Copy,
+	/// Clone the file's blocks (APFS, Btrfs, XFS). The destination shares
+	/// storage with the source until either is written to.
+	Reflink,
+	/// A reflink, or failing that a hardlink. A hardlinked destination is the
+	/// same file as its source, so editing one edits the other.
+	Link,
+}
+
+impl CopyStrategy {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			CopyStrategy::Copy => "copy",
+			CopyStrategy::Reflink => "reflink",
+			CopyStrategy::Link => "link",
+		}
+	}
+}
+
+/// Whether a copy may replace a destination file that already exists and
+/// differs from the source.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OverwritePolicy {
+	#[default]
+	Always,
+	/// Leave the destination as it is and skip the file.
+	Never,
+	/// Overwrite only when the source was modified later.
+	IfNewer,
+	/// Leave it in the review queue.
+	Review,
+}
+
+impl OverwritePolicy {
+	pub fn as_str(&self) -> &'static str {
+		match self {
+			OverwritePolicy::Always => "always",
+			OverwritePolicy::Never => "never",
+			OverwritePolicy::IfNewer => "if_newer",
+			OverwritePolicy::Review => "review",
+		}
+	}
+
+	/// `Some(true)` to overwrite, `Some(false)` to leave the destination as it
+	/// is, `None` if it goes to review. `IfNewer` keeps the destination when
+	/// either mtime is unknown.
+	pub fn overwrites(&self, source_modified: Option<SystemTime>, dest_modified: Option<SystemTime>) -> Option<bool> {
+		match self {
+			OverwritePolicy::Always => Some(true),
+			OverwritePolicy::Never => Some(false),
+			OverwritePolicy::IfNewer => Some(matches!((source_modified, dest_modified), (Some(s), Some(d)) if s > d)),
+			OverwritePolicy::Review => None,
+		}
+	}
+}
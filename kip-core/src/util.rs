@@ -1,6 +1,10 @@
 //! Utility functions for Kip core
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+/// Marker for DB-stored paths whose bytes aren't valid UTF-8. Stored paths
+/// are absolute, so a real path can never start with this.
+const RAW_PATH_PREFIX: &str = "kip-raw-path:";
 
 /// Calculate BLAKE3 hash of file contents
 pub fn hash_file(path: &Path) -> Result<String, std::io::Error> {
@@ -37,3 +41,49 @@ pub fn is_hidden(path: &Path) -> bool {
 		.map(|n| n.starts_with('.'))
 		.unwrap_or(false)
 }
+
+/// Encode a path for storage in a DB string field.
+///
+/// Valid UTF-8 paths are stored unchanged. Anything else is stored as
+/// `kip-raw-path:<hex bytes>` so the exact on-disk name survives the round
+/// trip — `to_string_lossy` would mangle it and the file could not be
+/// reopened.
+pub fn encode_path(path: &Path) -> String {
+	if let Some(s) = path.to_str() {
+		return s.to_string();
+	}
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::ffi::OsStrExt;
+		let hex: String = path.as_os_str().as_bytes().iter().map(|b| format!("{b:02x}")).collect();
+		format!("{RAW_PATH_PREFIX}{hex}")
+	}
+
+	#[cfg(not(unix))]
+	{
+		path.to_string_lossy().to_string()
+	}
+}
+
+/// Decode a path stored with `encode_path`.
+pub fn decode_path(stored: &str) -> PathBuf {
+	#[cfg(unix)]
+	if let Some(hex) = stored.strip_prefix(RAW_PATH_PREFIX) {
+		use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+		let bytes: Option<Vec<u8>> = (0..hex.len())
+			.step_by(2)
+			.map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+			.collect();
+		if let Some(bytes) = bytes {
+			return PathBuf::from(OsString::from_vec(bytes));
+		}
+	}
+
+	PathBuf::from(stored)
+}
+
+/// Human-readable form of a stored path (lossy for non-UTF-8 names).
+pub fn display_path(stored: &str) -> String {
+	decode_path(stored).to_string_lossy().to_string()
+}